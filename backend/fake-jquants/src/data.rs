@@ -0,0 +1,166 @@
+//! シード付きの合成株価データ
+//!
+//! バックエンドの合成データ ([`SyntheticProvider`]) を J-Quants API のレコード形式に変換する。
+//! 同じシード・銘柄コードであれば、リクエストした期間に関係なく同じ日付には同じ値を返す。
+
+use backend::data_provider::DateRange;
+use backend::data_provider::synthetic::SyntheticProvider;
+use chrono::NaiveDate;
+use rust_decimal::prelude::ToPrimitive;
+use serde::Serialize;
+
+pub use backend::data_provider::synthetic::ORIGIN;
+
+/// J-Quants API V2 日足レコード (`GET /v2/equities/bars/daily` の `data` 要素)
+///
+/// 株式分割等は発生させないため、調整後の値は未調整の値と同じ。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyBar {
+    #[serde(rename = "Date")]
+    pub date: String,
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "O")]
+    pub open: f64,
+    #[serde(rename = "H")]
+    pub high: f64,
+    #[serde(rename = "L")]
+    pub low: f64,
+    #[serde(rename = "C")]
+    pub close: f64,
+    #[serde(rename = "Vo")]
+    pub volume: f64,
+    #[serde(rename = "AdjFactor")]
+    pub adj_factor: f64,
+    #[serde(rename = "AdjO")]
+    pub adj_open: f64,
+    #[serde(rename = "AdjH")]
+    pub adj_high: f64,
+    #[serde(rename = "AdjL")]
+    pub adj_low: f64,
+    #[serde(rename = "AdjC")]
+    pub adj_close: f64,
+    #[serde(rename = "AdjVo")]
+    pub adj_volume: f64,
+}
+
+/// J-Quants API V2 銘柄マスタレコード (`GET /v2/equities/master` の `data` 要素)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EquityMaster {
+    #[serde(rename = "Date")]
+    pub date: String,
+    #[serde(rename = "Code")]
+    pub code: String,
+    #[serde(rename = "CoName")]
+    pub company_name: String,
+    #[serde(rename = "CoNameEn")]
+    pub company_name_en: String,
+    #[serde(rename = "S33Nm")]
+    pub sector_name: String,
+    #[serde(rename = "MktNm")]
+    pub market_name: String,
+}
+
+/// API レスポンスの 5 桁の銘柄コード (4 桁のコードには末尾に 0 を付ける)
+pub fn response_code(code: &str) -> String {
+    if code.len() == 4 {
+        format!("{code}0")
+    } else {
+        code.to_string()
+    }
+}
+
+/// 指定期間の日足を生成する (営業日のみ、日付昇順)
+///
+/// 銘柄コードとして扱えないコードは空を返す。
+pub fn daily_bars(seed: u64, code: &str, from: NaiveDate, to: NaiveDate) -> Vec<DailyBar> {
+    let provider = SyntheticProvider::new(seed);
+    if provider.instrument(code).is_none() {
+        return Vec::new();
+    }
+    let response_code = response_code(code);
+
+    provider
+        .daily_bars(code, &DateRange { from, to })
+        .into_iter()
+        .map(|bar| {
+            let [open, high, low, close] =
+                [bar.open, bar.high, bar.low, bar.close].map(|p| p.to_f64().unwrap_or_default());
+            let volume = bar.volume as f64;
+            DailyBar {
+                date: bar.timestamp.format("%Y-%m-%d").to_string(),
+                code: response_code.clone(),
+                open,
+                high,
+                low,
+                close,
+                volume,
+                adj_factor: 1.0,
+                adj_open: open,
+                adj_high: high,
+                adj_low: low,
+                adj_close: close,
+                adj_volume: volume,
+            }
+        })
+        .collect()
+}
+
+/// 銘柄マスタを生成する。銘柄コードとして扱えないコードは None を返す
+pub fn equity_master(seed: u64, code: &str, today: NaiveDate) -> Option<EquityMaster> {
+    let instrument = SyntheticProvider::new(seed).instrument(code)?;
+
+    Some(EquityMaster {
+        date: today.format("%Y-%m-%d").to_string(),
+        code: instrument.id,
+        company_name: instrument.name,
+        company_name_en: format!("Synthetic {code}"),
+        sector_name: instrument.sector.unwrap_or_default(),
+        market_name: instrument.market_segment.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[rstest]
+    fn test_daily_bars_are_deterministic_across_ranges() {
+        let wide = daily_bars(0, "7203", date(2025, 1, 1), date(2025, 1, 31));
+        let narrow = daily_bars(0, "7203", date(2025, 1, 10), date(2025, 1, 17));
+
+        let start = wide.iter().position(|b| b.date == "2025-01-10").unwrap();
+        assert_eq!(narrow, wide[start..start + narrow.len()]);
+    }
+
+    #[rstest]
+    #[case::different_code(0, "6758")]
+    #[case::different_seed(1, "7203")]
+    fn test_daily_bars_depend_on_seed_and_code(#[case] seed: u64, #[case] code: &str) {
+        let base = daily_bars(0, "7203", date(2025, 1, 6), date(2025, 1, 10));
+        let other = daily_bars(seed, code, date(2025, 1, 6), date(2025, 1, 10));
+
+        let closes = |bars: &[DailyBar]| bars.iter().map(|b| b.close).collect::<Vec<_>>();
+        assert_ne!(closes(&base), closes(&other));
+    }
+
+    #[rstest]
+    fn test_daily_bars_are_valid_ohlcv_on_trading_days() {
+        let bars = daily_bars(0, "7203", date(2024, 1, 1), date(2024, 12, 31));
+
+        // 2024 年の東証の営業日数
+        assert_eq!(bars.len(), 245);
+        for bar in &bars {
+            assert_eq!(bar.code, "72030");
+            assert!(bar.low <= bar.open.min(bar.close), "{bar:?}");
+            assert!(bar.high >= bar.open.max(bar.close), "{bar:?}");
+            assert!(bar.low >= 1.0, "{bar:?}");
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! J-Quants API V2 のフェイクサーバー
+//!
+//! シード付きの合成データを返す。ネットワークや API キーなしでバックエンドを
+//! エンドツーエンドで動かすために使う (バックエンドの `JQUANTS_BASE_URL` にこのサーバーを指定する)。
+
+mod data;
+mod server;
+
+use std::net::SocketAddr;
+
+use clap::Parser;
+
+/// J-Quants API V2 のフェイクサーバー
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// 待ち受けポート
+    #[arg(long, env = "FAKE_JQUANTS_PORT", default_value_t = 8080)]
+    port: u16,
+
+    #[command(flatten)]
+    config: server::Config,
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], cli.port));
+    tracing::info!(seed = cli.config.seed, "listening on {addr}");
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, server::router(cli.config)).await
+}
@@ -0,0 +1,499 @@
+//! J-Quants API V2 互換のエンドポイント
+//!
+//! バックエンドが使用する以下のエンドポイントのみを実装する。
+//!
+//! - `GET /equities/bars/daily` (`code` 必須、`from`/`to`/`date`、`pagination_key` によるページネーション)
+//! - `GET /equities/master` (`code` 必須)
+//!
+//! エラーを再現するため、以下の銘柄コードは特別扱いする。
+//!
+//! - `9990`: 常に 500 を返す
+//! - `9991`: 常に 429 を返す
+//! - `9999`: 存在しない銘柄として空の `data` を返す
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Query, Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use backend::data_provider::synthetic::rng::SplitMix64;
+
+use crate::data::{self, DailyBar, EquityMaster};
+
+const CODE_INTERNAL_ERROR: &str = "9990";
+const CODE_RATE_LIMITED: &str = "9991";
+const CODE_NOT_FOUND: &str = "9999";
+
+/// レートリミットのウィンドウ幅 (60 秒)
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// フェイクサーバーの設定
+#[derive(clap::Args, Debug, Clone)]
+pub struct Config {
+    /// 合成データの乱数シード
+    #[arg(long, env = "FAKE_JQUANTS_SEED", default_value_t = 0)]
+    pub seed: u64,
+
+    /// 日足の 1 ページあたりのレコード数
+    #[arg(long, env = "FAKE_JQUANTS_PAGE_SIZE", default_value_t = 100,
+          value_parser = clap::value_parser!(u16).range(1..))]
+    pub page_size: u16,
+
+    /// 指定した場合、`x-api-key` ヘッダーが一致しないリクエストに 403 を返す
+    #[arg(long, env = "FAKE_JQUANTS_API_KEY")]
+    pub api_key: Option<String>,
+
+    /// 1 分間に受け付けるリクエスト数 (超過分には 429 を返す。0 は無制限)
+    #[arg(long, env = "FAKE_JQUANTS_RATE_LIMIT", default_value_t = 0)]
+    pub rate_limit: usize,
+
+    /// 500 を返すリクエストの割合 (0.0 ~ 1.0)
+    #[arg(long, env = "FAKE_JQUANTS_ERROR_RATE", default_value_t = 0.0)]
+    pub error_rate: f64,
+}
+
+struct AppState {
+    config: Config,
+    /// 直近 1 分間のリクエスト受付時刻 (古い順)
+    requests: Mutex<VecDeque<Instant>>,
+    /// エラーを発生させるかどうかを決める乱数
+    error_rng: Mutex<SplitMix64>,
+}
+
+/// J-Quants API 形式のエラーレスポンス
+struct ApiError {
+    status: StatusCode,
+    message: &'static str,
+    /// `Retry-After` ヘッダーで返す秒数
+    retry_after: Option<u64>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: &'static str) -> Self {
+        Self {
+            status,
+            message,
+            retry_after: None,
+        }
+    }
+
+    fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct Body {
+            message: &'static str,
+        }
+        let mut response = (
+            self.status,
+            Json(Body {
+                message: self.message,
+            }),
+        )
+            .into_response();
+        if let Some(secs) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+pub fn router(config: Config) -> Router {
+    let state = Arc::new(AppState {
+        error_rng: Mutex::new(SplitMix64::new(config.seed)),
+        config,
+        requests: Mutex::new(VecDeque::new()),
+    });
+
+    Router::new()
+        .route("/equities/bars/daily", get(daily_bars))
+        .route("/equities/master", get(equities_master))
+        .layer(middleware::from_fn_with_state(state.clone(), guard))
+        .with_state(state)
+}
+
+/// API キーの検証、レートリミット、エラーの発生をエンドポイントの前段で行う
+async fn guard(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let config = &state.config;
+
+    if let Some(api_key) = &config.api_key {
+        let given = headers.get("x-api-key").and_then(|v| v.to_str().ok());
+        if given != Some(api_key.as_str()) {
+            return Err(ApiError::new(StatusCode::FORBIDDEN, "Invalid API key"));
+        }
+    }
+
+    if config.rate_limit > 0 {
+        let now = Instant::now();
+        let mut requests = state.requests.lock().await;
+        while requests
+            .front()
+            .is_some_and(|oldest| now.duration_since(*oldest) >= RATE_LIMIT_WINDOW)
+        {
+            requests.pop_front();
+        }
+        if let Some(oldest) = requests.front()
+            && requests.len() >= config.rate_limit
+        {
+            // 最も古いリクエストがウィンドウから外れるまでの秒数 (切り上げ)
+            let wait = RATE_LIMIT_WINDOW.saturating_sub(now.duration_since(*oldest));
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            tracing::info!(retry_after, "レートリミット超過のため 429 を返します");
+            return Err(
+                ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests")
+                    .with_retry_after(retry_after),
+            );
+        }
+        requests.push_back(now);
+    }
+
+    if config.error_rate > 0.0 && state.error_rng.lock().await.next_f64() < config.error_rate {
+        tracing::info!("エラーを発生させて 500 を返します");
+        return Err(internal_error());
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[derive(Deserialize)]
+struct DailyBarsQuery {
+    code: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    date: Option<String>,
+    pagination_key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DailyBarsResponse {
+    data: Vec<DailyBar>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pagination_key: Option<String>,
+}
+
+async fn daily_bars(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DailyBarsQuery>,
+) -> Result<Json<DailyBarsResponse>, ApiError> {
+    let code = instrument_code(query.code.as_deref())?;
+    if code == CODE_NOT_FOUND {
+        return Ok(Json(DailyBarsResponse {
+            data: Vec::new(),
+            pagination_key: None,
+        }));
+    }
+
+    let today = Utc::now().date_naive();
+    let (from, to) = match query.date.as_deref() {
+        Some(date) => {
+            let date = parse_date(date)?;
+            (date, date)
+        }
+        None => (
+            query
+                .from
+                .as_deref()
+                .map(parse_date)
+                .transpose()?
+                .unwrap_or(data::ORIGIN),
+            query
+                .to
+                .as_deref()
+                .map(parse_date)
+                .transpose()?
+                .unwrap_or(today),
+        ),
+    };
+
+    let offset = match query.pagination_key.as_deref() {
+        Some(key) => key
+            .parse::<usize>()
+            .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "Invalid pagination_key"))?,
+        None => 0,
+    };
+
+    // 未来の日付のデータは返さない
+    let bars = data::daily_bars(state.config.seed, code, from, to.min(today));
+    let page_size = usize::from(state.config.page_size);
+    let end = offset.saturating_add(page_size).min(bars.len());
+    let page = bars.get(offset..end).unwrap_or_default().to_vec();
+    let pagination_key = (end < bars.len()).then(|| end.to_string());
+
+    Ok(Json(DailyBarsResponse {
+        data: page,
+        pagination_key,
+    }))
+}
+
+#[derive(Deserialize)]
+struct EquitiesMasterQuery {
+    code: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EquitiesMasterResponse {
+    data: Vec<EquityMaster>,
+}
+
+async fn equities_master(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EquitiesMasterQuery>,
+) -> Result<Json<EquitiesMasterResponse>, ApiError> {
+    let code = instrument_code(query.code.as_deref())?;
+    let data = if code == CODE_NOT_FOUND {
+        Vec::new()
+    } else {
+        data::equity_master(state.config.seed, code, Utc::now().date_naive())
+            .into_iter()
+            .collect()
+    };
+
+    Ok(Json(EquitiesMasterResponse { data }))
+}
+
+/// `code` クエリパラメータを検証し、エラー再現用のコードであればエラーを返す
+///
+/// 5 桁のコード (末尾 0) は 4 桁のコードとして扱う。
+fn instrument_code(code: Option<&str>) -> Result<&str, ApiError> {
+    let code = code.ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "code is required"))?;
+    let valid = matches!(code.len(), 4 | 5) && code.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "Invalid code"));
+    }
+    let code = match code.strip_suffix('0') {
+        Some(short) if code.len() == 5 => short,
+        _ => code,
+    };
+
+    match code {
+        CODE_INTERNAL_ERROR => Err(internal_error()),
+        CODE_RATE_LIMITED => Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too Many Requests",
+        )),
+        _ => Ok(code),
+    }
+}
+
+/// `YYYYMMDD` または `YYYY-MM-DD` 形式の日付をパースする
+fn parse_date(value: &str) -> Result<NaiveDate, ApiError> {
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "Invalid date"))
+}
+
+fn internal_error() -> ApiError {
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_test::TestServer;
+    use backend::data_provider::jquants::JQuantsClient;
+    use backend::data_provider::{DataProvider, DataProviderError, DateRange};
+    use rstest::rstest;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            seed: 0,
+            page_size: 100,
+            api_key: None,
+            rate_limit: 0,
+            error_rate: 0.0,
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// pagination_key をたどって全ページの日足レコードを取得する
+    async fn fetch_all_pages(server: &TestServer, code: &str) -> Vec<Value> {
+        let mut records = Vec::new();
+        let mut pagination_key: Option<String> = None;
+        loop {
+            let mut request = server
+                .get("/equities/bars/daily")
+                .add_query_param("code", code)
+                .add_query_param("from", "20250106")
+                .add_query_param("to", "20250117");
+            if let Some(key) = &pagination_key {
+                request = request.add_query_param("pagination_key", key);
+            }
+            let body: Value = request.await.json();
+            records.extend(body["data"].as_array().unwrap().iter().cloned());
+            match body.get("pagination_key").and_then(Value::as_str) {
+                Some(key) => pagination_key = Some(key.to_string()),
+                None => return records,
+            }
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_daily_bars_paginates_without_changing_records() {
+        let paged = TestServer::new(router(Config {
+            page_size: 3,
+            ..config()
+        }))
+        .unwrap();
+        let single = TestServer::new(router(config())).unwrap();
+
+        let paged_records = fetch_all_pages(&paged, "7203").await;
+        let single_records = fetch_all_pages(&single, "7203").await;
+
+        // 2025-01-06 ~ 2025-01-17 の営業日は 1/13 (成人の日) を除く 9 日
+        assert_eq!(paged_records.len(), 9);
+        assert_eq!(paged_records, single_records);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_master_returns_synthetic_instrument() {
+        let server = TestServer::new(router(config())).unwrap();
+
+        let response = server
+            .get("/equities/master")
+            .add_query_param("code", "7203")
+            .await;
+
+        response.assert_status_ok();
+        let body: Value = response.json();
+        assert_eq!(body["data"][0]["Code"], "7203");
+        assert_eq!(body["data"][0]["CoName"], "合成銘柄7203");
+    }
+
+    #[rstest]
+    #[case::internal_error("9990", StatusCode::INTERNAL_SERVER_ERROR)]
+    #[case::rate_limited("9991", StatusCode::TOO_MANY_REQUESTS)]
+    #[case::missing_code("", StatusCode::BAD_REQUEST)]
+    #[tokio::test]
+    async fn test_simulates_errors_by_code(#[case] code: &str, #[case] expected: StatusCode) {
+        let server = TestServer::new(router(config())).unwrap();
+
+        let mut request = server.get("/equities/master");
+        if !code.is_empty() {
+            request = request.add_query_param("code", code);
+        }
+        let response = request.expect_failure().await;
+
+        response.assert_status(expected);
+        assert!(response.json::<Value>()["message"].is_string());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_not_found_code_returns_empty_data() {
+        let server = TestServer::new(router(config())).unwrap();
+
+        for path in ["/equities/master", "/equities/bars/daily"] {
+            let response = server.get(path).add_query_param("code", "9999").await;
+            response.assert_json(&json!({ "data": [] }));
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_rate_limit_returns_429() {
+        let server = TestServer::new(router(Config {
+            rate_limit: 2,
+            ..config()
+        }))
+        .unwrap();
+
+        for _ in 0..2 {
+            server
+                .get("/equities/master")
+                .add_query_param("code", "7203")
+                .await
+                .assert_status_ok();
+        }
+        let response = server
+            .get("/equities/master")
+            .add_query_param("code", "7203")
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.header(RETRY_AFTER), "60");
+    }
+
+    #[rstest]
+    #[case::missing(None, StatusCode::FORBIDDEN)]
+    #[case::wrong(Some("wrong"), StatusCode::FORBIDDEN)]
+    #[case::valid(Some("secret"), StatusCode::OK)]
+    #[tokio::test]
+    async fn test_requires_api_key_when_configured(
+        #[case] api_key: Option<&str>,
+        #[case] expected: StatusCode,
+    ) {
+        let server = TestServer::new(router(Config {
+            api_key: Some("secret".to_string()),
+            ..config()
+        }))
+        .unwrap();
+
+        let mut request = server
+            .get("/equities/master")
+            .add_query_param("code", "7203");
+        if let Some(api_key) = api_key {
+            request = request.add_header("x-api-key", api_key);
+        }
+
+        request.await.assert_status(expected);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_serves_backend_client() -> Result<(), DataProviderError> {
+        let server = TestServer::builder()
+            .http_transport()
+            .build(router(Config {
+                page_size: 2,
+                ..config()
+            }))
+            .unwrap();
+        let base_url = server.server_address().unwrap().to_string();
+        let client = JQuantsClient::new("test-api-key".to_string())?
+            .with_api_base_url(base_url.trim_end_matches('/'));
+
+        let range = DateRange {
+            from: date(2025, 1, 6),
+            to: date(2025, 1, 10),
+        };
+        let bars = client.fetch_daily_bars("7203", &range).await?;
+        let instrument = client.fetch_instrument("7203").await?;
+
+        assert_eq!(bars.len(), 5);
+        assert!(bars.iter().all(|bar| bar.instrument_id == "7203"));
+        assert_eq!(instrument.id, "7203");
+
+        let not_found = client.fetch_instrument("9999").await;
+        assert!(matches!(not_found, Err(DataProviderError::NotFound(_))));
+        Ok(())
+    }
+}
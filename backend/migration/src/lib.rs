@@ -1,11 +1,39 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20260215_092115_initial_schema;
+mod m20261018_000001_add_watchlist_item_memo;
+mod m20261018_000002_add_smart_watchlists;
+mod m20261018_000003_add_watchlist_item_annotations;
+mod m20261018_000004_create_bar_anomalies;
+mod m20261018_000005_add_instrument_bars_updated_at;
+mod m20261018_000006_configure_bars_timescaledb;
+mod m20261018_000007_create_data_provider_cache;
+mod m20261018_000008_add_bar_source;
+mod m20261018_000009_create_api_rate_limits;
+mod m20261018_000010_create_data_fetch_jobs;
+mod m20261018_000011_allow_intraday_bars;
+mod m20261018_000012_prune_data_provider_cache;
+mod m20261018_000013_dedupe_and_prune_data_fetch_jobs;
 
 pub struct Migrator;
 
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20260215_092115_initial_schema::Migration)]
+        vec![
+            Box::new(m20260215_092115_initial_schema::Migration),
+            Box::new(m20261018_000001_add_watchlist_item_memo::Migration),
+            Box::new(m20261018_000002_add_smart_watchlists::Migration),
+            Box::new(m20261018_000003_add_watchlist_item_annotations::Migration),
+            Box::new(m20261018_000004_create_bar_anomalies::Migration),
+            Box::new(m20261018_000005_add_instrument_bars_updated_at::Migration),
+            Box::new(m20261018_000006_configure_bars_timescaledb::Migration),
+            Box::new(m20261018_000007_create_data_provider_cache::Migration),
+            Box::new(m20261018_000008_add_bar_source::Migration),
+            Box::new(m20261018_000009_create_api_rate_limits::Migration),
+            Box::new(m20261018_000010_create_data_fetch_jobs::Migration),
+            Box::new(m20261018_000011_allow_intraday_bars::Migration),
+            Box::new(m20261018_000012_prune_data_provider_cache::Migration),
+            Box::new(m20261018_000013_dedupe_and_prune_data_fetch_jobs::Migration),
+        ]
     }
 }
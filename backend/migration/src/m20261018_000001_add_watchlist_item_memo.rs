@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// watchlist_items テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum WatchlistItems {
+    Table,
+    Memo,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ウォッチリスト項目ごとの任意メモ (インポート/エクスポートで引き継ぐ)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WatchlistItems::Table)
+                    .add_column(ColumnDef::new(WatchlistItems::Memo).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WatchlistItems::Table)
+                    .drop_column(WatchlistItems::Memo)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// watchlists テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum Watchlists {
+    Table,
+    Kind,
+    Rule,
+}
+
+/// instruments テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum Instruments {
+    Table,
+    MarketSegment,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // static: watchlist_items を手動で管理する
+        // smart: rule に一致する銘柄で watchlist_items を再計算する
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Watchlists::Table)
+                    .add_column(
+                        ColumnDef::new(Watchlists::Kind)
+                            .string()
+                            .not_null()
+                            .default("static")
+                            .check(Expr::col(Watchlists::Kind).is_in(["static", "smart"])),
+                    )
+                    .add_column(ColumnDef::new(Watchlists::Rule).json_binary())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 市場区分 (プライム/スタンダード/グロース 等)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Instruments::Table)
+                    .add_column(ColumnDef::new(Instruments::MarketSegment).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Instruments::Table)
+                    .drop_column(Instruments::MarketSegment)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Watchlists::Table)
+                    .drop_column(Watchlists::Rule)
+                    .drop_column(Watchlists::Kind)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
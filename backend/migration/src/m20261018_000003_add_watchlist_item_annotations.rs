@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// watchlist_items テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum WatchlistItems {
+    Table,
+    Tags,
+    EntryPrice,
+    TargetPrice,
+    StopPrice,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // tags: [{"name": "...", "color": "#RRGGBB"}] 形式の配列
+        // entry/target/stop_price: 価格アラートの閾値として使う任意の価格
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WatchlistItems::Table)
+                    .add_column(
+                        ColumnDef::new(WatchlistItems::Tags)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .add_column(ColumnDef::new(WatchlistItems::EntryPrice).decimal())
+                    .add_column(ColumnDef::new(WatchlistItems::TargetPrice).decimal())
+                    .add_column(ColumnDef::new(WatchlistItems::StopPrice).decimal())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WatchlistItems::Table)
+                    .drop_column(WatchlistItems::Tags)
+                    .drop_column(WatchlistItems::EntryPrice)
+                    .drop_column(WatchlistItems::TargetPrice)
+                    .drop_column(WatchlistItems::StopPrice)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,92 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// instruments テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum Instruments {
+    Table,
+    Id,
+}
+
+/// bar_anomalies テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum BarAnomalies {
+    Table,
+    InstrumentId,
+    Timeframe,
+    Timestamp,
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+    Reasons,
+    DetectedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // bar_anomalies: 取り込み時の検証に失敗し、bars に書き込まなかったバー
+        // reasons: 検証に失敗した理由の配列 (例: ["high_below_open_close"])
+        manager
+            .create_table(
+                Table::create()
+                    .table(BarAnomalies::Table)
+                    .col(
+                        ColumnDef::new(BarAnomalies::InstrumentId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BarAnomalies::Timeframe).string().not_null())
+                    .col(
+                        ColumnDef::new(BarAnomalies::Timestamp)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BarAnomalies::Open).decimal().not_null())
+                    .col(ColumnDef::new(BarAnomalies::High).decimal().not_null())
+                    .col(ColumnDef::new(BarAnomalies::Low).decimal().not_null())
+                    .col(ColumnDef::new(BarAnomalies::Close).decimal().not_null())
+                    .col(
+                        ColumnDef::new(BarAnomalies::Volume)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BarAnomalies::Reasons)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BarAnomalies::DetectedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(BarAnomalies::InstrumentId)
+                            .col(BarAnomalies::Timeframe)
+                            .col(BarAnomalies::Timestamp),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(BarAnomalies::Table, BarAnomalies::InstrumentId)
+                            .to(Instruments::Table, Instruments::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .check(Expr::col(BarAnomalies::Timeframe).is_in(["1d"]))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BarAnomalies::Table).to_owned())
+            .await
+    }
+}
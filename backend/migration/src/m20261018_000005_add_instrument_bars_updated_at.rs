@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// instruments テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum Instruments {
+    Table,
+    BarsUpdatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // bars_updated_at: 銘柄のバーデータを最後に書き込んだ日時 (未取得の場合は NULL)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Instruments::Table)
+                    .add_column(
+                        ColumnDef::new(Instruments::BarsUpdatedAt).timestamp_with_time_zone(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Instruments::Table)
+                    .drop_column(Instruments::BarsUpdatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
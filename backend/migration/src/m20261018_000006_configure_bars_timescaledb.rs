@@ -0,0 +1,136 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// bars のチャンク間隔 (デフォルトは 7 days)
+///
+/// 7 日では日足の圧縮セグメントが 5 行程度になり圧縮が効かない。
+/// 一方で分足を取り込んだ際に 1 チャンクがメモリに収まる大きさに抑えるため 30 日とする。
+const CHUNK_TIME_INTERVAL: &str = "30 days";
+
+/// この期間より古いチャンクを圧縮する (初期値)
+///
+/// バックフィル等で古いチャンクに書き込むことはあるが、圧縮済みチャンクへの upsert も可能。
+/// 起動時に環境変数 `BARS_COMPRESS_AFTER_DAYS` で変更できる。
+const COMPRESS_AFTER: &str = "60 days";
+
+/// 分足・時間足の保持期間の初期値 (timeframe, 日数)
+///
+/// 日足は保持期間を設けず、すべて保持する。
+const DEFAULT_RETENTION_DAYS: [(&str, i32); 4] =
+    [("1m", 30), ("5m", 90), ("15m", 180), ("1h", 365)];
+
+/// bar_retention_policies テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum BarRetentionPolicies {
+    Table,
+    Timeframe,
+    RetentionDays,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // TimescaleDB の設定は sea-query DSL では表現できないため raw SQL
+        db.execute_unprepared(&format!(
+            "SELECT set_chunk_time_interval('bars', INTERVAL '{CHUNK_TIME_INTERVAL}')"
+        ))
+        .await?;
+
+        // 銘柄・時間足ごとにセグメント化し、時系列の読み出しに合わせて timestamp 降順で圧縮する
+        db.execute_unprepared(
+            "ALTER TABLE bars SET (\
+                timescaledb.compress, \
+                timescaledb.compress_segmentby = 'instrument_id, timeframe', \
+                timescaledb.compress_orderby = 'timestamp DESC'\
+            )",
+        )
+        .await?;
+        db.execute_unprepared(&format!(
+            "SELECT add_compression_policy('bars', INTERVAL '{COMPRESS_AFTER}')"
+        ))
+        .await?;
+
+        // bar_retention_policies: 時間足ごとの保持期間 (行がない時間足は無期限に保持する)
+        manager
+            .create_table(
+                Table::create()
+                    .table(BarRetentionPolicies::Table)
+                    .col(
+                        ColumnDef::new(BarRetentionPolicies::Timeframe)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BarRetentionPolicies::RetentionDays)
+                            .integer()
+                            .not_null(),
+                    )
+                    .check(Expr::col(BarRetentionPolicies::Timeframe).ne("1d"))
+                    .check(Expr::col(BarRetentionPolicies::RetentionDays).gt(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        let mut insert = Query::insert();
+        insert.into_table(BarRetentionPolicies::Table).columns([
+            BarRetentionPolicies::Timeframe,
+            BarRetentionPolicies::RetentionDays,
+        ]);
+        for (timeframe, days) in DEFAULT_RETENTION_DAYS {
+            insert.values_panic([timeframe.into(), days.into()]);
+        }
+        manager.exec_stmt(insert).await?;
+
+        // 保持期間を過ぎたバーを削除する TimescaleDB のジョブ
+        // (add_retention_policy はチャンク単位で全時間足を削除するため使えない)
+        db.execute_unprepared(
+            "CREATE PROCEDURE bars_apply_retention(job_id integer, config jsonb) \
+             LANGUAGE plpgsql AS $$ \
+             BEGIN \
+                 DELETE FROM bars b \
+                 USING bar_retention_policies p \
+                 WHERE b.timeframe = p.timeframe \
+                   AND b.timestamp < now() - make_interval(days => p.retention_days); \
+             END \
+             $$",
+        )
+        .await?;
+        db.execute_unprepared("SELECT add_job('bars_apply_retention', INTERVAL '1 day')")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "SELECT delete_job(job_id) FROM timescaledb_information.jobs \
+             WHERE proc_name = 'bars_apply_retention'",
+        )
+        .await?;
+        db.execute_unprepared("DROP PROCEDURE bars_apply_retention(integer, jsonb)")
+            .await?;
+        manager
+            .drop_table(Table::drop().table(BarRetentionPolicies::Table).to_owned())
+            .await?;
+
+        db.execute_unprepared("SELECT remove_compression_policy('bars')")
+            .await?;
+        db.execute_unprepared(
+            "SELECT decompress_chunk(chunk, true) FROM show_chunks('bars') AS chunk",
+        )
+        .await?;
+        db.execute_unprepared("ALTER TABLE bars SET (timescaledb.compress = false)")
+            .await?;
+        db.execute_unprepared("SELECT set_chunk_time_interval('bars', INTERVAL '7 days')")
+            .await?;
+
+        Ok(())
+    }
+}
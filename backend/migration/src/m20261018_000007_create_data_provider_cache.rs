@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// data_provider_cache テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum DataProviderCache {
+    Table,
+    InstrumentId,
+    Timeframe,
+    FromDate,
+    ToDate,
+    Bars,
+    FetchedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // data_provider_cache: DataProvider から取得済みの期間と、そのレスポンスのバー
+        // bars: 期間内に取得したバーの配列 (データのない期間は空配列)
+        manager
+            .create_table(
+                Table::create()
+                    .table(DataProviderCache::Table)
+                    .col(
+                        ColumnDef::new(DataProviderCache::InstrumentId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(DataProviderCache::Timeframe)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(DataProviderCache::FromDate)
+                            .date()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(DataProviderCache::ToDate).date().not_null())
+                    .col(
+                        ColumnDef::new(DataProviderCache::Bars)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(DataProviderCache::FetchedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(DataProviderCache::InstrumentId)
+                            .col(DataProviderCache::Timeframe)
+                            .col(DataProviderCache::FromDate)
+                            .col(DataProviderCache::ToDate),
+                    )
+                    .check(
+                        Expr::col(DataProviderCache::FromDate)
+                            .lte(Expr::col(DataProviderCache::ToDate)),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DataProviderCache::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// bars テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum Bars {
+    Table,
+    Source,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // source: バーの取得元 (DataProvider 名や "csv_import"。このカラム追加前の行は NULL)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Bars::Table)
+                    .add_column(ColumnDef::new(Bars::Source).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Bars::Table)
+                    .drop_column(Bars::Source)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// api_rate_limits テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum ApiRateLimits {
+    Table,
+    Key,
+    State,
+    UpdatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // api_rate_limits: 外部 API のレートリミットの状態 (全プロセスで共有する)
+        // key: レートリミットを共有する単位 (例: "jquants:<API キーの SHA-256>")
+        // state: 直近の送信時刻や現在の上限等 (行ロックを取得して読み書きする)
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiRateLimits::Table)
+                    .col(
+                        ColumnDef::new(ApiRateLimits::Key)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ApiRateLimits::State)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApiRateLimits::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiRateLimits::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// data_fetch_jobs テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum DataFetchJobs {
+    Table,
+    Id,
+    InstrumentId,
+    FromDate,
+    ToDate,
+    Status,
+    BarCount,
+    Error,
+    CreatedAt,
+    FinishedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // data_fetch_jobs: バー取得時に欠損をデータプロバイダーから取得するジョブ
+        // status: "running" / "succeeded" / "failed"
+        // bar_count: 保存したバーの数 (成功時のみ)
+        // error: 失敗時のエラーメッセージ
+        manager
+            .create_table(
+                Table::create()
+                    .table(DataFetchJobs::Table)
+                    .col(
+                        ColumnDef::new(DataFetchJobs::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(
+                        ColumnDef::new(DataFetchJobs::InstrumentId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(DataFetchJobs::FromDate).date().not_null())
+                    .col(ColumnDef::new(DataFetchJobs::ToDate).date().not_null())
+                    .col(ColumnDef::new(DataFetchJobs::Status).string().not_null())
+                    .col(ColumnDef::new(DataFetchJobs::BarCount).integer().null())
+                    .col(ColumnDef::new(DataFetchJobs::Error).text().null())
+                    .col(
+                        ColumnDef::new(DataFetchJobs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(DataFetchJobs::FinishedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .check(Expr::col(DataFetchJobs::FromDate).lte(Expr::col(DataFetchJobs::ToDate)))
+                    .to_owned(),
+            )
+            .await?;
+
+        // 実行中のジョブを銘柄で検索するためのインデックス
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_data_fetch_jobs_instrument_id_status")
+                    .table(DataFetchJobs::Table)
+                    .col(DataFetchJobs::InstrumentId)
+                    .col(DataFetchJobs::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DataFetchJobs::Table).to_owned())
+            .await
+    }
+}
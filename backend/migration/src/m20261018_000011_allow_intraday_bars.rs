@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// bars に保存できる時間足
+///
+/// 分足・時間足は bar_retention_policies の保持期間に従って削除する。
+const TIMEFRAMES: [&str; 5] = ["1m", "5m", "15m", "1h", "1d"];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // 初期スキーマの CHECK は日足のみを許可しており、保持期間の対象になる行を保存できない
+        let timeframes = TIMEFRAMES
+            .iter()
+            .map(|timeframe| format!("'{timeframe}'"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        db.execute_unprepared(&format!(
+            "ALTER TABLE bars \
+             DROP CONSTRAINT bars_timeframe_check, \
+             ADD CONSTRAINT bars_timeframe_check CHECK (timeframe IN ({timeframes}))"
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DELETE FROM bars WHERE timeframe <> '1d'")
+            .await?;
+        db.execute_unprepared(
+            "ALTER TABLE bars \
+             DROP CONSTRAINT bars_timeframe_check, \
+             ADD CONSTRAINT bars_timeframe_check CHECK (timeframe = '1d')",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// この日数より前に取得した data_provider_cache の記録を削除する (初期値)
+///
+/// バーのある日は bars テーブルからも応答できるため、記録を削除しても再取得するのは
+/// データのない日と隔離されたバーの日のみ。
+const MAX_AGE_DAYS: i32 = 30;
+
+/// data_provider_cache テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum DataProviderCache {
+    Table,
+    FetchedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_data_provider_cache_fetched_at")
+                    .table(DataProviderCache::Table)
+                    .col(DataProviderCache::FetchedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // 古い記録を削除する TimescaleDB のジョブ (保持日数はジョブの config の max_age_days)
+        db.execute_unprepared(
+            "CREATE PROCEDURE data_provider_cache_prune(job_id integer, config jsonb) \
+             LANGUAGE plpgsql AS $$ \
+             BEGIN \
+                 DELETE FROM data_provider_cache \
+                 WHERE fetched_at < now() - make_interval(days => (config->>'max_age_days')::integer); \
+             END \
+             $$",
+        )
+        .await?;
+        db.execute_unprepared(&format!(
+            "SELECT add_job('data_provider_cache_prune', INTERVAL '1 day', \
+             config => '{{\"max_age_days\": {MAX_AGE_DAYS}}}')"
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "SELECT delete_job(job_id) FROM timescaledb_information.jobs \
+             WHERE proc_name = 'data_provider_cache_prune'",
+        )
+        .await?;
+        db.execute_unprepared("DROP PROCEDURE data_provider_cache_prune(integer, jsonb)")
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_data_provider_cache_fetched_at")
+                    .table(DataProviderCache::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// この日数より前に終了した data_fetch_jobs のジョブを削除する (初期値)
+const MAX_AGE_DAYS: i32 = 7;
+
+/// この時間 (分) を過ぎても実行中のジョブを、終了を記録できなかったものとして失敗にする (初期値)
+///
+/// バックエンドの `fetch_jobs::STALE_JOB_AFTER` と合わせる。
+const STALE_AFTER_MINUTES: i32 = 10;
+
+/// 失敗にしたジョブに記録するエラーメッセージ (`data_fetch_jobs::ABANDONED_ERROR` と合わせる)
+const ABANDONED_ERROR: &str = "job was abandoned before it finished";
+
+/// data_fetch_jobs テーブルのカラム識別子
+#[derive(DeriveIden)]
+enum DataFetchJobs {
+    Table,
+    FinishedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // 同じ銘柄・期間の実行中のジョブが重複していれば、最新のもの以外を失敗にする
+        db.execute_unprepared(&format!(
+            "UPDATE data_fetch_jobs AS j \
+             SET status = 'failed', error = '{ABANDONED_ERROR}', finished_at = now() \
+             WHERE j.status = 'running' AND EXISTS ( \
+                 SELECT 1 FROM data_fetch_jobs AS o \
+                 WHERE o.status = 'running' \
+                   AND o.instrument_id = j.instrument_id \
+                   AND o.from_date = j.from_date \
+                   AND o.to_date = j.to_date \
+                   AND (o.created_at, o.id) > (j.created_at, j.id))"
+        ))
+        .await?;
+
+        // 実行中のジョブは銘柄・期間ごとに 1 つ (同時に開始したリクエストで重複させない)
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX uq_data_fetch_jobs_running \
+             ON data_fetch_jobs (instrument_id, from_date, to_date) \
+             WHERE status = 'running'",
+        )
+        .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_data_fetch_jobs_finished_at")
+                    .table(DataFetchJobs::Table)
+                    .col(DataFetchJobs::FinishedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // 終了を記録できなかったジョブを失敗にし、古いジョブを削除する TimescaleDB のジョブ
+        // (しきい値はジョブの config の stale_after_minutes と max_age_days)
+        db.execute_unprepared(&format!(
+            "CREATE PROCEDURE data_fetch_jobs_prune(job_id integer, config jsonb) \
+             LANGUAGE plpgsql AS $$ \
+             BEGIN \
+                 UPDATE data_fetch_jobs \
+                 SET status = 'failed', error = '{ABANDONED_ERROR}', finished_at = now() \
+                 WHERE status = 'running' \
+                   AND created_at < now() - make_interval(mins => (config->>'stale_after_minutes')::integer); \
+                 DELETE FROM data_fetch_jobs \
+                 WHERE finished_at < now() - make_interval(days => (config->>'max_age_days')::integer); \
+             END \
+             $$"
+        ))
+        .await?;
+        db.execute_unprepared(&format!(
+            "SELECT add_job('data_fetch_jobs_prune', INTERVAL '1 hour', \
+             config => '{{\"max_age_days\": {MAX_AGE_DAYS}, \"stale_after_minutes\": {STALE_AFTER_MINUTES}}}')"
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "SELECT delete_job(job_id) FROM timescaledb_information.jobs \
+             WHERE proc_name = 'data_fetch_jobs_prune'",
+        )
+        .await?;
+        db.execute_unprepared("DROP PROCEDURE data_fetch_jobs_prune(integer, jsonb)")
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_data_fetch_jobs_finished_at")
+                    .table(DataFetchJobs::Table)
+                    .to_owned(),
+            )
+            .await?;
+        db.execute_unprepared("DROP INDEX uq_data_fetch_jobs_running")
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! 東京証券取引所 (JPX) の営業日カレンダー
+//!
+//! 土日、国民の祝日 (振替休日・国民の休日を含む)、年末年始 (12/31 ~ 1/3) を休業日とする。
+//! 祝日は 2000 ~ 2099 年の祝日法に基づいて計算する (春分・秋分の日は近似式による)。
+//! この範囲外の日付は祝日を判定できないため、土日と年末年始以外を営業日とみなす。
+
+use std::ops::RangeInclusive;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// 祝日を判定できる年の範囲
+pub const SUPPORTED_YEARS: RangeInclusive<i32> = 2000..=2099;
+
+/// 祝日を判定できる日付かどうか
+pub fn is_supported(date: NaiveDate) -> bool {
+    SUPPORTED_YEARS.contains(&date.year())
+}
+
+/// 東証の営業日かどうか
+///
+/// 祝日を判定できない日付 ([`SUPPORTED_YEARS`] の範囲外) は祝日を休業日としない。
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+        && !is_year_end_holiday(date)
+        && (!is_supported(date) || !is_holiday(date))
+}
+
+/// 年末年始の休業日 (12/31 ~ 1/3) かどうか
+fn is_year_end_holiday(date: NaiveDate) -> bool {
+    matches!((date.month(), date.day()), (12, 31) | (1, 1..=3))
+}
+
+/// 国民の祝日・振替休日・国民の休日かどうか
+pub fn is_holiday(date: NaiveDate) -> bool {
+    is_national_holiday(date) || is_substitute_holiday(date) || is_citizens_holiday(date)
+}
+
+/// 振替休日: 日曜の祝日の後、最初の祝日でない日
+///
+/// 2006 年以前は日曜の祝日の翌日 (月曜) のみ。
+fn is_substitute_holiday(date: NaiveDate) -> bool {
+    if is_national_holiday(date) || date.weekday() == Weekday::Sun {
+        return false;
+    }
+
+    let mut day = date.pred_opt();
+    while let Some(d) = day.filter(|d| is_national_holiday(*d)) {
+        if d.weekday() == Weekday::Sun {
+            return true;
+        }
+        if date.year() < 2007 {
+            return false;
+        }
+        day = d.pred_opt();
+    }
+    false
+}
+
+/// 国民の休日: 前日と翌日が祝日に挟まれた平日
+fn is_citizens_holiday(date: NaiveDate) -> bool {
+    !is_national_holiday(date)
+        && date.weekday() != Weekday::Sun
+        && date.pred_opt().is_some_and(is_national_holiday)
+        && date.succ_opt().is_some_and(is_national_holiday)
+}
+
+/// 祝日法で定められた国民の祝日 (振替休日・国民の休日を除く)
+fn is_national_holiday(date: NaiveDate) -> bool {
+    let year = date.year();
+    let day = date.day();
+    let nth_monday = |n: u8| {
+        date.weekday() == Weekday::Mon && u8::try_from((day - 1) / 7 + 1).is_ok_and(|i| i == n)
+    };
+
+    match date.month() {
+        // 元日, 成人の日
+        1 => day == 1 || nth_monday(2),
+        // 建国記念の日, 天皇誕生日 (2020 年 ~)
+        2 => day == 11 || (year >= 2020 && day == 23),
+        // 春分の日
+        3 => day == vernal_equinox_day(year),
+        // 昭和の日 (2006 年以前はみどりの日), 2019 年の即位に伴う休日
+        4 => day == 29 || (year == 2019 && day == 30),
+        // 憲法記念日, みどりの日 (2007 年 ~), こどもの日, 2019 年の即位の日
+        5 => matches!(day, 3..=5) || (year == 2019 && day == 1),
+        // 海の日 (2020, 2021 年は東京オリンピックに伴う移動, スポーツの日を含む)
+        7 => match year {
+            2020 => matches!(day, 23 | 24),
+            2021 => matches!(day, 22 | 23),
+            ..=2002 => day == 20,
+            _ => nth_monday(3),
+        },
+        // 山の日 (2016 年 ~)
+        8 => match year {
+            2020 => day == 10,
+            2021 => day == 8,
+            2016.. => day == 11,
+            _ => false,
+        },
+        // 敬老の日, 秋分の日
+        9 => {
+            let respect_for_aged = if year <= 2002 {
+                day == 15
+            } else {
+                nth_monday(3)
+            };
+            respect_for_aged || day == autumnal_equinox_day(year)
+        }
+        // 体育の日・スポーツの日, 2019 年の即位礼正殿の儀
+        10 => (!matches!(year, 2020 | 2021) && nth_monday(2)) || (year == 2019 && day == 22),
+        // 文化の日, 勤労感謝の日
+        11 => matches!(day, 3 | 23),
+        // 天皇誕生日 (~ 2018 年)
+        12 => year <= 2018 && day == 23,
+        _ => false,
+    }
+}
+
+/// 春分の日 (1980 ~ 2099 年の近似式)
+fn vernal_equinox_day(year: i32) -> u32 {
+    equinox_day(20.8431, year)
+}
+
+/// 秋分の日 (1980 ~ 2099 年の近似式)
+fn autumnal_equinox_day(year: i32) -> u32 {
+    equinox_day(23.2488, year)
+}
+
+fn equinox_day(base: f64, year: i32) -> u32 {
+    let elapsed = year - 1980;
+    let day = base + 0.242194 * f64::from(elapsed) - f64::from(elapsed.div_euclid(4));
+    // 近似式の値は 19 ~ 24 の範囲に収まる
+    day.floor() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[rstest]
+    #[case::weekday(date(2025, 1, 6), true)]
+    #[case::saturday(date(2025, 6, 7), false)]
+    #[case::sunday(date(2025, 6, 8), false)]
+    #[case::new_year(date(2025, 1, 1), false)]
+    #[case::new_year_holidays(date(2025, 1, 3), false)]
+    #[case::year_end(date(2025, 12, 31), false)]
+    #[case::last_trading_day(date(2025, 12, 30), true)]
+    #[case::coming_of_age_day(date(2025, 1, 13), false)]
+    #[case::emperors_birthday_substitute(date(2025, 2, 24), false)]
+    #[case::vernal_equinox(date(2025, 3, 20), false)]
+    #[case::golden_week_substitute(date(2025, 5, 6), false)]
+    #[case::marine_day(date(2025, 7, 21), false)]
+    #[case::mountain_day_substitute(date(2024, 8, 12), false)]
+    #[case::autumnal_equinox(date(2025, 9, 23), false)]
+    #[case::citizens_holiday(date(2026, 9, 22), false)]
+    #[case::sports_day(date(2025, 10, 13), false)]
+    #[case::olympics_marine_day(date(2021, 7, 22), false)]
+    #[case::olympics_moved_sports_day(date(2021, 10, 11), true)]
+    #[case::enthronement(date(2019, 5, 1), false)]
+    #[case::old_emperors_birthday(date(2019, 12, 23), true)]
+    #[case::unsupported_weekday(date(1999, 1, 11), true)]
+    #[case::unsupported_weekend(date(1999, 1, 9), false)]
+    #[case::unsupported_year_end(date(1999, 12, 31), false)]
+    fn test_is_trading_day(#[case] date: NaiveDate, #[case] expected: bool) {
+        assert_eq!(is_trading_day(date), expected);
+    }
+}
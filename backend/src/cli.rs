@@ -1,4 +1,8 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::services::bar_import::BarCsvLayout;
 
 /// T-Rader バックエンドサーバー
 #[derive(Parser, Debug, PartialEq, Eq)]
@@ -15,6 +19,35 @@ pub struct Cli {
     /// マイグレーションをスキップしてサーバーを起動する
     #[arg(long, conflicts_with = "migrate_only")]
     pub skip_migration: bool,
+
+    /// サーバーを起動せずに実行するサブコマンド
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// サブコマンド (マイグレーション実行後に処理して終了する)
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// OHLCV の日足 CSV を一括インポートする
+    ImportBars(ImportBarsArgs),
+}
+
+/// `import-bars` サブコマンドの引数
+#[derive(clap::Args, Debug, PartialEq, Eq)]
+pub struct ImportBarsArgs {
+    /// インポートする CSV ファイル
+    pub file: PathBuf,
+
+    /// 全行をこの銘柄コードとして扱う (銘柄コード列がない CSV 用)
+    #[arg(long)]
+    pub instrument_id: Option<String>,
+
+    /// DB に書き込まず、既存データとの差分のみをレポートする
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub layout: BarCsvLayout,
 }
 
 #[cfg(test)]
@@ -27,15 +60,46 @@ mod tests {
     }
 
     #[rstest]
-    #[case::no_flags(&["t-rader"], Cli { dump_openapi: false, migrate_only: false, skip_migration: false })]
-    #[case::dump_openapi(&["t-rader", "--dump-openapi"], Cli { dump_openapi: true, migrate_only: false, skip_migration: false })]
-    #[case::migrate_only(&["t-rader", "--migrate-only"], Cli { dump_openapi: false, migrate_only: true, skip_migration: false })]
-    #[case::skip_migration(&["t-rader", "--skip-migration"], Cli { dump_openapi: false, migrate_only: false, skip_migration: true })]
+    #[case::no_flags(&["t-rader"], Cli { dump_openapi: false, migrate_only: false, skip_migration: false, command: None })]
+    #[case::dump_openapi(&["t-rader", "--dump-openapi"], Cli { dump_openapi: true, migrate_only: false, skip_migration: false, command: None })]
+    #[case::migrate_only(&["t-rader", "--migrate-only"], Cli { dump_openapi: false, migrate_only: true, skip_migration: false, command: None })]
+    #[case::skip_migration(&["t-rader", "--skip-migration"], Cli { dump_openapi: false, migrate_only: false, skip_migration: true, command: None })]
     fn test_parse_valid_flags(#[case] args: &[&str], #[case] expected: Cli) {
         let cli = parse(args);
         assert_eq!(cli.ok(), Some(expected));
     }
 
+    #[rstest]
+    fn test_parse_import_bars() {
+        let cli = parse(&[
+            "t-rader",
+            "import-bars",
+            "bars.csv",
+            "--instrument-id",
+            "7203",
+            "--dry-run",
+            "--date-format",
+            "%Y/%m/%d",
+            "--delimiter",
+            ";",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            cli.command,
+            Some(Command::ImportBars(ImportBarsArgs {
+                file: PathBuf::from("bars.csv"),
+                instrument_id: Some("7203".to_string()),
+                dry_run: true,
+                layout: BarCsvLayout {
+                    date_format: "%Y/%m/%d".to_string(),
+                    delimiter: ';',
+                    ..Default::default()
+                },
+            }))
+        );
+    }
+
     #[rstest]
     #[case::migrate_only_and_skip_migration(&["t-rader", "--migrate-only", "--skip-migration"])]
     fn test_parse_conflicting_flags(#[case] args: &[&str]) {
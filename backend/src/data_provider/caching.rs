@@ -0,0 +1,519 @@
+//! DataProvider の前段に置くローカルキャッシュ
+//!
+//! `bars` テーブルに保存済みの日足と、`data_provider_cache` テーブルに記録した
+//! 取得済み期間のレスポンスから応答し、未取得の期間のみを内側のプロバイダーに問い合わせる。
+
+use std::collections::{BTreeMap, HashSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures_util::StreamExt;
+use futures_util::stream::{self, BoxStream};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+
+use crate::calendar;
+use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::entities::{bars, data_provider_cache};
+use crate::models::{Bar, CircuitBreakerStatus, Instrument, ProviderHealth, Timeframe};
+
+/// データのない日を取得済みとみなす期間
+///
+/// 取得した期間内でバーのない営業日は、上場前や売買停止のほか、配信の遅延
+/// (J-Quants Free プランは 12 週間遅れ) でまだ取得できなかった可能性がある。
+/// この期間を過ぎたら再び内側のプロバイダーに問い合わせる。
+const EMPTY_COVERAGE_TTL: Duration = Duration::days(1);
+
+/// 1 回の取得で内側のプロバイダーに問い合わせる期間の最大数
+///
+/// 未取得の期間がこれより多く分かれている場合は、最初から最後の未取得日までを 1 回で取得する。
+const MAX_UNCOVERED_RANGES: usize = 3;
+
+/// キャッシュ付き DataProvider
+///
+/// 日足の取得のみをキャッシュし、銘柄情報の取得はそのまま内側のプロバイダーに委譲する。
+/// キャッシュの読み書きに失敗した場合はログを出力し、キャッシュなしで動作する。
+/// 当日・前日のデータは未確定の可能性があるため取得済みとして記録しない。
+/// 記録は `data_provider_cache_prune` ジョブが古いものから削除する。
+pub struct CachingProvider<P> {
+    db: DatabaseConnection,
+    inner: P,
+}
+
+/// キャッシュから読み込んだ日足
+struct CachedBars {
+    /// タイムスタンプ順のバー
+    bars: BTreeMap<DateTime<Utc>, Bar>,
+    /// 取得済みの日付
+    covered: HashSet<NaiveDate>,
+}
+
+impl<P> CachingProvider<P> {
+    pub fn new(db: DatabaseConnection, inner: P) -> Self {
+        Self { db, inner }
+    }
+
+    /// `bars` テーブルと取得済み期間のキャッシュから、期間内の日足を読み込む
+    ///
+    /// `bars` テーブルのバー (検証済み) をキャッシュのレスポンスより優先する。
+    async fn load(&self, instrument_id: &str, range: &DateRange) -> Result<CachedBars, DbErr> {
+        let timeframe = Timeframe::Daily.to_string();
+        let mut cached = CachedBars {
+            bars: BTreeMap::new(),
+            covered: HashSet::new(),
+        };
+
+        let stored = bars::Entity::find()
+            .filter(bars::Column::InstrumentId.eq(instrument_id))
+            .filter(bars::Column::Timeframe.eq(&timeframe))
+            .filter(bars::Column::Timestamp.gte(start_of_day(range.from)))
+            .filter(bars::Column::Timestamp.lt(start_of_day(range.to + Duration::days(1))))
+            .all(&self.db)
+            .await?;
+        for model in stored {
+            let bar = Bar::from(model);
+            cached.covered.insert(bar.timestamp.date_naive());
+            cached.bars.insert(bar.timestamp, bar);
+        }
+
+        let entries = data_provider_cache::Entity::find()
+            .filter(data_provider_cache::Column::InstrumentId.eq(instrument_id))
+            .filter(data_provider_cache::Column::Timeframe.eq(&timeframe))
+            .filter(data_provider_cache::Column::FromDate.lte(range.to))
+            .filter(data_provider_cache::Column::ToDate.gte(range.from))
+            .all(&self.db)
+            .await?;
+        let empty_coverage_since = Utc::now() - EMPTY_COVERAGE_TTL;
+        for entry in entries {
+            // データのない日も含めて取得済みとするのは、取得から間もない記録のみ
+            if entry.fetched_at.to_utc() > empty_coverage_since {
+                let from = entry.from_date.max(range.from);
+                let to = entry.to_date.min(range.to);
+                cached
+                    .covered
+                    .extend(from.iter_days().take_while(|date| *date <= to));
+            }
+
+            let bars: Vec<Bar> = serde_json::from_value(entry.bars)
+                .map_err(|e| DbErr::Json(format!("invalid cached bars: {e}")))?;
+            for bar in bars {
+                let date = bar.timestamp.date_naive();
+                if range.from <= date && date <= range.to {
+                    cached.covered.insert(date);
+                    cached.bars.entry(bar.timestamp).or_insert(bar);
+                }
+            }
+        }
+
+        Ok(cached)
+    }
+
+    /// 内側のプロバイダーから取得した期間とレスポンスを記録する
+    async fn store(
+        &self,
+        instrument_id: &str,
+        range: &DateRange,
+        bars: &[Bar],
+    ) -> Result<(), DbErr> {
+        // 当日・前日のデータは未確定の可能性があるため記録しない
+        let to = range.to.min(Utc::now().date_naive() - Duration::days(2));
+        if to < range.from {
+            return Ok(());
+        }
+
+        let bars: Vec<&Bar> = bars
+            .iter()
+            .filter(|bar| bar.timestamp.date_naive() <= to)
+            .collect();
+        let bars = serde_json::to_value(bars)
+            .map_err(|e| DbErr::Json(format!("failed to serialize bars: {e}")))?;
+
+        data_provider_cache::Entity::insert(data_provider_cache::ActiveModel {
+            instrument_id: Set(instrument_id.to_string()),
+            timeframe: Set(Timeframe::Daily.to_string()),
+            from_date: Set(range.from),
+            to_date: Set(to),
+            bars: Set(bars),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([
+                data_provider_cache::Column::InstrumentId,
+                data_provider_cache::Column::Timeframe,
+                data_provider_cache::Column::FromDate,
+                data_provider_cache::Column::ToDate,
+            ])
+            .update_columns([
+                data_provider_cache::Column::Bars,
+                data_provider_cache::Column::FetchedAt,
+            ])
+            .to_owned(),
+        )
+        .exec_without_returning(&self.db)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl<P: DataProvider> CachingProvider<P> {
+    /// 未取得の期間を内側のプロバイダーからページ単位で取得し、全ページを取得できたら記録する
+    fn fetch_uncovered_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        let pages = self
+            .inner
+            .fetch_daily_bar_pages(instrument_id, range.clone());
+        stream::unfold(
+            Some((pages, Vec::new(), range)),
+            move |state| async move {
+                let (mut pages, mut fetched, range) = state?;
+                match pages.next().await {
+                    Some(Ok(page)) => {
+                        fetched.extend(page.iter().cloned());
+                        Some((Ok(page), Some((pages, fetched, range))))
+                    }
+                    // 途中で失敗した期間は取得済みとして記録しない
+                    Some(Err(e)) => Some((Err(e), None)),
+                    None => {
+                        if let Err(e) = self.store(instrument_id, &range, &fetched).await {
+                            tracing::warn!(instrument_id, error = %e, "キャッシュの書き込みに失敗しました");
+                        }
+                        None
+                    }
+                }
+            },
+        )
+        .boxed()
+    }
+}
+
+#[async_trait]
+impl<P: DataProvider> DataProvider for CachingProvider<P> {
+    async fn fetch_daily_bars(
+        &self,
+        instrument_id: &str,
+        range: &DateRange,
+    ) -> Result<Vec<Bar>, DataProviderError> {
+        let cached = match self.load(instrument_id, range).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!(instrument_id, error = %e, "キャッシュの読み込みに失敗しました");
+                return self.inner.fetch_daily_bars(instrument_id, range).await;
+            }
+        };
+
+        let uncovered = uncovered_ranges(range, &cached.covered);
+        tracing::debug!(
+            instrument_id,
+            cached_bars = cached.bars.len(),
+            uncovered_ranges = uncovered.len(),
+            "キャッシュから日足データを取得しました"
+        );
+
+        let mut bars = cached.bars;
+        for sub_range in uncovered {
+            let fetched = self
+                .inner
+                .fetch_daily_bars(instrument_id, &sub_range)
+                .await?;
+            if let Err(e) = self.store(instrument_id, &sub_range, &fetched).await {
+                tracing::warn!(instrument_id, error = %e, "キャッシュの書き込みに失敗しました");
+            }
+            bars.extend(fetched.into_iter().map(|bar| (bar.timestamp, bar)));
+        }
+
+        Ok(bars.into_values().collect())
+    }
+
+    /// キャッシュにある日足を最初のページとして返し、続けて未取得の期間を内側のプロバイダーから
+    /// ページ単位で返す
+    fn fetch_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        stream::once(async move {
+            let cached = match self.load(instrument_id, &range).await {
+                Ok(cached) => cached,
+                Err(e) => {
+                    tracing::warn!(instrument_id, error = %e, "キャッシュの読み込みに失敗しました");
+                    return self.inner.fetch_daily_bar_pages(instrument_id, range);
+                }
+            };
+
+            let uncovered = uncovered_ranges(&range, &cached.covered);
+            tracing::debug!(
+                instrument_id,
+                cached_bars = cached.bars.len(),
+                uncovered_ranges = uncovered.len(),
+                "キャッシュから日足データを取得しました"
+            );
+
+            let cached_page =
+                (!cached.bars.is_empty()).then(|| Ok(cached.bars.into_values().collect()));
+            stream::iter(cached_page)
+                .chain(stream::iter(uncovered).flat_map(move |sub_range| {
+                    self.fetch_uncovered_pages(instrument_id, sub_range)
+                }))
+                .boxed()
+        })
+        .flatten()
+        .boxed()
+    }
+
+    /// 再開した取得は期間の途中からのため、取得済みとして記録せずに内側のプロバイダーに委譲する
+    fn resume_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        pagination_key: String,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        self.inner
+            .resume_daily_bar_pages(instrument_id, range, pagination_key)
+    }
+
+    async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
+        self.inner.fetch_instrument(instrument_id).await
+    }
+
+    async fn health(&self) -> Vec<ProviderHealth> {
+        self.inner.health().await
+    }
+
+    async fn circuit_breaker(&self) -> Option<CircuitBreakerStatus> {
+        self.inner.circuit_breaker().await
+    }
+}
+
+/// 期間内で未取得の営業日を、連続する期間にまとめて返す
+///
+/// 休業日は取得済みかどうかに関係なく期間の区切りにしない。
+fn uncovered_ranges(range: &DateRange, covered: &HashSet<NaiveDate>) -> Vec<DateRange> {
+    let mut ranges: Vec<DateRange> = Vec::new();
+    let mut extending = false;
+
+    for date in range
+        .from
+        .iter_days()
+        .take_while(|date| *date <= range.to)
+        .filter(|date| calendar::is_trading_day(*date))
+    {
+        if covered.contains(&date) {
+            extending = false;
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if extending => last.to = date,
+            _ => ranges.push(DateRange {
+                from: date,
+                to: date,
+            }),
+        }
+        extending = true;
+    }
+
+    if ranges.len() > MAX_UNCOVERED_RANGES {
+        let from = ranges.first().map(|r| r.from).unwrap_or(range.from);
+        let to = ranges.last().map(|r| r.to).unwrap_or(range.to);
+        return vec![DateRange { from, to }];
+    }
+    ranges
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<chrono::FixedOffset> {
+    date.and_time(chrono::NaiveTime::MIN)
+        .and_utc()
+        .fixed_offset()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use futures_util::TryStreamExt;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::data_provider::mock::MockDataProvider;
+    use crate::models::instrument::Market;
+    use crate::repositories;
+    use crate::testing::create_test_db;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    fn range(from: u32, to: u32) -> DateRange {
+        DateRange {
+            from: date(from),
+            to: date(to),
+        }
+    }
+
+    fn make_bar(day: u32) -> Bar {
+        Bar {
+            instrument_id: "7203".to_string(),
+            timeframe: Timeframe::Daily,
+            timestamp: Utc.from_utc_datetime(&date(day).and_time(chrono::NaiveTime::MIN)),
+            open: Decimal::new(100, 0),
+            high: Decimal::new(110, 0),
+            low: Decimal::new(90, 0),
+            close: Decimal::new(100 + i64::from(day), 0),
+            volume: 1000,
+            source: None,
+        }
+    }
+
+    /// 2025-01-06 ~ 01-17 の営業日 (01-13 は成人の日)
+    const TRADING_DAYS: [u32; 9] = [6, 7, 8, 9, 10, 14, 15, 16, 17];
+
+    fn caching_provider(db: DatabaseConnection) -> CachingProvider<MockDataProvider> {
+        let inner = MockDataProvider::new()
+            .with_instruments(vec![Instrument {
+                id: "7203".to_string(),
+                name: "トヨタ自動車".to_string(),
+                market: Market::Tse,
+                sector: None,
+                market_segment: None,
+            }])
+            .with_bars(TRADING_DAYS.iter().map(|day| make_bar(*day)).collect());
+        CachingProvider::new(db, inner)
+    }
+
+    fn requests(provider: &CachingProvider<MockDataProvider>) -> Vec<DateRange> {
+        provider.inner.requests()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn repeated_request_is_served_from_cache(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let provider = caching_provider(db);
+
+        let first = provider
+            .fetch_daily_bars("7203", &range(6, 17))
+            .await
+            .unwrap();
+        let second = provider
+            .fetch_daily_bars("7203", &range(6, 17))
+            .await
+            .unwrap();
+
+        assert_eq!(first.len(), TRADING_DAYS.len());
+        assert_eq!(second, first);
+        assert_eq!(requests(&provider), vec![range(6, 17)]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn overlapping_request_forwards_only_uncovered_range(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let provider = caching_provider(db);
+
+        provider
+            .fetch_daily_bars("7203", &range(6, 10))
+            .await
+            .unwrap();
+        let bars = provider
+            .fetch_daily_bars("7203", &range(8, 17))
+            .await
+            .unwrap();
+
+        assert_eq!(bars.len(), 7);
+        assert_eq!(requests(&provider), vec![range(6, 10), range(14, 17)]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn pages_serve_cache_first_and_record_uncovered_range(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let provider = caching_provider(db);
+        provider
+            .fetch_daily_bars("7203", &range(6, 10))
+            .await
+            .unwrap();
+
+        let pages: Vec<Vec<Bar>> = provider
+            .fetch_daily_bar_pages("7203", range(8, 17))
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![3, 4]);
+        // 全ページを取得した期間は記録し、次回はキャッシュから応答する
+        provider
+            .fetch_daily_bars("7203", &range(6, 17))
+            .await
+            .unwrap();
+        assert_eq!(requests(&provider), vec![range(6, 10), range(14, 17)]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn bars_in_table_are_not_fetched(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        repositories::instruments::ensure_exists(&db, "7203", "トヨタ自動車".to_string())
+            .await
+            .unwrap();
+        let mut stored = make_bar(7);
+        stored.close = Decimal::new(999, 0);
+        repositories::bars::upsert_bars(&db, vec![make_bar(6), stored, make_bar(8)])
+            .await
+            .unwrap();
+        let provider = caching_provider(db);
+
+        let bars = provider
+            .fetch_daily_bars("7203", &range(6, 10))
+            .await
+            .unwrap();
+
+        assert_eq!(bars.len(), 5);
+        assert_eq!(bars[1].close, Decimal::new(999, 0));
+        assert_eq!(requests(&provider), vec![range(9, 10)]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn empty_coverage_expires(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let provider = caching_provider(db.clone());
+        provider
+            .fetch_daily_bars("7203", &range(6, 17))
+            .await
+            .unwrap();
+        // 取得時には 01-14 以降のデータがなかった (配信の遅延等) ものとして、取得日時を過去にする
+        data_provider_cache::Entity::update_many()
+            .col_expr(
+                data_provider_cache::Column::Bars,
+                sea_orm::sea_query::Expr::value(
+                    serde_json::to_value((6..=10).map(make_bar).collect::<Vec<_>>()).unwrap(),
+                ),
+            )
+            .col_expr(
+                data_provider_cache::Column::FetchedAt,
+                sea_orm::sea_query::Expr::value((Utc::now() - Duration::days(2)).fixed_offset()),
+            )
+            .exec(&db)
+            .await
+            .unwrap();
+
+        let bars = provider
+            .fetch_daily_bars("7203", &range(6, 17))
+            .await
+            .unwrap();
+
+        assert_eq!(bars.len(), TRADING_DAYS.len());
+        // バーのある日はキャッシュから応答し、データのなかった日のみ再取得する
+        assert_eq!(requests(&provider), vec![range(6, 17), range(14, 17)]);
+    }
+
+    #[rstest]
+    #[case::all_covered(&[6, 7, 8, 9, 10, 14, 15, 16, 17], vec![])]
+    #[case::none_covered(&[], vec![range(6, 17)])]
+    #[case::holiday_does_not_split(&[6, 7, 8], vec![range(9, 17)])]
+    #[case::gaps(&[7, 10], vec![range(6, 6), range(8, 9), range(14, 17)])]
+    #[case::too_many_gaps(&[7, 9, 14, 16], vec![range(6, 17)])]
+    fn test_uncovered_ranges(#[case] covered: &[u32], #[case] expected: Vec<DateRange>) {
+        let covered = covered.iter().map(|day| date(*day)).collect();
+        assert_eq!(uncovered_ranges(&range(6, 17), &covered), expected);
+    }
+}
@@ -0,0 +1,413 @@
+//! ローカルの CSV ディレクトリから読み込む DataProvider
+//!
+//! 購入したヒストリカルデータ等を J-Quants API の代わりに使うためのプロバイダー。
+//! ディレクトリは以下のレイアウトで配置する。
+//!
+//! ```text
+//! <dir>/daily/<銘柄コード>.csv      日足 (標準形式。列構成は BarCsvLayout の既定値)
+//! <dir>/daily/<銘柄コード>.jp.txt   日足 (Stooq 形式。Stooq のファイルをそのまま置く)
+//! <dir>/instruments.csv             銘柄情報 (任意。code,name,sector,market_segment 列)
+//! ```
+//!
+//! 日足の CSV は 1 ファイル 1 銘柄で、銘柄コード列の値は無視してファイル名の銘柄として扱う。
+//! 日付列は日付順でなくてもよい。
+//! `instruments.csv` は最初に銘柄情報を取得したときに 1 回だけ読み込む (変更はプロセスの再起動後に反映される)。
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::models::instrument::Market;
+use crate::models::{Bar, Instrument};
+use crate::services::bar_import::{self, BarCsvLayout};
+
+/// CSV ディレクトリから読み込んだバーの取得元 (`bars.source`)
+pub const SOURCE: &str = "csv_directory";
+
+const DAILY_DIR: &str = "daily";
+const INSTRUMENTS_FILE: &str = "instruments.csv";
+
+/// 日足 CSV の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvFormat {
+    /// `date,open,high,low,close,volume` (日付は YYYY-MM-DD)
+    #[default]
+    Standard,
+    /// Stooq のヒストリカルデータ (`<DATE>` は YYYYMMDD)
+    Stooq,
+}
+
+impl CsvFormat {
+    fn layout(self) -> BarCsvLayout {
+        match self {
+            CsvFormat::Standard => BarCsvLayout::default(),
+            CsvFormat::Stooq => BarCsvLayout::stooq(),
+        }
+    }
+
+    fn file_name(self, instrument_id: &str) -> String {
+        match self {
+            CsvFormat::Standard => format!("{instrument_id}.csv"),
+            CsvFormat::Stooq => format!("{}.jp.txt", instrument_id.to_ascii_lowercase()),
+        }
+    }
+}
+
+impl FromStr for CsvFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(CsvFormat::Standard),
+            "stooq" => Ok(CsvFormat::Stooq),
+            _ => Err(format!(
+                "unknown CSV format '{s}' (expected standard or stooq)"
+            )),
+        }
+    }
+}
+
+/// `instruments.csv` の 1 行
+#[derive(Debug, Deserialize)]
+struct InstrumentRecord {
+    code: String,
+    name: String,
+    #[serde(default)]
+    sector: Option<String>,
+    #[serde(default)]
+    market_segment: Option<String>,
+}
+
+/// CSV ディレクトリから日足と銘柄情報を読み込む DataProvider
+///
+/// 日足のファイルがない銘柄には NotFound を返す。
+/// 検証に失敗した行はログを出力して読み飛ばす。
+/// `instruments.csv` にない銘柄は、日足のファイルがあれば銘柄コードを銘柄名として返す。
+pub struct CsvDirectoryProvider {
+    dir: PathBuf,
+    format: CsvFormat,
+    /// `instruments.csv` の銘柄情報 (銘柄コード → 銘柄情報)
+    instruments: OnceCell<HashMap<String, Instrument>>,
+}
+
+impl CsvDirectoryProvider {
+    pub fn new(dir: PathBuf, format: CsvFormat) -> Self {
+        Self {
+            dir,
+            format,
+            instruments: OnceCell::new(),
+        }
+    }
+
+    /// 日足ファイルのパス
+    ///
+    /// 銘柄コードをファイル名に使うため、英数字以外を含むコードは拒否する。
+    fn daily_path(&self, instrument_id: &str) -> Result<PathBuf, DataProviderError> {
+        if instrument_id.is_empty() || !instrument_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(not_found(instrument_id));
+        }
+        Ok(self
+            .dir
+            .join(DAILY_DIR)
+            .join(self.format.file_name(instrument_id)))
+    }
+
+    /// `instruments.csv` の銘柄情報 (初回のみ読み込む。ファイルがなければ空)
+    ///
+    /// 読み込みに失敗した場合は記録せず、次の呼び出しで読み込み直す。
+    async fn instruments(&self) -> Result<&HashMap<String, Instrument>, DataProviderError> {
+        self.instruments
+            .get_or_try_init(|| load_instruments(self.dir.join(INSTRUMENTS_FILE)))
+            .await
+    }
+}
+
+#[async_trait]
+impl DataProvider for CsvDirectoryProvider {
+    async fn fetch_daily_bars(
+        &self,
+        instrument_id: &str,
+        range: &DateRange,
+    ) -> Result<Vec<Bar>, DataProviderError> {
+        let path = self.daily_path(instrument_id)?;
+        let content = read_to_string(&path)
+            .await?
+            .ok_or_else(|| not_found(instrument_id))?;
+
+        let parsed = bar_import::parse_csv(&content, &self.format.layout(), Some(instrument_id))
+            .map_err(|e| DataProviderError::File(format!("{}: {e}", path.display())))?;
+        if let Some(first) = parsed.errors.first() {
+            tracing::warn!(
+                path = %path.display(),
+                skipped_rows = parsed.errors.len(),
+                line = first.line,
+                error = first.message,
+                "CSV の不正な行を読み飛ばしました"
+            );
+        }
+
+        let mut bars: Vec<Bar> = parsed
+            .bars
+            .into_iter()
+            .filter(|bar| {
+                let date = bar.timestamp.date_naive();
+                range.from <= date && date <= range.to
+            })
+            .map(|bar| Bar {
+                source: Some(SOURCE.to_string()),
+                ..bar
+            })
+            .collect();
+        bars.sort_by_key(|b| b.timestamp);
+        Ok(bars)
+    }
+
+    async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
+        if let Some(instrument) = self.instruments().await?.get(instrument_id) {
+            return Ok(instrument.clone());
+        }
+
+        let path = self.daily_path(instrument_id)?;
+        if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            return Err(not_found(instrument_id));
+        }
+        Ok(Instrument {
+            id: instrument_id.to_string(),
+            name: instrument_id.to_string(),
+            market: Market::Tse,
+            sector: None,
+            market_segment: None,
+        })
+    }
+}
+
+fn not_found(instrument_id: &str) -> DataProviderError {
+    DataProviderError::NotFound(format!("instrument '{instrument_id}' not found"))
+}
+
+/// `instruments.csv` を読み込む。存在しない場合は空の銘柄情報を返す
+async fn load_instruments(path: PathBuf) -> Result<HashMap<String, Instrument>, DataProviderError> {
+    let Some(content) = read_to_string(&path).await? else {
+        return Ok(HashMap::new());
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    reader
+        .deserialize::<InstrumentRecord>()
+        .map(|record| {
+            let record =
+                record.map_err(|e| DataProviderError::File(format!("{}: {e}", path.display())))?;
+            let instrument = Instrument {
+                id: record.code.clone(),
+                name: record.name,
+                market: Market::Tse,
+                sector: record.sector.filter(|s| !s.is_empty()),
+                market_segment: record.market_segment.filter(|s| !s.is_empty()),
+            };
+            Ok((record.code, instrument))
+        })
+        .collect()
+}
+
+/// ファイルを読み込む。存在しない場合は None を返す
+async fn read_to_string(path: &Path) -> Result<Option<String>, DataProviderError> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(DataProviderError::File(format!(
+            "failed to read {}: {e}",
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use indoc::indoc;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    /// テストごとに CSV ディレクトリを用意し、終了時に削除する
+    struct CsvDir(PathBuf);
+
+    impl CsvDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("csv-directory-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join(DAILY_DIR)).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, path: &str, content: &str) -> &Self {
+            std::fs::write(self.0.join(path), content).unwrap();
+            self
+        }
+    }
+
+    impl Drop for CsvDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn range(from: NaiveDate, to: NaiveDate) -> DateRange {
+        DateRange { from, to }
+    }
+
+    #[rstest]
+    #[case::standard(
+        CsvFormat::Standard,
+        "daily/7203.csv",
+        indoc! {"
+            date,open,high,low,close,volume
+            2025-01-08,2960,2990,2950,2980,1200
+            2025-01-06,2900,2950,2880,2940,1000
+            2025-01-07,2940,2970,2920,2960,1100
+        "}
+    )]
+    #[case::stooq(
+        CsvFormat::Stooq,
+        "daily/7203.jp.txt",
+        indoc! {"
+            <TICKER>,<PER>,<DATE>,<TIME>,<OPEN>,<HIGH>,<LOW>,<CLOSE>,<VOL>,<OPENINT>
+            7203.JP,D,20250106,000000,2900,2950,2880,2940,1000,0
+            7203.JP,D,20250107,000000,2940,2970,2920,2960,1100,0
+            7203.JP,D,20250108,000000,2960,2990,2950,2980,1200,0
+        "}
+    )]
+    #[tokio::test]
+    async fn test_reads_daily_bars_in_range(
+        #[case] format: CsvFormat,
+        #[case] path: &str,
+        #[case] content: &str,
+    ) -> Result<(), DataProviderError> {
+        let dir = CsvDir::new(&format!("daily-{format:?}"));
+        dir.write(path, content);
+        let provider = CsvDirectoryProvider::new(dir.0.clone(), format);
+
+        let bars = provider
+            .fetch_daily_bars("7203", &range(date(2025, 1, 7), date(2025, 1, 8)))
+            .await?;
+
+        assert_eq!(
+            bars.iter()
+                .map(|b| (b.timestamp.date_naive(), b.close, b.volume))
+                .collect::<Vec<_>>(),
+            vec![
+                (date(2025, 1, 7), Decimal::new(2960, 0), 1100),
+                (date(2025, 1, 8), Decimal::new(2980, 0), 1200),
+            ]
+        );
+        assert!(bars.iter().all(|b| b.instrument_id == "7203"));
+        assert!(bars.iter().all(|b| b.source.as_deref() == Some(SOURCE)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_skips_invalid_rows() -> Result<(), DataProviderError> {
+        let dir = CsvDir::new("invalid-rows");
+        dir.write(
+            "daily/7203.csv",
+            indoc! {"
+                date,open,high,low,close,volume
+                2025-01-06,2900,2950,2880,2940,1000
+                2025-01-07,2940,2900,2920,2960,1100
+                2025-01-08,abc,2990,2950,2980,1200
+            "},
+        );
+        let provider = CsvDirectoryProvider::new(dir.0.clone(), CsvFormat::Standard);
+
+        let bars = provider
+            .fetch_daily_bars("7203", &range(date(2025, 1, 1), date(2025, 1, 31)))
+            .await?;
+
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].timestamp.date_naive(), date(2025, 1, 6));
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_reads_instrument_from_instruments_csv() -> Result<(), DataProviderError> {
+        let dir = CsvDir::new("instruments");
+        dir.write(
+            INSTRUMENTS_FILE,
+            indoc! {"
+                code,name,sector,market_segment
+                6758,ソニーグループ,電気機器,プライム
+                7203,トヨタ自動車,輸送用機器,
+            "},
+        )
+        .write("daily/9984.csv", "date,open,high,low,close,volume\n");
+        let provider = CsvDirectoryProvider::new(dir.0.clone(), CsvFormat::Standard);
+
+        assert_eq!(
+            provider.fetch_instrument("7203").await?,
+            Instrument {
+                id: "7203".to_string(),
+                name: "トヨタ自動車".to_string(),
+                market: Market::Tse,
+                sector: Some("輸送用機器".to_string()),
+                market_segment: None,
+            }
+        );
+        // instruments.csv にない銘柄は日足のファイルがあれば銘柄コードを銘柄名とする
+        assert_eq!(provider.fetch_instrument("9984").await?.name, "9984");
+        // instruments.csv は初回のみ読み込む
+        dir.write(INSTRUMENTS_FILE, "code,name\n7203,変更後\n");
+        assert_eq!(
+            provider.fetch_instrument("7203").await?.name,
+            "トヨタ自動車"
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_reports_invalid_files_as_file_errors() {
+        let dir = CsvDir::new("invalid-file");
+        dir.write(INSTRUMENTS_FILE, "code,name\n7203\n")
+            .write("daily/7203.csv", "date,open\n2025-01-06,100\n");
+        let provider = CsvDirectoryProvider::new(dir.0.clone(), CsvFormat::Standard);
+
+        let instrument = provider.fetch_instrument("7203").await;
+        assert!(matches!(instrument, Err(DataProviderError::File(_))));
+        let bars = provider
+            .fetch_daily_bars("7203", &range(date(2025, 1, 1), date(2025, 1, 31)))
+            .await;
+        assert!(matches!(bars, Err(DataProviderError::File(_))));
+    }
+
+    #[rstest]
+    #[case::missing_file("1301")]
+    #[case::path_traversal("../7203")]
+    #[tokio::test]
+    async fn test_returns_not_found_for_unknown_instrument(#[case] instrument_id: &str) {
+        let dir = CsvDir::new(&format!("not-found-{}", instrument_id.len()));
+        dir.write("daily/7203.csv", "date,open,high,low,close,volume\n");
+        let provider = CsvDirectoryProvider::new(dir.0.clone(), CsvFormat::Standard);
+
+        let bars = provider
+            .fetch_daily_bars(instrument_id, &range(date(2025, 1, 1), date(2025, 1, 31)))
+            .await;
+        assert!(matches!(bars, Err(DataProviderError::NotFound(_))));
+        let instrument = provider.fetch_instrument(instrument_id).await;
+        assert!(matches!(instrument, Err(DataProviderError::NotFound(_))));
+    }
+}
@@ -0,0 +1,552 @@
+//! 優先順位付きの複数プロバイダーによるフォールバック
+//!
+//! 設定した優先順位でプロバイダーを試し、一時的な障害 (通信エラー、レートリミット、5xx、
+//! サーキットブレーカーの Open) の場合は次のプロバイダーに切り替える。
+//! プロバイダーごとの成否を記録し、連続して失敗しているプロバイダーは後回しにする。
+
+use std::ops::ControlFlow;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use futures_util::stream::{self, BoxStream};
+use tokio::sync::Mutex;
+
+use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::models::{Bar, Instrument, ProviderHealth};
+
+/// 連続でこの回数失敗したプロバイダーを異常とみなし、正常なプロバイダーの後に回す
+const UNHEALTHY_AFTER_FAILURES: u32 = 3;
+
+/// フォールバック付き DataProvider
+///
+/// 正常なプロバイダーを優先順位どおりに試し、すべて失敗した場合は異常なプロバイダーも
+/// 優先順位どおりに試す。異常なプロバイダーも 1 回成功すれば正常に戻る。
+/// NotFound や 4xx 等のプロバイダーに依存しないエラーは切り替えずにそのまま返す。
+/// ページ単位の取得では、最初のページを取得できるまでのみ切り替え、以降のページの失敗は稼働状況にのみ記録する。
+pub struct FallbackProvider<P> {
+    providers: Vec<NamedProvider<P>>,
+}
+
+struct NamedProvider<P> {
+    name: String,
+    provider: P,
+    health: Mutex<HealthState>,
+}
+
+/// プロバイダーごとの成否の記録
+#[derive(Default)]
+struct HealthState {
+    consecutive_failures: u32,
+    total_requests: u64,
+    total_failures: u64,
+    last_success_at: Option<DateTime<Utc>>,
+    last_failure_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+impl HealthState {
+    fn is_healthy(&self) -> bool {
+        self.consecutive_failures < UNHEALTHY_AFTER_FAILURES
+    }
+
+    fn record<T>(&mut self, result: &Result<T, DataProviderError>) {
+        self.total_requests += 1;
+        match result {
+            Err(e) if is_provider_failure(e) => {
+                self.consecutive_failures += 1;
+                self.total_failures += 1;
+                self.last_failure_at = Some(Utc::now());
+                self.last_error = Some(e.to_string());
+            }
+            // NotFound 等はプロバイダーが応答できているため成功として扱う
+            _ => {
+                self.consecutive_failures = 0;
+                self.last_success_at = Some(Utc::now());
+            }
+        }
+    }
+}
+
+/// プロバイダーの異常として記録するエラーか
+///
+/// 切り替えるべきエラーに加え、応答を解釈できないエラーも異常として数える
+/// (同じ応答を他のプロバイダーで取り直しても解決しないため、切り替えはしない)。
+fn is_provider_failure(error: &DataProviderError) -> bool {
+    should_fall_over(error)
+        || matches!(
+            error,
+            DataProviderError::Parse(_)
+                | DataProviderError::Fixture(_)
+                | DataProviderError::File(_)
+        )
+}
+
+/// 次のプロバイダーに切り替えるべきエラーか
+fn should_fall_over(error: &DataProviderError) -> bool {
+    match error {
+        DataProviderError::Network(_)
+        | DataProviderError::RateLimited { .. }
+        | DataProviderError::CircuitOpen { .. } => true,
+        DataProviderError::Api { status, .. } => *status >= 500,
+        DataProviderError::NotFound(_)
+        | DataProviderError::Parse(_)
+        | DataProviderError::Fixture(_)
+        | DataProviderError::File(_)
+        | DataProviderError::PageLimitExceeded { .. } => false,
+    }
+}
+
+impl<P> FallbackProvider<P> {
+    /// 優先順位順 (先頭が最優先) の (名前, プロバイダー) からフォールバックを組み立てる
+    pub fn new(providers: Vec<(String, P)>) -> Self {
+        Self {
+            providers: providers
+                .into_iter()
+                .map(|(name, provider)| NamedProvider {
+                    name,
+                    provider,
+                    health: Mutex::new(HealthState::default()),
+                })
+                .collect(),
+        }
+    }
+
+    /// 試す順に並べたプロバイダー (正常なものを優先し、それぞれ優先順位順)
+    async fn attempt_order(&self) -> Vec<&NamedProvider<P>> {
+        let mut healthy = Vec::new();
+        let mut unhealthy = Vec::new();
+        for named in &self.providers {
+            if named.health.lock().await.is_healthy() {
+                healthy.push(named);
+            } else {
+                unhealthy.push(named);
+            }
+        }
+        healthy.extend(unhealthy);
+        healthy
+    }
+}
+
+impl<P: DataProvider> FallbackProvider<P> {
+    /// `open` でプロバイダーごとのページのストリームを開き、最初のページを取得できるまで切り替える
+    ///
+    /// 途中のページで失敗しても、取得済みのページと重複するため切り替えない (失敗は稼働状況に記録する)。
+    fn fall_over_pages<'a>(
+        &'a self,
+        operation: &'static str,
+        instrument_id: &'a str,
+        open: impl Fn(&'a P) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> + Send + 'a,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        stream::once(async move {
+            let mut last_error = None;
+            for named in self.attempt_order().await {
+                let mut pages = open(&named.provider);
+                let first = pages.next().await.transpose();
+                match named.settle(operation, instrument_id, first).await {
+                    ControlFlow::Break(Ok(first)) => {
+                        let rest = named.record_failures(operation, instrument_id, pages);
+                        return stream::iter(first.map(Ok)).chain(rest).boxed();
+                    }
+                    ControlFlow::Break(Err(e)) => return stream::iter([Err(e)]).boxed(),
+                    ControlFlow::Continue(e) => last_error = Some(e),
+                }
+            }
+            stream::iter([Err(all_failed(last_error))]).boxed()
+        })
+        .flatten()
+        .boxed()
+    }
+}
+
+/// すべてのプロバイダーが失敗した場合のエラー (最後に失敗したプロバイダーのエラー)
+fn all_failed(last_error: Option<DataProviderError>) -> DataProviderError {
+    last_error
+        .unwrap_or_else(|| DataProviderError::Network("no data provider is configured".to_string()))
+}
+
+impl<P> NamedProvider<P> {
+    /// 問い合わせの結果を記録し、次のプロバイダーに切り替えるかを判定する
+    ///
+    /// 切り替える場合はエラーを `Continue` で、そのまま返す場合は結果を `Break` で返す。
+    async fn settle<T>(
+        &self,
+        operation: &str,
+        instrument_id: &str,
+        result: Result<T, DataProviderError>,
+    ) -> ControlFlow<Result<T, DataProviderError>, DataProviderError> {
+        self.health.lock().await.record(&result);
+        match result {
+            Err(e) if should_fall_over(&e) => {
+                tracing::warn!(
+                    provider = self.name,
+                    operation,
+                    instrument_id,
+                    error = %e,
+                    "データプロバイダーの取得に失敗したため、次のプロバイダーに切り替えます"
+                );
+                ControlFlow::Continue(e)
+            }
+            result => ControlFlow::Break(result),
+        }
+    }
+
+    /// 2 ページ目以降のページをそのまま返し、プロバイダーの異常による失敗を記録するストリーム
+    fn record_failures<'a>(
+        &'a self,
+        operation: &'static str,
+        instrument_id: &'a str,
+        pages: BoxStream<'a, Result<Vec<Bar>, DataProviderError>>,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>>
+    where
+        P: Sync,
+    {
+        pages
+            .then(move |result| async move {
+                if let Err(e) = &result
+                    && is_provider_failure(e)
+                {
+                    tracing::warn!(
+                        provider = self.name,
+                        operation,
+                        instrument_id,
+                        error = %e,
+                        "データプロバイダーの途中のページの取得に失敗しました"
+                    );
+                    self.health.lock().await.record(&result);
+                }
+                result
+            })
+            .boxed()
+    }
+}
+
+#[async_trait]
+impl<P: DataProvider> DataProvider for FallbackProvider<P> {
+    async fn fetch_daily_bars(
+        &self,
+        instrument_id: &str,
+        range: &DateRange,
+    ) -> Result<Vec<Bar>, DataProviderError> {
+        let mut last_error = None;
+        for named in self.attempt_order().await {
+            let result = named.provider.fetch_daily_bars(instrument_id, range).await;
+            match named
+                .settle("fetch_daily_bars", instrument_id, result)
+                .await
+            {
+                ControlFlow::Break(result) => return result,
+                ControlFlow::Continue(e) => last_error = Some(e),
+            }
+        }
+        Err(all_failed(last_error))
+    }
+
+    fn fetch_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        self.fall_over_pages("fetch_daily_bar_pages", instrument_id, move |provider| {
+            provider.fetch_daily_bar_pages(instrument_id, range.clone())
+        })
+    }
+
+    /// `pagination_key` を発行したプロバイダーは記録していないため、優先順位どおりに再開を試す
+    ///
+    /// 再開に対応していないプロバイダーは最初のページから取得し直す。
+    fn resume_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        pagination_key: String,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        self.fall_over_pages("resume_daily_bar_pages", instrument_id, move |provider| {
+            provider.resume_daily_bar_pages(instrument_id, range.clone(), pagination_key.clone())
+        })
+    }
+
+    async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
+        let mut last_error = None;
+        for named in self.attempt_order().await {
+            let result = named.provider.fetch_instrument(instrument_id).await;
+            match named
+                .settle("fetch_instrument", instrument_id, result)
+                .await
+            {
+                ControlFlow::Break(result) => return result,
+                ControlFlow::Continue(e) => last_error = Some(e),
+            }
+        }
+        Err(all_failed(last_error))
+    }
+
+    async fn health(&self) -> Vec<ProviderHealth> {
+        let mut report = Vec::with_capacity(self.providers.len());
+        for named in &self.providers {
+            let circuit_breaker = named.provider.circuit_breaker().await;
+            let health = named.health.lock().await;
+            report.push(ProviderHealth {
+                name: named.name.clone(),
+                healthy: health.is_healthy(),
+                consecutive_failures: health.consecutive_failures,
+                total_requests: health.total_requests,
+                total_failures: health.total_failures,
+                last_success_at: health.last_success_at,
+                last_failure_at: health.last_failure_at,
+                last_error: health.last_error.clone(),
+                circuit_breaker,
+            });
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::data_provider::mock::{MockDataProvider, date, make_bar, sample_instrument};
+
+    /// 取得元を `source` とした日足を返すプロバイダー (登録したエラーを先に返す)
+    fn mock_provider(errors: Vec<DataProviderError>, source: &str) -> MockDataProvider {
+        let bar = Bar {
+            source: Some(source.to_string()),
+            ..make_bar("72030", date(2025, 1, 6), 100)
+        };
+        MockDataProvider::new()
+            .with_bars(vec![bar])
+            .with_instruments(vec![sample_instrument("72030")])
+            .with_errors(errors)
+    }
+
+    fn network_error() -> DataProviderError {
+        DataProviderError::Network("connection refused".to_string())
+    }
+
+    fn fallback(primary: Vec<DataProviderError>) -> FallbackProvider<MockDataProvider> {
+        FallbackProvider::new(vec![
+            ("primary".to_string(), mock_provider(primary, "primary")),
+            (
+                "secondary".to_string(),
+                mock_provider(Vec::new(), "secondary"),
+            ),
+        ])
+    }
+
+    fn network_errors(count: u32) -> Vec<DataProviderError> {
+        (0..count).map(|_| network_error()).collect()
+    }
+
+    fn range() -> DateRange {
+        DateRange {
+            from: date(2025, 1, 6),
+            to: date(2025, 1, 6),
+        }
+    }
+
+    async fn fetch_source(provider: &FallbackProvider<MockDataProvider>) -> Option<String> {
+        let bars = provider
+            .fetch_daily_bars("72030", &range())
+            .await
+            .expect("fetch failed");
+        bars[0].source.clone()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_uses_primary_provider_first() {
+        let provider = fallback(Vec::new());
+
+        assert_eq!(fetch_source(&provider).await.as_deref(), Some("primary"));
+    }
+
+    #[rstest]
+    #[case::network(network_error())]
+    #[case::rate_limited(DataProviderError::RateLimited { retries: 3 })]
+    #[case::server_error(DataProviderError::Api { status: 503, message: "unavailable".to_string() })]
+    #[case::circuit_open(DataProviderError::CircuitOpen { retry_after_secs: 30 })]
+    #[tokio::test]
+    async fn test_falls_over_on_transient_errors(#[case] error: DataProviderError) {
+        let provider = fallback(vec![error]);
+
+        assert_eq!(fetch_source(&provider).await.as_deref(), Some("secondary"));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_fetch_daily_bar_pages_falls_over_before_first_page() {
+        let provider = fallback(vec![network_error()]);
+
+        let pages: Vec<Vec<Bar>> = provider
+            .fetch_daily_bar_pages("72030", range())
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0][0].source.as_deref(), Some("secondary"));
+        assert_eq!(provider.health().await[0].consecutive_failures, 1);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_fetch_daily_bar_pages_records_later_page_failures() {
+        let days = [date(2025, 1, 6), date(2025, 1, 7)];
+        let provider = FallbackProvider::new(vec![
+            (
+                "primary".to_string(),
+                MockDataProvider::new()
+                    .with_bars(
+                        days.iter()
+                            .map(|day| make_bar("72030", *day, 100))
+                            .collect(),
+                    )
+                    .with_instruments(vec![sample_instrument("72030")])
+                    .with_pages(1, 1)
+                    .with_page_error(network_error()),
+            ),
+            (
+                "secondary".to_string(),
+                mock_provider(Vec::new(), "secondary"),
+            ),
+        ]);
+        let range = DateRange {
+            from: days[0],
+            to: days[1],
+        };
+
+        let pages: Vec<_> = provider
+            .fetch_daily_bar_pages("72030", range)
+            .collect()
+            .await;
+
+        // 取得済みのページと重複するため切り替えず、失敗を稼働状況に記録する
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].is_ok());
+        assert!(matches!(pages[1], Err(DataProviderError::Network(_))));
+        let health = provider.health().await;
+        assert_eq!(health[0].total_requests, 2);
+        assert_eq!(health[0].consecutive_failures, 1);
+        assert_eq!(
+            health[0].last_error.as_deref(),
+            Some("network error: connection refused")
+        );
+        assert_eq!(health[1].total_requests, 0);
+    }
+
+    #[rstest]
+    #[case::not_found(DataProviderError::NotFound("72030".to_string()))]
+    #[case::client_error(DataProviderError::Api { status: 403, message: "forbidden".to_string() })]
+    #[case::parse(DataProviderError::Parse("invalid".to_string()))]
+    #[tokio::test]
+    async fn test_returns_non_transient_errors_without_falling_over(
+        #[case] error: DataProviderError,
+    ) {
+        let expected = error.to_string();
+        let provider = fallback(vec![error]);
+
+        let result = provider.fetch_instrument("72030").await;
+
+        assert_eq!(result.map_err(|e| e.to_string()), Err(expected));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_parse_errors_count_as_failures_without_falling_over() {
+        let provider = fallback(vec![DataProviderError::Parse("invalid".to_string())]);
+
+        let result = provider.fetch_daily_bars("72030", &range()).await;
+
+        assert!(matches!(result, Err(DataProviderError::Parse(_))));
+        let health = provider.health().await;
+        assert_eq!(health[0].consecutive_failures, 1);
+        assert_eq!(health[0].total_failures, 1);
+        assert_eq!(
+            health[0].last_error.as_deref(),
+            Some("failed to parse response: invalid")
+        );
+        assert_eq!(health[1].total_requests, 0);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_returns_last_error_when_all_providers_fail() {
+        let provider = FallbackProvider::new(vec![
+            (
+                "primary".to_string(),
+                mock_provider(vec![network_error()], "primary"),
+            ),
+            (
+                "secondary".to_string(),
+                mock_provider(
+                    vec![DataProviderError::RateLimited { retries: 3 }],
+                    "secondary",
+                ),
+            ),
+        ]);
+
+        let result = provider.fetch_daily_bars("72030", &range()).await;
+
+        assert!(matches!(
+            result,
+            Err(DataProviderError::RateLimited { retries: 3 })
+        ));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_tracks_health_and_deprioritizes_unhealthy_provider() {
+        let failures = UNHEALTHY_AFTER_FAILURES;
+        let provider = fallback(network_errors(failures));
+
+        for _ in 0..failures {
+            assert_eq!(fetch_source(&provider).await.as_deref(), Some("secondary"));
+        }
+        let health = provider.health().await;
+        assert_eq!(
+            health
+                .iter()
+                .map(|h| (h.name.as_str(), h.healthy, h.consecutive_failures))
+                .collect::<Vec<_>>(),
+            vec![("primary", false, 3), ("secondary", true, 0)]
+        );
+        assert_eq!(
+            health[0].last_error.as_deref(),
+            Some("network error: connection refused")
+        );
+        assert_eq!(health[1].total_requests, 3);
+
+        // 異常なプロバイダーは後回しにする
+        assert_eq!(fetch_source(&provider).await.as_deref(), Some("secondary"));
+        assert_eq!(provider.health().await[0].total_requests, 3);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_unhealthy_provider_recovers_after_success() {
+        let failures = UNHEALTHY_AFTER_FAILURES;
+        let provider = FallbackProvider::new(vec![
+            (
+                "primary".to_string(),
+                mock_provider(network_errors(failures), "primary"),
+            ),
+            (
+                "secondary".to_string(),
+                mock_provider(network_errors(failures + 1), "secondary"),
+            ),
+        ]);
+        for _ in 0..failures {
+            assert!(provider.fetch_instrument("72030").await.is_err());
+        }
+
+        // 両方とも異常な場合は優先順位どおりに試す
+        assert_eq!(fetch_source(&provider).await.as_deref(), Some("primary"));
+        let health = provider.health().await;
+        assert!(health[0].healthy);
+        assert_eq!(health[0].total_failures, 3);
+        assert!(health[0].last_success_at.is_some());
+    }
+}
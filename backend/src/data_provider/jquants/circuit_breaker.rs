@@ -0,0 +1,261 @@
+//! J-Quants API 呼び出しのサーキットブレーカー
+//!
+//! API の障害時にリトライを繰り返して待機し続けないよう、連続して失敗したら一定時間
+//! リクエストを送らずに即座に失敗させる。
+//!
+//! - Closed: 通常どおりリクエストを送る。連続失敗回数が閾値に達すると Open に遷移する
+//! - Open: リクエストを送らずに `CircuitOpen` を返す。待機時間が過ぎると Half-Open に遷移する
+//! - Half-Open: 試行として 1 リクエストだけ送る。成功すると Closed、失敗すると Open に戻る
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use crate::data_provider::DataProviderError;
+use crate::models::{CircuitBreakerStatus, CircuitState};
+
+/// 既定の Open に遷移する連続失敗回数
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+/// 既定の Open を維持する時間
+pub const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(60);
+
+/// サーキットブレーカーの設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Open に遷移する連続失敗回数 (リトライを含めた 1 リクエストを 1 回と数える)
+    pub failure_threshold: u32,
+    /// Open を維持する時間 (経過後に 1 リクエストだけ試行する)
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_duration: DEFAULT_OPEN_DURATION,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed {
+        consecutive_failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// 試行中のリクエストを送り始めた時刻
+    HalfOpen {
+        trial_started: Instant,
+    },
+}
+
+/// クライアントの全リクエストで共有するサーキットブレーカー
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    /// リクエストを送ってよいかを判定する
+    ///
+    /// Open の間と、Half-Open で試行中のリクエストがある間は `CircuitOpen` を返す。
+    /// 試行中のリクエストが Open の維持時間を過ぎても終わらない場合 (キャンセルされた場合等) は
+    /// 新しい試行を許可する。
+    pub async fn acquire(&self) -> Result<(), DataProviderError> {
+        let now = Instant::now();
+        let mut state = self.state.lock().await;
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => Err(circuit_open(until - now)),
+            State::HalfOpen { trial_started }
+                if now < trial_started + self.config.open_duration =>
+            {
+                Err(circuit_open(
+                    trial_started + self.config.open_duration - now,
+                ))
+            }
+            State::Open { .. } | State::HalfOpen { .. } => {
+                tracing::info!("サーキットブレーカーを Half-Open にして J-Quants API を試行します");
+                *state = State::HalfOpen { trial_started: now };
+                Ok(())
+            }
+        }
+    }
+
+    /// リクエストの成功 (API が応答した) を記録する
+    pub async fn record_success(&self) {
+        let mut state = self.state.lock().await;
+        if matches!(*state, State::HalfOpen { .. }) {
+            tracing::info!("J-Quants API が復旧したため、サーキットブレーカーを Closed に戻します");
+        }
+        *state = State::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    /// リクエストの失敗 (通信エラー、レートリミット、5xx) を記録する
+    pub async fn record_failure(&self) {
+        let mut state = self.state.lock().await;
+        let consecutive_failures = match *state {
+            State::Closed {
+                consecutive_failures,
+            } => consecutive_failures + 1,
+            // Half-Open の試行が失敗したら即座に Open に戻す
+            State::HalfOpen { .. } => self.config.failure_threshold,
+            // Open 中に送り始めていたリクエストの失敗は Open の期間を延ばさない
+            State::Open { .. } => return,
+        };
+
+        if consecutive_failures >= self.config.failure_threshold {
+            tracing::warn!(
+                consecutive_failures,
+                open_secs = self.config.open_duration.as_secs(),
+                "J-Quants API の呼び出しが連続して失敗したため、サーキットブレーカーを Open にします"
+            );
+            *state = State::Open {
+                until: Instant::now() + self.config.open_duration,
+            };
+        } else {
+            *state = State::Closed {
+                consecutive_failures,
+            };
+        }
+    }
+
+    /// 現在の状態
+    pub async fn status(&self) -> CircuitBreakerStatus {
+        let now = Instant::now();
+        let state = *self.state.lock().await;
+        let (state, consecutive_failures, retry_at) = match state {
+            State::Closed {
+                consecutive_failures,
+            } => (CircuitState::Closed, consecutive_failures, None),
+            State::Open { until } => (
+                CircuitState::Open,
+                self.config.failure_threshold,
+                Some(until.saturating_duration_since(now)),
+            ),
+            State::HalfOpen { .. } => (CircuitState::HalfOpen, self.config.failure_threshold, None),
+        };
+
+        CircuitBreakerStatus {
+            state,
+            consecutive_failures,
+            failure_threshold: self.config.failure_threshold,
+            retry_at: retry_at.and_then(|remaining| {
+                chrono::Duration::from_std(remaining)
+                    .ok()
+                    .map(|remaining| Utc::now() + remaining)
+            }),
+        }
+    }
+}
+
+fn circuit_open(remaining: Duration) -> DataProviderError {
+    DataProviderError::CircuitOpen {
+        retry_after_secs: remaining.as_secs_f64().ceil() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn breaker(failure_threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold,
+            open_duration: Duration::from_secs(30),
+        })
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_opens_after_consecutive_failures() {
+        let breaker = breaker(3);
+
+        for _ in 0..2 {
+            breaker.acquire().await.unwrap();
+            breaker.record_failure().await;
+        }
+        assert_eq!(breaker.status().await.state, CircuitState::Closed);
+        assert_eq!(breaker.status().await.consecutive_failures, 2);
+
+        breaker.acquire().await.unwrap();
+        breaker.record_failure().await;
+
+        assert_eq!(breaker.status().await.state, CircuitState::Open);
+        assert!(matches!(
+            breaker.acquire().await,
+            Err(DataProviderError::CircuitOpen {
+                retry_after_secs: 30
+            })
+        ));
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_success_resets_consecutive_failures() {
+        let breaker = breaker(2);
+
+        breaker.record_failure().await;
+        breaker.record_success().await;
+        breaker.record_failure().await;
+
+        assert_eq!(breaker.status().await.state, CircuitState::Closed);
+        assert_eq!(breaker.status().await.consecutive_failures, 1);
+    }
+
+    #[rstest]
+    #[case::trial_succeeds(true, CircuitState::Closed)]
+    #[case::trial_fails(false, CircuitState::Open)]
+    #[tokio::test(start_paused = true)]
+    async fn test_half_open_allows_single_trial(
+        #[case] trial_succeeds: bool,
+        #[case] expected: CircuitState,
+    ) {
+        let breaker = breaker(1);
+        breaker.record_failure().await;
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        breaker.acquire().await.unwrap();
+        assert_eq!(breaker.status().await.state, CircuitState::HalfOpen);
+        // 試行中は他のリクエストを通さない
+        assert!(breaker.acquire().await.is_err());
+
+        if trial_succeeds {
+            breaker.record_success().await;
+        } else {
+            breaker.record_failure().await;
+        }
+
+        assert_eq!(breaker.status().await.state, expected);
+        assert_eq!(breaker.acquire().await.is_ok(), trial_succeeds);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_abandoned_trial_is_retried_after_open_duration() {
+        let breaker = breaker(1);
+        breaker.record_failure().await;
+        tokio::time::advance(Duration::from_secs(30)).await;
+        breaker.acquire().await.unwrap();
+
+        // 試行の結果が記録されないまま Open の維持時間が過ぎたら、再び試行を許可する
+        tokio::time::advance(Duration::from_secs(30)).await;
+
+        assert!(breaker.acquire().await.is_ok());
+    }
+}
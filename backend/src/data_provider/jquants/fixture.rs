@@ -0,0 +1,132 @@
+//! J-Quants API レスポンスの記録・再生用フィクスチャ
+//!
+//! フィクスチャは以下のレイアウトで保存する。レコードは API レスポンスの `data` 配列の
+//! 要素をそのまま保存するため、実際のレスポンスと同じ形式で再生できる。
+//!
+//! ```text
+//! <dir>/daily_bars/<銘柄コード>.json  {"data": [日足レコード (日付順)]}
+//! <dir>/master/<銘柄コード>.json      銘柄マスタのレスポンスボディ
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::data_provider::DataProviderError;
+
+const DAILY_BARS_DIR: &str = "daily_bars";
+const MASTER_DIR: &str = "master";
+
+/// フィクスチャの保存先ディレクトリ
+pub(crate) struct FixtureStore {
+    dir: PathBuf,
+}
+
+impl FixtureStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// 記録済みの日足レコードを読み込む。未記録の銘柄は None を返す
+    pub async fn read_daily_bars(
+        &self,
+        instrument_id: &str,
+    ) -> Result<Option<Vec<Value>>, DataProviderError> {
+        let path = self.path(DAILY_BARS_DIR, instrument_id)?;
+        let Some(body) = read_json(&path).await? else {
+            return Ok(None);
+        };
+        match body.get("data") {
+            Some(Value::Array(records)) => Ok(Some(records.clone())),
+            _ => Err(DataProviderError::Fixture(format!(
+                "{}: missing data array",
+                path.display()
+            ))),
+        }
+    }
+
+    /// 日足レコードを記録する
+    ///
+    /// 既存のフィクスチャとは `Date` 単位でマージし、同じ日付のレコードは新しいもので上書きする。
+    pub async fn write_daily_bars(
+        &self,
+        instrument_id: &str,
+        records: Vec<Value>,
+    ) -> Result<(), DataProviderError> {
+        let existing = self
+            .read_daily_bars(instrument_id)
+            .await?
+            .unwrap_or_default();
+
+        let mut by_date = BTreeMap::new();
+        for record in existing.into_iter().chain(records) {
+            let Some(date) = record.get("Date").and_then(Value::as_str) else {
+                continue;
+            };
+            by_date.insert(date.to_string(), record);
+        }
+
+        let path = self.path(DAILY_BARS_DIR, instrument_id)?;
+        let records: Vec<Value> = by_date.into_values().collect();
+        write_json(&path, &json!({ "data": records })).await
+    }
+
+    /// 記録済みの銘柄マスタのレスポンスを読み込む。未記録の銘柄は None を返す
+    pub async fn read_master(
+        &self,
+        instrument_id: &str,
+    ) -> Result<Option<Value>, DataProviderError> {
+        read_json(&self.path(MASTER_DIR, instrument_id)?).await
+    }
+
+    /// 銘柄マスタのレスポンスを記録する
+    pub async fn write_master(
+        &self,
+        instrument_id: &str,
+        body: &Value,
+    ) -> Result<(), DataProviderError> {
+        write_json(&self.path(MASTER_DIR, instrument_id)?, body).await
+    }
+
+    /// フィクスチャのパスを返す
+    ///
+    /// 銘柄コードをファイル名に使うため、英数字以外を含むコードは拒否する。
+    fn path(&self, kind: &str, instrument_id: &str) -> Result<PathBuf, DataProviderError> {
+        if instrument_id.is_empty() || !instrument_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(DataProviderError::NotFound(format!(
+                "instrument '{instrument_id}' not found"
+            )));
+        }
+        Ok(self.dir.join(kind).join(format!("{instrument_id}.json")))
+    }
+}
+
+async fn read_json(path: &Path) -> Result<Option<Value>, DataProviderError> {
+    let content = match tokio::fs::read(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(DataProviderError::Fixture(format!(
+                "failed to read {}: {e}",
+                path.display()
+            )));
+        }
+    };
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| DataProviderError::Fixture(format!("invalid JSON in {}: {e}", path.display())))
+}
+
+async fn write_json(path: &Path, body: &Value) -> Result<(), DataProviderError> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(|e| {
+            DataProviderError::Fixture(format!("failed to create {}: {e}", parent.display()))
+        })?;
+    }
+    let content = serde_json::to_vec_pretty(body)
+        .map_err(|e| DataProviderError::Fixture(format!("failed to serialize fixture: {e}")))?;
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| DataProviderError::Fixture(format!("failed to write {}: {e}", path.display())))
+}
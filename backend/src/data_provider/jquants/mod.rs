@@ -1,92 +1,56 @@
+mod circuit_breaker;
+mod fixture;
 #[cfg(test)]
 mod mock;
+mod rate_limit;
+mod replay;
 mod response;
+mod scheduler;
 #[cfg(test)]
 mod tests;
 
-use std::collections::VecDeque;
+use std::path::PathBuf;
 
+use async_trait::async_trait;
 use chrono::{NaiveDate, TimeZone, Utc};
+use futures_util::stream::{self, BoxStream};
+use futures_util::{StreamExt, TryStreamExt};
 use reqwest::Url;
 use rust_decimal::Decimal;
-use tokio::sync::Mutex;
+use sea_orm::DatabaseConnection;
+use sha2::{Digest, Sha256};
 
 use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::models::CircuitBreakerStatus;
 use crate::models::bar::{Bar, Timeframe};
 use crate::models::instrument::{Instrument, Market};
-use response::{DailyBarsResponse, EquitiesMasterResponse, ErrorResponse};
+use circuit_breaker::CircuitBreaker;
+pub use circuit_breaker::CircuitBreakerConfig;
+use fixture::FixtureStore;
+use rate_limit::{RateLimitHeaders, RateLimiter, retry_backoff};
+pub use replay::ReplayProvider;
+use response::{DailyBar, DailyBarsResponse, EquitiesMasterResponse, EquityMaster, ErrorResponse};
+
+/// J-Quants API から取得したバーの取得元 (`bars.source`)
+pub const SOURCE: &str = "jquants";
 
 const DEFAULT_BASE_URL: &str = "https://api.jquants.com/v2";
 const MAX_RETRIES: u32 = 3;
-const INITIAL_BACKOFF_MS: u64 = 500;
-/// API サーバーのバグで同じ pagination_key が返り続けた場合の安全策
+/// レートリミットを共有する単位 (`api_rate_limits.key`) の接頭辞
+const RATE_LIMIT_KEY_PREFIX: &str = "jquants";
+/// 1 回の取得で辿るページ数の上限 (API サーバーのバグで同じ pagination_key が返り続けた場合の安全策)
 const MAX_PAGES: u32 = 100;
 
-/// レートリミットのウィンドウ幅 (60 秒)
-const RATE_LIMIT_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
-/// ウィンドウ内の最大リクエスト数 (J-Quants 無料プラン: 1 分間に 5 リクエスト)
-const RATE_LIMIT_MAX_REQUESTS: usize = 5;
-
-/// スライディングウィンドウ方式のレートリミッター
-///
-/// 直近 60 秒間のリクエスト送信時刻を記録し、上限に達している場合は
-/// 最も古いリクエストがウィンドウから外れるまで待機する。
-struct RateLimiter {
-    /// 直近のリクエスト送信時刻 (古い順)
-    timestamps: Mutex<VecDeque<tokio::time::Instant>>,
-}
-
-impl RateLimiter {
-    fn new() -> Self {
-        Self {
-            timestamps: Mutex::new(VecDeque::with_capacity(RATE_LIMIT_MAX_REQUESTS)),
-        }
-    }
-
-    /// リクエスト送信の許可を取得する
-    ///
-    /// ウィンドウ内のリクエスト数が上限に達している場合、最も古いリクエストが
-    /// ウィンドウから外れるまで待機する。
-    async fn acquire(&self) {
-        loop {
-            let now = tokio::time::Instant::now();
-
-            let mut timestamps = self.timestamps.lock().await;
-
-            // ウィンドウ外のタイムスタンプを削除
-            while let Some(&oldest) = timestamps.front() {
-                if now.duration_since(oldest) >= RATE_LIMIT_WINDOW {
-                    timestamps.pop_front();
-                } else {
-                    break;
-                }
-            }
-
-            if timestamps.len() < RATE_LIMIT_MAX_REQUESTS {
-                // 枠がある: タイムスタンプを記録して通過
-                timestamps.push_back(now);
-                return;
-            }
-
-            // 枠がない: 最も古いリクエストがウィンドウから外れるまで待つ
-            let oldest = timestamps[0];
-            let sleep_target = oldest + RATE_LIMIT_WINDOW;
-            drop(timestamps); // ロックを解放してから sleep
-
-            tracing::info!(
-                wait_ms = sleep_target.saturating_duration_since(now).as_millis() as u64,
-                "レートリミットに到達、待機中"
-            );
-            tokio::time::sleep_until(sleep_target).await;
-        }
-    }
-}
-
 /// J-Quants API V2 クライアント
 ///
 /// API Key 認証方式で J-Quants API V2 にアクセスする。
-/// アプリケーションレベルのレートリミッター (1 分間 5 リクエスト) を内蔵し、
-/// 429 (Rate Limited) と 5xx に対して指数バックオフでリトライする。
+/// アプリケーションレベルのレートリミッター (既定で 1 分間 5 リクエスト) を内蔵し、
+/// 429 (Rate Limited) と 5xx に対して `Retry-After` または揺らぎ付きの指数バックオフでリトライする。
+/// レートリミッターの上限はレスポンスのヘッダーと 429 から調整し、DB で全プロセスと共有できる。
+/// 枠を待つリクエストは [`with_priority`](crate::data_provider::priority::with_priority) で
+/// 指定した優先度順に送信する。
+/// リトライしても失敗するリクエストが続いた場合は、サーキットブレーカーで一定時間リクエストを止める。
+/// 記録モードでは、取得したレスポンスを [`ReplayProvider`] で再生できるフィクスチャとして保存する。
 ///
 /// Debug は意図的に derive しない (api_key の漏洩防止)
 pub struct JQuantsClient {
@@ -94,6 +58,11 @@ pub struct JQuantsClient {
     base_url: String,
     api_key: String,
     rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
+    /// 記録モードのときのフィクスチャの保存先
+    recorder: Option<FixtureStore>,
+    /// 1 回の取得で辿るページ数の上限
+    max_pages: u32,
 }
 
 impl JQuantsClient {
@@ -108,9 +77,41 @@ impl JQuantsClient {
             base_url: DEFAULT_BASE_URL.to_string(),
             api_key,
             rate_limiter: RateLimiter::new(),
+            circuit_breaker: CircuitBreaker::new(CircuitBreakerConfig::default()),
+            recorder: None,
+            max_pages: MAX_PAGES,
         })
     }
 
+    /// API のベース URL を差し替える (フェイクサーバーに接続する場合等)
+    pub fn with_api_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// サーキットブレーカーの閾値を差し替える
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = CircuitBreaker::new(config);
+        self
+    }
+
+    /// レートリミットの状態を DB に保存し、同じ API キーを使う全プロセスで上限を共有する
+    ///
+    /// 再起動やレプリカの追加で、1 分間のリクエスト数の上限を超えないようにする。
+    pub fn with_shared_rate_limit(mut self, db: DatabaseConnection) -> Self {
+        self.rate_limiter = RateLimiter::new().with_shared_store(db, rate_limit_key(&self.api_key));
+        self
+    }
+
+    /// 記録モードを有効にする
+    ///
+    /// 取得したレスポンスを `dir` 配下にフィクスチャとして保存する。
+    /// 同じ銘柄の日足は日付単位で既存のフィクスチャにマージする。
+    pub fn with_recording(mut self, dir: PathBuf) -> Self {
+        self.recorder = Some(FixtureStore::new(dir));
+        self
+    }
+
     /// テスト用: ベース URL を差し替え可能にする
     #[cfg(test)]
     pub fn with_base_url(base_url: &str, api_key: &str) -> Result<Self, DataProviderError> {
@@ -124,24 +125,51 @@ impl JQuantsClient {
             base_url: base_url.to_string(),
             api_key: api_key.to_string(),
             rate_limiter: RateLimiter::new(),
+            circuit_breaker: CircuitBreaker::new(CircuitBreakerConfig::default()),
+            recorder: None,
+            max_pages: MAX_PAGES,
         })
     }
 
+    /// テスト用: 1 回の取得で辿るページ数の上限を差し替える
+    #[cfg(test)]
+    pub fn with_max_pages(mut self, max_pages: u32) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// サーキットブレーカーと指数バックオフ付き GET リクエスト
+    ///
+    /// サーキットブレーカーが Open の場合はリクエストを送らずに `CircuitOpen` を返す。
+    /// リトライしても通信エラー・429・5xx で失敗した場合をサーキットブレーカーの失敗として数える。
+    async fn get_with_retry(&self, url: &Url) -> Result<reqwest::Response, DataProviderError> {
+        self.circuit_breaker.acquire().await?;
+
+        let result = self.send_with_retry(url).await;
+        match &result {
+            Err(DataProviderError::Network(_) | DataProviderError::RateLimited { .. }) => {
+                self.circuit_breaker.record_failure().await;
+            }
+            Err(DataProviderError::Api { status, .. }) if *status >= 500 => {
+                self.circuit_breaker.record_failure().await;
+            }
+            _ => self.circuit_breaker.record_success().await,
+        }
+        result
+    }
+
     /// 指数バックオフ付き GET リクエスト
     ///
     /// レートリミッターで送信間隔を制御した上で、429 と 5xx に対してリトライする。
     /// それ以外のエラーは即座に返す。
-    async fn get_with_retry(&self, url: &Url) -> Result<reqwest::Response, DataProviderError> {
+    async fn send_with_retry(&self, url: &Url) -> Result<reqwest::Response, DataProviderError> {
         let mut last_error = None;
+        let mut retry_after = None;
         let url_str = url.as_str();
 
         for attempt in 0..=MAX_RETRIES {
-            // 各リクエスト (リトライ含む) の前にレートリミッターの許可を取得
-            self.rate_limiter.acquire().await;
-
             if attempt > 0 {
-                let backoff =
-                    std::time::Duration::from_millis(INITIAL_BACKOFF_MS * 2u64.pow(attempt - 1));
+                let backoff = retry_backoff(attempt, retry_after.take());
                 tracing::warn!(
                     attempt,
                     backoff_ms = backoff.as_millis() as u64,
@@ -151,6 +179,9 @@ impl JQuantsClient {
                 tokio::time::sleep(backoff).await;
             }
 
+            // 各リクエスト (リトライ含む) の前にレートリミッターの許可を取得
+            let sent_at = self.rate_limiter.acquire().await;
+
             let response = self
                 .http
                 .get(url.clone())
@@ -160,16 +191,32 @@ impl JQuantsClient {
                 .map_err(|e| DataProviderError::Network(e.to_string()))?;
 
             let status = response.status().as_u16();
+            let headers = RateLimitHeaders::from_headers(response.headers(), Utc::now());
+            self.rate_limiter.update_quota(&headers).await;
 
             match status {
-                200..=299 => return Ok(response),
+                200..=299 => {
+                    self.rate_limiter.record_success().await;
+                    return Ok(response);
+                }
                 429 => {
-                    tracing::warn!(attempt, url = url_str, "レートリミット超過 (429)");
+                    tracing::warn!(
+                        attempt,
+                        url = url_str,
+                        retry_after_secs = headers.retry_after.map(|d| d.as_secs()),
+                        "レートリミット超過 (429)"
+                    );
+                    self.rate_limiter
+                        .record_rate_limited(sent_at, headers.retry_after)
+                        .await;
+                    retry_after = headers.retry_after;
                     last_error = Some(DataProviderError::RateLimited { retries: attempt });
                 }
                 500..=599 => {
                     let message = Self::extract_error_message(response).await;
                     tracing::warn!(attempt, status, url = url_str, %message, "サーバーエラー、リトライ実行");
+                    // 503 等の Retry-After にも従う
+                    retry_after = headers.retry_after;
                     last_error = Some(DataProviderError::Api { status, message });
                 }
                 _ => {
@@ -212,87 +259,145 @@ impl JQuantsClient {
         }
         Ok(url)
     }
-}
 
-impl DataProvider for JQuantsClient {
-    async fn fetch_daily_bars(
+    /// 日足データを `pagination_key` のページから順に取得するストリーム
+    ///
+    /// [`DataProviderError::PageLimitExceeded`] で打ち切られた取得を、エラーの
+    /// `pagination_key` を渡して再開できる。None の場合は最初のページから取得する。
+    pub fn fetch_daily_bar_pages_from<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        pagination_key: Option<String>,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        let max_pages = self.max_pages;
+        stream::try_unfold(Some((pagination_key, 0)), move |cursor| {
+            let range = range.clone();
+            async move {
+                let Some((pagination_key, pages)) = cursor else {
+                    return Ok(None);
+                };
+                if pages == max_pages
+                    && let Some(pagination_key) = pagination_key
+                {
+                    tracing::warn!(
+                        instrument_id,
+                        max_pages,
+                        pagination_key,
+                        "ページネーション上限に到達、取得を打ち切り"
+                    );
+                    return Err(DataProviderError::PageLimitExceeded {
+                        pages,
+                        pagination_key,
+                    });
+                }
+
+                let page = self
+                    .fetch_daily_bars_page(instrument_id, &range, pagination_key.as_deref())
+                    .await?;
+                let next = page.pagination_key.map(|key| (Some(key), pages + 1));
+                Ok(Some((page.bars, next)))
+            }
+        })
+        .boxed()
+    }
+
+    /// 日足データを 1 ページ取得する
+    async fn fetch_daily_bars_page(
         &self,
         instrument_id: &str,
         range: &DateRange,
-    ) -> Result<Vec<Bar>, DataProviderError> {
-        let mut all_bars = Vec::new();
-        let mut pagination_key: Option<String> = None;
+        pagination_key: Option<&str>,
+    ) -> Result<DailyBarsPage, DataProviderError> {
         let from_str = range.from.format("%Y%m%d").to_string();
         let to_str = range.to.format("%Y%m%d").to_string();
+        let mut params = vec![
+            ("code", instrument_id),
+            ("from", &from_str),
+            ("to", &to_str),
+        ];
+        if let Some(key) = pagination_key {
+            params.push(("pagination_key", key));
+        }
 
-        for page in 0..MAX_PAGES {
-            let mut params = vec![
-                ("code", instrument_id),
-                ("from", &from_str),
-                ("to", &to_str),
-            ];
-
-            if let Some(key) = &pagination_key {
-                params.push(("pagination_key", key));
-            }
+        let url = self.build_url("/equities/bars/daily", &params)?;
 
-            let url = self.build_url("/equities/bars/daily", &params)?;
+        tracing::debug!(%url, instrument_id, "J-Quants API から日足データを取得中");
 
-            tracing::debug!(%url, instrument_id, "J-Quants API から日足データを取得中");
+        let response = self.get_with_retry(&url).await?;
+        let raw: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| DataProviderError::Parse(e.to_string()))?;
+        let body: DailyBarsResponse = serde_json::from_value(raw.clone())
+            .map_err(|e| DataProviderError::Parse(e.to_string()))?;
 
-            let response = self.get_with_retry(&url).await?;
-            let body: DailyBarsResponse = response
-                .json()
+        // 既存のフィクスチャと日付単位でマージされるため、ページごとに記録する
+        if let Some(recorder) = &self.recorder
+            && let Some(records) = raw.get("data").and_then(|d| d.as_array())
+            && let Err(e) = recorder
+                .write_daily_bars(instrument_id, records.clone())
                 .await
-                .map_err(|e| DataProviderError::Parse(e.to_string()))?;
-
-            for d in body.data {
-                // 調整後価格が null のレコードはスキップ (非取引日等)
-                let (Some(adj_open), Some(adj_high), Some(adj_low), Some(adj_close)) =
-                    (d.adj_open, d.adj_high, d.adj_low, d.adj_close)
-                else {
-                    continue;
-                };
+        {
+            tracing::warn!(instrument_id, error = %e, "日足データの記録に失敗しました");
+        }
 
-                let date = NaiveDate::parse_from_str(&d.date, "%Y-%m-%d").map_err(|e| {
-                    DataProviderError::Parse(format!("invalid date '{}': {e}", d.date))
-                })?;
+        let mut bars = to_bars(instrument_id, body.data, SOURCE)?;
+        bars.sort_by_key(|b| b.timestamp);
+        Ok(DailyBarsPage {
+            bars,
+            pagination_key: body.pagination_key,
+        })
+    }
+}
 
-                let timestamp = Utc.from_utc_datetime(
-                    &date
-                        .and_hms_opt(0, 0, 0)
-                        .ok_or_else(|| DataProviderError::Parse("invalid time".to_string()))?,
-                );
+/// 日足データの 1 ページ分
+struct DailyBarsPage {
+    bars: Vec<Bar>,
+    /// 次のページの pagination_key (最終ページは None)
+    pagination_key: Option<String>,
+}
 
-                all_bars.push(Bar {
-                    // API レスポンスの Code (5 桁) ではなく、引数の instrument_id (4 桁) を使う
-                    instrument_id: instrument_id.to_string(),
-                    timeframe: Timeframe::Daily,
-                    timestamp,
-                    open: Self::to_decimal(adj_open)?,
-                    high: Self::to_decimal(adj_high)?,
-                    low: Self::to_decimal(adj_low)?,
-                    close: Self::to_decimal(adj_close)?,
-                    volume: d.adj_volume.map(|v| v.round() as i64).unwrap_or(0),
-                });
-            }
+/// API キーごとのレートリミットを共有する単位 (`api_rate_limits.key`)
+///
+/// 上限は API キーごとに課されるため、API キーの SHA-256 ハッシュで区別する (API キーそのものは保存しない)。
+fn rate_limit_key(api_key: &str) -> String {
+    format!(
+        "{RATE_LIMIT_KEY_PREFIX}:{:x}",
+        Sha256::digest(api_key.as_bytes())
+    )
+}
 
-            pagination_key = body.pagination_key;
-            if pagination_key.is_none() {
-                break;
-            }
+#[async_trait]
+impl DataProvider for JQuantsClient {
+    async fn fetch_daily_bars(
+        &self,
+        instrument_id: &str,
+        range: &DateRange,
+    ) -> Result<Vec<Bar>, DataProviderError> {
+        let mut bars: Vec<Bar> = self
+            .fetch_daily_bar_pages(instrument_id, range.clone())
+            .try_concat()
+            .await?;
+        bars.sort_by_key(|b| b.timestamp);
+        Ok(bars)
+    }
 
-            if page == MAX_PAGES - 1 {
-                tracing::warn!(
-                    instrument_id,
-                    max_pages = MAX_PAGES,
-                    "ページネーション上限に到達、取得を打ち切り"
-                );
-            }
-        }
+    fn fetch_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        self.fetch_daily_bar_pages_from(instrument_id, range, None)
+    }
 
-        all_bars.sort_by_key(|b| b.timestamp);
-        Ok(all_bars)
+    fn resume_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        pagination_key: String,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        self.fetch_daily_bar_pages_from(instrument_id, range, Some(pagination_key))
     }
 
     async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
@@ -301,21 +406,85 @@ impl DataProvider for JQuantsClient {
         tracing::debug!(%url, instrument_id, "J-Quants API から銘柄情報を取得中");
 
         let response = self.get_with_retry(&url).await?;
-        let body: EquitiesMasterResponse = response
+        let raw: serde_json::Value = response
             .json()
             .await
             .map_err(|e| DataProviderError::Parse(e.to_string()))?;
+        let body: EquitiesMasterResponse = serde_json::from_value(raw.clone())
+            .map_err(|e| DataProviderError::Parse(e.to_string()))?;
 
-        let master = body.data.into_iter().next().ok_or_else(|| {
-            DataProviderError::NotFound(format!("instrument '{instrument_id}' not found"))
-        })?;
+        if let Some(recorder) = &self.recorder
+            && let Err(e) = recorder.write_master(instrument_id, &raw).await
+        {
+            tracing::warn!(instrument_id, error = %e, "銘柄情報の記録に失敗しました");
+        }
 
-        Ok(Instrument {
-            id: master.code,
-            name: master.company_name,
-            // J-Quants は東証上場銘柄のみを提供する
-            market: Market::Tse,
-            sector: master.sector_name,
-        })
+        to_instrument(instrument_id, body.data)
+    }
+
+    async fn circuit_breaker(&self) -> Option<CircuitBreakerStatus> {
+        Some(self.circuit_breaker.status().await)
+    }
+}
+
+/// 日足レスポンスのレコードを Bar に変換する
+///
+/// 調整後価格が null のレコード (非取引日等) はスキップする。
+fn to_bars(
+    instrument_id: &str,
+    records: Vec<DailyBar>,
+    source: &str,
+) -> Result<Vec<Bar>, DataProviderError> {
+    let mut bars = Vec::with_capacity(records.len());
+
+    for d in records {
+        let (Some(adj_open), Some(adj_high), Some(adj_low), Some(adj_close)) =
+            (d.adj_open, d.adj_high, d.adj_low, d.adj_close)
+        else {
+            continue;
+        };
+
+        let date = NaiveDate::parse_from_str(&d.date, "%Y-%m-%d")
+            .map_err(|e| DataProviderError::Parse(format!("invalid date '{}': {e}", d.date)))?;
+
+        let timestamp = Utc.from_utc_datetime(
+            &date
+                .and_hms_opt(0, 0, 0)
+                .ok_or_else(|| DataProviderError::Parse("invalid time".to_string()))?,
+        );
+
+        bars.push(Bar {
+            // API レスポンスの Code (5 桁) ではなく、引数の instrument_id (4 桁) を使う
+            instrument_id: instrument_id.to_string(),
+            timeframe: Timeframe::Daily,
+            timestamp,
+            open: JQuantsClient::to_decimal(adj_open)?,
+            high: JQuantsClient::to_decimal(adj_high)?,
+            low: JQuantsClient::to_decimal(adj_low)?,
+            close: JQuantsClient::to_decimal(adj_close)?,
+            volume: d.adj_volume.map(|v| v.round() as i64).unwrap_or(0),
+            source: Some(source.to_string()),
+        });
     }
+
+    Ok(bars)
+}
+
+/// 銘柄マスタレスポンスのレコードを Instrument に変換する
+fn to_instrument(
+    instrument_id: &str,
+    records: Vec<EquityMaster>,
+) -> Result<Instrument, DataProviderError> {
+    let master = records.into_iter().next().ok_or_else(|| {
+        DataProviderError::NotFound(format!("instrument '{instrument_id}' not found"))
+    })?;
+
+    Ok(Instrument {
+        id: master.code,
+        name: master.company_name,
+        // J-Quants は東証上場銘柄のみを提供する
+        market: Market::Tse,
+        sector: master.sector_name,
+        market_segment: master.market_name,
+    })
 }
@@ -0,0 +1,845 @@
+//! J-Quants API のレートリミット
+//!
+//! 送信前にスライディングウィンドウで送信数を制御し、レスポンスのヘッダー
+//! (`Retry-After`、`X-RateLimit-*`) と 429 からサーバー側の上限を学習する。
+//! 同じ API キーを使う複数のプロセスで上限を共有するため、状態を `api_rate_limits` テーブルに保存できる。
+//! 共有する状態の時刻はプロセス間の時計のずれの影響を受けないよう、PostgreSQL の時計で記録する。
+
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, RandomState};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, DatabaseTransaction, DbErr,
+    EntityTrait, QueryFilter, Set, Statement, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::scheduler::Scheduler;
+use crate::data_provider::priority::{RequestPriority, current_priority};
+use crate::entities::api_rate_limits;
+
+/// レートリミットのウィンドウ幅 (60 秒)
+pub(super) const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+/// ウィンドウ内の既定の最大リクエスト数 (J-Quants 無料プラン: 1 分間に 5 リクエスト)
+pub(super) const RATE_LIMIT_MAX_REQUESTS: usize = 5;
+/// 指数バックオフの初期待機時間
+const INITIAL_BACKOFF_MS: u64 = 500;
+/// サーバーが指示する待機時間の上限 (異常に長い値で処理が止まり続けないようにする)
+const MAX_SERVER_WAIT: Duration = Duration::from_secs(300);
+/// 共有ストアで `Interactive` 以外のリクエストに使わせない、ウィンドウ内の枠の数
+///
+/// 優先度付きキューはプロセス内にしかないため、別のプロセスのバックフィルが枠を使い切ると
+/// ユーザーが待っているリクエストも枠が空くまで待たされる。これを避けるために枠を残しておく。
+const INTERACTIVE_RESERVED_SLOTS: usize = 1;
+/// `X-RateLimit-Reset` をエポック秒とみなす下限 (これより小さい値は残り秒数とみなす)
+const RESET_EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+/// スライディングウィンドウ方式のレートリミッター
+///
+/// 直近 60 秒間のリクエスト送信時刻を記録し、上限に達している場合は
+/// 最も古いリクエストがウィンドウから外れるまで待機する。
+/// 上限は 429 を受けるとウィンドウ内で受け付けられた数まで下げ、
+/// 429 のないウィンドウが続くと 1 つずつ戻す。
+///
+/// 枠を待つリクエストは優先度順に枠を取得し、優先度の低いリクエストは空いた枠を使う。
+/// 共有ストアを設定した場合は状態を DB に保存し、全プロセスで 1 つの上限を共有する。
+/// 優先度順に並べるのはプロセス内の待ちキューだけで、プロセスをまたいだ順序は保証しない。
+/// 代わりに共有ストアでは [`INTERACTIVE_RESERVED_SLOTS`] の枠を `Interactive` のリクエスト用に残す。
+/// DB の読み書きに失敗した場合はログを出力し、プロセス内の状態で代替する。
+pub(super) struct RateLimiter {
+    /// プロセス内の状態の時刻の基準
+    /// (基準からの経過時間を tokio の時計で測り、テストで時間を進められるようにする)
+    epoch: (DateTime<Utc>, Instant),
+    /// プロセス内の状態
+    local: Mutex<State>,
+    /// 枠を待っているリクエストの優先度付きキュー (プロセス内)
+    scheduler: Scheduler,
+    /// 全プロセスで共有する状態の保存先
+    shared: Option<SharedStore>,
+}
+
+/// 送信の可否
+enum Acquire {
+    /// 送信してよい (送信時刻)
+    Sent(DateTime<Utc>),
+    /// サーバーの指示で送信を止めている (期限)
+    Paused(DateTime<Utc>),
+    /// ウィンドウ内の上限に達している (最も古いリクエストがウィンドウから外れる時刻)
+    WindowFull(DateTime<Utc>),
+}
+
+/// レートリミッターの状態
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct State {
+    /// 直近のリクエスト送信時刻 (古い順)
+    timestamps: VecDeque<DateTime<Utc>>,
+    /// ウィンドウ内の最大リクエスト数
+    max_requests: usize,
+    /// `max_requests` を戻す上限 (サーバーが `X-RateLimit-Limit` で通知した値、通知がなければ既定値)
+    ceiling: usize,
+    /// 最後に `max_requests` を変更した時刻
+    adjusted_at: DateTime<Utc>,
+    /// サーバーの指示で送信を止める期限
+    paused_until: Option<DateTime<Utc>>,
+}
+
+impl State {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            timestamps: VecDeque::with_capacity(RATE_LIMIT_MAX_REQUESTS),
+            max_requests: RATE_LIMIT_MAX_REQUESTS,
+            ceiling: RATE_LIMIT_MAX_REQUESTS,
+            adjusted_at: now,
+            paused_until: None,
+        }
+    }
+
+    fn pause(&mut self, now: DateTime<Utc>, wait: Duration) {
+        let until = now + delta(wait.min(MAX_SERVER_WAIT));
+        if self.paused_until.is_none_or(|current| current < until) {
+            self.paused_until = Some(until);
+        }
+    }
+
+    /// ウィンドウ内の枠を 1 つ取得する
+    ///
+    /// `reserved` の枠は残し、上限から `reserved` を引いた数 (最低 1) までしか取得しない。
+    fn try_acquire(&mut self, now: DateTime<Utc>, reserved: usize) -> Acquire {
+        if let Some(until) = self.paused_until {
+            if now < until {
+                return Acquire::Paused(until);
+            }
+            self.paused_until = None;
+        }
+
+        // ウィンドウ外のタイムスタンプを削除
+        let window = delta(RATE_LIMIT_WINDOW);
+        while self
+            .timestamps
+            .front()
+            .is_some_and(|&oldest| now - oldest >= window)
+        {
+            self.timestamps.pop_front();
+        }
+
+        let limit = self.max_requests.saturating_sub(reserved).max(1);
+        match self.timestamps.front() {
+            Some(&oldest) if self.timestamps.len() >= limit => Acquire::WindowFull(oldest + window),
+            _ => {
+                self.timestamps.push_back(now);
+                Acquire::Sent(now)
+            }
+        }
+    }
+
+    fn update_quota(&mut self, now: DateTime<Utc>, headers: &RateLimitHeaders) {
+        if let Some(limit) = headers.limit {
+            let limit = limit.max(1);
+            if limit != self.ceiling {
+                tracing::info!(limit, "サーバーが通知したレートリミットの上限を使います");
+            }
+            // 429 で下げていない場合はサーバーの上限に合わせ、下げている場合は上限を超えないようにする
+            self.max_requests = if self.max_requests >= self.ceiling {
+                limit
+            } else {
+                self.max_requests.min(limit)
+            };
+            self.ceiling = limit;
+        }
+
+        if headers.remaining == Some(0)
+            && let Some(reset_after) = headers.reset_after
+        {
+            self.pause(now, reset_after);
+        }
+    }
+
+    fn record_success(&mut self, now: DateTime<Utc>) {
+        if self.max_requests < self.ceiling && now - self.adjusted_at >= delta(RATE_LIMIT_WINDOW) {
+            self.max_requests += 1;
+            self.adjusted_at = now;
+            tracing::info!(
+                max_requests = self.max_requests,
+                "429 が発生していないため、レートリミットの上限を戻します"
+            );
+        }
+    }
+
+    fn record_rate_limited(
+        &mut self,
+        now: DateTime<Utc>,
+        sent_at: DateTime<Utc>,
+        retry_after: Option<Duration>,
+    ) {
+        if let Some(index) = self.timestamps.iter().position(|&t| t == sent_at) {
+            self.timestamps.remove(index);
+        }
+
+        let accepted = self.timestamps.len().max(1);
+        if accepted < self.max_requests {
+            self.max_requests = accepted;
+            tracing::warn!(
+                max_requests = accepted,
+                "429 を受けたため、レートリミットの上限を下げます"
+            );
+        }
+        self.adjusted_at = now;
+
+        if let Some(wait) = retry_after {
+            self.pause(now, wait);
+        }
+    }
+}
+
+/// `api_rate_limits` テーブルの 1 行に保存する共有の状態
+struct SharedStore {
+    db: DatabaseConnection,
+    key: String,
+}
+
+impl SharedStore {
+    /// 行ロックを取得して状態を読み込み、`f` で更新して保存する
+    ///
+    /// `f` に渡す現在時刻は、行ロックを取得した後の PostgreSQL の `clock_timestamp()` とする。
+    async fn update<R>(&self, f: &impl Fn(&mut State, DateTime<Utc>) -> R) -> Result<R, DbErr> {
+        let txn = self.db.begin().await?;
+
+        let (mut state, now) = match self.lock(&txn).await? {
+            Some(locked) => locked,
+            None => {
+                // 初回は既定の状態の行を作る (同時に作られた場合はその行を使う)
+                let now = clock_timestamp(&txn).await?;
+                api_rate_limits::Entity::insert(api_rate_limits::ActiveModel {
+                    key: Set(self.key.clone()),
+                    state: Set(to_json(&State::new(now))?),
+                    ..Default::default()
+                })
+                .on_conflict(
+                    OnConflict::column(api_rate_limits::Column::Key)
+                        .do_nothing()
+                        .to_owned(),
+                )
+                .exec_without_returning(&txn)
+                .await?;
+                self.lock(&txn)
+                    .await?
+                    .ok_or_else(|| DbErr::RecordNotFound(format!("rate limit '{}'", self.key)))?
+            }
+        };
+
+        let result = f(&mut state, now);
+
+        api_rate_limits::Entity::update_many()
+            .set(api_rate_limits::ActiveModel {
+                state: Set(to_json(&state)?),
+                updated_at: Set(now.into()),
+                ..Default::default()
+            })
+            .filter(api_rate_limits::Column::Key.eq(self.key.as_str()))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+
+        Ok(result)
+    }
+
+    /// 行ロックを取得して状態を読み込み、ロック取得後の DB の現在時刻とともに返す (行がなければ None)
+    async fn lock(
+        &self,
+        txn: &DatabaseTransaction,
+    ) -> Result<Option<(State, DateTime<Utc>)>, DbErr> {
+        let Some(row) = txn
+            .query_one_raw(Statement::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                "SELECT state, clock_timestamp() AS now FROM api_rate_limits WHERE key = $1 FOR UPDATE",
+                [self.key.as_str().into()],
+            ))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let state: serde_json::Value = row.try_get("", "state")?;
+        let state = serde_json::from_value(state)
+            .map_err(|e| DbErr::Json(format!("invalid rate limit state: {e}")))?;
+        Ok(Some((state, row.try_get("", "now")?)))
+    }
+}
+
+/// DB の現在時刻 (トランザクション開始時刻ではなく実行時点の時刻)
+async fn clock_timestamp(txn: &DatabaseTransaction) -> Result<DateTime<Utc>, DbErr> {
+    txn.query_one_raw(Statement::from_string(
+        DatabaseBackend::Postgres,
+        "SELECT clock_timestamp() AS now",
+    ))
+    .await?
+    .ok_or_else(|| DbErr::RecordNotFound("clock_timestamp".to_string()))?
+    .try_get("", "now")
+}
+
+fn to_json(state: &State) -> Result<serde_json::Value, DbErr> {
+    serde_json::to_value(state)
+        .map_err(|e| DbErr::Json(format!("failed to serialize rate limit state: {e}")))
+}
+
+/// std の Duration を chrono の TimeDelta に変換する (範囲外は最大値)
+fn delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        let now = Utc::now();
+        Self {
+            epoch: (now, Instant::now()),
+            local: Mutex::new(State::new(now)),
+            scheduler: Scheduler::new(),
+            shared: None,
+        }
+    }
+
+    /// 状態を `api_rate_limits` テーブルの `key` の行に保存し、全プロセスで共有する
+    pub fn with_shared_store(mut self, db: DatabaseConnection, key: impl Into<String>) -> Self {
+        self.shared = Some(SharedStore {
+            db,
+            key: key.into(),
+        });
+        self
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        let (epoch, started) = self.epoch;
+        epoch + delta(started.elapsed())
+    }
+
+    /// 状態を `f` で更新する
+    ///
+    /// 共有ストアがあればその状態を、なければ (または読み書きに失敗した場合は) プロセス内の状態を使う。
+    /// `f` には使う状態の時計 (共有ストアは DB、プロセス内の状態は `now`) の現在時刻を渡す。
+    async fn update<R>(&self, f: impl Fn(&mut State, DateTime<Utc>) -> R) -> R {
+        if let Some(shared) = &self.shared {
+            match shared.update(&f).await {
+                Ok(result) => return result,
+                Err(e) => tracing::warn!(
+                    key = shared.key,
+                    error = %e,
+                    "共有レートリミットの読み書きに失敗したため、プロセス内の状態を使います"
+                ),
+            }
+        }
+        f(&mut *self.local.lock().await, self.now())
+    }
+
+    /// リクエスト送信の許可を取得し、送信時刻を返す
+    ///
+    /// 現在のタスクの優先度 ([`current_priority`]) で待ちキューに並び、先頭になってから枠を取得する。
+    /// サーバーの指示で送信を止めている間は期限まで待機する。
+    /// ウィンドウ内のリクエスト数が上限に達している場合、最も古いリクエストが
+    /// ウィンドウから外れるまで待機する。
+    /// 共有ストアを使う場合、`Interactive` 以外のリクエストは [`INTERACTIVE_RESERVED_SLOTS`] の枠を残して待機する。
+    pub async fn acquire(&self) -> DateTime<Utc> {
+        let priority = current_priority();
+        let reserved = if self.shared.is_some() && priority != RequestPriority::Interactive {
+            INTERACTIVE_RESERVED_SLOTS
+        } else {
+            0
+        };
+        let mut ticket = self.scheduler.enter(priority);
+        loop {
+            // 優先度の高いリクエストが待っている間は枠を取得しない
+            if !ticket.is_next() {
+                ticket.changed().await;
+                continue;
+            }
+
+            // ロック (共有ストアの場合は行ロック) は判定の間だけ保持し、解放してから sleep
+            // 待機時間は判定に使った時計で求める
+            let (acquired, now) = self
+                .update(|state, now| (state.try_acquire(now, reserved), now))
+                .await;
+            let (until, message) = match acquired {
+                Acquire::Sent(sent_at) => return sent_at,
+                Acquire::Paused(until) => (until, "サーバーの指示により送信を停止中"),
+                Acquire::WindowFull(until) => (until, "レートリミットに到達、待機中"),
+            };
+
+            let wait = (until - now).to_std().unwrap_or_default();
+            tracing::info!(wait_ms = wait.as_millis() as u64, ?priority, message);
+            // 待機中に優先度の高いリクエストが並んだら、先頭を譲るために判定し直す
+            tokio::select! {
+                () = tokio::time::sleep(wait) => {}
+                () = ticket.changed() => {}
+            }
+        }
+    }
+
+    /// レスポンスのレートリミット関連ヘッダーを反映する
+    ///
+    /// `X-RateLimit-Limit` を上限として使い、`X-RateLimit-Remaining` が 0 の場合は
+    /// `X-RateLimit-Reset` まで送信を止める。
+    pub async fn update_quota(&self, headers: &RateLimitHeaders) {
+        self.update(|state, now| state.update_quota(now, headers))
+            .await;
+    }
+
+    /// 成功したリクエストを記録する
+    ///
+    /// 上限を下げてから 429 のないままウィンドウ幅が経過していれば、上限を 1 つ戻す。
+    pub async fn record_success(&self) {
+        self.update(|state, now| state.record_success(now)).await;
+    }
+
+    /// 429 を受けたリクエストを記録する
+    ///
+    /// 拒否されたリクエストはサーバーの枠を消費していないとみなしてウィンドウから除き、
+    /// ウィンドウ内で受け付けられたリクエスト数 (最低 1) を新しい上限とする。
+    /// `Retry-After` が指定されていれば、その間はすべてのリクエストの送信を止める。
+    pub async fn record_rate_limited(&self, sent_at: DateTime<Utc>, retry_after: Option<Duration>) {
+        self.update(|state, now| state.record_rate_limited(now, sent_at, retry_after))
+            .await;
+    }
+
+    /// 現在のウィンドウ内の最大リクエスト数
+    #[cfg(test)]
+    pub async fn max_requests(&self) -> usize {
+        self.update(|state, _| state.max_requests).await
+    }
+}
+
+/// レスポンスのレートリミット関連ヘッダー
+///
+/// J-Quants が返す場合に備え、`X-RateLimit-*` と IETF ドラフトの `RateLimit-*` の両方を読む。
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct RateLimitHeaders {
+    /// `Retry-After` (秒数または HTTP-date)
+    pub retry_after: Option<Duration>,
+    /// ウィンドウ内の最大リクエスト数
+    pub limit: Option<usize>,
+    /// ウィンドウ内の残りリクエスト数
+    pub remaining: Option<usize>,
+    /// ウィンドウがリセットされるまでの時間
+    pub reset_after: Option<Duration>,
+}
+
+impl RateLimitHeaders {
+    pub fn from_headers(headers: &HeaderMap, now: DateTime<Utc>) -> Self {
+        Self {
+            retry_after: header_str(headers, &[RETRY_AFTER.as_str()])
+                .and_then(|value| parse_retry_after(value, now)),
+            limit: header_number(headers, &["x-ratelimit-limit", "ratelimit-limit"])
+                .and_then(|n| usize::try_from(n).ok()),
+            remaining: header_number(headers, &["x-ratelimit-remaining", "ratelimit-remaining"])
+                .and_then(|n| usize::try_from(n).ok()),
+            reset_after: header_number(headers, &["x-ratelimit-reset", "ratelimit-reset"])
+                .map(|n| parse_reset(n, now)),
+        }
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+}
+
+/// 数値ヘッダーの先頭の数値を読む (`5, 5;w=60` のようなポリシー付きの値も許容する)
+fn header_number(headers: &HeaderMap, names: &[&str]) -> Option<u64> {
+    let value = header_str(headers, names)?;
+    value.split([',', ';']).next()?.trim().parse().ok()
+}
+
+/// `Retry-After` の秒数または HTTP-date を待機時間に変換する
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+/// `X-RateLimit-Reset` の残り秒数またはエポック秒を待機時間に変換する
+fn parse_reset(value: u64, now: DateTime<Utc>) -> Duration {
+    if value < RESET_EPOCH_THRESHOLD {
+        return Duration::from_secs(value);
+    }
+    let now = u64::try_from(now.timestamp()).unwrap_or_default();
+    Duration::from_secs(value.saturating_sub(now))
+}
+
+/// リトライ前の待機時間
+///
+/// `Retry-After` が指定されていればその時間を、なければ指数バックオフの時間を待つ。
+/// 複数のリクエストが同時にリトライしないよう、どちらにもランダムな揺らぎを加える。
+pub(super) fn retry_backoff(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    match retry_after {
+        Some(wait) => {
+            wait.min(MAX_SERVER_WAIT) + Duration::from_millis(jitter_ms(INITIAL_BACKOFF_MS))
+        }
+        None => {
+            // 半分を固定、残り半分をランダムにする (equal jitter)
+            let backoff = INITIAL_BACKOFF_MS * 2u64.pow(attempt.saturating_sub(1));
+            Duration::from_millis(backoff / 2 + jitter_ms(backoff / 2))
+        }
+    }
+}
+
+/// 0 以上 `max` 以下のランダムなミリ秒数
+fn jitter_ms(max: u64) -> u64 {
+    // RandomState はインスタンスごとに異なるキーを持つため、乱数クレートなしで揺らぎを作れる
+    RandomState::new().hash_one(max) % (max + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::TimeZone;
+    use reqwest::header::HeaderValue;
+    use rstest::rstest;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::data_provider::priority::{RequestPriority, with_priority};
+    use crate::testing::create_test_db;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    async fn fill(limiter: &RateLimiter, count: usize) -> Vec<DateTime<Utc>> {
+        let mut sent = Vec::new();
+        for _ in 0..count {
+            sent.push(limiter.acquire().await);
+        }
+        sent
+    }
+
+    /// 100ms 以内に acquire が通過するか
+    async fn acquires_immediately(limiter: &RateLimiter) -> bool {
+        tokio::time::timeout(Duration::from_millis(100), limiter.acquire())
+            .await
+            .is_ok()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_allows_requests_within_limit() {
+        let limiter = RateLimiter::new();
+
+        // 上限以内のリクエストは即座に通過する
+        for _ in 0..RATE_LIMIT_MAX_REQUESTS {
+            limiter.acquire().await;
+        }
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_blocks_when_limit_exceeded() {
+        let limiter = RateLimiter::new();
+
+        // 上限まで消費
+        fill(&limiter, RATE_LIMIT_MAX_REQUESTS).await;
+
+        // 次の acquire は待機するはず
+        assert!(
+            !acquires_immediately(&limiter).await,
+            "上限超過時に acquire がブロックされるべき"
+        );
+
+        // ウィンドウを経過させると通過する
+        tokio::time::advance(RATE_LIMIT_WINDOW).await;
+        assert!(
+            acquires_immediately(&limiter).await,
+            "ウィンドウ経過後に acquire が通過するべき"
+        );
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limited_tightens_to_accepted_requests() {
+        let limiter = RateLimiter::new();
+        let sent = fill(&limiter, 3).await;
+
+        limiter.record_rate_limited(sent[2], None).await;
+
+        // 受け付けられた 2 件を上限とし、拒否された 1 件は枠を消費しない
+        assert_eq!(limiter.max_requests().await, 2);
+        assert!(!acquires_immediately(&limiter).await);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limited_keeps_at_least_one_request() {
+        let limiter = RateLimiter::new();
+        let sent = limiter.acquire().await;
+
+        limiter.record_rate_limited(sent, None).await;
+
+        assert_eq!(limiter.max_requests().await, 1);
+        assert!(acquires_immediately(&limiter).await);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_limit_recovers_after_window_without_rate_limit() {
+        let limiter = RateLimiter::new();
+        let sent = limiter.acquire().await;
+        limiter.record_rate_limited(sent, None).await;
+
+        limiter.record_success().await;
+        assert_eq!(limiter.max_requests().await, 1);
+
+        tokio::time::advance(RATE_LIMIT_WINDOW).await;
+        limiter.record_success().await;
+        limiter.record_success().await;
+        assert_eq!(limiter.max_requests().await, 2);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after_pauses_all_requests() {
+        let limiter = RateLimiter::new();
+        let sent = limiter.acquire().await;
+
+        limiter
+            .record_rate_limited(sent, Some(Duration::from_secs(10)))
+            .await;
+
+        assert!(!acquires_immediately(&limiter).await);
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(acquires_immediately(&limiter).await);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_update_quota_follows_server_limit() {
+        let limiter = RateLimiter::new();
+
+        limiter
+            .update_quota(&RateLimitHeaders {
+                limit: Some(60),
+                ..RateLimitHeaders::default()
+            })
+            .await;
+
+        assert_eq!(limiter.max_requests().await, 60);
+        fill(&limiter, 60).await;
+        assert!(!acquires_immediately(&limiter).await);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_update_quota_pauses_when_remaining_is_zero() {
+        let limiter = RateLimiter::new();
+
+        limiter
+            .update_quota(&RateLimitHeaders {
+                remaining: Some(0),
+                reset_after: Some(Duration::from_secs(5)),
+                ..RateLimitHeaders::default()
+            })
+            .await;
+
+        assert!(!acquires_immediately(&limiter).await);
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(acquires_immediately(&limiter).await);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_interactive_request_jumps_ahead_of_bulk() {
+        let limiter = Arc::new(RateLimiter::new());
+        // 1 秒おきに上限まで消費し、枠が 1 つずつ空くようにする
+        for _ in 0..RATE_LIMIT_MAX_REQUESTS {
+            limiter.acquire().await;
+            tokio::time::advance(Duration::from_secs(1)).await;
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for priority in [RequestPriority::Bulk, RequestPriority::Interactive] {
+            let limiter = Arc::clone(&limiter);
+            let tx = tx.clone();
+            tokio::spawn(with_priority(priority, async move {
+                limiter.acquire().await;
+                tx.send(priority).unwrap();
+            }));
+            // Bulk が先に並んで枠を待ち始めてから Interactive を並べる
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+
+        assert_eq!(rx.recv().await, Some(RequestPriority::Interactive));
+        assert_eq!(rx.recv().await, Some(RequestPriority::Bulk));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_shared_store_shares_quota_between_limiters(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let first = RateLimiter::new().with_shared_store(db.clone(), "jquants");
+        // 別プロセス (または再起動後のプロセス) のレートリミッター
+        let second = RateLimiter::new().with_shared_store(db, "jquants");
+
+        // Interactive 以外のリクエストは予約した枠を残して上限に達する
+        fill(&first, RATE_LIMIT_MAX_REQUESTS - INTERACTIVE_RESERVED_SLOTS).await;
+
+        assert!(!acquires_immediately(&second).await);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_shared_store_shares_learned_limit(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let first = RateLimiter::new().with_shared_store(db.clone(), "jquants");
+        let second = RateLimiter::new().with_shared_store(db.clone(), "jquants");
+        let other_key = RateLimiter::new().with_shared_store(db, "other");
+
+        let sent = fill(&first, 2).await;
+        first.record_rate_limited(sent[1], None).await;
+
+        assert_eq!(second.max_requests().await, 1);
+        assert!(!acquires_immediately(&second).await);
+        assert_eq!(other_key.max_requests().await, RATE_LIMIT_MAX_REQUESTS);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_shared_store_reserves_slots_for_interactive_requests(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let backfill = RateLimiter::new().with_shared_store(db.clone(), "jquants");
+        // ユーザーの操作を受け付ける別プロセスのレートリミッター
+        let api = RateLimiter::new().with_shared_store(db, "jquants");
+
+        with_priority(RequestPriority::Bulk, async {
+            fill(
+                &backfill,
+                RATE_LIMIT_MAX_REQUESTS - INTERACTIVE_RESERVED_SLOTS,
+            )
+            .await;
+            assert!(!acquires_immediately(&backfill).await);
+        })
+        .await;
+
+        // 別プロセスの優先度付きキューには並んでいないが、残した枠で送信できる
+        let interactive =
+            with_priority(RequestPriority::Interactive, acquires_immediately(&api)).await;
+        assert!(interactive);
+        assert!(!acquires_immediately(&api).await);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_shared_store_uses_database_clock(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        // 時計が 1 時間遅れているプロセスのレートリミッター
+        let mut lagging = RateLimiter::new().with_shared_store(db.clone(), "jquants");
+        lagging.epoch.0 -= TimeDelta::hours(1);
+        let second = RateLimiter::new().with_shared_store(db, "jquants");
+
+        fill(
+            &lagging,
+            RATE_LIMIT_MAX_REQUESTS - INTERACTIVE_RESERVED_SLOTS,
+        )
+        .await;
+
+        // プロセスの時計で記録すると 1 時間前の送信とみなされ、枠が空いてしまう
+        assert!(!acquires_immediately(&second).await);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_falls_back_to_local_state_when_store_fails(pool: PgPool) {
+        // マイグレーションを実行せず、api_rate_limits テーブルがない状態
+        let db = sea_orm::SqlxPostgresConnector::from_sqlx_postgres_pool(pool);
+        let limiter = RateLimiter::new().with_shared_store(db, "jquants");
+
+        fill(
+            &limiter,
+            RATE_LIMIT_MAX_REQUESTS - INTERACTIVE_RESERVED_SLOTS,
+        )
+        .await;
+
+        assert!(!acquires_immediately(&limiter).await);
+    }
+
+    #[rstest]
+    #[case::seconds(&[("retry-after", "30")], Some(Duration::from_secs(30)))]
+    #[case::http_date(&[("retry-after", "Mon, 06 Jan 2025 09:00:45 GMT")], Some(Duration::from_secs(45)))]
+    #[case::past_http_date(&[("retry-after", "Mon, 06 Jan 2025 08:59:00 GMT")], Some(Duration::ZERO))]
+    #[case::invalid(&[("retry-after", "soon")], None)]
+    #[case::missing(&[], None)]
+    fn test_parses_retry_after(
+        #[case] pairs: &[(&'static str, &'static str)],
+        #[case] expected: Option<Duration>,
+    ) {
+        let parsed = RateLimitHeaders::from_headers(&headers(pairs), now());
+
+        assert_eq!(parsed.retry_after, expected);
+    }
+
+    #[rstest]
+    #[case::x_ratelimit(&[
+        ("x-ratelimit-limit", "5"),
+        ("x-ratelimit-remaining", "0"),
+        ("x-ratelimit-reset", "12"),
+    ])]
+    #[case::ietf_draft(&[
+        ("ratelimit-limit", "5, 5;w=60"),
+        ("ratelimit-remaining", "0"),
+        ("ratelimit-reset", "12"),
+    ])]
+    #[case::epoch_reset(&[
+        ("x-ratelimit-limit", "5"),
+        ("x-ratelimit-remaining", "0"),
+        ("x-ratelimit-reset", "1736154012"),
+    ])]
+    fn test_parses_quota_headers(#[case] pairs: &[(&'static str, &'static str)]) {
+        let parsed = RateLimitHeaders::from_headers(&headers(pairs), now());
+
+        assert_eq!(
+            parsed,
+            RateLimitHeaders {
+                retry_after: None,
+                limit: Some(5),
+                remaining: Some(0),
+                reset_after: Some(Duration::from_secs(12)),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::first_retry(1, 250, 500)]
+    #[case::third_retry(3, 1000, 2000)]
+    fn test_backoff_has_jitter_within_bounds(
+        #[case] attempt: u32,
+        #[case] min_ms: u64,
+        #[case] max_ms: u64,
+    ) {
+        for _ in 0..100 {
+            let backoff = retry_backoff(attempt, None);
+            assert!(
+                (Duration::from_millis(min_ms)..=Duration::from_millis(max_ms)).contains(&backoff),
+                "{backoff:?}"
+            );
+        }
+    }
+
+    #[rstest]
+    fn test_backoff_honours_retry_after() {
+        let backoff = retry_backoff(1, Some(Duration::from_secs(3)));
+
+        assert!(
+            (Duration::from_secs(3)..=Duration::from_millis(3500)).contains(&backoff),
+            "{backoff:?}"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::models::{Bar, Instrument};
+
+use super::fixture::FixtureStore;
+use super::response::{DailyBar, EquitiesMasterResponse};
+use super::{to_bars, to_instrument};
+
+/// 再生したバーの取得元 (`bars.source`)
+pub const SOURCE: &str = "jquants_replay";
+
+/// 記録済みの J-Quants API レスポンスを再生する DataProvider
+///
+/// [`JQuantsClient::with_recording`](super::JQuantsClient::with_recording) で記録した
+/// フィクスチャから応答し、ネットワークにはアクセスしない。
+/// 日足は記録済みのレコードから要求された期間を切り出して返す。
+/// 記録されていない銘柄には NotFound を返す。
+pub struct ReplayProvider {
+    fixtures: FixtureStore,
+}
+
+impl ReplayProvider {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            fixtures: FixtureStore::new(dir),
+        }
+    }
+}
+
+#[async_trait]
+impl DataProvider for ReplayProvider {
+    async fn fetch_daily_bars(
+        &self,
+        instrument_id: &str,
+        range: &DateRange,
+    ) -> Result<Vec<Bar>, DataProviderError> {
+        let records = self
+            .fixtures
+            .read_daily_bars(instrument_id)
+            .await?
+            .ok_or_else(|| {
+                DataProviderError::NotFound(format!("instrument '{instrument_id}' not found"))
+            })?;
+        let records: Vec<DailyBar> = serde_json::from_value(serde_json::Value::Array(records))
+            .map_err(|e| DataProviderError::Parse(e.to_string()))?;
+
+        let mut bars: Vec<Bar> = to_bars(instrument_id, records, SOURCE)?
+            .into_iter()
+            .filter(|bar| {
+                let date = bar.timestamp.date_naive();
+                range.from <= date && date <= range.to
+            })
+            .collect();
+        bars.sort_by_key(|b| b.timestamp);
+        Ok(bars)
+    }
+
+    async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
+        let body = self
+            .fixtures
+            .read_master(instrument_id)
+            .await?
+            .ok_or_else(|| {
+                DataProviderError::NotFound(format!("instrument '{instrument_id}' not found"))
+            })?;
+        let body: EquitiesMasterResponse =
+            serde_json::from_value(body).map_err(|e| DataProviderError::Parse(e.to_string()))?;
+
+        to_instrument(instrument_id, body.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::data_provider::jquants::mock::{JQuantsMockServer, MockBar};
+
+    /// テストごとに空のフィクスチャディレクトリを用意し、終了時に削除する
+    struct FixtureDir(PathBuf);
+
+    impl FixtureDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("jquants-replay-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for FixtureDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn sample_bar(date_str: &'static str, close: f64) -> MockBar {
+        MockBar {
+            date: date_str,
+            code: "86970",
+            adj_open: Some(100.0),
+            adj_high: Some(110.0),
+            adj_low: Some(95.0),
+            adj_close: Some(close),
+            adj_volume: Some(1000.0),
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_replays_recorded_daily_bars() -> Result<(), DataProviderError> {
+        let dir = FixtureDir::new("daily-bars");
+        let mock = JQuantsMockServer::start().await;
+        mock.daily_bars()
+            .code("8697")
+            .bars(vec![
+                sample_bar("2025-01-06", 105.0),
+                sample_bar("2025-01-07", 106.0),
+                sample_bar("2025-01-08", 107.0),
+            ])
+            .ok()
+            .await;
+
+        let range = DateRange {
+            from: date(2025, 1, 6),
+            to: date(2025, 1, 10),
+        };
+        let client = mock.client()?.with_recording(dir.0.clone());
+        // 再生したバーは取得元だけが異なる
+        let recorded: Vec<Bar> = client
+            .fetch_daily_bars("8697", &range)
+            .await?
+            .into_iter()
+            .map(|bar| Bar {
+                source: Some(SOURCE.to_string()),
+                ..bar
+            })
+            .collect();
+
+        let replay = ReplayProvider::new(dir.0.clone());
+        assert_eq!(replay.fetch_daily_bars("8697", &range).await?, recorded);
+
+        // 記録済みの範囲から要求された期間だけを切り出す
+        let narrowed = DateRange {
+            from: date(2025, 1, 7),
+            to: date(2025, 1, 7),
+        };
+        let bars = replay.fetch_daily_bars("8697", &narrowed).await?;
+        assert_eq!(bars, recorded[1..2]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_recording_merges_by_date() -> Result<(), DataProviderError> {
+        let dir = FixtureDir::new("merge");
+        let store = FixtureStore::new(dir.0.clone());
+        let record =
+            |date: &str, close: f64| json!({ "Date": date, "Code": "86970", "AdjC": close });
+
+        store
+            .write_daily_bars(
+                "8697",
+                vec![record("2025-01-07", 1.0), record("2025-01-06", 1.0)],
+            )
+            .await?;
+        store
+            .write_daily_bars(
+                "8697",
+                vec![record("2025-01-07", 2.0), record("2025-01-08", 2.0)],
+            )
+            .await?;
+
+        let records = store.read_daily_bars("8697").await?;
+        assert_eq!(
+            records,
+            Some(vec![
+                record("2025-01-06", 1.0),
+                record("2025-01-07", 2.0),
+                record("2025-01-08", 2.0),
+            ])
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_replays_recorded_instrument() -> Result<(), DataProviderError> {
+        let dir = FixtureDir::new("instrument");
+        let mock = JQuantsMockServer::start().await;
+        mock.instrument().code("8697").ok().await;
+
+        let client = mock.client()?.with_recording(dir.0.clone());
+        let recorded = client.fetch_instrument("8697").await?;
+
+        let replay = ReplayProvider::new(dir.0.clone());
+        assert_eq!(replay.fetch_instrument("8697").await?, recorded);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::unrecorded("7203")]
+    #[case::path_traversal("../8697")]
+    #[tokio::test]
+    async fn test_returns_not_found_for_unrecorded_instrument(#[case] instrument_id: &str) {
+        let dir = FixtureDir::new(&format!("not-found-{}", instrument_id.len()));
+        let replay = ReplayProvider::new(dir.0.clone());
+        let range = DateRange {
+            from: date(2025, 1, 6),
+            to: date(2025, 1, 10),
+        };
+
+        let bars = replay.fetch_daily_bars(instrument_id, &range).await;
+        assert!(matches!(bars, Err(DataProviderError::NotFound(_))));
+        let instrument = replay.fetch_instrument(instrument_id).await;
+        assert!(matches!(instrument, Err(DataProviderError::NotFound(_))));
+    }
+}
@@ -37,13 +37,16 @@ pub(crate) struct EquitiesMasterResponse {
 
 /// J-Quants API V2 銘柄マスタ 1 レコード
 ///
-/// J-Quants は東証上場銘柄のみを提供するため、`MktNm` は構造体には含めない。
+/// J-Quants は東証上場銘柄のみを提供するため、`MktNm` は市場ではなく
+/// 市場区分 (プライム/スタンダード/グロース 等) として扱う。
 #[derive(Debug, Deserialize)]
 pub(crate) struct EquityMaster {
     #[serde(rename = "Code")]
     pub code: String,
     #[serde(rename = "CoName")]
     pub company_name: String,
+    #[serde(rename = "MktNm")]
+    pub market_name: Option<String>,
     #[serde(rename = "S33Nm")]
     pub sector_name: Option<String>,
 }
@@ -0,0 +1,190 @@
+//! レートリミットの枠を待つリクエストの優先度付きキュー
+//!
+//! 待っているリクエストを優先度順 (同じ優先度では到着順) に並べ、先頭のリクエストだけが
+//! レートリミットの枠を取得できるようにする。優先度の低いリクエストが待ち続けないよう、
+//! 待ち時間が [`AGING_STEP`] 経過するごとに優先度を 1 段階上げて扱う。
+//!
+//! キューはプロセス内にしかなく、同じ API キーを共有する別のプロセスのリクエストとは順序を調整しない。
+//! プロセスをまたいで `Interactive` のリクエストを待たせないよう、共有のレートリミットでは
+//! `Interactive` 用に枠を残している (`rate_limit::INTERACTIVE_RESERVED_SLOTS`)。
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::data_provider::priority::RequestPriority;
+
+/// 待ち時間がこの時間を経過するごとに、優先度を 1 段階上げて扱う
+const AGING_STEP: Duration = Duration::from_secs(120);
+
+/// 優先度付きの待ちキュー
+pub(super) struct Scheduler {
+    /// 待っているリクエスト (到着順)
+    ///
+    /// [`Ticket`] の Drop で取り除くため、tokio ではなく std の Mutex を使う。
+    waiters: Mutex<Vec<Waiter>>,
+    /// 待ちキューが変化するたびに増える番号 (待っているリクエストに先頭が変わった可能性を知らせる)
+    version: watch::Sender<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Waiter {
+    /// 到着順の通し番号
+    id: u64,
+    priority: RequestPriority,
+    enqueued_at: Instant,
+}
+
+impl Waiter {
+    /// 待ち時間を考慮した順位 (小さいほど先)
+    fn rank(&self, now: Instant) -> (u64, u64) {
+        let promoted = now.duration_since(self.enqueued_at).as_secs() / AGING_STEP.as_secs();
+        ((self.priority as u64).saturating_sub(promoted), self.id)
+    }
+}
+
+/// 待ちキューに並んでいる間保持する整理券 (Drop でキューから外れる)
+pub(super) struct Ticket<'a> {
+    scheduler: &'a Scheduler,
+    id: u64,
+    version: watch::Receiver<u64>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            waiters: Mutex::new(Vec::new()),
+            version: watch::Sender::new(0),
+        }
+    }
+
+    fn waiters(&self) -> MutexGuard<'_, Vec<Waiter>> {
+        // 保持中に panic しうる処理はないが、念のため poison は無視する
+        self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn notify(&self) {
+        self.version
+            .send_modify(|version| *version = version.wrapping_add(1));
+    }
+
+    /// `priority` の優先度で待ちキューに並ぶ
+    pub fn enter(&self, priority: RequestPriority) -> Ticket<'_> {
+        let version = self.version.subscribe();
+        let id = {
+            let mut waiters = self.waiters();
+            let id = waiters.last().map_or(0, |last| last.id + 1);
+            waiters.push(Waiter {
+                id,
+                priority,
+                enqueued_at: Instant::now(),
+            });
+            id
+        };
+        self.notify();
+        Ticket {
+            scheduler: self,
+            id,
+            version,
+        }
+    }
+}
+
+impl Ticket<'_> {
+    /// 待っているリクエストの中で先頭か
+    pub fn is_next(&mut self) -> bool {
+        // ここまでの変化は確認済みとし、以降の変化だけを `changed` で待つ
+        self.version.borrow_and_update();
+        let now = Instant::now();
+        self.scheduler
+            .waiters()
+            .iter()
+            .min_by_key(|waiter| waiter.rank(now))
+            .is_some_and(|waiter| waiter.id == self.id)
+    }
+
+    /// 待ちキューが変化するまで待つ
+    ///
+    /// 待ち時間による優先度の繰り上げで先頭が入れ替わることもあるため、
+    /// 変化がなくても [`AGING_STEP`] ごとに戻る。
+    pub async fn changed(&mut self) {
+        // Sender は Scheduler が保持しているため、Err (Sender の Drop) にはならない
+        let _ = tokio::time::timeout(AGING_STEP, self.version.changed()).await;
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        self.scheduler
+            .waiters()
+            .retain(|waiter| waiter.id != self.id);
+        self.scheduler.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_higher_priority_goes_first() {
+        let scheduler = Scheduler::new();
+
+        let mut bulk = scheduler.enter(RequestPriority::Bulk);
+        assert!(bulk.is_next());
+
+        let mut interactive = scheduler.enter(RequestPriority::Interactive);
+        assert!(interactive.is_next());
+        assert!(!bulk.is_next());
+
+        drop(interactive);
+        assert!(bulk.is_next());
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_same_priority_is_first_come_first_served() {
+        let scheduler = Scheduler::new();
+
+        let mut first = scheduler.enter(RequestPriority::Scheduled);
+        let mut second = scheduler.enter(RequestPriority::Scheduled);
+
+        assert!(first.is_next());
+        assert!(!second.is_next());
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_long_waiting_request_is_promoted() {
+        let scheduler = Scheduler::new();
+        let mut bulk = scheduler.enter(RequestPriority::Bulk);
+
+        // Bulk から Interactive まで 2 段階繰り上がる
+        tokio::time::advance(AGING_STEP * 2).await;
+        let mut interactive = scheduler.enter(RequestPriority::Interactive);
+
+        assert!(bulk.is_next());
+        assert!(!interactive.is_next());
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_changed_wakes_when_queue_changes() {
+        let scheduler = Scheduler::new();
+        let mut bulk = scheduler.enter(RequestPriority::Bulk);
+        bulk.is_next();
+
+        let _interactive = scheduler.enter(RequestPriority::Interactive);
+
+        // 変化を見逃さずに即座に戻る
+        tokio::time::timeout(Duration::from_millis(1), bulk.changed())
+            .await
+            .unwrap();
+        assert!(!bulk.is_next());
+    }
+}
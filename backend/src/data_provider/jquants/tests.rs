@@ -1,12 +1,15 @@
 use chrono::NaiveDate;
+use futures_util::{StreamExt, TryStreamExt};
 use rstest::rstest;
 use rust_decimal::Decimal;
 use serde_json::json;
 use wiremock::matchers::{method, path, query_param};
 use wiremock::{Mock, ResponseTemplate};
 
+use crate::data_provider::jquants::CircuitBreakerConfig;
 use crate::data_provider::jquants::mock::{JQuantsMockServer, MockBar};
 use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::models::Bar;
 
 fn date(year: i32, month: u32, day: u32) -> NaiveDate {
     NaiveDate::from_ymd_opt(year, month, day).unwrap_or_default()
@@ -171,6 +174,85 @@ mod fetch_daily_bars {
         assert_eq!(bars[1].close, dec(102.0));
         Ok(())
     }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_pages_are_streamed_and_page_limit_is_resumable() -> Result<(), DataProviderError>
+    {
+        let mock = JQuantsMockServer::start().await;
+        mock.daily_bars()
+            .code("8697")
+            .bars(vec![sample_bar("2025-01-06", 100.0)])
+            .pagination_key("page2")
+            .up_to_n_times(1)
+            .ok()
+            .await;
+        mock.daily_bars()
+            .code("8697")
+            .bars(vec![sample_bar("2025-01-07", 102.0)])
+            .with_pagination_key_param("page2")
+            .pagination_key("page3")
+            .ok()
+            .await;
+        mock.daily_bars()
+            .code("8697")
+            .bars(vec![sample_bar("2025-01-08", 104.0)])
+            .with_pagination_key_param("page3")
+            .ok()
+            .await;
+
+        let client = mock.client()?.with_max_pages(2);
+        let mut pages = client.fetch_daily_bar_pages("8697", default_range());
+
+        // 上限までのページは取得でき次第返し、上限に達したら続きの pagination_key を返す
+        assert_eq!(
+            pages.try_next().await?.map(|bars| bars[0].close),
+            Some(dec(100.0))
+        );
+        assert_eq!(
+            pages.try_next().await?.map(|bars| bars[0].close),
+            Some(dec(102.0))
+        );
+        let Some(Err(DataProviderError::PageLimitExceeded {
+            pages: 2,
+            pagination_key,
+        })) = pages.next().await
+        else {
+            panic!("expected PageLimitExceeded");
+        };
+        assert_eq!(pagination_key, "page3");
+        assert!(pages.next().await.is_none());
+
+        let resumed: Vec<Vec<Bar>> = client
+            .fetch_daily_bar_pages_from("8697", default_range(), Some(pagination_key))
+            .try_collect()
+            .await?;
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0][0].close, dec(104.0));
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_page_limit_fails_instead_of_truncating() -> Result<(), DataProviderError> {
+        let mock = JQuantsMockServer::start().await;
+        // 同じ pagination_key が返り続ける
+        mock.daily_bars()
+            .code("8697")
+            .bars(vec![sample_bar("2025-01-06", 100.0)])
+            .pagination_key("loop")
+            .ok()
+            .await;
+
+        let client = mock.client()?.with_max_pages(3);
+        let result = client.fetch_daily_bars("8697", &default_range()).await;
+
+        assert!(matches!(
+            result,
+            Err(DataProviderError::PageLimitExceeded { pages: 3, .. })
+        ));
+        Ok(())
+    }
 }
 
 // === fetch_instrument ===
@@ -290,65 +372,77 @@ mod error_handling {
 
     #[rstest]
     #[tokio::test]
-    async fn test_429_exhausts_retries() -> Result<(), DataProviderError> {
+    async fn test_429_waits_for_retry_after() -> Result<(), DataProviderError> {
         let mock = JQuantsMockServer::start().await;
-        mock.error().rate_limited("/equities/master").await;
+
+        Mock::given(method("GET"))
+            .and(path("/equities/master"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "1")
+                    .set_body_json(json!({
+                        "message": "Too Many Requests",
+                    })),
+            )
+            .up_to_n_times(1)
+            .mount(mock.server_ref())
+            .await;
+        mock.instrument().ok().await;
 
         let client = mock.client()?;
-        let result = client.fetch_instrument("86970").await;
+        let started = std::time::Instant::now();
+        let instrument = client.fetch_instrument("86970").await?;
 
-        assert!(matches!(result, Err(DataProviderError::RateLimited { .. })));
+        assert_eq!(instrument.id, "86970");
+        assert!(started.elapsed() >= std::time::Duration::from_secs(1));
         Ok(())
     }
-}
-
-// === レートリミッター ===
-
-mod rate_limiter {
-    use super::super::{RATE_LIMIT_MAX_REQUESTS, RATE_LIMIT_WINDOW, RateLimiter};
-    use rstest::rstest;
 
     #[rstest]
     #[tokio::test]
-    async fn test_allows_requests_within_limit() {
-        let limiter = RateLimiter::new();
+    async fn test_429_exhausts_retries() -> Result<(), DataProviderError> {
+        let mock = JQuantsMockServer::start().await;
+        mock.error().rate_limited("/equities/master").await;
 
-        // 上限以内のリクエストは即座に通過する
-        for _ in 0..RATE_LIMIT_MAX_REQUESTS {
-            limiter.acquire().await;
-        }
+        let client = mock.client()?;
+        let result = client.fetch_instrument("86970").await;
+
+        assert!(matches!(result, Err(DataProviderError::RateLimited { .. })));
+        Ok(())
     }
 
     #[rstest]
-    #[tokio::test(start_paused = true)]
-    async fn test_blocks_when_limit_exceeded() {
-        let limiter = RateLimiter::new();
-
-        // 上限まで消費
-        for _ in 0..RATE_LIMIT_MAX_REQUESTS {
-            limiter.acquire().await;
-        }
+    #[tokio::test]
+    async fn test_circuit_breaker_fails_fast_after_threshold() -> Result<(), DataProviderError> {
+        let mock = JQuantsMockServer::start().await;
+        mock.error().rate_limited("/equities/master").await;
 
-        // 次の acquire は待機するはず
-        let acquire_future = limiter.acquire();
-        let result =
-            tokio::time::timeout(std::time::Duration::from_millis(100), acquire_future).await;
-        assert!(result.is_err(), "上限超過時に acquire がブロックされるべき");
-
-        // ウィンドウを経過させると通過する
-        tokio::time::advance(RATE_LIMIT_WINDOW).await;
-        let acquire_future = limiter.acquire();
-        let result =
-            tokio::time::timeout(std::time::Duration::from_millis(100), acquire_future).await;
-        assert!(result.is_ok(), "ウィンドウ経過後に acquire が通過するべき");
+        let client = mock.client()?.with_circuit_breaker(CircuitBreakerConfig {
+            failure_threshold: 1,
+            open_duration: std::time::Duration::from_secs(60),
+        });
+        let first = client.fetch_instrument("86970").await;
+        let requests_before = mock.server_ref().received_requests().await.unwrap().len();
+        let second = client.fetch_instrument("86970").await;
+        let requests_after = mock.server_ref().received_requests().await.unwrap().len();
+
+        assert!(matches!(first, Err(DataProviderError::RateLimited { .. })));
+        assert!(matches!(second, Err(DataProviderError::CircuitOpen { .. })));
+        // Open の間は API にリクエストを送らない
+        assert_eq!(requests_after, requests_before);
+        let status = client.circuit_breaker().await.unwrap();
+        assert_eq!(status.state, crate::models::CircuitState::Open);
+        assert!(status.retry_at.is_some());
+        Ok(())
     }
 }
 
-// === DataProviderKind ===
+// === dyn DataProvider ===
+
+mod dyn_data_provider {
+    use std::sync::Arc;
 
-mod data_provider_kind {
     use super::*;
-    use crate::data_provider::DataProviderKind;
 
     #[rstest]
     #[tokio::test]
@@ -362,8 +456,8 @@ mod data_provider_kind {
             .await;
 
         let client = mock.client()?;
-        let kind = DataProviderKind::JQuants(client);
-        let instrument = kind.fetch_instrument("72030").await?;
+        let provider: Arc<dyn DataProvider> = Arc::new(client);
+        let instrument = provider.fetch_instrument("72030").await?;
 
         assert_eq!(instrument.id, "72030");
         assert_eq!(instrument.name, "トヨタ自動車");
@@ -381,11 +475,22 @@ mod data_provider_kind {
             .await;
 
         let client = mock.client()?;
-        let kind = DataProviderKind::JQuants(client);
-        let bars = kind.fetch_daily_bars("8697", &default_range()).await?;
+        let provider: Arc<dyn DataProvider> = Arc::new(client);
+        let bars = provider.fetch_daily_bars("8697", &default_range()).await?;
 
         assert_eq!(bars.len(), 1);
         assert_eq!(bars[0].close, dec(105.0));
         Ok(())
     }
 }
+
+#[rstest]
+fn test_rate_limit_key_is_derived_from_api_key_hash() {
+    let key = super::rate_limit_key("secret-api-key");
+
+    assert!(key.starts_with("jquants:"));
+    assert_eq!(key.len(), "jquants:".len() + 64);
+    assert!(!key.contains("secret-api-key"));
+    assert_eq!(key, super::rate_limit_key("secret-api-key"));
+    assert_ne!(key, super::rate_limit_key("other-api-key"));
+}
@@ -1,4 +1,11 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
 use chrono::{NaiveDate, TimeZone, Utc};
+use futures_util::StreamExt;
+use futures_util::stream::{self, BoxStream};
 use rust_decimal::Decimal;
 
 use crate::data_provider::{DataProvider, DataProviderError, DateRange};
@@ -8,39 +15,133 @@ use crate::models::instrument::{Instrument, Market};
 /// テスト用のモックデータプロバイダー
 ///
 /// 事前に登録されたデータを返す。登録されていない銘柄には NotFound を返す。
-struct MockDataProvider {
+/// `with_errors` で登録したエラーは、登録データより先に 1 回の呼び出しにつき 1 件ずつ返す。
+/// `fetch_daily_bars` に渡された期間を記録し、`requests` で取得できる。
+/// `with_delay` を指定すると `fetch_daily_bars` の応答をその時間だけ遅らせる。
+/// `with_pages` を指定すると `fetch_daily_bar_pages` で日足を複数のページに分けて返す。
+/// `with_page_error` を指定すると、ページ数の上限を超えた位置で `PageLimitExceeded` の代わりにそのエラーを返す。
+pub(crate) struct MockDataProvider {
     bars: Vec<Bar>,
     instruments: Vec<Instrument>,
+    delay: Option<Duration>,
+    /// 1 ページのバーの数と、ページ数の上限
+    pages: Option<(usize, usize)>,
+    /// ページ数の上限を超えた位置で返すエラー
+    page_error: Mutex<Option<DataProviderError>>,
+    errors: Mutex<VecDeque<DataProviderError>>,
+    requests: Mutex<Vec<DateRange>>,
 }
 
 impl MockDataProvider {
     /// 空のモックプロバイダーを作成する
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             bars: Vec::new(),
             instruments: Vec::new(),
+            delay: None,
+            pages: None,
+            page_error: Mutex::new(None),
+            errors: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
         }
     }
 
     /// バーデータを登録する (ビルダーパターン)
-    fn with_bars(mut self, bars: Vec<Bar>) -> Self {
+    pub(crate) fn with_bars(mut self, bars: Vec<Bar>) -> Self {
         self.bars = bars;
         self
     }
 
     /// 銘柄情報を登録する (ビルダーパターン)
-    fn with_instruments(mut self, instruments: Vec<Instrument>) -> Self {
+    pub(crate) fn with_instruments(mut self, instruments: Vec<Instrument>) -> Self {
         self.instruments = instruments;
         self
     }
+
+    /// `fetch_daily_bars` の応答を遅らせる時間を設定する (ビルダーパターン)
+    pub(crate) fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// `fetch_daily_bar_pages` で `page_size` 件ずつのページに分けて返す (ビルダーパターン)
+    ///
+    /// `max_pages` を超えるページがある場合は、上限までのページの後に `PageLimitExceeded` を返す。
+    pub(crate) fn with_pages(mut self, page_size: usize, max_pages: usize) -> Self {
+        self.pages = Some((page_size, max_pages));
+        self
+    }
+
+    /// ページ数の上限を超えた位置で 1 回だけ返すエラーを登録する (ビルダーパターン)
+    pub(crate) fn with_page_error(self, error: DataProviderError) -> Self {
+        *self.page_error.lock().unwrap() = Some(error);
+        self
+    }
+
+    /// 先頭の呼び出しから順に返すエラーを登録する (ビルダーパターン)
+    pub(crate) fn with_errors(self, errors: Vec<DataProviderError>) -> Self {
+        *self.errors.lock().unwrap() = errors.into();
+        self
+    }
+
+    /// これまでに `fetch_daily_bars` に渡された期間 (呼び出し順)
+    pub(crate) fn requests(&self) -> Vec<DateRange> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn next_error(&self) -> Option<DataProviderError> {
+        self.errors.lock().unwrap().pop_front()
+    }
+
+    /// `first_page` ページ目 (1 始まり) からページ単位で返すストリーム
+    fn pages_from<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        first_page: usize,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        stream::once(async move {
+            let bars = self.fetch_daily_bars(instrument_id, &range).await;
+            let (Ok(bars), Some((page_size, max_pages))) = (&bars, self.pages) else {
+                return stream::iter(vec![bars]);
+            };
+            let mut pages: Vec<_> = bars
+                .chunks(page_size)
+                .skip(first_page.saturating_sub(1))
+                .map(|page| Ok(page.to_vec()))
+                .collect();
+            if pages.len() > max_pages {
+                pages.truncate(max_pages);
+                let error = self.page_error.lock().unwrap().take();
+                pages.push(Err(error.unwrap_or_else(|| {
+                    DataProviderError::PageLimitExceeded {
+                        pages: max_pages as u32,
+                        pagination_key: format!("page{}", first_page + max_pages),
+                    }
+                })));
+            }
+            stream::iter(pages)
+        })
+        .flatten()
+        .boxed()
+    }
 }
 
+#[async_trait]
 impl DataProvider for MockDataProvider {
     async fn fetch_daily_bars(
         &self,
         instrument_id: &str,
         range: &DateRange,
     ) -> Result<Vec<Bar>, DataProviderError> {
+        self.requests.lock().unwrap().push(range.clone());
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+        if let Some(e) = self.next_error() {
+            return Err(e);
+        }
+
         let instrument_exists = self.instruments.iter().any(|i| i.id == instrument_id);
 
         if !instrument_exists {
@@ -68,7 +169,33 @@ impl DataProvider for MockDataProvider {
         Ok(bars)
     }
 
+    fn fetch_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        self.pages_from(instrument_id, range, 1)
+    }
+
+    /// `pagination_key` は `page<N>` (N ページ目から再開する)
+    fn resume_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        pagination_key: String,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        let first_page = pagination_key
+            .strip_prefix("page")
+            .and_then(|page| page.parse().ok())
+            .unwrap_or(1);
+        self.pages_from(instrument_id, range, first_page)
+    }
+
     async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
+        if let Some(e) = self.next_error() {
+            return Err(e);
+        }
+
         self.instruments
             .iter()
             .find(|i| i.id == instrument_id)
@@ -80,12 +207,12 @@ impl DataProvider for MockDataProvider {
 }
 
 /// テスト用ヘルパー: NaiveDate を簡潔に作成する
-fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+pub(crate) fn date(year: i32, month: u32, day: u32) -> NaiveDate {
     NaiveDate::from_ymd_opt(year, month, day).unwrap_or_default()
 }
 
 /// テスト用ヘルパー: 指定日の Bar を生成する
-fn make_bar(instrument_id: &str, d: NaiveDate, close: i64) -> Bar {
+pub(crate) fn make_bar(instrument_id: &str, d: NaiveDate, close: i64) -> Bar {
     let timestamp = Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0).unwrap_or_default());
     Bar {
         instrument_id: instrument_id.to_string(),
@@ -96,16 +223,18 @@ fn make_bar(instrument_id: &str, d: NaiveDate, close: i64) -> Bar {
         low: Decimal::new(close - 10, 0),
         close: Decimal::new(close, 0),
         volume: 1000,
+        source: None,
     }
 }
 
 /// テスト用ヘルパー: サンプル銘柄情報を作成する
-fn sample_instrument(id: &str) -> Instrument {
+pub(crate) fn sample_instrument(id: &str) -> Instrument {
     Instrument {
         id: id.to_string(),
         name: format!("Test Instrument {id}"),
         market: Market::Tse,
         sector: Some("Technology".to_string()),
+        market_segment: Some("プライム".to_string()),
     }
 }
 
@@ -203,6 +332,24 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_returns_registered_errors_first(provider: MockDataProvider) {
+        let provider =
+            provider.with_errors(vec![DataProviderError::Network("timeout".to_string())]);
+        let range = DateRange {
+            from: date(2025, 1, 6),
+            to: date(2025, 1, 6),
+        };
+
+        let first = provider.fetch_daily_bars("86970", &range).await;
+        let second = provider.fetch_daily_bars("86970", &range).await;
+
+        assert!(matches!(first, Err(DataProviderError::Network(_))));
+        assert_eq!(second.map(|bars| bars.len()).ok(), Some(1));
+        assert_eq!(provider.requests(), vec![range.clone(), range]);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_fetch_instrument_unknown_returns_not_found(provider: MockDataProvider) {
@@ -1,11 +1,20 @@
+pub mod caching;
+pub mod csv_directory;
+pub mod fallback;
 pub mod jquants;
 #[cfg(test)]
-mod mock;
+pub(crate) mod mock;
+pub mod priority;
+pub mod synthetic;
 
+use std::sync::Arc;
+
+use async_trait::async_trait;
 use chrono::NaiveDate;
+use futures_util::StreamExt;
+use futures_util::stream::{self, BoxStream};
 
-use crate::models::{Bar, Instrument};
-use jquants::JQuantsClient;
+use crate::models::{Bar, CircuitBreakerStatus, Instrument, ProviderHealth};
 
 /// データプロバイダーで発生しうるエラー
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +38,24 @@ pub enum DataProviderError {
     /// レスポンスのパースに失敗
     #[error("failed to parse response: {0}")]
     Parse(String),
+
+    /// 記録・再生用のフィクスチャファイルの読み書きに失敗
+    #[error("fixture error: {0}")]
+    Fixture(String),
+
+    /// ローカルのデータファイル (CSV ディレクトリ等) の読み込みまたは解釈に失敗
+    #[error("data file error: {0}")]
+    File(String),
+
+    /// サーキットブレーカーが Open のため、リクエストを送らずに失敗した
+    #[error("circuit breaker is open (retry after {retry_after_secs}s)")]
+    CircuitOpen { retry_after_secs: u64 },
+
+    /// ページ数の上限に到達したため取得を打ち切った
+    ///
+    /// それまでのページは返し終えており、`pagination_key` のページから取得を再開できる。
+    #[error("page limit of {pages} pages exceeded (resume from pagination_key {pagination_key})")]
+    PageLimitExceeded { pages: u32, pagination_key: String },
 }
 
 /// 日足データの取得期間を指定するパラメータ
@@ -43,9 +70,9 @@ pub struct DateRange {
 /// 株価データプロバイダーの抽象化 trait
 ///
 /// 日足 OHLCV データや銘柄情報の取得元を差し替え可能にする。
-/// Axum のハンドラから使用するため Send + Sync を要求する。
-/// crate 内でのみ使用するため async fn in trait の auto trait bounds は問題にならない。
-#[expect(async_fn_in_trait, reason = "crate 内でのみ使用する trait のため")]
+/// Axum のハンドラや `tokio::spawn` したタスクから使用するため Send + Sync を要求する。
+/// `Arc<dyn DataProvider>` として実行時に差し替えられるよう、async-trait で dyn 互換にしている。
+#[async_trait]
 pub trait DataProvider: Send + Sync {
     /// 指定銘柄・期間の日足バーデータを取得する
     ///
@@ -57,34 +84,85 @@ pub trait DataProvider: Send + Sync {
         range: &DateRange,
     ) -> Result<Vec<Bar>, DataProviderError>;
 
+    /// 指定銘柄・期間の日足バーデータを、ページ単位で取得でき次第返すストリーム
+    ///
+    /// 呼び出し側はすべてのページを待たずに保存を始められる。
+    /// ページ内のバーはタイムスタンプ昇順だが、ページ間の順序は保証しない。
+    /// エラーを返した後はページを返さない。
+    /// ページ分割のないプロバイダーは、`fetch_daily_bars` の結果を 1 ページとして返す。
+    fn fetch_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        stream::once(async move { self.fetch_daily_bars(instrument_id, &range).await }).boxed()
+    }
+
+    /// [`DataProviderError::PageLimitExceeded`] で打ち切られた `fetch_daily_bar_pages` の取得を、
+    /// エラーの `pagination_key` のページから再開するストリーム
+    ///
+    /// ページ分割のないプロバイダーは `PageLimitExceeded` を返さないため、既定では最初のページから取得し直す。
+    fn resume_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        _pagination_key: String,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        self.fetch_daily_bar_pages(instrument_id, range)
+    }
+
     /// 指定銘柄の情報を取得する
     async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError>;
-}
 
-/// DataProvider の具体的な実装を列挙する enum
-///
-/// async fn in trait は dyn 互換でないため、`Arc<dyn DataProvider>` の代わりに
-/// enum ディスパッチでポリモーフィズムを実現する。
-pub enum DataProviderKind {
-    JQuants(JQuantsClient),
+    /// フォールバックに含まれるプロバイダーごとの稼働状況 (優先順位順)
+    ///
+    /// フォールバックを構成していない場合は空を返す。
+    async fn health(&self) -> Vec<ProviderHealth> {
+        Vec::new()
+    }
+
+    /// サーキットブレーカーの状態 (サーキットブレーカーを持たない場合は None)
+    async fn circuit_breaker(&self) -> Option<CircuitBreakerStatus> {
+        None
+    }
 }
 
-impl DataProvider for DataProviderKind {
+#[async_trait]
+impl<P: DataProvider + ?Sized> DataProvider for Arc<P> {
     async fn fetch_daily_bars(
         &self,
         instrument_id: &str,
         range: &DateRange,
     ) -> Result<Vec<Bar>, DataProviderError> {
-        match self {
-            DataProviderKind::JQuants(client) => {
-                client.fetch_daily_bars(instrument_id, range).await
-            }
-        }
+        (**self).fetch_daily_bars(instrument_id, range).await
+    }
+
+    fn fetch_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        (**self).fetch_daily_bar_pages(instrument_id, range)
+    }
+
+    fn resume_daily_bar_pages<'a>(
+        &'a self,
+        instrument_id: &'a str,
+        range: DateRange,
+        pagination_key: String,
+    ) -> BoxStream<'a, Result<Vec<Bar>, DataProviderError>> {
+        (**self).resume_daily_bar_pages(instrument_id, range, pagination_key)
     }
 
     async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
-        match self {
-            DataProviderKind::JQuants(client) => client.fetch_instrument(instrument_id).await,
-        }
+        (**self).fetch_instrument(instrument_id).await
+    }
+
+    async fn health(&self) -> Vec<ProviderHealth> {
+        (**self).health().await
+    }
+
+    async fn circuit_breaker(&self) -> Option<CircuitBreakerStatus> {
+        (**self).circuit_breaker().await
     }
 }
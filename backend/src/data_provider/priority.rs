@@ -0,0 +1,61 @@
+//! DataProvider へのリクエストの優先度
+//!
+//! 優先度は `DataProvider` の各メソッドの引数ではなくタスクローカルで伝える。
+//! 呼び出し側は [`with_priority`] で処理全体を囲み、レートリミットのあるプロバイダー
+//! (J-Quants API) は [`current_priority`] で読み取って送信順を決める。
+
+use std::future::Future;
+
+tokio::task_local! {
+    static PRIORITY: RequestPriority;
+}
+
+/// リクエストの優先度 (先頭ほど優先)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum RequestPriority {
+    /// ユーザーが応答を待っているリクエスト (チャートの表示等)
+    Interactive,
+    /// ユーザー操作や定期実行をきっかけにした、少量のバックグラウンドのリクエスト
+    #[default]
+    Scheduled,
+    /// 長期間のバックフィル等の大量のリクエスト (空いている枠だけを使う)
+    Bulk,
+}
+
+/// `future` の中で DataProvider に送るリクエストの優先度を `priority` にする
+pub async fn with_priority<F: Future>(priority: RequestPriority, future: F) -> F::Output {
+    PRIORITY.scope(priority, future).await
+}
+
+/// 現在のタスクのリクエストの優先度 ([`with_priority`] の外では `Scheduled`)
+pub fn current_priority() -> RequestPriority {
+    PRIORITY.try_with(|priority| *priority).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_current_priority_defaults_to_scheduled() {
+        assert_eq!(current_priority(), RequestPriority::Scheduled);
+    }
+
+    #[rstest]
+    #[case::interactive(RequestPriority::Interactive)]
+    #[case::bulk(RequestPriority::Bulk)]
+    #[tokio::test]
+    async fn test_with_priority_is_visible_across_awaits(#[case] priority: RequestPriority) {
+        let seen = with_priority(priority, async {
+            tokio::task::yield_now().await;
+            current_priority()
+        })
+        .await;
+
+        assert_eq!(seen, priority);
+        assert_eq!(current_priority(), RequestPriority::Scheduled);
+    }
+}
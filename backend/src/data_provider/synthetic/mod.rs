@@ -0,0 +1,423 @@
+//! 決定的な合成株価データを生成する DataProvider
+//!
+//! 銘柄コード (とシード) から決まる乱数列で、以下のモデルにより株価を生成する。
+//!
+//! - 日次の終値: ボラティリティ・レジーム (平常時 / 荒れ相場) を持つ幾何ブラウン運動
+//! - 始値: 前日終値からのギャップ (まれに大きなジャンプ)
+//! - 日中の値動き: 始値と終値を結ぶブラウン橋 (1 分足)
+//!
+//! 価格は東証の呼値に丸め、前日終値を基準値段とする制限値幅に収める。
+//! 日足は内部で生成した 1 分足を集計したものとする。分足・時間足は DataProvider から
+//! 取得する経路がないため、1 分足は日足の生成にのみ使い、外部には返さない。
+//! 同じ銘柄コードであれば、要求した期間に関係なく同じ日には同じ値を返す。
+
+pub mod rng;
+mod tse;
+
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+
+use crate::calendar;
+use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::models::instrument::Market;
+use crate::models::{Bar, Instrument, Timeframe};
+use rng::{SplitMix64, seed_from_str};
+
+/// 合成データの開始日 (この日以降の営業日のデータを生成する)
+pub const ORIGIN: NaiveDate = match NaiveDate::from_ymd_opt(2010, 1, 4) {
+    Some(date) => date,
+    None => NaiveDate::MIN,
+};
+
+/// 合成データのバーの取得元 (`bars.source`)
+pub const SOURCE: &str = "synthetic";
+
+/// 年率のドリフト
+const ANNUAL_DRIFT: f64 = 0.05;
+/// 1 年あたりの営業日数
+const TRADING_DAYS_PER_YEAR: f64 = 245.0;
+/// 平常時の日次ボラティリティ
+const CALM_VOLATILITY: f64 = 0.012;
+/// 荒れ相場の日次ボラティリティ
+const TURBULENT_VOLATILITY: f64 = 0.035;
+/// 平常時から荒れ相場に移る確率 (1 日あたり)
+const CALM_TO_TURBULENT: f64 = 0.02;
+/// 荒れ相場から平常時に戻る確率 (1 日あたり)
+const TURBULENT_TO_CALM: f64 = 0.10;
+/// 寄り付きのギャップの大きさ (日次ボラティリティに対する比)
+const GAP_VOLATILITY_RATIO: f64 = 0.3;
+/// 寄り付きで大きなジャンプが起きる確率
+const JUMP_PROBABILITY: f64 = 0.02;
+/// 大きなジャンプの標準偏差
+const JUMP_VOLATILITY: f64 = 0.05;
+
+/// 前場 (9:00 ~ 11:30 JST) の分数
+const MORNING_MINUTES: u32 = 150;
+/// 後場 (12:30 ~ 15:30 JST) の分数
+const AFTERNOON_MINUTES: u32 = 180;
+const SESSION_MINUTES: u32 = MORNING_MINUTES + AFTERNOON_MINUTES;
+
+const SECTORS: [&str; 6] = [
+    "情報・通信業",
+    "電気機器",
+    "輸送用機器",
+    "銀行業",
+    "医薬品",
+    "小売業",
+];
+const MARKET_SEGMENTS: [&str; 3] = ["プライム", "スタンダード", "グロース"];
+
+/// 合成データの DataProvider
+///
+/// 4 桁の英数字の銘柄コードはすべて存在する銘柄として扱う。
+pub struct SyntheticProvider {
+    seed: u64,
+}
+
+/// 1 営業日分の日次の状態
+struct Session {
+    date: NaiveDate,
+    /// 基準値段 (前日終値)
+    base: f64,
+    open: f64,
+    close: f64,
+    volatility: f64,
+    volume: f64,
+}
+
+/// 1 分足 (呼値に丸めた価格)
+struct MinuteBar {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: i64,
+}
+
+impl SyntheticProvider {
+    /// `seed` を変えると、同じ銘柄コードでも別の値動きになる
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// 指定期間の日足を生成する (営業日のみ、タイムスタンプ昇順)
+    pub fn daily_bars(&self, instrument_id: &str, range: &DateRange) -> Vec<Bar> {
+        self.sessions(instrument_id, range.to)
+            .into_iter()
+            .filter(|session| session.date >= range.from)
+            .filter_map(|session| {
+                let minutes = self.minute_bars(instrument_id, &session);
+                let (first, last) = (minutes.first()?, minutes.last()?);
+                Some(Bar {
+                    instrument_id: instrument_id.to_string(),
+                    timeframe: Timeframe::Daily,
+                    timestamp: Utc.from_utc_datetime(&session.date.and_time(NaiveTime::MIN)),
+                    open: price(first.open),
+                    high: price(minutes.iter().map(|m| m.high).fold(f64::MIN, f64::max)),
+                    low: price(minutes.iter().map(|m| m.low).fold(f64::MAX, f64::min)),
+                    close: price(last.close),
+                    volume: minutes.iter().map(|m| m.volume).sum(),
+                    source: Some(SOURCE.to_string()),
+                })
+            })
+            .collect()
+    }
+
+    /// 銘柄情報を生成する。4 桁の英数字でない銘柄コードは None を返す
+    pub fn instrument(&self, instrument_id: &str) -> Option<Instrument> {
+        let valid = instrument_id.len() == 4
+            && instrument_id
+                .chars()
+                .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase());
+        if !valid {
+            return None;
+        }
+
+        let hash = seed_from_str(self.seed, instrument_id);
+        let sector = SECTORS[(hash % SECTORS.len() as u64) as usize];
+        let segment = MARKET_SEGMENTS[((hash >> 8) % MARKET_SEGMENTS.len() as u64) as usize];
+        Some(Instrument {
+            id: instrument_id.to_string(),
+            name: format!("合成銘柄{instrument_id}"),
+            market: Market::Tse,
+            sector: Some(sector.to_string()),
+            market_segment: Some(segment.to_string()),
+        })
+    }
+
+    /// [`ORIGIN`] から `to` までの各営業日の日次の状態を生成する
+    ///
+    /// 要求した期間に関係なく同じ乱数列を使うため、常に [`ORIGIN`] から順に生成する。
+    /// 営業日ごとに消費する乱数の数は一定にする。
+    fn sessions(&self, instrument_id: &str, to: NaiveDate) -> Vec<Session> {
+        let mut rng = SplitMix64::new(seed_from_str(self.seed, instrument_id));
+        let mut base = tse::round_to_tick(300.0 + rng.next_f64() * 7_700.0);
+        // 出来高の水準 (1 万 ~ 500 万株の対数一様分布)
+        let base_volume = 10f64.powf(4.0 + rng.next_f64() * 2.7);
+        let drift = ANNUAL_DRIFT / TRADING_DAYS_PER_YEAR;
+        let mut turbulent = false;
+        let mut sessions = Vec::new();
+
+        for date in ORIGIN
+            .iter_days()
+            .take_while(|date| *date <= to)
+            .filter(|date| calendar::is_trading_day(*date))
+        {
+            let regime = rng.next_f64();
+            let gap = rng.next_normal();
+            let jump = rng.next_f64();
+            let jump_size = rng.next_normal();
+            let shock = rng.next_normal();
+            let activity = rng.next_f64();
+
+            turbulent = if turbulent {
+                regime >= TURBULENT_TO_CALM
+            } else {
+                regime < CALM_TO_TURBULENT
+            };
+            let volatility = if turbulent {
+                TURBULENT_VOLATILITY
+            } else {
+                CALM_VOLATILITY
+            };
+
+            let mut gap = gap * volatility * GAP_VOLATILITY_RATIO;
+            if jump < JUMP_PROBABILITY {
+                gap += jump_size * JUMP_VOLATILITY;
+            }
+            let limits = tse::price_limits(base);
+            let open = clamp_to_limits(base * gap.exp(), limits);
+            let log_return = drift - volatility * volatility / 2.0 + volatility * shock;
+            let close = clamp_to_limits(open * log_return.exp(), limits);
+            // 値動きが大きい日ほど出来高が増える
+            let volume =
+                base_volume * (1.0 + (log_return + gap).abs() / volatility) * (0.5 + activity);
+
+            sessions.push(Session {
+                date,
+                base,
+                open,
+                close,
+                volatility,
+                volume,
+            });
+            base = close;
+        }
+
+        sessions
+    }
+
+    /// 始値と終値を結ぶブラウン橋で 1 分足を生成する
+    ///
+    /// 乱数は銘柄コードと日付から決まるため、前後の営業日の生成には影響しない。
+    fn minute_bars(&self, instrument_id: &str, session: &Session) -> Vec<MinuteBar> {
+        let days = u64::try_from(session.date.signed_duration_since(ORIGIN).num_days())
+            .unwrap_or_default();
+        let mut rng = SplitMix64::new(
+            seed_from_str(self.seed, instrument_id) ^ days.wrapping_mul(0xD6E8_FEB8_6659_FD93),
+        );
+        let limits = tse::price_limits(session.base);
+        let steps = f64::from(SESSION_MINUTES);
+        let step_volatility = session.volatility / steps.sqrt();
+
+        // ブラウン運動 W_t (t = 1..=N) を作り、W_N = 0 となるよう橋にする
+        let mut walk = Vec::with_capacity(SESSION_MINUTES as usize);
+        let mut position = 0.0;
+        for _ in 0..SESSION_MINUTES {
+            position += rng.next_normal() * step_volatility;
+            walk.push(position);
+        }
+        let (log_open, log_close) = (session.open.ln(), session.close.ln());
+
+        // 寄り付きと引けに出来高が集まる U 字型の配分
+        let weights: Vec<f64> = (0..SESSION_MINUTES)
+            .map(|minute| {
+                let (elapsed, length) = if minute < MORNING_MINUTES {
+                    (minute, MORNING_MINUTES)
+                } else {
+                    (minute - MORNING_MINUTES, AFTERNOON_MINUTES)
+                };
+                let x = f64::from(elapsed) / f64::from(length) * 2.0 - 1.0;
+                1.0 + 2.0 * x * x
+            })
+            .collect();
+        let total_weight: f64 = weights.iter().sum();
+
+        let mut previous = session.open;
+        let mut bars = Vec::with_capacity(SESSION_MINUTES as usize);
+        for (minute, (w, weight)) in (0..SESSION_MINUTES).zip(walk.iter().zip(&weights)) {
+            let t = f64::from(minute + 1) / steps;
+            let close = if minute + 1 == SESSION_MINUTES {
+                session.close
+            } else {
+                let bridge = w - t * position;
+                clamp_to_limits(
+                    (log_open + t * (log_close - log_open) + bridge).exp(),
+                    limits,
+                )
+            };
+            let upper_wick = rng.next_normal().abs() * step_volatility / 2.0;
+            let lower_wick = rng.next_normal().abs() * step_volatility / 2.0;
+            let noise = rng.next_f64();
+
+            let open = previous;
+            let high =
+                clamp_to_limits(open.max(close) * upper_wick.exp(), limits).max(open.max(close));
+            let low =
+                clamp_to_limits(open.min(close) * (-lower_wick).exp(), limits).min(open.min(close));
+            let volume = session.volume * weight / total_weight * (0.5 + noise);
+
+            bars.push(MinuteBar {
+                open,
+                high,
+                low,
+                close,
+                volume: volume.round() as i64,
+            });
+            previous = close;
+        }
+
+        bars
+    }
+}
+
+#[async_trait]
+impl DataProvider for SyntheticProvider {
+    async fn fetch_daily_bars(
+        &self,
+        instrument_id: &str,
+        range: &DateRange,
+    ) -> Result<Vec<Bar>, DataProviderError> {
+        self.fetch_instrument(instrument_id).await?;
+        Ok(self.daily_bars(instrument_id, range))
+    }
+
+    async fn fetch_instrument(&self, instrument_id: &str) -> Result<Instrument, DataProviderError> {
+        self.instrument(instrument_id).ok_or_else(|| {
+            DataProviderError::NotFound(format!("instrument '{instrument_id}' not found"))
+        })
+    }
+}
+
+/// 呼値に丸め、制限値幅に収める
+fn clamp_to_limits(value: f64, (lower, upper): (f64, f64)) -> f64 {
+    tse::round_to_tick(value).clamp(lower, upper)
+}
+
+/// 呼値に丸めた価格を Decimal に変換する (呼値はすべて整数)
+fn price(value: f64) -> Decimal {
+    Decimal::from(value.round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn range(from: NaiveDate, to: NaiveDate) -> DateRange {
+        DateRange { from, to }
+    }
+
+    #[rstest]
+    fn test_daily_bars_are_deterministic_across_ranges() {
+        let provider = SyntheticProvider::new(0);
+        let wide = provider.daily_bars("7203", &range(date(2025, 1, 1), date(2025, 1, 31)));
+        let narrow = SyntheticProvider::new(0)
+            .daily_bars("7203", &range(date(2025, 1, 10), date(2025, 1, 17)));
+
+        let start = wide
+            .iter()
+            .position(|bar| bar.timestamp.date_naive() == date(2025, 1, 10))
+            .unwrap();
+        assert_eq!(narrow, wide[start..start + narrow.len()]);
+    }
+
+    #[rstest]
+    #[case::different_code(0, "6758")]
+    #[case::different_seed(1, "7203")]
+    fn test_daily_bars_depend_on_seed_and_code(#[case] seed: u64, #[case] code: &str) {
+        let range = range(date(2025, 1, 6), date(2025, 1, 10));
+        let base = SyntheticProvider::new(0).daily_bars("7203", &range);
+        let other = SyntheticProvider::new(seed).daily_bars(code, &range);
+
+        let closes = |bars: &[Bar]| bars.iter().map(|b| b.close).collect::<Vec<_>>();
+        assert_ne!(closes(&base), closes(&other));
+    }
+
+    #[rstest]
+    #[case::toyota("7203")]
+    #[case::sony("6758")]
+    #[case::alphanumeric("130A")]
+    fn test_daily_bars_follow_tse_rules(#[case] code: &str) {
+        let bars = SyntheticProvider::new(0)
+            .daily_bars(code, &range(date(2024, 1, 1), date(2024, 12, 31)));
+
+        // 2024 年の東証の営業日数
+        assert_eq!(bars.len(), 245);
+        for bar in &bars {
+            assert!(
+                calendar::is_trading_day(bar.timestamp.date_naive()),
+                "{bar:?}"
+            );
+            assert!(bar.low <= bar.open.min(bar.close), "{bar:?}");
+            assert!(bar.high >= bar.open.max(bar.close), "{bar:?}");
+            assert!(bar.low >= Decimal::ONE, "{bar:?}");
+            assert!(bar.volume > 0, "{bar:?}");
+        }
+        for pair in bars.windows(2) {
+            let base = pair[0].close.try_into().unwrap();
+            let (lower, upper) = tse::price_limits(base);
+            let (lower, upper) = (price(lower), price(upper));
+            assert!(pair[1].low >= lower && pair[1].high <= upper, "{pair:?}");
+        }
+    }
+
+    #[rstest]
+    fn test_minute_bars_aggregate_to_daily_bar() {
+        let provider = SyntheticProvider::new(0);
+        let day = date(2025, 1, 7);
+        let daily = provider.daily_bars("7203", &range(day, day));
+        let session = provider
+            .sessions("7203", day)
+            .into_iter()
+            .find(|session| session.date == day)
+            .unwrap();
+        let minutes = provider.minute_bars("7203", &session);
+
+        assert_eq!(minutes.len(), SESSION_MINUTES as usize);
+        let (first, last) = (&minutes[0], &minutes[minutes.len() - 1]);
+        let aggregated = Bar {
+            instrument_id: "7203".to_string(),
+            timeframe: Timeframe::Daily,
+            timestamp: Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN)),
+            open: price(first.open),
+            high: price(minutes.iter().map(|m| m.high).fold(f64::MIN, f64::max)),
+            low: price(minutes.iter().map(|m| m.low).fold(f64::MAX, f64::min)),
+            close: price(last.close),
+            volume: minutes.iter().map(|m| m.volume).sum(),
+            source: Some(SOURCE.to_string()),
+        };
+        assert_eq!(daily, vec![aggregated]);
+        for pair in minutes.windows(2) {
+            assert_eq!(pair[0].close, pair[1].open);
+        }
+    }
+
+    #[rstest]
+    #[case::too_short("720")]
+    #[case::lowercase("130a")]
+    #[tokio::test]
+    async fn test_rejects_invalid_code(#[case] code: &str) {
+        let provider = SyntheticProvider::new(0);
+        let bars = provider
+            .fetch_daily_bars(code, &range(date(2025, 1, 6), date(2025, 1, 10)))
+            .await;
+        assert!(matches!(bars, Err(DataProviderError::NotFound(_))));
+    }
+}
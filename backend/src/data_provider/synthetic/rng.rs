@@ -0,0 +1,42 @@
+/// SplitMix64 擬似乱数生成器
+///
+/// 外部クレートに依存せず、シードから同じ乱数列を再現できる。
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// [0, 1) の一様乱数
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// 標準正規乱数 (Box-Muller 法)
+    ///
+    /// 呼び出しごとに常に 2 つの一様乱数を消費する。
+    pub fn next_normal(&mut self) -> f64 {
+        // ln(0) を避けるため (0, 1] に変換する
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+}
+
+/// 文字列とシードから 64 bit のシードを作る (FNV-1a)
+pub fn seed_from_str(seed: u64, value: &str) -> u64 {
+    value
+        .bytes()
+        .fold(0xCBF2_9CE4_8422_2325 ^ seed, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+        })
+}
@@ -0,0 +1,102 @@
+//! 東証の呼値 (ティックサイズ) と制限値幅
+//!
+//! TOPIX500 構成銘柄以外に適用される呼値の単位と、基準値段ごとの制限値幅の表に基づく。
+
+/// 呼値の単位 (値段の上限, 単位)
+const TICK_SIZES: [(f64, f64); 10] = [
+    (3_000.0, 1.0),
+    (5_000.0, 5.0),
+    (30_000.0, 10.0),
+    (50_000.0, 50.0),
+    (300_000.0, 100.0),
+    (500_000.0, 500.0),
+    (3_000_000.0, 1_000.0),
+    (5_000_000.0, 5_000.0),
+    (30_000_000.0, 10_000.0),
+    (50_000_000.0, 50_000.0),
+];
+const MAX_TICK_SIZE: f64 = 100_000.0;
+
+/// 制限値幅 (基準値段の上限 (未満), 値幅)
+const PRICE_LIMITS: [(f64, f64); 30] = [
+    (100.0, 30.0),
+    (200.0, 50.0),
+    (500.0, 80.0),
+    (700.0, 100.0),
+    (1_000.0, 150.0),
+    (1_500.0, 300.0),
+    (2_000.0, 400.0),
+    (3_000.0, 500.0),
+    (5_000.0, 700.0),
+    (7_000.0, 1_000.0),
+    (10_000.0, 1_500.0),
+    (15_000.0, 3_000.0),
+    (20_000.0, 4_000.0),
+    (30_000.0, 5_000.0),
+    (50_000.0, 7_000.0),
+    (70_000.0, 10_000.0),
+    (100_000.0, 15_000.0),
+    (150_000.0, 30_000.0),
+    (200_000.0, 40_000.0),
+    (300_000.0, 50_000.0),
+    (500_000.0, 70_000.0),
+    (700_000.0, 100_000.0),
+    (1_000_000.0, 150_000.0),
+    (1_500_000.0, 300_000.0),
+    (2_000_000.0, 400_000.0),
+    (3_000_000.0, 500_000.0),
+    (5_000_000.0, 700_000.0),
+    (7_000_000.0, 1_000_000.0),
+    (10_000_000.0, 1_500_000.0),
+    (15_000_000.0, 3_000_000.0),
+];
+const MAX_PRICE_LIMIT: f64 = 4_000_000.0;
+
+/// 値段に対する呼値の単位
+pub fn tick_size(price: f64) -> f64 {
+    TICK_SIZES
+        .iter()
+        .find(|(upper, _)| price <= *upper)
+        .map_or(MAX_TICK_SIZE, |(_, tick)| *tick)
+}
+
+/// 値段を呼値の単位に丸める (最低 1 円)
+pub fn round_to_tick(price: f64) -> f64 {
+    let tick = tick_size(price);
+    ((price / tick).round() * tick).max(1.0)
+}
+
+/// 基準値段 (前日終値) に対する値幅制限 (下限, 上限)
+pub fn price_limits(base: f64) -> (f64, f64) {
+    let limit = PRICE_LIMITS
+        .iter()
+        .find(|(upper, _)| base < *upper)
+        .map_or(MAX_PRICE_LIMIT, |(_, limit)| *limit);
+    ((base - limit).max(1.0), base + limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::one_yen(2_999.4, 2_999.0)]
+    #[case::boundary(3_000.0, 3_000.0)]
+    #[case::five_yen(3_002.6, 3_005.0)]
+    #[case::ten_yen(12_345.0, 12_350.0)]
+    #[case::minimum(0.2, 1.0)]
+    fn test_round_to_tick(#[case] price: f64, #[case] expected: f64) {
+        assert_eq!(round_to_tick(price), expected);
+    }
+
+    #[rstest]
+    #[case::low_price(50.0, (20.0, 80.0))]
+    #[case::boundary(100.0, (50.0, 150.0))]
+    #[case::mid_price(2_500.0, (2_000.0, 3_000.0))]
+    #[case::floor(10.0, (1.0, 40.0))]
+    fn test_price_limits(#[case] base: f64, #[case] expected: (f64, f64)) {
+        assert_eq!(price_limits(base), expected);
+    }
+}
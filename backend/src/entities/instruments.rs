@@ -11,16 +11,26 @@ pub struct Model {
     pub name: String,
     pub market: String,
     pub sector: Option<String>,
+    pub market_segment: Option<String>,
+    pub bars_updated_at: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::bar_anomalies::Entity")]
+    BarAnomalies,
     #[sea_orm(has_many = "super::bars::Entity")]
     Bars,
     #[sea_orm(has_many = "super::watchlist_items::Entity")]
     WatchlistItems,
 }
 
+impl Related<super::bar_anomalies::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BarAnomalies.def()
+    }
+}
+
 impl Related<super::bars::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Bars.def()
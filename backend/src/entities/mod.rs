@@ -2,7 +2,12 @@
 
 pub mod prelude;
 
+pub mod api_rate_limits;
+pub mod bar_anomalies;
+pub mod bar_retention_policies;
 pub mod bars;
+pub mod data_fetch_jobs;
+pub mod data_provider_cache;
 pub mod instruments;
 pub mod watchlist_items;
 pub mod watchlists;
@@ -1,6 +1,11 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 2.0
 
+pub use super::api_rate_limits::Entity as ApiRateLimits;
+pub use super::bar_anomalies::Entity as BarAnomalies;
+pub use super::bar_retention_policies::Entity as BarRetentionPolicies;
 pub use super::bars::Entity as Bars;
+pub use super::data_fetch_jobs::Entity as DataFetchJobs;
+pub use super::data_provider_cache::Entity as DataProviderCache;
 pub use super::instruments::Entity as Instruments;
 pub use super::watchlist_items::Entity as WatchlistItems;
 pub use super::watchlists::Entity as Watchlists;
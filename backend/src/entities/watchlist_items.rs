@@ -12,6 +12,12 @@ pub struct Model {
     pub instrument_id: String,
     pub sort_order: i32,
     pub added_at: DateTimeWithTimeZone,
+    pub memo: Option<String>,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub tags: Json,
+    pub entry_price: Option<Decimal>,
+    pub target_price: Option<Decimal>,
+    pub stop_price: Option<Decimal>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -11,6 +11,9 @@ pub struct Model {
     pub name: String,
     pub sort_order: i32,
     pub created_at: DateTimeWithTimeZone,
+    pub kind: String,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub rule: Option<Json>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -27,6 +27,9 @@ pub enum AppError {
 
     #[error("service unavailable: {0}")]
     ServiceUnavailable(String),
+
+    #[error("internal error: {0}")]
+    Internal(String),
 }
 
 /// API エラーレスポンスの JSON 構造
@@ -39,7 +42,7 @@ pub struct ErrorResponse {
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, message) = match &self {
-            AppError::Database(_) | AppError::Config(_) => {
+            AppError::Database(_) | AppError::Config(_) | AppError::Internal(_) => {
                 // 内部エラーの詳細はログに記録し、クライアントには汎用メッセージのみ返す
                 tracing::error!("{self}");
                 (
@@ -53,7 +56,7 @@ impl IntoResponse for AppError {
             AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
             AppError::DataProvider(e) => match e {
                 DataProviderError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-                DataProviderError::RateLimited { .. } => {
+                DataProviderError::RateLimited { .. } | DataProviderError::CircuitOpen { .. } => {
                     tracing::error!("{self}");
                     (
                         StatusCode::SERVICE_UNAVAILABLE,
@@ -87,4 +90,13 @@ mod tests {
         let response = error.into_response();
         assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
     }
+
+    #[rstest]
+    fn test_circuit_open_returns_503() {
+        let error = AppError::DataProvider(DataProviderError::CircuitOpen {
+            retry_after_secs: 30,
+        });
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
 }
@@ -1,14 +1,31 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use axum::Json;
+use axum::body::Body;
 use axum::extract::State;
-use chrono::NaiveDate;
+use axum::http::StatusCode;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
 use serde::Deserialize;
+use tokio::sync::mpsc;
 use utoipa::IntoParams;
 
 use crate::AppState;
-use crate::entities::bars;
+use crate::data_provider::DateRange;
+use crate::entities::bar_anomalies;
 use crate::error::{AppError, ErrorResponse};
-use crate::extractors::JsonQuery;
+use crate::extractors::{JsonBody, JsonQuery};
+use crate::handlers::watchlists::validate_instrument_id;
+use crate::models::{
+    AlignedBars, BarImportReport, BarOrder, BarsPage, FetchJob, MultiBarsQueryRequest, Timeframe,
+};
 use crate::repositories;
+use crate::services::bar_export::{self, BarExportFormat};
+use crate::services::bar_import::{self, BarCsvLayout};
+use crate::services::fetch_jobs::{self, JobOutcome};
+use crate::services::{backfill, bar_alignment};
 
 /// バーデータ取得のクエリパラメータ
 #[derive(Debug, Deserialize, IntoParams)]
@@ -16,87 +33,475 @@ use crate::repositories;
 pub struct BarsQueryParams {
     /// 銘柄コード (必須)
     pub instrument_id: String,
-    /// 時間足 (デフォルト: "1d")
+    /// 時間足 ("1m", "5m", "15m", "1h", "1d"。デフォルト: "1d")
     #[serde(default = "default_timeframe")]
     pub timeframe: String,
     /// 取得開始日 (YYYY-MM-DD, inclusive)
     pub from: Option<NaiveDate>,
     /// 取得終了日 (YYYY-MM-DD, inclusive)
     pub to: Option<NaiveDate>,
+    /// 取得件数の上限 (1〜10000, 省略時は全件)
+    #[param(minimum = 1, maximum = 10000)]
+    pub limit: Option<u64>,
+    /// 前のページのレスポンスの `next_cursor` の値
+    pub cursor: Option<DateTime<FixedOffset>>,
+    /// タイムスタンプの並び順 (デフォルト: "asc")
+    #[serde(default)]
+    pub order: BarOrder,
+    /// true の場合、期間内の欠損をデータプロバイダーから取得してから返す (デフォルト: false)
+    #[serde(default)]
+    pub ensure: bool,
+    /// `ensure=true` で欠損の取得を待つ最大時間 (ミリ秒, 0〜30000, デフォルト: 10000)
+    #[param(minimum = 0, maximum = 30000)]
+    pub timeout_ms: Option<u64>,
 }
 
+/// 1 リクエストで取得できるバーの上限
+const MAX_BARS_LIMIT: u64 = 10_000;
+
+/// `ensure=true` で欠損の取得を待つ時間のデフォルト (ミリ秒)
+const DEFAULT_ENSURE_TIMEOUT_MS: u64 = 10_000;
+
+/// `ensure=true` で欠損の取得を待つ時間の上限 (ミリ秒)
+const MAX_ENSURE_TIMEOUT_MS: u64 = 30_000;
+
 fn default_timeframe() -> String {
     "1d".to_string()
 }
 
+/// 時間足を検証する
+fn validate_timeframe(timeframe: &str) -> Result<Timeframe, AppError> {
+    timeframe.parse().map_err(|_| {
+        let valid_timeframes = Timeframe::ALL.map(Timeframe::as_str);
+        AppError::Validation(format!(
+            "invalid timeframe: {timeframe}. valid values: {valid_timeframes:?}"
+        ))
+    })
+}
+
+/// 日付の範囲 (両端 inclusive) をタイムスタンプの範囲に変換する
+///
+/// from: その日の 00:00:00 UTC
+/// to: その日の 23:59:59 UTC (inclusive)
+fn to_timestamp_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> (Option<DateTime<FixedOffset>>, Option<DateTime<FixedOffset>>) {
+    let from = from
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().fixed_offset());
+
+    let to = to
+        .and_then(|d| d.and_hms_opt(23, 59, 59))
+        .map(|dt| dt.and_utc().fixed_offset());
+
+    (from, to)
+}
+
 /// バーデータを取得する
+///
+/// `limit` を指定するとタイムスタンプによるキーセットページネーションを行う。
+/// 続きがある場合はレスポンスの `next_cursor` を次のリクエストの `cursor` に指定する。`order=desc` と組み合わせると新しい順に過去へ遡って取得できる。
+///
+/// `ensure=true` (日足のみ) を指定すると、期間内の営業日で日足が保存されていない日を
+/// データプロバイダーから取得して保存してから返す (最初のページのみ)。`from` の省略時は
+/// バックフィルの対象期間の開始日、`to` の省略時は前日までを対象にする。
+/// 取得が `timeout_ms` 以内に終わらない場合は 202 でジョブを返し、取得はバックグラウンドで
+/// 続行する。ジョブの状態は `GET /api/jobs/{id}` で確認できる。
+/// 取得してもバーのなかった日は、取得から一定時間は取得し直さない。
 #[utoipa::path(
     get,
     path = "/api/bars",
     tag = "bars",
     params(BarsQueryParams),
     responses(
-        (status = 200, description = "バーデータ一覧", body = Vec<bars::Model>),
+        (status = 200, description = "バーデータ一覧", body = BarsPage),
+        (status = 202, description = "欠損の取得が待ち時間内に終わらなかった (`ensure=true` のみ)", body = FetchJob, headers(
+            ("location" = String, description = "ジョブの状態を取得する URL"),
+        )),
         (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 404, description = "データプロバイダーに銘柄が見つからない (`ensure=true` のみ)", body = ErrorResponse),
         (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+        (status = 503, description = "データプロバイダーが未設定、または取得に失敗した (`ensure=true` のみ)", body = ErrorResponse),
     )
 )]
 pub async fn list_bars(
     State(state): State<AppState>,
-    JsonQuery(params): JsonQuery<BarsQueryParams>,
-) -> Result<Json<Vec<bars::Model>>, AppError> {
-    if params.instrument_id.trim().is_empty() {
+    JsonQuery(mut params): JsonQuery<BarsQueryParams>,
+) -> Result<Response, AppError> {
+    // ensure=true ではデータプロバイダーに問い合わせるため、銘柄コードとして不正な値は受け付けない
+    params.instrument_id = validate_instrument_id(&params.instrument_id)?;
+
+    let timeframe = validate_timeframe(&params.timeframe)?;
+    // データプロバイダーから取得できるのは日足のみ
+    if params.ensure && timeframe != Timeframe::Daily {
         return Err(AppError::Validation(
-            "instrument_id must not be empty".to_string(),
+            "ensure=true is only supported for timeframe 1d".to_string(),
         ));
     }
-
-    let valid_timeframes = ["1d"];
-    if !valid_timeframes.contains(&params.timeframe.as_str()) {
+    if params
+        .limit
+        .is_some_and(|limit| !(1..=MAX_BARS_LIMIT).contains(&limit))
+    {
         return Err(AppError::Validation(format!(
-            "invalid timeframe: {}. valid values: {:?}",
-            params.timeframe, valid_timeframes
+            "limit must be between 1 and {MAX_BARS_LIMIT}"
+        )));
+    }
+    let timeout_ms = params.timeout_ms.unwrap_or(DEFAULT_ENSURE_TIMEOUT_MS);
+    if timeout_ms > MAX_ENSURE_TIMEOUT_MS {
+        return Err(AppError::Validation(format!(
+            "timeout_ms must be at most {MAX_ENSURE_TIMEOUT_MS}"
         )));
     }
 
-    // NaiveDate -> DateTime<FixedOffset> に変換
-    // from: その日の 00:00:00 UTC
-    // to: その日の 23:59:59 UTC (inclusive)
-    let from = params
-        .from
-        .and_then(|d| d.and_hms_opt(0, 0, 0))
-        .map(|dt| dt.and_utc().fixed_offset());
+    if params.ensure
+        && params.cursor.is_none()
+        && let Some(job) =
+            ensure_daily_bars(&state, &params, Duration::from_millis(timeout_ms)).await?
+    {
+        let location = format!("/api/jobs/{}", job.id);
+        return Ok((StatusCode::ACCEPTED, [(LOCATION, location)], Json(job)).into_response());
+    }
 
-    let to = params
-        .to
-        .and_then(|d| d.and_hms_opt(23, 59, 59))
-        .map(|dt| dt.and_utc().fixed_offset());
+    let (from, to) = to_timestamp_range(params.from, params.to);
 
     let query = repositories::bars::BarsQuery {
         instrument_id: params.instrument_id,
         timeframe: params.timeframe,
         from,
         to,
+        limit: params.limit,
+        cursor: params.cursor,
+        order: params.order,
+    };
+
+    let page = repositories::bars::find_bars(&state.db, query).await?;
+
+    Ok(Json(BarsPage {
+        bars: page.bars,
+        next_cursor: page.next_cursor.map(|cursor| cursor.to_utc()),
+    })
+    .into_response())
+}
+
+/// 期間内の欠損をデータプロバイダーから取得する
+///
+/// 取得が `timeout` 以内に終わらなかった場合は、バックグラウンドで続行するジョブを返す。
+async fn ensure_daily_bars(
+    state: &AppState,
+    params: &BarsQueryParams,
+    timeout: Duration,
+) -> Result<Option<FetchJob>, AppError> {
+    let today = Utc::now().date_naive();
+    let range = DateRange {
+        from: params
+            .from
+            .unwrap_or_else(|| backfill::backfill_range(today).from),
+        // 当日分はまだ確定していないため取得しない
+        to: params
+            .to
+            .unwrap_or(today)
+            .min(today - chrono::Duration::days(1)),
+    };
+    if range.from > range.to {
+        return Ok(None);
+    }
+
+    let Some(uncovered) =
+        fetch_jobs::uncovered_range(&state.db, &params.instrument_id, &range).await?
+    else {
+        return Ok(None);
+    };
+    let provider =
+        Arc::clone(state.data_provider.as_ref().ok_or_else(|| {
+            AppError::ServiceUnavailable("data provider is not configured".into())
+        })?);
+
+    let job = fetch_jobs::start(&state.db, provider, &params.instrument_id, &uncovered).await?;
+    match fetch_jobs::wait(&state.db, job, timeout).await? {
+        JobOutcome::Completed => Ok(None),
+        JobOutcome::Pending(job) => Ok(Some(job)),
+    }
+}
+
+/// 複数銘柄のバーデータ取得で指定できる銘柄数の上限
+const MAX_QUERY_INSTRUMENTS: usize = 50;
+
+/// 複数銘柄のバーデータをタイムスタンプを揃えて取得する
+///
+/// 比較チャート等で重ねて表示できるよう、全銘柄のバーを同じタイムスタンプ軸に並べる。
+#[utoipa::path(
+    post,
+    path = "/api/bars/query",
+    tag = "bars",
+    request_body = MultiBarsQueryRequest,
+    responses(
+        (status = 200, description = "銘柄ごとのバーデータ", body = AlignedBars),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 422, description = "リクエストボディのパースに失敗", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn query_bars(
+    State(state): State<AppState>,
+    JsonBody(payload): JsonBody<MultiBarsQueryRequest>,
+) -> Result<Json<AlignedBars>, AppError> {
+    if payload.instrument_ids.is_empty() {
+        return Err(AppError::Validation(
+            "instrument_ids must not be empty".to_string(),
+        ));
+    }
+    if payload.instrument_ids.len() > MAX_QUERY_INSTRUMENTS {
+        return Err(AppError::Validation(format!(
+            "instrument_ids must contain at most {MAX_QUERY_INSTRUMENTS} items"
+        )));
+    }
+
+    let mut instrument_ids = Vec::with_capacity(payload.instrument_ids.len());
+    for instrument_id in &payload.instrument_ids {
+        let instrument_id = instrument_id.trim().to_string();
+        if instrument_id.is_empty() {
+            return Err(AppError::Validation(
+                "instrument_ids must not contain empty values".to_string(),
+            ));
+        }
+        if instrument_ids.contains(&instrument_id) {
+            return Err(AppError::Validation(format!(
+                "instrument {instrument_id} is duplicated"
+            )));
+        }
+        instrument_ids.push(instrument_id);
+    }
+
+    validate_timeframe(&payload.timeframe)?;
+    let (from, to) = to_timestamp_range(payload.from, payload.to);
+
+    let bars = repositories::bars::find_bars_for_instruments(
+        &state.db,
+        &instrument_ids,
+        &payload.timeframe,
+        from,
+        to,
+    )
+    .await?;
+
+    Ok(Json(bar_alignment::align(
+        &instrument_ids,
+        bars,
+        payload.fill,
+    )))
+}
+
+/// バーデータエクスポートのクエリパラメータ
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportBarsQueryParams {
+    /// 銘柄コード (カンマ区切り, 例: "7203,6758,9984")
+    pub instrument_ids: String,
+    /// 時間足 ("1m", "5m", "15m", "1h", "1d"。デフォルト: "1d")
+    #[serde(default = "default_timeframe")]
+    pub timeframe: String,
+    /// 取得開始日 (YYYY-MM-DD, inclusive)
+    pub from: Option<NaiveDate>,
+    /// 取得終了日 (YYYY-MM-DD, inclusive)
+    pub to: Option<NaiveDate>,
+    /// エクスポート形式 (デフォルト: "csv")
+    #[serde(default)]
+    pub format: BarExportFormat,
+}
+
+/// 1 回のエクスポートで指定できる銘柄数の上限
+const MAX_EXPORT_INSTRUMENTS: usize = 500;
+
+/// バーデータを CSV / Arrow IPC / Parquet でエクスポートする
+///
+/// DB から読み込みながらバッチ単位でエンコードしてストリーミングする。
+/// 行は銘柄コード・タイムスタンプの昇順に並ぶ。
+/// ストリーミング開始後に発生したエラーはレスポンスの途中切断として通知される。
+#[utoipa::path(
+    get,
+    path = "/api/bars/export",
+    tag = "bars",
+    params(ExportBarsQueryParams),
+    responses(
+        (status = 200, description = "エクスポートしたバーデータ", content(
+            (String = "text/csv"),
+            (Vec<u8> = "application/vnd.apache.arrow.stream"),
+            (Vec<u8> = "application/vnd.apache.parquet"),
+        )),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn export_bars(
+    State(state): State<AppState>,
+    JsonQuery(params): JsonQuery<ExportBarsQueryParams>,
+) -> Result<Response, AppError> {
+    let instrument_ids: Vec<String> = params
+        .instrument_ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect();
+    if instrument_ids.is_empty() {
+        return Err(AppError::Validation(
+            "instrument_ids must not be empty".to_string(),
+        ));
+    }
+    if instrument_ids.len() > MAX_EXPORT_INSTRUMENTS {
+        return Err(AppError::Validation(format!(
+            "instrument_ids must contain at most {MAX_EXPORT_INSTRUMENTS} items"
+        )));
+    }
+
+    validate_timeframe(&params.timeframe)?;
+    let (from, to) = to_timestamp_range(params.from, params.to);
+    let format = params.format;
+
+    let query = bar_export::BarExportQuery {
+        instrument_ids,
+        timeframe: params.timeframe,
+        from,
+        to,
+        format,
     };
 
-    let bars = repositories::bars::find_bars(&state.db, query).await?;
+    // エンコード済みのチャンクをチャネル経由でレスポンスボディに流す
+    let (tx, rx) = mpsc::channel(4);
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        if let Err(e) = bar_export::export_bars(&db, query, &tx).await {
+            tracing::error!(error = %e, "バーデータのエクスポートに失敗しました");
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+    let body = Body::from_stream(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }));
+
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"bars.{}\"", format.extension()),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// 隔離されたバー一覧のクエリパラメータ
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BarAnomaliesQueryParams {
+    /// 銘柄コード (省略時は全銘柄)
+    pub instrument_id: Option<String>,
+    /// 取得件数の上限 (1 ~ 1000, デフォルト: 100)
+    #[serde(default = "default_anomalies_limit")]
+    pub limit: u64,
+}
+
+fn default_anomalies_limit() -> u64 {
+    100
+}
+
+/// 取得件数の上限の最大値
+const MAX_ANOMALIES_LIMIT: u64 = 1_000;
+
+/// 取り込み時の検証で隔離されたバーの一覧を取得する
+///
+/// 検出日時の新しい順に返す。
+#[utoipa::path(
+    get,
+    path = "/api/bars/anomalies",
+    tag = "bars",
+    params(BarAnomaliesQueryParams),
+    responses(
+        (status = 200, description = "隔離されたバーの一覧", body = Vec<bar_anomalies::Model>),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn list_bar_anomalies(
+    State(state): State<AppState>,
+    JsonQuery(params): JsonQuery<BarAnomaliesQueryParams>,
+) -> Result<Json<Vec<bar_anomalies::Model>>, AppError> {
+    if !(1..=MAX_ANOMALIES_LIMIT).contains(&params.limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {MAX_ANOMALIES_LIMIT}"
+        )));
+    }
+
+    let anomalies = repositories::bar_anomalies::find_anomalies(
+        &state.db,
+        params.instrument_id.as_deref(),
+        params.limit,
+    )
+    .await?;
+
+    Ok(Json(anomalies))
+}
 
-    Ok(Json(bars))
+/// バーデータインポートのクエリパラメータ
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportBarsQueryParams {
+    /// 全行をこの銘柄コードとして扱う (銘柄コード列がない CSV 用)
+    pub instrument_id: Option<String>,
+    /// true の場合は DB に書き込まず、既存データとの差分のみをレポートする
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// OHLCV の日足 CSV を一括インポートする
+///
+/// 列構成はクエリパラメータで指定する。検証に失敗した行はスキップしてレポートに含め、
+/// 既存のバーと値が異なる行は上書きする (dry_run では上書き対象を conflicts として返す)。
+/// リクエストボディは 1MB までのため、大きなファイルは CLI の `import-bars` を使う。
+#[utoipa::path(
+    post,
+    path = "/api/bars/import",
+    tag = "bars",
+    params(ImportBarsQueryParams, BarCsvLayout),
+    request_body(content = String, content_type = "text/csv"),
+    responses(
+        (status = 200, description = "インポート結果", body = BarImportReport),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn import_bars(
+    State(state): State<AppState>,
+    JsonQuery(params): JsonQuery<ImportBarsQueryParams>,
+    JsonQuery(layout): JsonQuery<BarCsvLayout>,
+    body: String,
+) -> Result<Json<BarImportReport>, AppError> {
+    let parsed = bar_import::parse_csv(&body, &layout, params.instrument_id.as_deref())?;
+    let report = bar_import::import_bars(&state.db, parsed, params.dry_run).await?;
+    Ok(Json(report))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
     use axum::http::StatusCode;
     use chrono::{NaiveDate, TimeZone, Utc};
     use rust_decimal::Decimal;
     use sea_orm::sea_query::OnConflict;
-    use sea_orm::{DatabaseConnection, EntityTrait, Set, SqlxPostgresConnector};
+    use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, Set, SqlxPostgresConnector};
     use sqlx::PgPool;
 
-    use crate::entities::instruments;
+    use crate::data_provider::caching::CachingProvider;
+    use crate::data_provider::mock::{MockDataProvider, sample_instrument};
+    use crate::entities::{data_provider_cache, instruments};
     use crate::models::bar::{Bar, Timeframe};
     use crate::repositories;
-    use crate::testing::create_test_server;
+    use crate::testing::{create_test_server, create_test_server_with_provider};
 
     /// テスト用の instrument を DB に挿入する
     async fn insert_test_instrument(db: &DatabaseConnection, id: &str) {
@@ -105,6 +510,8 @@ mod tests {
             name: Set(format!("Test {id}")),
             market: Set("TSE".to_string()),
             sector: Set(None),
+            market_segment: Set(None),
+            bars_updated_at: Set(None),
         })
         .on_conflict(
             OnConflict::column(instruments::Column::Id)
@@ -131,9 +538,16 @@ mod tests {
             low: Decimal::new(close - 10, 0),
             close: Decimal::new(close, 0),
             volume: 1000,
+            source: None,
         }
     }
 
+    /// `GET /api/bars` のレスポンスからバーの配列を取り出す
+    fn page_bars(response: &axum_test::TestResponse) -> Vec<serde_json::Value> {
+        serde_json::from_value(response.json::<serde_json::Value>()["bars"].clone())
+            .expect("response must have bars")
+    }
+
     /// テストサーバーとセットアップ済みの DB 接続を返す
     ///
     /// PgPool を clone してサーバー用と直接操作用に分ける。
@@ -160,19 +574,58 @@ mod tests {
         let response = server.get("/api/bars?instrument_id=7203").await;
         response.assert_status_ok();
 
-        let body: Vec<serde_json::Value> = response.json();
+        let body = page_bars(&response);
         assert_eq!(body.len(), 1);
         assert_eq!(body[0]["instrument_id"], "7203");
         assert_eq!(body[0]["timeframe"], "1d");
     }
 
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_returns_intraday_timeframe(pool: PgPool) {
+        let (server, db) = setup(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let date = NaiveDate::from_ymd_opt(2025, 1, 6).expect("invalid date");
+        let bars = vec![
+            make_test_bar("7203", date, 100),
+            Bar {
+                timeframe: Timeframe::FiveMinutes,
+                ..make_test_bar("7203", date, 101)
+            },
+        ];
+        repositories::bars::upsert_bars(&db, bars)
+            .await
+            .expect("upsert failed");
+
+        let response = server
+            .get("/api/bars?instrument_id=7203&timeframe=5m")
+            .await;
+        response.assert_status_ok();
+
+        let body = page_bars(&response);
+        assert_eq!(body.len(), 1);
+        assert_eq!(body[0]["timeframe"], "5m");
+        assert_eq!(body[0]["close"], 101.0);
+    }
+
     #[sqlx::test(migrations = false)]
     async fn list_bars_with_invalid_params_returns_400(pool: PgPool) {
         let server = create_test_server(pool).await;
 
         let cases = [
             ("empty_instrument_id", "?instrument_id="),
-            ("invalid_timeframe", "?instrument_id=7203&timeframe=5m"),
+            ("invalid_timeframe", "?instrument_id=7203&timeframe=1w"),
+            (
+                "ensure_intraday",
+                "?instrument_id=7203&timeframe=5m&ensure=true",
+            ),
+            ("zero_limit", "?instrument_id=7203&limit=0"),
+            ("too_large_limit", "?instrument_id=7203&limit=10001"),
+            ("invalid_cursor", "?instrument_id=7203&cursor=yesterday"),
+            (
+                "too_large_timeout",
+                "?instrument_id=7203&ensure=true&timeout_ms=30001",
+            ),
         ];
 
         for (name, query) in cases {
@@ -192,7 +645,7 @@ mod tests {
         let response = server.get("/api/bars?instrument_id=9999").await;
         response.assert_status_ok();
 
-        let body: Vec<serde_json::Value> = response.json();
+        let body = page_bars(&response);
         assert!(body.is_empty());
     }
 
@@ -227,9 +680,496 @@ mod tests {
             .await;
         response.assert_status_ok();
 
-        let body: Vec<serde_json::Value> = response.json();
+        let body = page_bars(&response);
         assert_eq!(body.len(), 1);
         assert_eq!(body[0]["instrument_id"], "7203");
         assert_eq!(body[0]["close"], 105.0);
     }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_loads_older_history_with_next_cursor(pool: PgPool) {
+        let (server, db) = setup(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let bars = (6..=8)
+            .map(|day| {
+                make_test_bar(
+                    "7203",
+                    NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date"),
+                    100 + i64::from(day),
+                )
+            })
+            .collect();
+        repositories::bars::upsert_bars(&db, bars)
+            .await
+            .expect("upsert failed");
+
+        let response = server
+            .get("/api/bars?instrument_id=7203&order=desc&limit=2")
+            .await;
+        response.assert_status_ok();
+        let body = page_bars(&response);
+        assert_eq!(body.len(), 2);
+        assert_eq!(body[0]["close"], 108.0);
+        assert_eq!(body[1]["close"], 107.0);
+        let cursor = response.json::<serde_json::Value>()["next_cursor"].clone();
+        assert_eq!(cursor, "2025-01-07T00:00:00Z");
+
+        let response = server
+            .get(&format!(
+                "/api/bars?instrument_id=7203&order=desc&limit=2&cursor={}",
+                cursor.as_str().expect("cursor must be a string")
+            ))
+            .await;
+        response.assert_status_ok();
+        let body = page_bars(&response);
+        assert_eq!(body.len(), 1);
+        assert_eq!(body[0]["close"], 106.0);
+        assert_eq!(
+            response.json::<serde_json::Value>()["next_cursor"],
+            serde_json::Value::Null
+        );
+    }
+
+    /// 2025-01-06 ~ 01-10 (全て営業日) の日足を返すモックプロバイダー
+    fn provider_with_week_of_bars() -> MockDataProvider {
+        MockDataProvider::new()
+            .with_instruments(vec![sample_instrument("7203")])
+            .with_bars(
+                (6..=10)
+                    .map(|day| {
+                        make_test_bar("7203", NaiveDate::from_ymd_opt(2025, 1, day).unwrap(), 100)
+                    })
+                    .collect(),
+            )
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_with_ensure_fetches_missing_days(pool: PgPool) {
+        let server =
+            create_test_server_with_provider(pool, Arc::new(provider_with_week_of_bars())).await;
+
+        let response = server
+            .get("/api/bars?instrument_id=7203&from=2025-01-06&to=2025-01-10&ensure=true")
+            .await;
+
+        response.assert_status_ok();
+        let body = page_bars(&response);
+        assert_eq!(body.len(), 5);
+        assert_eq!(body[0]["instrument_id"], "7203");
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_with_ensure_refetches_days_cached_without_bars(pool: PgPool) {
+        let cache_db = SqlxPostgresConnector::from_sqlx_postgres_pool(pool.clone());
+        let provider = Arc::new(provider_with_week_of_bars());
+        let server = create_test_server_with_provider(
+            pool,
+            Arc::new(CachingProvider::new(cache_db.clone(), provider.clone())),
+        )
+        .await;
+        // 7 時間前に取得したがバーのなかった期間 (キャッシュはまだ取得済みとみなす)
+        data_provider_cache::ActiveModel {
+            instrument_id: Set("7203".to_string()),
+            timeframe: Set("1d".to_string()),
+            from_date: Set(NaiveDate::from_ymd_opt(2025, 1, 6).unwrap()),
+            to_date: Set(NaiveDate::from_ymd_opt(2025, 1, 10).unwrap()),
+            bars: Set(serde_json::json!([])),
+            fetched_at: Set((Utc::now() - chrono::Duration::hours(7)).fixed_offset()),
+        }
+        .insert(&cache_db)
+        .await
+        .unwrap();
+
+        let response = server
+            .get("/api/bars?instrument_id=7203&from=2025-01-06&to=2025-01-10&ensure=true")
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(page_bars(&response).len(), 5);
+        assert_eq!(provider.requests().len(), 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_with_ensure_returns_202_when_fetch_is_slow(pool: PgPool) {
+        let provider = provider_with_week_of_bars().with_delay(Duration::from_millis(500));
+        let server = create_test_server_with_provider(pool, Arc::new(provider)).await;
+
+        let response = server
+            .get("/api/bars?instrument_id=7203&from=2025-01-06&to=2025-01-10&ensure=true&timeout_ms=0")
+            .await;
+
+        response.assert_status(StatusCode::ACCEPTED);
+        let job: serde_json::Value = response.json();
+        assert_eq!(job["status"], "running");
+        assert_eq!(job["from"], "2025-01-06");
+        assert_eq!(job["to"], "2025-01-10");
+        let location = response.header("location");
+        assert_eq!(
+            location.to_str().unwrap(),
+            format!("/api/jobs/{}", job["id"].as_str().unwrap())
+        );
+
+        // ジョブはバックグラウンドで続行する
+        let mut job = job;
+        for _ in 0..50 {
+            job = server.get(location.to_str().unwrap()).await.json();
+            if job["status"] != "running" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(job["status"], "succeeded");
+        assert_eq!(job["bar_count"], 5);
+
+        let response = server
+            .get("/api/bars?instrument_id=7203&from=2025-01-06&to=2025-01-10")
+            .await;
+        assert_eq!(page_bars(&response).len(), 5);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_with_ensure_for_unknown_instrument_returns_404(pool: PgPool) {
+        let server =
+            create_test_server_with_provider(pool, Arc::new(provider_with_week_of_bars())).await;
+
+        let response = server
+            .get("/api/bars?instrument_id=9999&from=2025-01-06&to=2025-01-10&ensure=true")
+            .await;
+
+        response.assert_status_not_found();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_with_ensure_rejects_invalid_instrument_id(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        // データプロバイダーの有無を確認する前に銘柄コードを検証する
+        let response = server
+            .get("/api/bars?instrument_id=..%2F7203&from=2025-01-06&to=2025-01-10&ensure=true")
+            .await;
+
+        response.assert_status_bad_request();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bars_with_ensure_without_provider_returns_503(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server
+            .get("/api/bars?instrument_id=7203&from=2025-01-06&to=2025-01-10&ensure=true")
+            .await;
+
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn query_bars_aligns_and_forward_fills_series(pool: PgPool) {
+        let (server, db) = setup(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        insert_test_instrument(&db, "6758").await;
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        let bars = vec![
+            make_test_bar("7203", date(6), 100),
+            make_test_bar("7203", date(8), 103),
+            make_test_bar("6758", date(7), 200),
+            make_test_bar("6758", date(8), 210),
+        ];
+        repositories::bars::upsert_bars(&db, bars)
+            .await
+            .expect("upsert failed");
+
+        let response = server
+            .post("/api/bars/query")
+            .json(&serde_json::json!({
+                "instrument_ids": ["7203", "6758"],
+                "from": "2025-01-06",
+                "fill": "forward",
+            }))
+            .await;
+        response.assert_status_ok();
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["timestamps"],
+            serde_json::json!([
+                "2025-01-06T00:00:00Z",
+                "2025-01-07T00:00:00Z",
+                "2025-01-08T00:00:00Z",
+            ])
+        );
+        let series = body["series"].as_array().expect("series must be an array");
+        assert_eq!(series[0]["instrument_id"], "7203");
+        assert_eq!(series[0]["bars"][1]["close"], 100.0);
+        assert_eq!(series[0]["bars"][1]["filled"], true);
+        assert_eq!(series[1]["instrument_id"], "6758");
+        assert_eq!(series[1]["bars"][0], serde_json::Value::Null);
+        assert_eq!(series[1]["bars"][1]["filled"], false);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn query_bars_with_invalid_params_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let cases = [
+            (
+                "empty_instrument_ids",
+                serde_json::json!({ "instrument_ids": [] }),
+            ),
+            (
+                "duplicated_instrument_id",
+                serde_json::json!({ "instrument_ids": ["7203", "7203"] }),
+            ),
+            (
+                "invalid_timeframe",
+                serde_json::json!({ "instrument_ids": ["7203"], "timeframe": "1w" }),
+            ),
+        ];
+
+        for (name, payload) in cases {
+            let response = server.post("/api/bars/query").json(&payload).await;
+            assert_eq!(
+                response.status_code(),
+                StatusCode::BAD_REQUEST,
+                "case '{name}' should return 400"
+            );
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn export_bars_streams_csv_for_multiple_instruments(pool: PgPool) {
+        let (server, db) = setup(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        insert_test_instrument(&db, "6758").await;
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        let bars = vec![
+            make_test_bar("7203", date(6), 100),
+            make_test_bar("7203", date(7), 105),
+            make_test_bar("6758", date(7), 200),
+            make_test_bar("9984", date(7), 300),
+        ];
+        insert_test_instrument(&db, "9984").await;
+        repositories::bars::upsert_bars(&db, bars)
+            .await
+            .expect("upsert failed");
+
+        let response = server
+            .get("/api/bars/export?instrument_ids=7203,6758&from=2025-01-07")
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.header("content-type"), "text/csv; charset=utf-8");
+        assert_eq!(
+            response.text(),
+            indoc::indoc! {"
+                instrument_id,timestamp,open,high,low,close,volume
+                6758,2025-01-07T00:00:00+00:00,200,210,190,200,1000
+                7203,2025-01-07T00:00:00+00:00,105,115,95,105,1000
+            "}
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn export_bars_as_parquet_is_readable(pool: PgPool) {
+        let (server, db) = setup(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        let bars = (6..=8)
+            .map(|day| {
+                make_test_bar(
+                    "7203",
+                    NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date"),
+                    100,
+                )
+            })
+            .collect();
+        repositories::bars::upsert_bars(&db, bars)
+            .await
+            .expect("upsert failed");
+
+        let response = server
+            .get("/api/bars/export?instrument_ids=7203&format=parquet")
+            .await;
+
+        response.assert_status_ok();
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+            response.as_bytes().clone(),
+        )
+        .expect("response must be a parquet file")
+        .build()
+        .expect("failed to build parquet reader");
+        let rows: usize = reader
+            .map(|batch| batch.expect("invalid record batch").num_rows())
+            .sum();
+        assert_eq!(rows, 3);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn export_bars_with_invalid_params_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let cases = [
+            ("empty_instrument_ids", "?instrument_ids=,"),
+            ("invalid_timeframe", "?instrument_ids=7203&timeframe=1w"),
+            ("invalid_format", "?instrument_ids=7203&format=xlsx"),
+        ];
+
+        for (name, query) in cases {
+            let response = server.get(&format!("/api/bars/export{query}")).await;
+            assert_eq!(
+                response.status_code(),
+                StatusCode::BAD_REQUEST,
+                "case '{name}' should return 400"
+            );
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn import_bars_dry_run_reports_conflicts_without_writing(pool: PgPool) {
+        let (server, db) = setup(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        repositories::bars::upsert_bars(
+            &db,
+            vec![
+                make_test_bar("7203", date(6), 100),
+                make_test_bar("7203", date(7), 105),
+            ],
+        )
+        .await
+        .expect("upsert failed");
+
+        let response = server
+            .post("/api/bars/import?dry_run=true")
+            .text(indoc::indoc! {"
+                instrument_id,date,open,high,low,close,volume
+                7203,2025-01-06,100,110,90,100,1000
+                7203,2025-01-07,105,115,95,106,1000
+                7203,2025-01-08,106,116,96,107,1000
+                7203,2025-01-09,106,100,96,107,1000
+            "})
+            .await;
+
+        response.assert_status_ok();
+        let report: serde_json::Value = response.json();
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "dry_run": true,
+                "total_rows": 4,
+                "valid_rows": 3,
+                "new_rows": 1,
+                "unchanged_rows": 1,
+                "conflicting_rows": 1,
+                "written_rows": 0,
+                "write_elapsed_ms": 0,
+                "rows_per_sec": 0,
+                "conflicts": [{
+                    "instrument_id": "7203",
+                    "timestamp": "2025-01-07T00:00:00Z",
+                    "existing": { "open": 105.0, "high": 115.0, "low": 95.0, "close": 105.0, "volume": 1000 },
+                    "imported": { "open": 105.0, "high": 115.0, "low": 95.0, "close": 106.0, "volume": 1000 },
+                }],
+                "errors": [{
+                    "line": 5,
+                    "message": "high is below open/close: open=106, high=100, low=96, close=107, volume=1000",
+                }],
+            })
+        );
+
+        // DB は変更されていない
+        let bars = repositories::bars::find_bars(
+            &db,
+            repositories::bars::BarsQuery {
+                instrument_id: "7203".to_string(),
+                timeframe: "1d".to_string(),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("find_bars failed")
+        .bars;
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[1].close, Decimal::new(105, 0));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn import_bars_writes_rows_with_custom_layout(pool: PgPool) {
+        let (server, _db) = setup(pool).await;
+
+        let response = server
+            .post("/api/bars/import?instrument_id=7203&date_column=Date&date_format=%25Y%25m%25d")
+            .text(indoc::indoc! {"
+                Date,Open,High,Low,Close,Volume
+                20250106,100,110,90,100,1000
+                20250107,105,115,95,106,1200
+            "})
+            .await;
+
+        response.assert_status_ok();
+        let report: serde_json::Value = response.json();
+        assert_eq!(report["new_rows"], 2);
+        assert_eq!(report["written_rows"], 2);
+        assert!(report["rows_per_sec"].as_u64().unwrap() > 0);
+
+        // 未登録の銘柄も作成されて保存される
+        let response = server.get("/api/bars?instrument_id=7203").await;
+        response.assert_status_ok();
+        let bars = page_bars(&response);
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[1]["close"], 106.0);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn import_bars_with_missing_column_returns_400(pool: PgPool) {
+        let (server, _db) = setup(pool).await;
+
+        let response = server
+            .post("/api/bars/import")
+            .text("instrument_id,date,open,high,low,close\n")
+            .await;
+
+        response.assert_status_bad_request();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bar_anomalies_returns_quarantined_bars(pool: PgPool) {
+        let (server, db) = setup(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        insert_test_instrument(&db, "6758").await;
+
+        // 2025-01-13 は成人の日
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        crate::services::bar_validation::quarantine_invalid_bars(
+            &db,
+            vec![
+                make_test_bar("7203", date(13), 100),
+                make_test_bar("6758", date(13), 200),
+            ],
+        )
+        .await
+        .expect("quarantine failed");
+
+        let response = server.get("/api/bars/anomalies?instrument_id=7203").await;
+
+        response.assert_status_ok();
+        let anomalies: Vec<serde_json::Value> = response.json();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0]["instrument_id"], "7203");
+        assert_eq!(anomalies[0]["timestamp"], "2025-01-13T00:00:00Z");
+        assert_eq!(
+            anomalies[0]["reasons"],
+            serde_json::json!(["non_trading_day"])
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_bar_anomalies_with_invalid_limit_returns_400(pool: PgPool) {
+        let (server, _db) = setup(pool).await;
+
+        let response = server.get("/api/bars/anomalies?limit=0").await;
+
+        response.assert_status_bad_request();
+    }
 }
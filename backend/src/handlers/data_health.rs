@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::AppState;
+use crate::data_provider::DateRange;
+use crate::data_provider::priority::{RequestPriority, with_priority};
+use crate::error::{AppError, ErrorResponse};
+use crate::extractors::{JsonBody, JsonQuery};
+use crate::models::{
+    DataHealthReport, ProviderHealth, RepairDataHealthRequest, RepairDataHealthResponse,
+};
+use crate::services::{backfill, data_health};
+
+/// 欠損レポートのクエリパラメータ
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DataHealthQueryParams {
+    /// 対象期間の開始日 (YYYY-MM-DD, 省略時はバックフィルの対象期間)
+    pub from: Option<NaiveDate>,
+    /// 対象期間の終了日 (YYYY-MM-DD, 省略時はバックフィルの対象期間)
+    pub to: Option<NaiveDate>,
+}
+
+/// 対象期間を決定する
+///
+/// 省略した側はバックフィルの対象期間 (J-Quants Free プランの取得可能範囲) で補う。
+fn resolve_range(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<DateRange, AppError> {
+    let default = backfill::backfill_range(Utc::now().date_naive());
+    let range = DateRange {
+        from: from.unwrap_or(default.from),
+        to: to.unwrap_or(default.to),
+    };
+    if range.from > range.to {
+        return Err(AppError::Validation(
+            "from must be less than or equal to to".to_string(),
+        ));
+    }
+    Ok(range)
+}
+
+/// ウォッチリストに含まれる銘柄のバーデータの欠損レポートを取得する
+///
+/// JPX カレンダー上の営業日のうち、日足が保存されていない日を銘柄ごとに返す。
+#[utoipa::path(
+    get,
+    path = "/api/data-health",
+    tag = "data_health",
+    params(DataHealthQueryParams),
+    responses(
+        (status = 200, description = "欠損レポート", body = DataHealthReport),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn get_data_health(
+    State(state): State<AppState>,
+    JsonQuery(params): JsonQuery<DataHealthQueryParams>,
+) -> Result<Json<DataHealthReport>, AppError> {
+    let range = resolve_range(params.from, params.to)?;
+    let report = data_health::build_report(&state.db, &range).await?;
+    Ok(Json(report))
+}
+
+/// 欠損している期間のバックフィルをバックグラウンドで実行する
+///
+/// 銘柄ごとに最初の欠損日から最後の欠損日までを再取得する。
+/// バックフィルの完了は待たずに 202 を返す。
+#[utoipa::path(
+    post,
+    path = "/api/data-health/repair",
+    tag = "data_health",
+    request_body = RepairDataHealthRequest,
+    responses(
+        (status = 202, description = "バックフィルを開始した", body = RepairDataHealthResponse),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 422, description = "リクエストボディのパースに失敗", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+        (status = 503, description = "データプロバイダーが未設定", body = ErrorResponse),
+    )
+)]
+pub async fn repair_data_health(
+    State(state): State<AppState>,
+    JsonBody(body): JsonBody<RepairDataHealthRequest>,
+) -> Result<(StatusCode, Json<RepairDataHealthResponse>), AppError> {
+    let provider =
+        Arc::clone(state.data_provider.as_ref().ok_or_else(|| {
+            AppError::ServiceUnavailable("data provider is not configured".into())
+        })?);
+    let range = resolve_range(body.from, body.to)?;
+
+    let report = data_health::build_report(&state.db, &range).await?;
+    let targets = data_health::repair_targets(&report, body.instrument_ids.as_deref());
+
+    if !targets.is_empty() {
+        let db = state.db.clone();
+        let queued = targets.clone();
+        // 欠損の補完は大量の取得になりうるため、空いているレートリミットの枠だけを使う
+        tokio::spawn(with_priority(RequestPriority::Bulk, async move {
+            data_health::repair(&db, provider.as_ref(), queued).await;
+        }));
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(RepairDataHealthResponse { queued: targets }),
+    ))
+}
+
+/// データプロバイダーごとの稼働状況を取得する
+///
+/// フォールバックを構成するプロバイダーを優先順位順に返す。
+#[utoipa::path(
+    get,
+    path = "/api/data-health/providers",
+    tag = "data_health",
+    responses(
+        (status = 200, description = "プロバイダーごとの稼働状況", body = Vec<ProviderHealth>),
+        (status = 503, description = "データプロバイダーが未設定", body = ErrorResponse),
+    )
+)]
+pub async fn get_provider_health(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ProviderHealth>>, AppError> {
+    Ok(Json(state.data_provider()?.health().await))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use std::sync::Arc;
+
+    use crate::data_provider::fallback::FallbackProvider;
+    use crate::data_provider::mock::{MockDataProvider, sample_instrument};
+    use crate::data_provider::{DataProvider, DataProviderError};
+    use crate::testing::{create_test_server, create_test_server_with_provider};
+
+    #[sqlx::test(migrations = false)]
+    async fn get_data_health_reports_missing_dates(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist: serde_json::Value = server
+            .post("/api/watchlists")
+            .json(&json!({ "name": "監視" }))
+            .await
+            .json();
+        server
+            .post(&format!(
+                "/api/watchlists/{}/items",
+                watchlist["id"].as_str().unwrap()
+            ))
+            .json(&json!({ "instrument_id": "7203", "name": "トヨタ自動車" }))
+            .await
+            .assert_status(axum::http::StatusCode::CREATED);
+
+        let response = server
+            .get("/api/data-health?from=2025-01-10&to=2025-01-14")
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(
+            response.json::<serde_json::Value>(),
+            json!({
+                "from": "2025-01-10",
+                "to": "2025-01-14",
+                "expected_days": 2,
+                "instruments": [{
+                    "instrument_id": "7203",
+                    "name": "トヨタ自動車",
+                    "stored_days": 0,
+                    "missing_dates": ["2025-01-10", "2025-01-14"],
+                    "bars_updated_at": null,
+                }],
+            })
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn get_data_health_with_inverted_range_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server
+            .get("/api/data-health?from=2025-01-14&to=2025-01-10")
+            .await;
+
+        response.assert_status_bad_request();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn repair_data_health_without_provider_returns_503(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server
+            .post("/api/data-health/repair")
+            .json(&json!({}))
+            .await;
+
+        response.assert_status(axum::http::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn get_provider_health_reports_fallback_providers(pool: PgPool) {
+        let primary = MockDataProvider::new()
+            .with_errors(vec![DataProviderError::Network("timeout".to_string())]);
+        let secondary = MockDataProvider::new().with_instruments(vec![sample_instrument("7203")]);
+        let provider = FallbackProvider::new(vec![
+            ("primary".to_string(), primary),
+            ("secondary".to_string(), secondary),
+        ]);
+        provider.fetch_instrument("7203").await.unwrap();
+        let server = create_test_server_with_provider(pool, Arc::new(provider)).await;
+
+        let response = server.get("/api/data-health/providers").await;
+
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body[0]["name"], "primary");
+        assert_eq!(body[0]["consecutive_failures"], 1);
+        assert_eq!(body[0]["last_error"], "network error: timeout");
+        assert_eq!(body[1]["name"], "secondary");
+        assert_eq!(body[1]["total_requests"], 1);
+        assert_eq!(body[1]["last_error"], serde_json::Value::Null);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn get_provider_health_without_provider_returns_503(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server.get("/api/data-health/providers").await;
+
+        response.assert_status(axum::http::StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
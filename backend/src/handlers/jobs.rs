@@ -0,0 +1,61 @@
+use axum::Json;
+use axum::extract::State;
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::error::{AppError, ErrorResponse};
+use crate::extractors::JsonPath;
+use crate::models::FetchJob;
+use crate::services::fetch_jobs;
+
+/// 欠損取得ジョブの状態を取得する
+///
+/// `GET /api/bars?ensure=true` が 202 で返したジョブの進捗を確認する。
+/// `status` が `succeeded` になった後に同じ条件でバーデータを取得し直す。
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    tag = "jobs",
+    params(
+        ("id" = Uuid, Path, description = "ジョブ ID"),
+    ),
+    responses(
+        (status = 200, description = "ジョブの状態", body = FetchJob),
+        (status = 400, description = "パスパラメータが不正", body = ErrorResponse),
+        (status = 404, description = "ジョブが見つからない", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn get_job(
+    State(state): State<AppState>,
+    JsonPath(id): JsonPath<Uuid>,
+) -> Result<Json<FetchJob>, AppError> {
+    Ok(Json(fetch_jobs::find(&state.db, id).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::testing::create_test_server;
+
+    #[sqlx::test(migrations = false)]
+    async fn get_job_returns_404_for_unknown_id(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server
+            .get("/api/jobs/00000000-0000-0000-0000-000000000001")
+            .await;
+
+        response.assert_status_not_found();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn get_job_with_invalid_id_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server.get("/api/jobs/not-a-uuid").await;
+
+        response.assert_status_bad_request();
+    }
+}
@@ -1,2 +1,4 @@
 pub mod bars;
+pub mod data_health;
+pub mod jobs;
 pub mod watchlists;
@@ -1,21 +1,37 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use axum::Json;
 use axum::extract::State;
 use axum::http::StatusCode;
-use sea_orm::sea_query::OnConflict;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use rust_decimal::Decimal;
 use sea_orm::{
-    ColumnTrait, ConnectionTrait, DatabaseBackend, DbErr, EntityTrait, FromQueryResult,
-    QueryFilter, QueryOrder, Set, SqlErr, Statement,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseBackend, DbErr, EntityTrait,
+    FromQueryResult, QueryFilter, QueryOrder, Set, Statement, TransactionTrait,
 };
+use serde::Deserialize;
+use utoipa::IntoParams;
 use uuid::Uuid;
 
 use crate::AppState;
-use crate::entities::{instruments, watchlist_items, watchlists};
+use crate::data_provider::priority::{RequestPriority, with_priority};
+use crate::entities::{bars, instruments, watchlist_items, watchlists};
 use crate::error::{AppError, ErrorResponse};
-use crate::extractors::{JsonBody, JsonPath};
-use crate::models::{AddWatchlistItemRequest, CreateWatchlistRequest};
-use crate::services::backfill;
+use crate::extractors::{JsonBody, JsonPath, JsonQuery};
+use crate::models::{
+    AddWatchlistItemRequest, BatchAddWatchlistItemsRequest, BatchAddWatchlistItemsResponse,
+    CreateWatchlistRequest, ImportSkippedItem, ImportWatchlistRequest, ImportWatchlistResponse,
+    PriceAlert, Timeframe, UpdateWatchlistItemRequest, WatchlistExport, WatchlistExportFormat,
+    WatchlistExportItem, WatchlistItemTag, WatchlistKind, WatchlistRule, WatchlistRuleInput,
+};
+use crate::repositories::bars as bars_repository;
+use crate::repositories::instruments as instruments_repository;
+use crate::repositories::watchlist_items as watchlist_items_repository;
+use crate::services::{
+    backfill, instruments as instrument_service, price_alerts, smart_watchlists, watchlist_transfer,
+};
 
 /// 文字列に印字可能な非空白文字が含まれているかを検証する。
 /// OpenAPI スキーマの `pattern: "\S"` 制約をサーバー側で実施する。
@@ -29,26 +45,146 @@ fn validate_non_blank(value: &str, field_name: &str) -> Result<String, AppError>
     Ok(trimmed)
 }
 
+/// 銘柄コードを検証し、前後の空白を除去した値を返す
+pub(crate) fn validate_instrument_id(value: &str) -> Result<String, AppError> {
+    let instrument_id = value.trim().to_string();
+    if instrument_id.is_empty() {
+        return Err(AppError::Validation(
+            "instrument_id must not be empty".to_string(),
+        ));
+    }
+    // 銘柄コードは英数字・ドット・ハイフン・アンダースコアのみ許可
+    if !instrument_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        return Err(AppError::Validation(
+            "instrument_id must contain only alphanumeric characters, dots, underscores, and hyphens".to_string(),
+        ));
+    }
+    Ok(instrument_id)
+}
+
+/// 追加した銘柄の銘柄情報同期と日足データのバックフィルをバックグラウンドで行う
+///
+/// DataProvider 未設定時は何もしない。複数銘柄は 1 タスク内で順に処理する。
+/// 銘柄情報の同期に失敗してもバックフィルは続行する。
+/// バックフィルは優先度 `Bulk` で取得し、ユーザーが応答を待つリクエストを妨げない。
+fn spawn_backfill(state: &AppState, instrument_ids: Vec<String>) {
+    let Some(provider) = &state.data_provider else {
+        return;
+    };
+    if instrument_ids.is_empty() {
+        return;
+    }
+
+    let db = state.db.clone();
+    let provider = Arc::clone(provider);
+    tokio::spawn(async move {
+        for instrument_id in instrument_ids {
+            if let Err(e) =
+                instrument_service::sync_instrument_metadata(&db, provider.as_ref(), &instrument_id)
+                    .await
+            {
+                tracing::warn!(instrument_id, error = %e, "銘柄情報の同期に失敗しました");
+            }
+            // 2 年分のバックフィルは、空いているレートリミットの枠だけを使う
+            with_priority(
+                RequestPriority::Bulk,
+                backfill::backfill_daily_bars(&db, provider.as_ref(), &instrument_id),
+            )
+            .await;
+        }
+    });
+}
+
+/// ウォッチリストを取得し、存在しない場合は 404 エラーを返す
+async fn find_watchlist(
+    db: &sea_orm::DatabaseConnection,
+    watchlist_id: Uuid,
+) -> Result<watchlists::Model, AppError> {
+    watchlists::Entity::find_by_id(watchlist_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("watchlist {watchlist_id} not found")))
+}
+
 /// ウォッチリストの存在を確認し、存在しない場合は 404 エラーを返す
 async fn ensure_watchlist_exists(
     db: &sea_orm::DatabaseConnection,
     watchlist_id: Uuid,
 ) -> Result<(), AppError> {
-    let exists = watchlists::Entity::find_by_id(watchlist_id)
-        .one(db)
-        .await?
-        .is_some();
+    find_watchlist(db, watchlist_id).await.map(|_| ())
+}
 
-    if !exists {
-        return Err(AppError::NotFound(format!(
-            "watchlist {watchlist_id} not found"
+/// 銘柄を手動で編集できるウォッチリストか確認する
+///
+/// スマートウォッチリストの銘柄はルールで決まるため、手動での追加・削除は 409 エラーとする。
+async fn ensure_static_watchlist(
+    db: &sea_orm::DatabaseConnection,
+    watchlist_id: Uuid,
+) -> Result<(), AppError> {
+    let watchlist = find_watchlist(db, watchlist_id).await?;
+
+    if watchlist.kind == WatchlistKind::Smart.as_str() {
+        return Err(AppError::Conflict(format!(
+            "watchlist {watchlist_id} is a smart watchlist; its items are managed by its rule"
         )));
     }
 
     Ok(())
 }
 
+/// ルールの各値を検証し、前後の空白を除去したルールを返す
+fn validate_rule(rule: WatchlistRule) -> Result<WatchlistRule, AppError> {
+    Ok(match rule {
+        WatchlistRule::Sector { sector } => WatchlistRule::Sector {
+            sector: validate_non_blank(&sector, "rule.sector")?,
+        },
+        WatchlistRule::MarketSegment { market_segment } => WatchlistRule::MarketSegment {
+            market_segment: validate_non_blank(&market_segment, "rule.market_segment")?,
+        },
+        WatchlistRule::Screen {
+            sector,
+            market_segment,
+            min_close,
+            max_close,
+        } => {
+            if sector.is_none()
+                && market_segment.is_none()
+                && min_close.is_none()
+                && max_close.is_none()
+            {
+                return Err(AppError::Validation(
+                    "rule must have at least one screen condition".to_string(),
+                ));
+            }
+            let min_close = validate_price(min_close, "rule.min_close")?;
+            let max_close = validate_price(max_close, "rule.max_close")?;
+            if matches!((min_close, max_close), (Some(min), Some(max)) if min > max) {
+                return Err(AppError::Validation(
+                    "rule.min_close must be less than or equal to rule.max_close".to_string(),
+                ));
+            }
+            WatchlistRule::Screen {
+                sector: sector
+                    .map(|sector| validate_non_blank(&sector, "rule.sector"))
+                    .transpose()?,
+                market_segment: market_segment
+                    .map(|segment| validate_non_blank(&segment, "rule.market_segment"))
+                    .transpose()?,
+                min_close,
+                max_close,
+            }
+        }
+        WatchlistRule::Holdings => WatchlistRule::Holdings,
+    })
+}
+
 /// ウォッチリストを作成する
+///
+/// `rule` を指定するとスマートウォッチリストとして作成し、登録済みの銘柄から
+/// ルールに一致するものを即座に登録する。
 #[utoipa::path(
     post,
     path = "/api/watchlists",
@@ -66,19 +202,34 @@ pub async fn create_watchlist(
     JsonBody(payload): JsonBody<CreateWatchlistRequest>,
 ) -> Result<(StatusCode, Json<watchlists::Model>), AppError> {
     let name = validate_non_blank(&payload.name, "name")?;
+    let rule = payload.rule.map(validate_rule).transpose()?;
+    let kind = if rule.is_some() {
+        WatchlistKind::Smart
+    } else {
+        WatchlistKind::Static
+    };
+    let rule = rule
+        .map(serde_json::to_value)
+        .transpose()
+        .map_err(|e| AppError::Internal(format!("failed to serialize watchlist rule: {e}")))?;
+
+    // 作成と銘柄の登録を 1 トランザクションで行い、銘柄のないスマートウォッチリストを見せない
+    let txn = state.db.begin().await?;
 
     // sort_order をサブクエリで算出し、INSERT をアトミックに実行する
-    let result = state
-        .db
+    let result = txn
         .query_one_raw(Statement::from_sql_and_values(
             DatabaseBackend::Postgres,
-            "INSERT INTO watchlists (id, name, sort_order) VALUES (gen_random_uuid(), $1, COALESCE((SELECT MAX(sort_order) FROM watchlists), -1) + 1) RETURNING id, name, sort_order, created_at",
-            [name.into()],
+            "INSERT INTO watchlists (id, name, sort_order, kind, rule) VALUES (gen_random_uuid(), $1, COALESCE((SELECT MAX(sort_order) FROM watchlists), -1) + 1, $2, $3) RETURNING id, name, sort_order, created_at, kind, rule",
+            [name.into(), kind.as_str().into(), rule.into()],
         ))
         .await?
         .ok_or_else(|| AppError::Database(DbErr::Custom("INSERT RETURNING returned no rows".to_string())))?;
 
     let created = watchlists::Model::from_query_result(&result, "")?;
+    smart_watchlists::refresh_watchlist(&txn, &created).await?;
+
+    txn.commit().await?;
 
     Ok((StatusCode::CREATED, Json(created)))
 }
@@ -129,6 +280,8 @@ pub async fn delete_watchlist(
         return Err(AppError::NotFound(format!("watchlist {id} not found")));
     }
 
+    smart_watchlists::refresh_after_change(&state.db, WatchlistRuleInput::Holdings).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -145,7 +298,7 @@ pub async fn delete_watchlist(
         (status = 201, description = "銘柄を追加した", body = watchlist_items::Model),
         (status = 400, description = "バリデーションエラー", body = ErrorResponse),
         (status = 404, description = "ウォッチリストが見つからない", body = ErrorResponse),
-        (status = 409, description = "銘柄が既にウォッチリストに存在する", body = ErrorResponse),
+        (status = 409, description = "銘柄が既にウォッチリストに存在する、またはスマートウォッチリスト", body = ErrorResponse),
         (status = 422, description = "リクエストボディのパースに失敗", body = ErrorResponse),
         (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
     )
@@ -155,80 +308,118 @@ pub async fn add_watchlist_item(
     JsonPath(watchlist_id): JsonPath<Uuid>,
     JsonBody(payload): JsonBody<AddWatchlistItemRequest>,
 ) -> Result<(StatusCode, Json<watchlist_items::Model>), AppError> {
-    let instrument_id = payload.instrument_id.trim().to_string();
-    if instrument_id.is_empty() {
+    let instrument_id = validate_instrument_id(&payload.instrument_id)?;
+    let name = validate_non_blank(&payload.name, "name")?;
+    ensure_static_watchlist(&state.db, watchlist_id).await?;
+    instruments_repository::ensure_exists(&state.db, &instrument_id, name).await?;
+
+    let item =
+        watchlist_items_repository::insert_item(&state.db, watchlist_id, &instrument_id, None)
+            .await?
+            .ok_or_else(|| {
+                AppError::Conflict(format!(
+                    "instrument {instrument_id} is already in the watchlist"
+                ))
+            })?;
+
+    // バックグラウンドで日足データをバックフィルする
+    spawn_backfill(&state, vec![instrument_id]);
+
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+/// 一括追加で 1 リクエストに指定できる銘柄数の上限
+const MAX_BATCH_ITEMS: usize = 500;
+
+/// ウォッチリストに複数の銘柄を一括追加する
+///
+/// 全銘柄コードを検証し、1 つでも不正な場合は何も追加せず 400 エラーを返す。
+/// 追加は 1 トランザクションで行い、指定順に sort_order を割り当てる。
+/// 既にウォッチリストに存在する銘柄は追加せず `conflicts` で返す。
+/// 追加した銘柄のバックフィルは 1 タスクにまとめて順に実行する。
+/// 未登録の銘柄は銘柄コードを銘柄名として登録し、バックフィル時に銘柄情報を同期する。
+#[utoipa::path(
+    post,
+    path = "/api/watchlists/{id}/items:batch",
+    tag = "watchlist_items",
+    params(
+        ("id" = Uuid, Path, description = "ウォッチリスト ID"),
+    ),
+    request_body = BatchAddWatchlistItemsRequest,
+    responses(
+        (status = 200, description = "一括追加の結果", body = BatchAddWatchlistItemsResponse),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 404, description = "ウォッチリストが見つからない", body = ErrorResponse),
+        (status = 409, description = "スマートウォッチリストには追加できない", body = ErrorResponse),
+        (status = 422, description = "リクエストボディのパースに失敗", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn batch_add_watchlist_items(
+    State(state): State<AppState>,
+    JsonPath(watchlist_id): JsonPath<Uuid>,
+    JsonBody(payload): JsonBody<BatchAddWatchlistItemsRequest>,
+) -> Result<Json<BatchAddWatchlistItemsResponse>, AppError> {
+    if payload.instrument_ids.is_empty() {
         return Err(AppError::Validation(
-            "instrument_id must not be empty".to_string(),
+            "instrument_ids must not be empty".to_string(),
         ));
     }
-    // 銘柄コードは英数字・ドット・ハイフン・アンダースコアのみ許可
-    if !instrument_id
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
-    {
-        return Err(AppError::Validation(
-            "instrument_id must contain only alphanumeric characters, dots, underscores, and hyphens".to_string(),
-        ));
+    if payload.instrument_ids.len() > MAX_BATCH_ITEMS {
+        return Err(AppError::Validation(format!(
+            "instrument_ids must contain at most {MAX_BATCH_ITEMS} items"
+        )));
     }
 
-    let name = validate_non_blank(&payload.name, "name")?;
-    ensure_watchlist_exists(&state.db, watchlist_id).await?;
+    let mut instrument_ids = Vec::with_capacity(payload.instrument_ids.len());
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, raw) in payload.instrument_ids.iter().enumerate() {
+        match validate_instrument_id(raw) {
+            Ok(id) if !seen.insert(id.clone()) => {
+                errors.push(format!("instrument_ids[{index}]: {id} is duplicated"));
+            }
+            Ok(id) => instrument_ids.push(id),
+            Err(AppError::Validation(reason)) => {
+                errors.push(format!("instrument_ids[{index}]: {reason}"));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors.join("; ")));
+    }
 
-    // 銘柄が存在しない場合は自動作成
-    let instrument_model = instruments::ActiveModel {
-        id: Set(instrument_id.clone()),
-        name: Set(name),
-        market: Set("TSE".to_string()),
-        sector: Set(None),
-    };
+    ensure_static_watchlist(&state.db, watchlist_id).await?;
 
-    instruments::Entity::insert(instrument_model)
-        .on_conflict(
-            OnConflict::column(instruments::Column::Id)
-                .do_nothing()
-                .to_owned(),
-        )
-        .exec_without_returning(&state.db)
-        .await?;
+    let mut added = Vec::new();
+    let mut conflicts = Vec::new();
 
-    // sort_order をサブクエリで算出し、INSERT をアトミックに実行する
-    let item_result = state
-        .db
-        .query_one_raw(Statement::from_sql_and_values(
-            DatabaseBackend::Postgres,
-            "INSERT INTO watchlist_items (watchlist_id, instrument_id, sort_order) VALUES ($1, $2, COALESCE((SELECT MAX(sort_order) FROM watchlist_items WHERE watchlist_id = $1), -1) + 1) RETURNING watchlist_id, instrument_id, sort_order, added_at",
-            [watchlist_id.into(), instrument_id.clone().into()],
-        ))
-        .await;
+    let txn = state.db.begin().await?;
 
-    let item = match item_result {
-        Ok(Some(row)) => watchlist_items::Model::from_query_result(&row, "")?,
-        Ok(None) => {
-            return Err(AppError::Database(DbErr::Custom(
-                "INSERT RETURNING returned no rows".to_string(),
-            )));
-        }
-        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
-            return Err(AppError::Conflict(format!(
-                "instrument {instrument_id} is already in the watchlist"
-            )));
-        }
-        Err(e) => {
-            return Err(AppError::Database(e));
-        }
-    };
+    for instrument_id in instrument_ids {
+        instruments_repository::ensure_exists(&txn, &instrument_id, instrument_id.clone()).await?;
 
-    // バックグラウンドで日足データをバックフィルする
-    if let Some(provider) = &state.data_provider {
-        let db = state.db.clone();
-        let provider = Arc::clone(provider);
-        let instrument_id = instrument_id.clone();
-        tokio::spawn(async move {
-            backfill::backfill_daily_bars(&db, provider.as_ref(), &instrument_id).await;
-        });
+        match watchlist_items_repository::insert_item(&txn, watchlist_id, &instrument_id, None)
+            .await?
+        {
+            Some(item) => added.push(item),
+            None => conflicts.push(instrument_id),
+        }
     }
 
-    Ok((StatusCode::CREATED, Json(item)))
+    txn.commit().await?;
+
+    // 追加した銘柄をまとめて 1 タスクでバックフィルする
+    spawn_backfill(
+        &state,
+        added
+            .iter()
+            .map(|item| item.instrument_id.clone())
+            .collect(),
+    );
+
+    Ok(Json(BatchAddWatchlistItemsResponse { added, conflicts }))
 }
 
 /// ウォッチリスト内の銘柄一覧を取得する
@@ -273,7 +464,8 @@ pub async fn list_watchlist_items(
     responses(
         (status = 204, description = "削除成功"),
         (status = 400, description = "パスパラメータが不正", body = ErrorResponse),
-        (status = 404, description = "銘柄が見つからない", body = ErrorResponse),
+        (status = 404, description = "ウォッチリストまたは銘柄が見つからない", body = ErrorResponse),
+        (status = 409, description = "スマートウォッチリストの銘柄は削除できない", body = ErrorResponse),
         (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
     )
 )]
@@ -281,6 +473,8 @@ pub async fn delete_watchlist_item(
     State(state): State<AppState>,
     JsonPath((watchlist_id, instrument_id)): JsonPath<(Uuid, String)>,
 ) -> Result<StatusCode, AppError> {
+    ensure_static_watchlist(&state.db, watchlist_id).await?;
+
     let result = watchlist_items::Entity::delete_many()
         .filter(watchlist_items::Column::WatchlistId.eq(watchlist_id))
         .filter(watchlist_items::Column::InstrumentId.eq(&instrument_id))
@@ -293,9 +487,352 @@ pub async fn delete_watchlist_item(
         )));
     }
 
+    smart_watchlists::refresh_after_change(&state.db, WatchlistRuleInput::Holdings).await;
+
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// タグ一覧を検証し、タグ名の前後の空白を除去した値を返す
+///
+/// 同名のタグ (大文字小文字を区別しない) の重複は許可しない。
+fn validate_tags(tags: Vec<WatchlistItemTag>) -> Result<Vec<WatchlistItemTag>, AppError> {
+    let mut seen = HashSet::new();
+    tags.into_iter()
+        .map(|tag| {
+            let name = validate_non_blank(&tag.name, "tags.name")?;
+            let is_hex_color = tag.color.len() == 7
+                && tag.color.starts_with('#')
+                && tag.color[1..].chars().all(|c| c.is_ascii_hexdigit());
+            if !is_hex_color {
+                return Err(AppError::Validation(format!(
+                    "tags.color must be in #RRGGBB format: {}",
+                    tag.color
+                )));
+            }
+            if !seen.insert(name.to_lowercase()) {
+                return Err(AppError::Validation(format!("tag {name} is duplicated")));
+            }
+            Ok(WatchlistItemTag {
+                name,
+                color: tag.color,
+            })
+        })
+        .collect()
+}
+
+/// 価格が正の値であることを検証する (None はクリアとして許可する)
+fn validate_price(price: Option<Decimal>, field_name: &str) -> Result<Option<Decimal>, AppError> {
+    match price {
+        Some(price) if price <= Decimal::ZERO => Err(AppError::Validation(format!(
+            "{field_name} must be greater than 0"
+        ))),
+        _ => Ok(price),
+    }
+}
+
+/// ウォッチリスト内の銘柄のメモ・タグ・価格を更新する
+///
+/// 省略したフィールドは変更しない。スマートウォッチリストの銘柄も更新できる。
+#[utoipa::path(
+    patch,
+    path = "/api/watchlists/{id}/items/{instrument_id}",
+    tag = "watchlist_items",
+    params(
+        ("id" = Uuid, Path, description = "ウォッチリスト ID"),
+        ("instrument_id" = String, Path, description = "銘柄コード"),
+    ),
+    request_body = UpdateWatchlistItemRequest,
+    responses(
+        (status = 200, description = "更新後の銘柄", body = watchlist_items::Model),
+        (status = 400, description = "バリデーションエラー", body = ErrorResponse),
+        (status = 404, description = "銘柄が見つからない", body = ErrorResponse),
+        (status = 422, description = "リクエストボディのパースに失敗", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn update_watchlist_item(
+    State(state): State<AppState>,
+    JsonPath((watchlist_id, instrument_id)): JsonPath<(Uuid, String)>,
+    JsonBody(payload): JsonBody<UpdateWatchlistItemRequest>,
+) -> Result<Json<watchlist_items::Model>, AppError> {
+    let tags = payload.tags.map(validate_tags).transpose()?;
+    let entry_price = payload
+        .entry_price
+        .map(|price| validate_price(price, "entry_price"))
+        .transpose()?;
+    let target_price = payload
+        .target_price
+        .map(|price| validate_price(price, "target_price"))
+        .transpose()?;
+    let stop_price = payload
+        .stop_price
+        .map(|price| validate_price(price, "stop_price"))
+        .transpose()?;
+
+    let item = watchlist_items::Entity::find_by_id((watchlist_id, instrument_id.clone()))
+        .one(&state.db)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "item {instrument_id} not found in watchlist {watchlist_id}"
+            ))
+        })?;
+
+    let mut active: watchlist_items::ActiveModel = item.clone().into();
+    if let Some(memo) = payload.memo {
+        let memo = memo
+            .map(|memo| memo.trim().to_string())
+            .filter(|memo| !memo.is_empty());
+        active.memo = Set(memo);
+    }
+    if let Some(tags) = tags {
+        let tags = serde_json::to_value(tags)
+            .map_err(|e| AppError::Internal(format!("failed to serialize tags: {e}")))?;
+        active.tags = Set(tags);
+    }
+    if let Some(entry_price) = entry_price {
+        active.entry_price = Set(entry_price);
+    }
+    if let Some(target_price) = target_price {
+        active.target_price = Set(target_price);
+    }
+    if let Some(stop_price) = stop_price {
+        active.stop_price = Set(stop_price);
+    }
+
+    if !active.is_changed() {
+        return Ok(Json(item));
+    }
+
+    let entry_price_changed = active.entry_price.is_set();
+    let updated = active.update(&state.db).await?;
+
+    if entry_price_changed {
+        smart_watchlists::refresh_after_change(&state.db, WatchlistRuleInput::Holdings).await;
+    }
+
+    Ok(Json(updated))
+}
+
+/// ウォッチリスト内の銘柄の価格アラートを取得する
+///
+/// 各銘柄の最新の日足に対して entry/target/stop の価格閾値を判定し、
+/// 到達したものを sort_order 順に返す。日足データのない銘柄は判定しない。
+#[utoipa::path(
+    get,
+    path = "/api/watchlists/{id}/alerts",
+    tag = "watchlist_items",
+    params(
+        ("id" = Uuid, Path, description = "ウォッチリスト ID"),
+    ),
+    responses(
+        (status = 200, description = "価格閾値に到達した銘柄のアラート", body = Vec<PriceAlert>),
+        (status = 400, description = "パスパラメータが不正", body = ErrorResponse),
+        (status = 404, description = "ウォッチリストが見つからない", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn list_watchlist_alerts(
+    State(state): State<AppState>,
+    JsonPath(watchlist_id): JsonPath<Uuid>,
+) -> Result<Json<Vec<PriceAlert>>, AppError> {
+    ensure_watchlist_exists(&state.db, watchlist_id).await?;
+
+    let items = watchlist_items::Entity::find()
+        .filter(watchlist_items::Column::WatchlistId.eq(watchlist_id))
+        .filter(
+            Condition::any()
+                .add(watchlist_items::Column::EntryPrice.is_not_null())
+                .add(watchlist_items::Column::TargetPrice.is_not_null())
+                .add(watchlist_items::Column::StopPrice.is_not_null()),
+        )
+        .order_by_asc(watchlist_items::Column::SortOrder)
+        .all(&state.db)
+        .await?;
+
+    let instrument_ids: Vec<String> = items.iter().map(|i| i.instrument_id.clone()).collect();
+    let latest_bars: HashMap<String, bars::Model> = bars_repository::find_latest_bars(
+        &state.db,
+        &instrument_ids,
+        &Timeframe::Daily.to_string(),
+    )
+    .await?
+    .into_iter()
+    .map(|bar| (bar.instrument_id.clone(), bar))
+    .collect();
+
+    let alerts = items
+        .iter()
+        .filter_map(|item| {
+            let bar = latest_bars.get(&item.instrument_id)?;
+            Some(price_alerts::evaluate(item, bar))
+        })
+        .flatten()
+        .collect();
+
+    Ok(Json(alerts))
+}
+
+/// ウォッチリストエクスポートのクエリパラメータ
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportWatchlistQueryParams {
+    /// エクスポート形式 (デフォルト: "json")
+    #[serde(default)]
+    pub format: WatchlistExportFormat,
+}
+
+/// ウォッチリストを JSON または CSV でエクスポートする
+///
+/// 銘柄は sort_order 順に並ぶ。
+#[utoipa::path(
+    get,
+    path = "/api/watchlists/{id}/export",
+    tag = "watchlists",
+    params(
+        ("id" = Uuid, Path, description = "ウォッチリスト ID"),
+        ExportWatchlistQueryParams,
+    ),
+    responses(
+        (status = 200, description = "エクスポートしたウォッチリスト", content(
+            (WatchlistExport = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "パラメータが不正", body = ErrorResponse),
+        (status = 404, description = "ウォッチリストが見つからない", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn export_watchlist(
+    State(state): State<AppState>,
+    JsonPath(watchlist_id): JsonPath<Uuid>,
+    JsonQuery(params): JsonQuery<ExportWatchlistQueryParams>,
+) -> Result<Response, AppError> {
+    let watchlist = find_watchlist(&state.db, watchlist_id).await?;
+
+    let items: Vec<WatchlistExportItem> = watchlist_items::Entity::find()
+        .filter(watchlist_items::Column::WatchlistId.eq(watchlist_id))
+        .order_by_asc(watchlist_items::Column::SortOrder)
+        .find_also_related(instruments::Entity)
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(|(item, instrument)| WatchlistExportItem {
+            instrument_id: item.instrument_id,
+            name: instrument.map(|i| i.name),
+            memo: item.memo,
+        })
+        .collect();
+
+    match params.format {
+        WatchlistExportFormat::Json => Ok(Json(WatchlistExport {
+            name: watchlist.name,
+            items,
+        })
+        .into_response()),
+        WatchlistExportFormat::Csv => {
+            let body = watchlist_transfer::to_csv(&items)?;
+            Ok((
+                [
+                    (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                    (
+                        CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"watchlist-{watchlist_id}.csv\""),
+                    ),
+                ],
+                body,
+            )
+                .into_response())
+        }
+    }
+}
+
+/// ウォッチリストに銘柄一覧をインポートする
+///
+/// 銘柄ごとに `add_watchlist_item` と同じ検証を行い、不正な銘柄や
+/// 既にウォッチリストに存在する銘柄はスキップして結果に含める。
+/// 銘柄名のない形式 (TradingView 等) で未登録の銘柄は、銘柄コードを銘柄名として登録する。
+#[utoipa::path(
+    post,
+    path = "/api/watchlists/{id}/import",
+    tag = "watchlists",
+    params(
+        ("id" = Uuid, Path, description = "ウォッチリスト ID"),
+    ),
+    request_body = ImportWatchlistRequest,
+    responses(
+        (status = 200, description = "インポート結果", body = ImportWatchlistResponse),
+        (status = 400, description = "インポート内容のパースに失敗", body = ErrorResponse),
+        (status = 404, description = "ウォッチリストが見つからない", body = ErrorResponse),
+        (status = 409, description = "スマートウォッチリストにはインポートできない", body = ErrorResponse),
+        (status = 422, description = "リクエストボディのパースに失敗", body = ErrorResponse),
+        (status = 500, description = "内部サーバーエラー", body = ErrorResponse),
+    )
+)]
+pub async fn import_watchlist(
+    State(state): State<AppState>,
+    JsonPath(watchlist_id): JsonPath<Uuid>,
+    JsonBody(payload): JsonBody<ImportWatchlistRequest>,
+) -> Result<Json<ImportWatchlistResponse>, AppError> {
+    ensure_static_watchlist(&state.db, watchlist_id).await?;
+    let entries = watchlist_transfer::parse(&payload.content, payload.format)?;
+
+    let mut added = Vec::new();
+    let mut skipped = Vec::new();
+    let mut seen = HashSet::new();
+
+    let txn = state.db.begin().await?;
+
+    for entry in entries {
+        let instrument_id = match validate_instrument_id(&entry.instrument_id) {
+            Ok(id) => id,
+            Err(AppError::Validation(reason)) => {
+                skipped.push(ImportSkippedItem {
+                    instrument_id: entry.instrument_id,
+                    reason,
+                });
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        if !seen.insert(instrument_id.clone()) {
+            skipped.push(ImportSkippedItem {
+                reason: format!("instrument {instrument_id} is duplicated in the import content"),
+                instrument_id,
+            });
+            continue;
+        }
+
+        let name = entry
+            .name
+            .as_deref()
+            .and_then(|name| validate_non_blank(name, "name").ok())
+            .unwrap_or_else(|| instrument_id.clone());
+        instruments_repository::ensure_exists(&txn, &instrument_id, name).await?;
+
+        let inserted =
+            watchlist_items_repository::insert_item(&txn, watchlist_id, &instrument_id, entry.memo)
+                .await?;
+
+        if inserted.is_some() {
+            added.push(instrument_id);
+        } else {
+            skipped.push(ImportSkippedItem {
+                reason: format!("instrument {instrument_id} is already in the watchlist"),
+                instrument_id,
+            });
+        }
+    }
+
+    txn.commit().await?;
+
+    // 新たに追加した銘柄の日足データをバックフィルする
+    spawn_backfill(&state, added.clone());
+
+    Ok(Json(ImportWatchlistResponse { added, skipped }))
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -613,4 +1150,498 @@ mod tests {
 
         response.assert_status(axum::http::StatusCode::NOT_FOUND);
     }
+
+    // --- ウォッチリストのインポート/エクスポート ---
+
+    /// ウォッチリストを作成し、指定した銘柄を順に追加して ID を返す
+    async fn create_watchlist_with_items(
+        server: &axum_test::TestServer,
+        items: &[(&str, &str)],
+    ) -> String {
+        let create_response = server
+            .post("/api/watchlists")
+            .json(&json!({ "name": "インポート/エクスポート" }))
+            .await;
+        let watchlist_id = create_response.json::<serde_json::Value>()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        for (instrument_id, name) in items {
+            server
+                .post(&format!("/api/watchlists/{watchlist_id}/items"))
+                .json(&json!({ "instrument_id": instrument_id, "name": name }))
+                .await
+                .assert_status(axum::http::StatusCode::CREATED);
+        }
+
+        watchlist_id
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn export_watchlist_returns_items_in_order(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id =
+            create_watchlist_with_items(&server, &[("7203", "トヨタ自動車"), ("6758", "ソニー")])
+                .await;
+
+        let response = server
+            .get(&format!("/api/watchlists/{watchlist_id}/export"))
+            .await;
+
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body,
+            json!({
+                "name": "インポート/エクスポート",
+                "items": [
+                    { "instrument_id": "7203", "name": "トヨタ自動車", "memo": null },
+                    { "instrument_id": "6758", "name": "ソニー", "memo": null },
+                ],
+            })
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn export_watchlist_as_csv(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[("7203", "トヨタ自動車")]).await;
+
+        let response = server
+            .get(&format!("/api/watchlists/{watchlist_id}/export?format=csv"))
+            .await;
+
+        response.assert_status_ok();
+        assert_eq!(response.header("content-type"), "text/csv; charset=utf-8");
+        assert_eq!(
+            response.text(),
+            indoc::indoc! {"
+                instrument_id,name,memo
+                7203,トヨタ自動車,
+            "}
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn import_watchlist_adds_new_items_and_reports_skipped(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[("7203", "トヨタ自動車")]).await;
+
+        let response = server
+            .post(&format!("/api/watchlists/{watchlist_id}/import"))
+            .json(&json!({ "content": "TSE:7203,TSE:6758,TSE:6758,TSE:bad!" }))
+            .await;
+
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["added"], json!(["6758"]));
+        let skipped: Vec<&str> = body["skipped"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["instrument_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(skipped, vec!["7203", "6758", "bad!"]);
+
+        // 既存の銘柄の後ろに追加されること
+        let list_response = server
+            .get(&format!("/api/watchlists/{watchlist_id}/items"))
+            .await;
+        let items: Vec<serde_json::Value> = list_response.json();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1]["instrument_id"], "6758");
+        assert_eq!(items[1]["sort_order"], 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn import_watchlist_round_trips_export(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let source_id = create_watchlist_with_items(
+            &server,
+            &[("9984", "ソフトバンクグループ"), ("7203", "トヨタ自動車")],
+        )
+        .await;
+        let target_id = create_watchlist_with_items(&server, &[]).await;
+
+        let exported = server
+            .get(&format!("/api/watchlists/{source_id}/export?format=csv"))
+            .await
+            .text();
+        server
+            .post(&format!("/api/watchlists/{target_id}/import"))
+            .json(&json!({ "content": exported, "format": "csv" }))
+            .await
+            .assert_status_ok();
+
+        let response = server
+            .get(&format!("/api/watchlists/{target_id}/export"))
+            .await;
+        let body: serde_json::Value = response.json();
+        let codes: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["instrument_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(codes, vec!["9984", "7203"]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn import_watchlist_with_invalid_json_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[]).await;
+
+        let response = server
+            .post(&format!("/api/watchlists/{watchlist_id}/import"))
+            .json(&json!({ "content": "{ invalid", "format": "json" }))
+            .await;
+
+        response.assert_status(axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    // --- スマートウォッチリスト ---
+
+    /// 業種 "輸送用機器" のスマートウォッチリストを作成し、その ID を返す
+    async fn create_smart_watchlist(server: &axum_test::TestServer) -> String {
+        let response = server
+            .post("/api/watchlists")
+            .json(&json!({
+                "name": "自動車",
+                "rule": { "type": "sector", "sector": "輸送用機器" },
+            }))
+            .await;
+        response.assert_status(axum::http::StatusCode::CREATED);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["kind"], "smart");
+        body["id"].as_str().unwrap().to_string()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn create_smart_watchlist_registers_matching_instruments(pool: PgPool) {
+        let server = create_test_server(pool.clone()).await;
+        sqlx::query(
+            "INSERT INTO instruments (id, name, market, sector) VALUES ('7203', 'トヨタ自動車', 'TSE', '輸送用機器'), ('6758', 'ソニー', 'TSE', '電気機器')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let watchlist_id = create_smart_watchlist(&server).await;
+
+        let response = server
+            .get(&format!("/api/watchlists/{watchlist_id}/items"))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        let codes: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["instrument_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(codes, vec!["7203"]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn create_static_watchlist_has_no_rule(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server
+            .post("/api/watchlists")
+            .json(&json!({ "name": "手動" }))
+            .await;
+
+        response.assert_status(axum::http::StatusCode::CREATED);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["kind"], "static");
+        assert_eq!(body["rule"], serde_json::Value::Null);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn create_smart_watchlist_with_blank_rule_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        let response = server
+            .post("/api/watchlists")
+            .json(&json!({
+                "name": "空ルール",
+                "rule": { "type": "market_segment", "market_segment": "  " },
+            }))
+            .await;
+
+        response.assert_status(axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn create_smart_watchlist_with_invalid_screen_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+
+        for rule in [
+            json!({ "type": "screen" }),
+            json!({ "type": "screen", "min_close": 3000, "max_close": 1000 }),
+            json!({ "type": "screen", "min_close": 0 }),
+        ] {
+            let response = server
+                .post("/api/watchlists")
+                .json(&json!({ "name": "スクリーン", "rule": rule }))
+                .await;
+
+            response.assert_status(axum::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn holdings_watchlist_follows_entry_price_updates(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let static_id = create_watchlist_with_items(&server, &[("7203", "トヨタ自動車")]).await;
+        let response = server
+            .post("/api/watchlists")
+            .json(&json!({ "name": "保有銘柄", "rule": { "type": "holdings" } }))
+            .await;
+        response.assert_status(axum::http::StatusCode::CREATED);
+        let holdings_id = response.json::<serde_json::Value>()["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let holding_codes = || async {
+            let body: serde_json::Value = server
+                .get(&format!("/api/watchlists/{holdings_id}/items"))
+                .await
+                .json();
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["instrument_id"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(holding_codes().await.is_empty());
+
+        server
+            .patch(&format!("/api/watchlists/{static_id}/items/7203"))
+            .json(&json!({ "entry_price": 2500 }))
+            .await
+            .assert_status_ok();
+        assert_eq!(holding_codes().await, vec!["7203"]);
+
+        server
+            .delete(&format!("/api/watchlists/{static_id}/items/7203"))
+            .await
+            .assert_status(axum::http::StatusCode::NO_CONTENT);
+        assert!(holding_codes().await.is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn manual_edit_of_smart_watchlist_returns_409(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_smart_watchlist(&server).await;
+
+        let add_response = server
+            .post(&format!("/api/watchlists/{watchlist_id}/items"))
+            .json(&json!({ "instrument_id": "7203", "name": "トヨタ自動車" }))
+            .await;
+        let delete_response = server
+            .delete(&format!("/api/watchlists/{watchlist_id}/items/7203"))
+            .await;
+        let import_response = server
+            .post(&format!("/api/watchlists/{watchlist_id}/import"))
+            .json(&json!({ "content": "7203" }))
+            .await;
+
+        add_response.assert_status(axum::http::StatusCode::CONFLICT);
+        delete_response.assert_status(axum::http::StatusCode::CONFLICT);
+        import_response.assert_status(axum::http::StatusCode::CONFLICT);
+    }
+
+    // --- ウォッチリスト項目の更新・価格アラート ---
+
+    #[sqlx::test(migrations = false)]
+    async fn update_watchlist_item_sets_and_clears_fields(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[("7203", "トヨタ自動車")]).await;
+        let path = format!("/api/watchlists/{watchlist_id}/items/7203");
+
+        let response = server
+            .patch(&path)
+            .json(&json!({
+                "memo": " 決算後に再確認 ",
+                "tags": [{ "name": "高配当", "color": "#FF8800" }],
+                "entry_price": 2900,
+                "target_price": 3500.5,
+            }))
+            .await;
+
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["memo"], "決算後に再確認");
+        assert_eq!(
+            body["tags"],
+            json!([{ "name": "高配当", "color": "#FF8800" }])
+        );
+        assert_eq!(body["entry_price"], 2900.0);
+        assert_eq!(body["target_price"], 3500.5);
+        assert_eq!(body["stop_price"], serde_json::Value::Null);
+
+        // 省略したフィールドは維持し、null を指定したフィールドはクリアする
+        let response = server
+            .patch(&path)
+            .json(&json!({ "memo": null, "stop_price": 2700 }))
+            .await;
+
+        response.assert_status_ok();
+        let items: serde_json::Value = server
+            .get(&format!("/api/watchlists/{watchlist_id}/items"))
+            .await
+            .json();
+        let item = &items[0];
+        assert_eq!(item["memo"], serde_json::Value::Null);
+        assert_eq!(item["tags"][0]["name"], "高配当");
+        assert_eq!(item["entry_price"], 2900.0);
+        assert_eq!(item["stop_price"], 2700.0);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn update_nonexistent_watchlist_item_returns_404(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[]).await;
+
+        let response = server
+            .patch(&format!("/api/watchlists/{watchlist_id}/items/7203"))
+            .json(&json!({ "memo": "メモ" }))
+            .await;
+
+        response.assert_status_not_found();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn update_watchlist_item_with_invalid_values_returns_400(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[("7203", "トヨタ自動車")]).await;
+
+        for payload in [
+            json!({ "tags": [{ "name": "注目", "color": "red" }] }),
+            json!({ "tags": [
+                { "name": "注目", "color": "#FF0000" },
+                { "name": "注目", "color": "#00FF00" },
+            ] }),
+            json!({ "tags": [{ "name": " ", "color": "#FF0000" }] }),
+            json!({ "stop_price": 0 }),
+        ] {
+            let response = server
+                .patch(&format!("/api/watchlists/{watchlist_id}/items/7203"))
+                .json(&payload)
+                .await;
+
+            response.assert_status(axum::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn list_watchlist_alerts_evaluates_latest_bar(pool: PgPool) {
+        let server = create_test_server(pool.clone()).await;
+        let watchlist_id =
+            create_watchlist_with_items(&server, &[("7203", "トヨタ自動車"), ("6758", "ソニー")])
+                .await;
+        sqlx::query(
+            "INSERT INTO bars (instrument_id, timeframe, timestamp, open, high, low, close, volume) VALUES \
+             ('7203', '1d', '2025-01-06T00:00:00Z', 3000, 3600, 2800, 3500, 1000), \
+             ('7203', '1d', '2025-01-07T00:00:00Z', 3000, 3100, 2900, 3000, 1000)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (instrument_id, payload) in [
+            ("7203", json!({ "target_price": 3050, "stop_price": 2800 })),
+            // 日足のない銘柄は判定しない
+            ("6758", json!({ "target_price": 1 })),
+        ] {
+            server
+                .patch(&format!(
+                    "/api/watchlists/{watchlist_id}/items/{instrument_id}"
+                ))
+                .json(&payload)
+                .await
+                .assert_status_ok();
+        }
+
+        let response = server
+            .get(&format!("/api/watchlists/{watchlist_id}/alerts"))
+            .await;
+
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body,
+            json!([{
+                "instrument_id": "7203",
+                "kind": "target",
+                "threshold": 3050.0,
+                "timestamp": "2025-01-07T00:00:00Z",
+                "close": 3000.0,
+            }])
+        );
+    }
+
+    // --- 銘柄の一括追加 ---
+
+    #[sqlx::test(migrations = false)]
+    async fn batch_add_watchlist_items_appends_in_order_and_reports_conflicts(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[("7203", "トヨタ自動車")]).await;
+
+        let response = server
+            .post(&format!("/api/watchlists/{watchlist_id}/items:batch"))
+            .json(&json!({ "instrument_ids": ["9984", " 7203 ", "6758"] }))
+            .await;
+
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        let added: Vec<(&str, i64)> = body["added"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["instrument_id"].as_str().unwrap(),
+                    item["sort_order"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(added, vec![("9984", 1), ("6758", 2)]);
+        assert_eq!(body["conflicts"], json!(["7203"]));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn batch_add_watchlist_items_with_invalid_code_adds_nothing(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_watchlist_with_items(&server, &[]).await;
+
+        for instrument_ids in [json!([]), json!(["9984", "72 03"]), json!(["9984", "9984"])] {
+            let response = server
+                .post(&format!("/api/watchlists/{watchlist_id}/items:batch"))
+                .json(&json!({ "instrument_ids": instrument_ids }))
+                .await;
+
+            response.assert_status(axum::http::StatusCode::BAD_REQUEST);
+        }
+
+        let items: serde_json::Value = server
+            .get(&format!("/api/watchlists/{watchlist_id}/items"))
+            .await
+            .json();
+        assert_eq!(items, json!([]));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn batch_add_to_smart_watchlist_returns_409(pool: PgPool) {
+        let server = create_test_server(pool).await;
+        let watchlist_id = create_smart_watchlist(&server).await;
+
+        let response = server
+            .post(&format!("/api/watchlists/{watchlist_id}/items:batch"))
+            .json(&json!({ "instrument_ids": ["7203"] }))
+            .await;
+
+        response.assert_status(axum::http::StatusCode::CONFLICT);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod calendar;
 pub mod cli;
 pub mod data_provider;
 pub mod entities;
@@ -26,25 +27,25 @@ use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::data_provider::DataProviderKind;
+use crate::data_provider::DataProvider;
 use crate::error::{AppError, ErrorResponse};
-use crate::handlers::{bars, watchlists};
+use crate::handlers::{bars, data_health, jobs, watchlists};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: DatabaseConnection,
     /// 株価データプロバイダー (J-Quants API 等)
     ///
-    /// `JQUANTS_API_KEY` 未設定時は None で起動する。
+    /// DataProvider を 1 つも設定していない場合は None で起動する。
     /// データ取得系のエンドポイントは利用時にエラーを返す。
-    pub data_provider: Option<Arc<DataProviderKind>>,
+    pub data_provider: Option<Arc<dyn DataProvider>>,
 }
 
 impl AppState {
     /// DataProvider を取得する
     ///
-    /// `JQUANTS_API_KEY` 未設定で起動した場合は 503 エラーを返す。
-    pub fn data_provider(&self) -> Result<&DataProviderKind, AppError> {
+    /// DataProvider なしで起動した場合は 503 エラーを返す。
+    pub fn data_provider(&self) -> Result<&dyn DataProvider, AppError> {
         self.data_provider
             .as_deref()
             .ok_or_else(|| AppError::ServiceUnavailable("data provider is not configured".into()))
@@ -56,9 +57,13 @@ impl AppState {
     tags(
         (name = "health", description = "ヘルスチェック"),
         (name = "bars", description = "バーデータ (OHLCV)"),
+        (name = "data_health", description = "バーデータの欠損検出と修復"),
+        (name = "jobs", description = "バーデータの欠損取得ジョブ"),
         (name = "watchlists", description = "ウォッチリスト管理"),
         (name = "watchlist_items", description = "ウォッチリスト内の銘柄管理"),
     ),
+    // Entity の Model のスキーマ (schemas.rs) からのみ参照されるスキーマ
+    components(schemas(crate::models::BarAnomalyReason)),
     info(
         title = "T-Rader API",
         version = "0.1.0",
@@ -73,6 +78,7 @@ mod app_state_tests {
     use sea_orm::{DatabaseBackend, MockDatabase};
 
     use super::*;
+    use crate::data_provider::mock::MockDataProvider;
 
     fn mock_db() -> sea_orm::DatabaseConnection {
         MockDatabase::new(DatabaseBackend::Postgres).into_connection()
@@ -80,10 +86,9 @@ mod app_state_tests {
 
     #[rstest]
     fn test_data_provider_returns_provider_when_set() {
-        let client = crate::data_provider::jquants::JQuantsClient::new("test-key".into()).unwrap();
         let state = AppState {
             db: mock_db(),
-            data_provider: Some(Arc::new(DataProviderKind::JQuants(client))),
+            data_provider: Some(Arc::new(MockDataProvider::new())),
         };
         assert!(state.data_provider().is_ok());
     }
@@ -114,9 +119,22 @@ fn build_openapi_router() -> OpenApiRouter<AppState> {
         .routes(routes!(watchlists::list_watchlists))
         .routes(routes!(watchlists::delete_watchlist))
         .routes(routes!(watchlists::add_watchlist_item))
+        .routes(routes!(watchlists::batch_add_watchlist_items))
         .routes(routes!(watchlists::list_watchlist_items))
         .routes(routes!(watchlists::delete_watchlist_item))
+        .routes(routes!(watchlists::update_watchlist_item))
+        .routes(routes!(watchlists::list_watchlist_alerts))
+        .routes(routes!(watchlists::export_watchlist))
+        .routes(routes!(watchlists::import_watchlist))
         .routes(routes!(bars::list_bars))
+        .routes(routes!(bars::query_bars))
+        .routes(routes!(bars::export_bars))
+        .routes(routes!(bars::import_bars))
+        .routes(routes!(bars::list_bar_anomalies))
+        .routes(routes!(data_health::get_data_health))
+        .routes(routes!(data_health::repair_data_health))
+        .routes(routes!(data_health::get_provider_health))
+        .routes(routes!(jobs::get_job))
 }
 
 /// OpenAPI スペックを生成する (DB 接続不要)
@@ -1,15 +1,21 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use backend::AppState;
-use backend::cli::Cli;
+use backend::cli::{Cli, Command};
 use backend::create_router;
-use backend::data_provider::DataProviderKind;
-use backend::data_provider::jquants::JQuantsClient;
+use backend::data_provider::DataProvider;
+use backend::data_provider::caching::CachingProvider;
+use backend::data_provider::csv_directory::{CsvDirectoryProvider, CsvFormat};
+use backend::data_provider::fallback::FallbackProvider;
+use backend::data_provider::jquants::{CircuitBreakerConfig, JQuantsClient, ReplayProvider};
+use backend::data_provider::synthetic::SyntheticProvider;
 use backend::error::AppError;
+use backend::services::bar_import;
 use clap::Parser;
 use migration::{Migrator, MigratorTrait};
-use sea_orm::{ConnectOptions, Database};
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
@@ -50,24 +56,32 @@ async fn main() -> Result<(), AppError> {
         tracing::info!("skipping database migrations (--skip-migration)");
     }
 
+    // BARS_COMPRESS_AFTER_DAYS: bars の圧縮ポリシーをマイグレーションの初期値から変更する
+    if let Some(days) = parse_env("BARS_COMPRESS_AFTER_DAYS")? {
+        backend::repositories::bars::set_compress_after(&db, days).await?;
+        tracing::info!(days, "bars の圧縮ポリシーを設定しました");
+    }
+
     // --migrate-only: マイグレーションのみ実行して終了する
     if cli.migrate_only {
         tracing::info!("migration completed, exiting (--migrate-only)");
         return Ok(());
     }
 
-    // J-Quants API キーが設定されている場合のみ DataProvider を初期化する
-    let data_provider = match std::env::var("JQUANTS_API_KEY") {
-        Ok(api_key) if !api_key.is_empty() => {
-            let client = JQuantsClient::new(api_key)?;
-            tracing::info!("J-Quants DataProvider を初期化しました");
-            Some(Arc::new(DataProviderKind::JQuants(client)))
-        }
-        _ => {
-            tracing::warn!("JQUANTS_API_KEY が未設定のため、DataProvider なしで起動します");
-            None
-        }
-    };
+    // サブコマンド: 処理して終了する
+    if let Some(Command::ImportBars(args)) = cli.command {
+        let content = std::fs::read_to_string(&args.file).map_err(|e| {
+            AppError::Config(format!("failed to read {}: {e}", args.file.display()))
+        })?;
+        let parsed = bar_import::parse_csv(&content, &args.layout, args.instrument_id.as_deref())?;
+        let report = bar_import::import_bars(&db, parsed, args.dry_run).await?;
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|e| AppError::Internal(format!("failed to serialize report: {e}")))?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    let data_provider = data_provider_from_env(&db)?;
 
     let state = AppState { db, data_provider };
 
@@ -90,3 +104,186 @@ async fn main() -> Result<(), AppError> {
 
     Ok(())
 }
+
+/// 空でない環境変数の値
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// 環境変数から DataProvider を初期化する
+///
+/// `DATA_PROVIDERS` にカンマ区切りで指定したプロバイダーを、その順の優先順位で
+/// フォールバックとして組み立てる。未指定の場合は 再生 (`JQUANTS_REPLAY_DIR`) >
+/// CSV ディレクトリ (`CSV_DATA_DIR`) > 合成データ (`SYNTHETIC_DATA_SEED`) >
+/// J-Quants API (`JQUANTS_API_KEY`) のうち
+/// 最初に設定されているものを使う。いずれも未設定の場合は None を返す。
+/// J-Quants API は、取得済みの期間を bars テーブルとキャッシュから応答して API の呼び出しを減らす
+/// (キャッシュは J-Quants API にのみ適用し、他のプロバイダーの応答は記録しない)。
+/// J-Quants API と、テスト用のデータを返すプロバイダー (再生・合成データ) は併用できない。
+fn data_provider_from_env(
+    db: &DatabaseConnection,
+) -> Result<Option<Arc<dyn DataProvider>>, AppError> {
+    let names: Vec<String> = match non_empty_env("DATA_PROVIDERS") {
+        Some(names) => names
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect(),
+        None => ["replay", "csv", "synthetic", "jquants"]
+            .into_iter()
+            .find(|name| provider_configured(name))
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+    };
+    if names.is_empty() {
+        tracing::warn!("DataProvider が未設定のため、DataProvider なしで起動します");
+        return Ok(None);
+    }
+    validate_provider_chain(&names)?;
+
+    let providers = names
+        .iter()
+        .map(|name| Ok((name.clone(), provider_from_env(name, db)?)))
+        .collect::<Result<Vec<_>, AppError>>()?;
+    tracing::info!(providers = names.join(","), "DataProvider を初期化しました");
+
+    Ok(Some(Arc::new(FallbackProvider::new(providers))))
+}
+
+/// テスト用のデータを返すプロバイダー (本物の株価と混ぜて保存してはならない)
+const TEST_DATA_PROVIDERS: [&str; 2] = ["replay", "synthetic"];
+
+/// `DATA_PROVIDERS` の組み合わせを検証する
+///
+/// J-Quants API の障害時に再生・合成データへ切り替わると、そのデータが本物の株価として
+/// bars テーブルに保存され、以降は J-Quants API から取得し直されなくなるため併用を禁止する。
+fn validate_provider_chain(names: &[String]) -> Result<(), AppError> {
+    if !names.iter().any(|name| name == "jquants") {
+        return Ok(());
+    }
+    match names
+        .iter()
+        .find(|name| TEST_DATA_PROVIDERS.contains(&name.as_str()))
+    {
+        Some(name) => Err(AppError::Config(format!(
+            "data provider '{name}' returns test data and cannot be combined with 'jquants' in DATA_PROVIDERS"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// 空でない環境変数の値をパースする
+fn parse_env<T>(name: &str) -> Result<Option<T>, AppError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    non_empty_env(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e| AppError::Config(format!("invalid {name} '{value}': {e}")))
+        })
+        .transpose()
+}
+
+/// プロバイダーに必要な環境変数が設定されているか
+fn provider_configured(name: &str) -> bool {
+    let var = match name {
+        "replay" => "JQUANTS_REPLAY_DIR",
+        "csv" => "CSV_DATA_DIR",
+        "synthetic" => "SYNTHETIC_DATA_SEED",
+        "jquants" => "JQUANTS_API_KEY",
+        _ => return false,
+    };
+    non_empty_env(var).is_some()
+}
+
+/// 環境変数に必要な値を取得する (未設定の場合はプロバイダー名を含めた設定エラー)
+fn required_env(name: &str, provider: &str) -> Result<String, AppError> {
+    non_empty_env(name).ok_or_else(|| {
+        AppError::Config(format!(
+            "{name} must be set to use data provider '{provider}'"
+        ))
+    })
+}
+
+/// `DATA_PROVIDERS` のプロバイダー名から DataProvider を初期化する
+fn provider_from_env(
+    name: &str,
+    db: &DatabaseConnection,
+) -> Result<Arc<dyn DataProvider>, AppError> {
+    match name {
+        // 記録済みのフィクスチャを再生し、ネットワークにはアクセスしない
+        "replay" => {
+            let dir = required_env("JQUANTS_REPLAY_DIR", name)?;
+            tracing::info!(dir, "記録済みの J-Quants API レスポンスを再生します");
+            Ok(Arc::new(ReplayProvider::new(PathBuf::from(dir))))
+        }
+        "csv" => {
+            let dir = required_env("CSV_DATA_DIR", name)?;
+            let format = match non_empty_env("CSV_DATA_FORMAT") {
+                Some(format) => format.parse().map_err(AppError::Config)?,
+                None => CsvFormat::default(),
+            };
+            tracing::info!(dir, ?format, "CSV ディレクトリから読み込みます");
+            Ok(Arc::new(CsvDirectoryProvider::new(
+                PathBuf::from(dir),
+                format,
+            )))
+        }
+        "synthetic" => {
+            required_env("SYNTHETIC_DATA_SEED", name)?;
+            let seed: u64 = parse_env("SYNTHETIC_DATA_SEED")?.unwrap_or_default();
+            tracing::info!(seed, "合成データを生成します");
+            Ok(Arc::new(SyntheticProvider::new(seed)))
+        }
+        "jquants" => {
+            // レートリミットは同じ API キーを使う全プロセスで共有する
+            let mut client = JQuantsClient::new(required_env("JQUANTS_API_KEY", name)?)?
+                .with_shared_rate_limit(db.clone());
+            if let Some(base_url) = non_empty_env("JQUANTS_BASE_URL") {
+                tracing::info!(base_url, "J-Quants API のベース URL を差し替えます");
+                client = client.with_api_base_url(base_url);
+            }
+            let mut circuit_breaker = CircuitBreakerConfig::default();
+            if let Some(threshold) = parse_env("JQUANTS_CIRCUIT_BREAKER_THRESHOLD")? {
+                circuit_breaker.failure_threshold = threshold;
+            }
+            if let Some(secs) = parse_env("JQUANTS_CIRCUIT_BREAKER_OPEN_SECS")? {
+                circuit_breaker.open_duration = std::time::Duration::from_secs(secs);
+            }
+            client = client.with_circuit_breaker(circuit_breaker);
+            if let Some(dir) = non_empty_env("JQUANTS_RECORD_DIR") {
+                tracing::info!(dir, "J-Quants API のレスポンスを記録します");
+                client = client.with_recording(PathBuf::from(dir));
+            }
+            // キャッシュは J-Quants API の応答にのみ適用する
+            Ok(Arc::new(CachingProvider::new(db.clone(), client)))
+        }
+        _ => Err(AppError::Config(format!(
+            "unknown data provider '{name}' in DATA_PROVIDERS (expected jquants, replay, csv or synthetic)"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::jquants_only("jquants", true)]
+    #[case::jquants_with_csv("jquants,csv", true)]
+    #[case::synthetic_only("synthetic", true)]
+    #[case::replay_with_synthetic("replay,synthetic", true)]
+    #[case::jquants_with_synthetic("jquants,synthetic", false)]
+    #[case::replay_before_jquants("replay,jquants", false)]
+    fn test_validate_provider_chain(#[case] names: &str, #[case] valid: bool) {
+        let names: Vec<String> = names.split(',').map(str::to_string).collect();
+
+        assert_eq!(validate_provider_chain(&names).is_ok(), valid);
+    }
+}
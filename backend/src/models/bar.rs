@@ -1,34 +1,70 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use sea_orm::Set;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use crate::entities::bars;
 
 /// 時間足の種類
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// bars テーブルの CHECK 制約 (`bars_timeframe_check`) と同じ値を持つ。
+/// 分足・時間足は `bar_retention_policies` の保持期間に従って削除される。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Timeframe {
+    /// 1 分足
+    #[serde(rename = "1m")]
+    OneMinute,
+    /// 5 分足
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    /// 15 分足
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    /// 1 時間足
+    #[serde(rename = "1h")]
+    OneHour,
     /// 日足
     #[serde(rename = "1d")]
     Daily,
 }
 
-impl std::fmt::Display for Timeframe {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Timeframe {
+    /// すべての時間足 (短い順)
+    pub const ALL: [Timeframe; 5] = [
+        Timeframe::OneMinute,
+        Timeframe::FiveMinutes,
+        Timeframe::FifteenMinutes,
+        Timeframe::OneHour,
+        Timeframe::Daily,
+    ];
+
+    /// API・DB で使う文字列表現
+    pub fn as_str(self) -> &'static str {
         match self {
-            Timeframe::Daily => write!(f, "1d"),
+            Timeframe::OneMinute => "1m",
+            Timeframe::FiveMinutes => "5m",
+            Timeframe::FifteenMinutes => "15m",
+            Timeframe::OneHour => "1h",
+            Timeframe::Daily => "1d",
         }
     }
 }
 
+impl std::fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl std::str::FromStr for Timeframe {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "1d" => Ok(Timeframe::Daily),
-            other => Err(format!("unknown timeframe: {other}")),
-        }
+        Timeframe::ALL
+            .into_iter()
+            .find(|timeframe| timeframe.as_str() == s)
+            .ok_or_else(|| format!("unknown timeframe: {s}"))
     }
 }
 
@@ -51,6 +87,9 @@ pub struct Bar {
     pub close: Decimal,
     /// 出来高
     pub volume: i64,
+    /// 取得元 (DataProvider 名や "csv_import"。不明な場合は None)
+    #[serde(default)]
+    pub source: Option<String>,
 }
 
 /// models::Bar -> entities::bars::ActiveModel 変換 (upsert 用)
@@ -65,6 +104,7 @@ impl From<Bar> for bars::ActiveModel {
             low: Set(bar.low),
             close: Set(bar.close),
             volume: Set(bar.volume),
+            source: Set(bar.source),
         }
     }
 }
@@ -84,6 +124,216 @@ impl From<bars::Model> for Bar {
             low: model.low,
             close: model.close,
             volume: model.volume,
+            source: model.source,
+        }
+    }
+}
+
+/// バーデータのタイムスタンプの並び順
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BarOrder {
+    /// 古い順
+    #[default]
+    Asc,
+    /// 新しい順
+    Desc,
+}
+
+/// 複数銘柄のバーデータ取得で、データのない日の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BarFill {
+    /// 補完せず null にする
+    #[default]
+    None,
+    /// 直前のバーの終値で補完する (出来高は 0)
+    Forward,
+}
+
+/// 複数銘柄のバーデータ取得リクエスト
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MultiBarsQueryRequest {
+    /// 銘柄コード一覧 (レスポンスの series はこの順に並ぶ)
+    #[schema(min_items = 1, max_items = 50)]
+    pub instrument_ids: Vec<String>,
+    /// 時間足 ("1m", "5m", "15m", "1h", "1d"。デフォルト: "1d")
+    #[serde(default = "default_timeframe")]
+    pub timeframe: String,
+    /// 取得開始日 (YYYY-MM-DD, inclusive)
+    pub from: Option<NaiveDate>,
+    /// 取得終了日 (YYYY-MM-DD, inclusive)
+    pub to: Option<NaiveDate>,
+    /// データのない日の扱い (デフォルト: "none")
+    #[serde(default)]
+    pub fill: BarFill,
+}
+
+fn default_timeframe() -> String {
+    Timeframe::Daily.to_string()
+}
+
+/// 1 銘柄のバーデータの 1 ページ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BarsPage {
+    /// バーデータ (`order` の順)
+    pub bars: Vec<bars::Model>,
+    /// 続きのページがある場合、次のリクエストの `cursor` に指定する値 (最後のページでは null)
+    pub next_cursor: Option<DateTime<Utc>>,
+}
+
+/// タイムスタンプを揃えた複数銘柄のバーデータ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AlignedBars {
+    /// いずれかの銘柄にバーが存在するタイムスタンプ (昇順)
+    pub timestamps: Vec<DateTime<Utc>>,
+    /// 銘柄ごとのバー (リクエストの instrument_ids 順)
+    pub series: Vec<AlignedSeries>,
+}
+
+/// 1 銘柄分のバーデータ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AlignedSeries {
+    /// 銘柄コード
+    pub instrument_id: String,
+    /// `timestamps` と同じ長さのバー配列 (データがない位置は null)
+    pub bars: Vec<Option<AlignedBar>>,
+}
+
+/// タイムスタンプを揃えたバー (タイムスタンプは `AlignedBars::timestamps` の同じ位置)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AlignedBar {
+    /// 始値
+    #[schema(value_type = f64)]
+    pub open: Decimal,
+    /// 高値
+    #[schema(value_type = f64)]
+    pub high: Decimal,
+    /// 安値
+    #[schema(value_type = f64)]
+    pub low: Decimal,
+    /// 終値
+    #[schema(value_type = f64)]
+    pub close: Decimal,
+    /// 出来高
+    pub volume: i64,
+    /// 前方補完したバーか
+    pub filled: bool,
+}
+
+/// OHLCV の値
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct OhlcvValues {
+    #[schema(value_type = f64)]
+    pub open: Decimal,
+    #[schema(value_type = f64)]
+    pub high: Decimal,
+    #[schema(value_type = f64)]
+    pub low: Decimal,
+    #[schema(value_type = f64)]
+    pub close: Decimal,
+    pub volume: i64,
+}
+
+impl From<&Bar> for OhlcvValues {
+    fn from(bar: &Bar) -> Self {
+        OhlcvValues {
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
         }
     }
 }
+
+impl From<&bars::Model> for OhlcvValues {
+    fn from(bar: &bars::Model) -> Self {
+        OhlcvValues {
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
+        }
+    }
+}
+
+/// バーデータの CSV インポート結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BarImportReport {
+    /// ドライラン (DB に書き込んでいない) か
+    pub dry_run: bool,
+    /// CSV のデータ行数 (ヘッダーを除く)
+    pub total_rows: usize,
+    /// 検証に成功した行数
+    pub valid_rows: usize,
+    /// DB に存在しなかった行数
+    pub new_rows: usize,
+    /// DB の既存の行と同じ値だった行数
+    pub unchanged_rows: usize,
+    /// DB の既存の行と値が異なる行数 (ドライランでない場合は上書きした)
+    pub conflicting_rows: usize,
+    /// DB に書き込んだ (挿入または更新した) 行数 (ドライランでは 0)
+    pub written_rows: u64,
+    /// 書き込みにかかった時間 (ミリ秒, ドライランでは 0)
+    pub write_elapsed_ms: u64,
+    /// 書き込みのスループット (行/秒, ドライランでは 0)
+    pub rows_per_sec: u64,
+    /// 既存の行と値が異なる行 (先頭から最大 100 件)
+    pub conflicts: Vec<BarImportConflict>,
+    /// 検証に失敗した行 (先頭から最大 100 件, インポートしない)
+    pub errors: Vec<BarImportRowError>,
+}
+
+/// 既存の行と値が異なるインポート行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BarImportConflict {
+    /// 銘柄コード
+    pub instrument_id: String,
+    /// タイムスタンプ
+    pub timestamp: DateTime<Utc>,
+    /// DB の既存の値
+    pub existing: OhlcvValues,
+    /// インポートする値
+    pub imported: OhlcvValues,
+}
+
+/// 検証に失敗した CSV の行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BarImportRowError {
+    /// CSV の行番号 (1 始まり, ヘッダー行を含む)
+    pub line: u64,
+    /// 失敗の理由
+    pub message: String,
+}
+
+/// 取り込み時の検証でバーを不正と判定した理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BarAnomalyReason {
+    /// 高値が始値・終値より低い
+    HighBelowOpenClose,
+    /// 安値が始値・終値より高い
+    LowAboveOpenClose,
+    /// 0 以下の価格がある
+    NonPositivePrice,
+    /// 出来高が負
+    NegativeVolume,
+    /// 東証の休業日のバー
+    NonTradingDay,
+}
+
+impl std::fmt::Display for BarAnomalyReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            BarAnomalyReason::HighBelowOpenClose => "high is below open/close",
+            BarAnomalyReason::LowAboveOpenClose => "low is above open/close",
+            BarAnomalyReason::NonPositivePrice => "price must be greater than 0",
+            BarAnomalyReason::NegativeVolume => "volume must not be negative",
+            BarAnomalyReason::NonTradingDay => "date is not a trading day",
+        };
+        f.write_str(message)
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// バーデータの欠損レポート
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct DataHealthReport {
+    /// 対象期間の開始日 (inclusive)
+    pub from: NaiveDate,
+    /// 対象期間の終了日 (inclusive)
+    pub to: NaiveDate,
+    /// 対象期間の東証の営業日数
+    pub expected_days: usize,
+    /// ウォッチリストに含まれる銘柄ごとの欠損状況 (銘柄コード順)
+    pub instruments: Vec<InstrumentDataHealth>,
+}
+
+/// 1 銘柄のバーデータの欠損状況
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct InstrumentDataHealth {
+    /// 銘柄コード
+    pub instrument_id: String,
+    /// 銘柄名
+    pub name: String,
+    /// 対象期間の営業日のうち、日足が保存されている日数
+    pub stored_days: usize,
+    /// 日足が保存されていない営業日 (昇順)
+    pub missing_dates: Vec<NaiveDate>,
+    /// バーデータを最後に書き込んだ日時 (未取得の場合は null)
+    pub bars_updated_at: Option<DateTime<FixedOffset>>,
+}
+
+/// 欠損の修復リクエスト
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RepairDataHealthRequest {
+    /// 修復する銘柄コード (省略時は欠損のある全銘柄)
+    pub instrument_ids: Option<Vec<String>>,
+    /// 対象期間の開始日 (YYYY-MM-DD, 省略時はバックフィルの対象期間)
+    pub from: Option<NaiveDate>,
+    /// 対象期間の終了日 (YYYY-MM-DD, 省略時はバックフィルの対象期間)
+    pub to: Option<NaiveDate>,
+}
+
+/// 欠損の修復としてキューに入れたバックフィル
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RepairTarget {
+    /// 銘柄コード
+    pub instrument_id: String,
+    /// バックフィルする期間の開始日 (最初の欠損日)
+    pub from: NaiveDate,
+    /// バックフィルする期間の終了日 (最後の欠損日)
+    pub to: NaiveDate,
+    /// 期間内の欠損日数
+    pub missing_days: usize,
+}
+
+/// 欠損の修復レスポンス
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RepairDataHealthResponse {
+    /// バックグラウンドで実行するバックフィル
+    pub queued: Vec<RepairTarget>,
+}
+
+/// データプロバイダーの稼働状況
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ProviderHealth {
+    /// プロバイダー名 (`DATA_PROVIDERS` に指定した名前)
+    pub name: String,
+    /// 正常か (連続で失敗していない)。異常なプロバイダーは正常なプロバイダーの後に試す
+    pub healthy: bool,
+    /// 連続で失敗した回数
+    pub consecutive_failures: u32,
+    /// 起動以降にこのプロバイダーに問い合わせた回数
+    pub total_requests: u64,
+    /// 起動以降に失敗した回数 (通信エラー、レートリミット、5xx のみを数える)
+    pub total_failures: u64,
+    /// 最後に成功した日時 (未成功の場合は null)
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// 最後に失敗した日時 (未失敗の場合は null)
+    pub last_failure_at: Option<DateTime<Utc>>,
+    /// 最後に失敗したときのエラー (未失敗の場合は null)
+    pub last_error: Option<String>,
+    /// サーキットブレーカーの状態 (サーキットブレーカーを持たないプロバイダーは null)
+    pub circuit_breaker: Option<CircuitBreakerStatus>,
+}
+
+/// サーキットブレーカーの状態 (Closed / Open / Half-Open)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// 通常どおりリクエストを送る
+    Closed,
+    /// リクエストを送らずに即座に失敗させる
+    Open,
+    /// 復旧を確認するため 1 リクエストだけ試行する
+    HalfOpen,
+}
+
+/// サーキットブレーカーの稼働状況
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct CircuitBreakerStatus {
+    /// 現在の状態
+    pub state: CircuitState,
+    /// 連続で失敗した回数
+    pub consecutive_failures: u32,
+    /// Open に遷移する連続失敗回数
+    pub failure_threshold: u32,
+    /// Open の場合、試行を再開する日時 (Open 以外は null)
+    pub retry_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,83 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::entities::data_fetch_jobs;
+use crate::error::AppError;
+
+/// 欠損取得ジョブの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchJobStatus {
+    /// データプロバイダーから取得中
+    Running,
+    /// 取得したバーを保存した
+    Succeeded,
+    /// 取得または保存に失敗した
+    Failed,
+}
+
+impl FetchJobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FetchJobStatus::Running => "running",
+            FetchJobStatus::Succeeded => "succeeded",
+            FetchJobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for FetchJobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "running" => Ok(FetchJobStatus::Running),
+            "succeeded" => Ok(FetchJobStatus::Succeeded),
+            "failed" => Ok(FetchJobStatus::Failed),
+            _ => Err(format!("unknown fetch job status: {s}")),
+        }
+    }
+}
+
+/// バー取得時に欠損をデータプロバイダーから取得するジョブ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FetchJob {
+    /// ジョブ ID
+    pub id: Uuid,
+    /// 銘柄コード
+    pub instrument_id: String,
+    /// 取得する期間の開始日 (最初の欠損日)
+    pub from: NaiveDate,
+    /// 取得する期間の終了日 (最後の欠損日)
+    pub to: NaiveDate,
+    /// 状態
+    pub status: FetchJobStatus,
+    /// 保存したバーの数 (成功時以外は null)
+    pub bar_count: Option<i32>,
+    /// 失敗時のエラーメッセージ (失敗時以外は null)
+    pub error: Option<String>,
+    /// ジョブを開始した日時
+    pub created_at: DateTime<FixedOffset>,
+    /// ジョブが終了した日時 (実行中は null)
+    pub finished_at: Option<DateTime<FixedOffset>>,
+}
+
+impl TryFrom<data_fetch_jobs::Model> for FetchJob {
+    type Error = AppError;
+
+    fn try_from(model: data_fetch_jobs::Model) -> Result<Self, Self::Error> {
+        Ok(Self {
+            status: model.status.parse().map_err(AppError::Internal)?,
+            id: model.id,
+            instrument_id: model.instrument_id,
+            from: model.from_date,
+            to: model.to_date,
+            bar_count: model.bar_count,
+            error: model.error,
+            created_at: model.created_at,
+            finished_at: model.finished_at,
+        })
+    }
+}
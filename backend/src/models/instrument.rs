@@ -27,4 +27,6 @@ pub struct Instrument {
     pub market: Market,
     /// 業種 (セクター)
     pub sector: Option<String>,
+    /// 市場区分 (例: "プライム")
+    pub market_segment: Option<String>,
 }
@@ -1,7 +1,24 @@
 pub mod bar;
+pub mod data_health;
+pub mod fetch_job;
 pub mod instrument;
 pub mod watchlist;
 
-pub use bar::{Bar, Timeframe};
+pub use bar::{
+    AlignedBar, AlignedBars, AlignedSeries, Bar, BarAnomalyReason, BarFill, BarImportConflict,
+    BarImportReport, BarImportRowError, BarOrder, BarsPage, MultiBarsQueryRequest, OhlcvValues,
+    Timeframe,
+};
+pub use data_health::{
+    CircuitBreakerStatus, CircuitState, DataHealthReport, InstrumentDataHealth, ProviderHealth,
+    RepairDataHealthRequest, RepairDataHealthResponse, RepairTarget,
+};
+pub use fetch_job::{FetchJob, FetchJobStatus};
 pub use instrument::Instrument;
-pub use watchlist::{AddWatchlistItemRequest, CreateWatchlistRequest};
+pub use watchlist::{
+    AddWatchlistItemRequest, BatchAddWatchlistItemsRequest, BatchAddWatchlistItemsResponse,
+    CreateWatchlistRequest, ImportSkippedItem, ImportWatchlistRequest, ImportWatchlistResponse,
+    PriceAlert, PriceAlertKind, UpdateWatchlistItemRequest, WatchlistExport, WatchlistExportFormat,
+    WatchlistExportItem, WatchlistImportFormat, WatchlistItemTag, WatchlistKind, WatchlistRule,
+    WatchlistRuleInput,
+};
@@ -1,6 +1,103 @@
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize};
 use utoipa::ToSchema;
 
+use crate::entities::watchlist_items;
+
+/// ウォッチリストの種類 (watchlists.kind)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchlistKind {
+    /// 銘柄を手動で追加・削除する
+    Static,
+    /// ルールに一致する銘柄が自動的に登録される
+    Smart,
+}
+
+impl WatchlistKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WatchlistKind::Static => "static",
+            WatchlistKind::Smart => "smart",
+        }
+    }
+}
+
+/// スマートウォッチリストの銘柄選定ルール
+///
+/// instruments テーブルに登録済みの銘柄に対して評価する。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WatchlistRule {
+    /// 業種 (33 業種区分) が一致する銘柄
+    Sector {
+        /// 業種名 (例: "輸送用機器")
+        #[schema(min_length = 1, pattern = r"\S")]
+        sector: String,
+    },
+    /// 市場区分が一致する銘柄
+    MarketSegment {
+        /// 市場区分 (例: "プライム")
+        #[schema(min_length = 1, pattern = r"\S")]
+        market_segment: String,
+    },
+    /// 保存したスクリーニング条件にすべて一致する銘柄
+    ///
+    /// 省略した条件は絞り込みに使わない。終値の条件は最新の日足に対して評価する。
+    Screen {
+        /// 業種名
+        #[serde(default)]
+        #[schema(min_length = 1, pattern = r"\S")]
+        sector: Option<String>,
+        /// 市場区分
+        #[serde(default)]
+        #[schema(min_length = 1, pattern = r"\S")]
+        market_segment: Option<String>,
+        /// 最新の終値の下限 (この値を含む)
+        #[serde(default)]
+        #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+        min_close: Option<Decimal>,
+        /// 最新の終値の上限 (この値を含む)
+        #[serde(default)]
+        #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+        max_close: Option<Decimal>,
+    },
+    /// 保有中の銘柄 (static のウォッチリストで entry_price を登録した銘柄)
+    Holdings,
+}
+
+/// スマートウォッチリストのルールが参照するデータ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchlistRuleInput {
+    /// 銘柄情報 (業種・市場区分)
+    Instruments,
+    /// 日足
+    Bars,
+    /// static のウォッチリストの銘柄と entry_price
+    Holdings,
+}
+
+impl WatchlistRule {
+    /// ルールの評価結果が `input` の変更で変わりうるか
+    pub fn depends_on(&self, input: WatchlistRuleInput) -> bool {
+        match self {
+            WatchlistRule::Sector { .. } | WatchlistRule::MarketSegment { .. } => {
+                input == WatchlistRuleInput::Instruments
+            }
+            WatchlistRule::Screen {
+                min_close,
+                max_close,
+                ..
+            } => match input {
+                WatchlistRuleInput::Instruments => true,
+                WatchlistRuleInput::Bars => min_close.is_some() || max_close.is_some(),
+                WatchlistRuleInput::Holdings => false,
+            },
+            WatchlistRule::Holdings => input == WatchlistRuleInput::Holdings,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct CreateWatchlistRequest {
@@ -8,6 +105,8 @@ pub struct CreateWatchlistRequest {
     // trim 後に空文字列になる入力 (制御文字のみ等) をスキーマレベルで排除する
     #[schema(min_length = 1, pattern = r"\S")]
     pub name: String,
+    /// 銘柄選定ルール (指定するとスマートウォッチリストになる)
+    pub rule: Option<WatchlistRule>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -21,3 +120,167 @@ pub struct AddWatchlistItemRequest {
     #[schema(min_length = 1, pattern = r"\S")]
     pub name: String,
 }
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BatchAddWatchlistItemsRequest {
+    /// 追加する銘柄コード (指定順に末尾へ追加する)
+    #[schema(min_items = 1, max_items = 500)]
+    pub instrument_ids: Vec<String>,
+}
+
+/// 銘柄一括追加の結果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchAddWatchlistItemsResponse {
+    /// 追加した銘柄 (指定順)
+    pub added: Vec<watchlist_items::Model>,
+    /// 既にウォッチリストに存在したため追加しなかった銘柄コード
+    pub conflicts: Vec<String>,
+}
+
+/// ウォッチリスト項目に付けるタグ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WatchlistItemTag {
+    /// タグ名
+    #[schema(min_length = 1, pattern = r"\S")]
+    pub name: String,
+    /// 表示色 (`#RRGGBB` 形式)
+    #[schema(pattern = r"^#[0-9A-Fa-f]{6}$")]
+    pub color: String,
+}
+
+/// フィールドの省略 (None) と null の指定 (Some(None)) を区別してデシリアライズする
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// ウォッチリスト項目の更新リクエスト
+///
+/// 省略したフィールドは変更しない。memo と各価格は `null` を指定するとクリアする。
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateWatchlistItemRequest {
+    /// メモ
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<String>)]
+    pub memo: Option<Option<String>>,
+    /// タグ一覧 (指定した場合は全体を置き換える)
+    pub tags: Option<Vec<WatchlistItemTag>>,
+    /// エントリー価格 (この価格に触れたらアラート)
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+    pub entry_price: Option<Option<Decimal>>,
+    /// 目標価格 (高値がこの価格以上になったらアラート)
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+    pub target_price: Option<Option<Decimal>>,
+    /// 損切り価格 (安値がこの価格以下になったらアラート)
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<f64>, exclusive_minimum = 0)]
+    pub stop_price: Option<Option<Decimal>>,
+}
+
+/// 価格アラートの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceAlertKind {
+    /// エントリー価格に到達した
+    Entry,
+    /// 目標価格に到達した
+    Target,
+    /// 損切り価格に到達した
+    Stop,
+}
+
+/// ウォッチリスト項目の価格閾値に到達したことを示すアラート
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PriceAlert {
+    /// 銘柄コード
+    pub instrument_id: String,
+    /// アラートの種類
+    pub kind: PriceAlertKind,
+    /// 閾値 (entry_price / target_price / stop_price)
+    #[schema(value_type = f64)]
+    pub threshold: Decimal,
+    /// 判定に使った日足のタイムスタンプ
+    pub timestamp: DateTime<Utc>,
+    /// 判定に使った日足の終値
+    #[schema(value_type = f64)]
+    pub close: Decimal,
+}
+
+/// ウォッチリストのエクスポート形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchlistExportFormat {
+    /// [`WatchlistExport`] の JSON
+    #[default]
+    Json,
+    /// `instrument_id,name,memo` ヘッダー付き CSV
+    Csv,
+}
+
+/// ウォッチリストのインポート形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchlistImportFormat {
+    /// [`WatchlistExport`] の JSON
+    Json,
+    /// 銘柄コード列を含むヘッダー付き CSV (エクスポート CSV、証券会社のお気に入り CSV 等)
+    Csv,
+    /// 改行・カンマ区切りの銘柄コード一覧 (TradingView の `TSE:7203` 形式等)
+    Text,
+}
+
+/// エクスポートしたウォッチリスト
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WatchlistExport {
+    /// ウォッチリスト名
+    pub name: String,
+    /// 銘柄一覧 (sort_order 順)
+    pub items: Vec<WatchlistExportItem>,
+}
+
+/// エクスポートしたウォッチリストの 1 銘柄
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WatchlistExportItem {
+    /// 銘柄コード
+    pub instrument_id: String,
+    /// 銘柄名
+    pub name: Option<String>,
+    /// メモ
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ImportWatchlistRequest {
+    /// インポートするファイルの内容
+    #[schema(min_length = 1)]
+    pub content: String,
+    /// インポート形式 (省略時は内容から自動判定する)
+    pub format: Option<WatchlistImportFormat>,
+}
+
+/// ウォッチリストのインポート結果
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportWatchlistResponse {
+    /// 追加した銘柄コード (追加順)
+    pub added: Vec<String>,
+    /// 追加しなかった銘柄とその理由
+    pub skipped: Vec<ImportSkippedItem>,
+}
+
+/// インポートで追加しなかった銘柄
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportSkippedItem {
+    /// 銘柄コード (入力値)
+    pub instrument_id: String,
+    /// 追加しなかった理由
+    pub reason: String,
+}
@@ -0,0 +1,85 @@
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+};
+
+use crate::entities::bar_anomalies;
+use crate::error::AppError;
+use crate::models::{Bar, BarAnomalyReason};
+
+/// 検証に失敗したバーを一括 upsert する
+///
+/// 同じバーが再度検出された場合は値・理由・検出日時を更新する。
+pub async fn upsert_anomalies(
+    db: &DatabaseConnection,
+    anomalies: Vec<(Bar, Vec<BarAnomalyReason>)>,
+) -> Result<(), AppError> {
+    if anomalies.is_empty() {
+        return Ok(());
+    }
+
+    let active_models = anomalies
+        .into_iter()
+        .map(|(bar, reasons)| {
+            Ok(bar_anomalies::ActiveModel {
+                instrument_id: Set(bar.instrument_id),
+                timeframe: Set(bar.timeframe.to_string()),
+                timestamp: Set(bar.timestamp.fixed_offset()),
+                open: Set(bar.open),
+                high: Set(bar.high),
+                low: Set(bar.low),
+                close: Set(bar.close),
+                volume: Set(bar.volume),
+                reasons: Set(serde_json::to_value(reasons).map_err(|e| {
+                    AppError::Internal(format!("failed to serialize anomaly reasons: {e}"))
+                })?),
+                ..Default::default()
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    bar_anomalies::Entity::insert_many(active_models)
+        .on_conflict(
+            OnConflict::columns([
+                bar_anomalies::Column::InstrumentId,
+                bar_anomalies::Column::Timeframe,
+                bar_anomalies::Column::Timestamp,
+            ])
+            .update_columns([
+                bar_anomalies::Column::Open,
+                bar_anomalies::Column::High,
+                bar_anomalies::Column::Low,
+                bar_anomalies::Column::Close,
+                bar_anomalies::Column::Volume,
+                bar_anomalies::Column::Reasons,
+            ])
+            .value(bar_anomalies::Column::DetectedAt, Expr::current_timestamp())
+            .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+
+    Ok(())
+}
+
+/// 隔離されたバーを検出日時の新しい順に取得する
+pub async fn find_anomalies(
+    db: &DatabaseConnection,
+    instrument_id: Option<&str>,
+    limit: u64,
+) -> Result<Vec<bar_anomalies::Model>, AppError> {
+    let mut select = bar_anomalies::Entity::find();
+    if let Some(instrument_id) = instrument_id {
+        select = select.filter(bar_anomalies::Column::InstrumentId.eq(instrument_id));
+    }
+
+    let results = select
+        .order_by_desc(bar_anomalies::Column::DetectedAt)
+        .order_by_asc(bar_anomalies::Column::InstrumentId)
+        .order_by_desc(bar_anomalies::Column::Timestamp)
+        .limit(limit)
+        .all(db)
+        .await?;
+
+    Ok(results)
+}
@@ -1,57 +1,248 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use chrono::{DateTime, FixedOffset};
-use sea_orm::sea_query::OnConflict;
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use futures_util::Stream;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, DatabaseConnectionType,
+    DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect, RuntimeErr, Select, Statement,
+    TransactionTrait,
+};
 
 use crate::entities::bars;
 use crate::error::AppError;
-use crate::models::Bar;
+use crate::models::{Bar, BarOrder};
+use crate::repositories::instruments;
+
+/// bars の 1 行あたりのバインドパラメータ数 (instrument_id, timeframe, timestamp, OHLCV, source)
+const PARAMS_PER_ROW: usize = 9;
+
+/// 1 回の INSERT で書き込む行数
+///
+/// PostgreSQL のバインドパラメータ上限 (65535) に収まる最大の行数とする。
+const UPSERT_BATCH_SIZE: usize = u16::MAX as usize / PARAMS_PER_ROW;
+
+/// 1 回の COPY で書き込む行数
+const COPY_BATCH_SIZE: usize = 50_000;
 
 /// バーデータを一括 upsert する
 ///
 /// 複合 PK (instrument_id, timeframe, timestamp) で重複排除し、
-/// 既存行は OHLCV カラムを更新する。
+/// 既存行は OHLCV カラムと取得元 (`source`) を更新する。取得元が未設定のバーは既存の取得元を残す。書き込んだ銘柄の `bars_updated_at` を更新する。
+/// 大量のデータ (全銘柄の同期やインポート等) は `copy_bars` を使う。
 pub async fn upsert_bars(db: &DatabaseConnection, bars_data: Vec<Bar>) -> Result<(), AppError> {
+    let instrument_ids: BTreeSet<String> = bars_data
+        .iter()
+        .map(|bar| bar.instrument_id.clone())
+        .collect();
+    let mut bars_data = bars_data.into_iter().peekable();
+
+    while bars_data.peek().is_some() {
+        let active_models: Vec<bars::ActiveModel> = bars_data
+            .by_ref()
+            .take(UPSERT_BATCH_SIZE)
+            .map(Into::into)
+            .collect();
+
+        bars::Entity::insert_many(active_models)
+            .on_conflict(
+                OnConflict::columns([
+                    bars::Column::InstrumentId,
+                    bars::Column::Timeframe,
+                    bars::Column::Timestamp,
+                ])
+                .update_columns([
+                    bars::Column::Open,
+                    bars::Column::High,
+                    bars::Column::Low,
+                    bars::Column::Close,
+                    bars::Column::Volume,
+                ])
+                .value(
+                    bars::Column::Source,
+                    Expr::cust("COALESCE(EXCLUDED.source, bars.source)"),
+                )
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+    }
+
+    instruments::touch_bars_updated_at(db, instrument_ids.into_iter().collect()).await?;
+
+    Ok(())
+}
+
+/// `copy_bars` の書き込み結果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CopyStats {
+    /// 入力したバーの数
+    pub total_rows: usize,
+    /// 書き込んだ (挿入または更新した) 行数
+    pub written_rows: u64,
+    /// 書き込みにかかった時間
+    pub elapsed: Duration,
+}
+
+impl CopyStats {
+    /// 入力したバーの数あたりのスループット (行/秒)
+    pub fn rows_per_sec(&self) -> f64 {
+        (self.total_rows as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)).round()
+    }
+}
+
+/// COPY でバーデータを一括 upsert する
+///
+/// バッチごとにトランザクション内で一時テーブルに COPY し、`bars` にマージする。
+/// `upsert_bars` と同じく既存行は OHLCV と取得元を更新し (取得元が未設定のバーは既存の取得元を残す)、
+/// 値が変わらない行は書き換えない。
+/// 入力内で PK が重複する行はいずれか 1 行のみを書き込む。
+/// 1 行以上書き込んだ銘柄の `bars_updated_at` を更新する (値が変わらない再取り込みでは更新しない)。
+/// 書き込んだ行数とスループットを返す。
+///
+/// sqlx のコネクションを直接使うため、PostgreSQL 以外 (MockDatabase 等) の接続ではエラーを返す。
+pub async fn copy_bars(
+    db: &DatabaseConnection,
+    bars_data: Vec<Bar>,
+) -> Result<CopyStats, AppError> {
     if bars_data.is_empty() {
-        return Ok(());
+        return Ok(CopyStats::default());
     }
 
-    let active_models: Vec<bars::ActiveModel> = bars_data.into_iter().map(Into::into).collect();
+    let DatabaseConnectionType::SqlxPostgresPoolConnection(_) = &db.inner else {
+        return Err(AppError::Internal(
+            "copy_bars requires a PostgreSQL connection".to_string(),
+        ));
+    };
+    let pool = db.get_postgres_connection_pool();
+    let started = Instant::now();
+    let total_rows = bars_data.len();
+    let mut written_rows = 0;
 
-    bars::Entity::insert_many(active_models)
-        .on_conflict(
-            OnConflict::columns([
-                bars::Column::InstrumentId,
-                bars::Column::Timeframe,
-                bars::Column::Timestamp,
-            ])
-            .update_columns([
-                bars::Column::Open,
-                bars::Column::High,
-                bars::Column::Low,
-                bars::Column::Close,
-                bars::Column::Volume,
-            ])
-            .to_owned(),
+    for chunk in bars_data.chunks(COPY_BATCH_SIZE) {
+        let mut tx = pool.begin().await.map_err(sqlx_error)?;
+
+        sqlx::query("CREATE TEMP TABLE bars_staging (LIKE bars INCLUDING DEFAULTS) ON COMMIT DROP")
+            .execute(&mut *tx)
+            .await
+            .map_err(sqlx_error)?;
+
+        let mut copy = tx
+            .copy_in_raw(
+                "COPY bars_staging (instrument_id, timeframe, timestamp, open, high, low, close, volume, source) \
+                 FROM STDIN WITH (FORMAT csv)",
+            )
+            .await
+            .map_err(sqlx_error)?;
+        copy.send(encode_copy_rows(chunk)?)
+            .await
+            .map_err(sqlx_error)?;
+        copy.finish().await.map_err(sqlx_error)?;
+
+        // 実際に書き込んだ行の銘柄だけ bars_updated_at を更新する
+        let written: i64 = sqlx::query_scalar(
+            "WITH written AS ( \
+                 INSERT INTO bars (instrument_id, timeframe, timestamp, open, high, low, close, volume, source) \
+                 SELECT DISTINCT ON (instrument_id, timeframe, timestamp) \
+                     instrument_id, timeframe, timestamp, open, high, low, close, volume, source \
+                 FROM bars_staging \
+                 ON CONFLICT (instrument_id, timeframe, timestamp) DO UPDATE SET \
+                     open = EXCLUDED.open, high = EXCLUDED.high, low = EXCLUDED.low, \
+                     close = EXCLUDED.close, volume = EXCLUDED.volume, \
+                     source = COALESCE(EXCLUDED.source, bars.source) \
+                 WHERE (bars.open, bars.high, bars.low, bars.close, bars.volume, bars.source) \
+                     IS DISTINCT FROM \
+                     (EXCLUDED.open, EXCLUDED.high, EXCLUDED.low, EXCLUDED.close, EXCLUDED.volume, \
+                      COALESCE(EXCLUDED.source, bars.source)) \
+                 RETURNING instrument_id \
+             ), touched AS ( \
+                 UPDATE instruments SET bars_updated_at = now() \
+                 WHERE id IN (SELECT instrument_id FROM written) \
+             ) \
+             SELECT count(*) FROM written",
         )
-        .exec_without_returning(db)
-        .await?;
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(sqlx_error)?;
 
-    Ok(())
+        tx.commit().await.map_err(sqlx_error)?;
+        written_rows += written as u64;
+    }
+
+    let stats = CopyStats {
+        total_rows,
+        written_rows,
+        elapsed: started.elapsed(),
+    };
+    tracing::info!(
+        total_rows,
+        written_rows,
+        elapsed_ms = stats.elapsed.as_millis(),
+        rows_per_sec = stats.rows_per_sec(),
+        "COPY によるバーデータの書き込みが完了しました"
+    );
+
+    Ok(stats)
+}
+
+/// COPY に送る CSV を組み立てる
+///
+/// 取得元が未設定のバーは空欄 (CSV 形式の COPY では NULL) として送る。
+fn encode_copy_rows(chunk: &[Bar]) -> Result<Vec<u8>, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for bar in chunk {
+        writer
+            .write_record([
+                bar.instrument_id.clone(),
+                bar.timeframe.to_string(),
+                bar.timestamp.to_rfc3339(),
+                bar.open.to_string(),
+                bar.high.to_string(),
+                bar.low.to_string(),
+                bar.close.to_string(),
+                bar.volume.to_string(),
+                bar.source.clone().unwrap_or_default(),
+            ])
+            .map_err(|e| AppError::Internal(format!("failed to encode bars for COPY: {e}")))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("failed to encode bars for COPY: {e}")))
+}
+
+fn sqlx_error(e: sqlx::Error) -> AppError {
+    AppError::Database(DbErr::Exec(RuntimeErr::SqlxError(Arc::new(e))))
 }
 
 /// バーデータの検索条件
+#[derive(Default)]
 pub struct BarsQuery {
     pub instrument_id: String,
     pub timeframe: String,
     pub from: Option<DateTime<FixedOffset>>,
     pub to: Option<DateTime<FixedOffset>>,
+    /// 取得件数の上限 (None の場合は全件)
+    pub limit: Option<u64>,
+    /// 前のページの `next_cursor`。このタイムスタンプより後 (desc の場合は前) のバーを返す
+    pub cursor: Option<DateTime<FixedOffset>>,
+    /// タイムスタンプの並び順
+    pub order: BarOrder,
+}
+
+/// 条件に一致するバーデータの 1 ページ
+pub struct BarsPage {
+    pub bars: Vec<bars::Model>,
+    /// 続きのページがある場合、次のリクエストの cursor に指定する値
+    pub next_cursor: Option<DateTime<FixedOffset>>,
 }
 
 /// 条件に一致するバーデータを取得する
-pub async fn find_bars(
-    db: &DatabaseConnection,
-    query: BarsQuery,
-) -> Result<Vec<bars::Model>, AppError> {
+///
+/// タイムスタンプによるキーセットページネーションを行う。
+/// 続きの有無を判定するため、limit + 1 件を取得する。
+pub async fn find_bars(db: &DatabaseConnection, query: BarsQuery) -> Result<BarsPage, AppError> {
     let mut select = bars::Entity::find()
         .filter(bars::Column::InstrumentId.eq(&query.instrument_id))
         .filter(bars::Column::Timeframe.eq(&query.timeframe));
@@ -64,11 +255,166 @@ pub async fn find_bars(
         select = select.filter(bars::Column::Timestamp.lte(to));
     }
 
-    let results = select.order_by_asc(bars::Column::Timestamp).all(db).await?;
+    select = match (query.order, query.cursor) {
+        (BarOrder::Asc, Some(cursor)) => select.filter(bars::Column::Timestamp.gt(cursor)),
+        (BarOrder::Desc, Some(cursor)) => select.filter(bars::Column::Timestamp.lt(cursor)),
+        (_, None) => select,
+    };
+
+    select = match query.order {
+        BarOrder::Asc => select.order_by_asc(bars::Column::Timestamp),
+        BarOrder::Desc => select.order_by_desc(bars::Column::Timestamp),
+    };
+
+    let mut results = select
+        .limit(query.limit.map(|limit| limit + 1))
+        .all(db)
+        .await?;
+
+    let next_cursor = match query.limit {
+        Some(limit) if results.len() as u64 > limit => {
+            results.truncate(limit as usize);
+            results.last().map(|bar| bar.timestamp)
+        }
+        _ => None,
+    };
+
+    Ok(BarsPage {
+        bars: results,
+        next_cursor,
+    })
+}
+
+/// 指定銘柄・時間足のうち、指定したタイムスタンプのバーデータを取得する
+pub async fn find_bars_at(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    timeframe: &str,
+    timestamps: Vec<DateTime<FixedOffset>>,
+) -> Result<Vec<bars::Model>, AppError> {
+    if timestamps.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let results = bars::Entity::find()
+        .filter(bars::Column::InstrumentId.eq(instrument_id))
+        .filter(bars::Column::Timeframe.eq(timeframe))
+        .filter(bars::Column::Timestamp.is_in(timestamps))
+        .all(db)
+        .await?;
+
+    Ok(results)
+}
+
+/// 複数銘柄それぞれの最新のバーデータを 1 回のクエリで取得する
+///
+/// バーデータのない銘柄は結果に含まない。結果は銘柄コードの昇順に並ぶ。
+pub async fn find_latest_bars(
+    db: &DatabaseConnection,
+    instrument_ids: &[String],
+    timeframe: &str,
+) -> Result<Vec<bars::Model>, AppError> {
+    if instrument_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let results = bars::Entity::find()
+        .distinct_on([bars::Column::InstrumentId])
+        .filter(bars::Column::InstrumentId.is_in(instrument_ids))
+        .filter(bars::Column::Timeframe.eq(timeframe))
+        .order_by_asc(bars::Column::InstrumentId)
+        .order_by_desc(bars::Column::Timestamp)
+        .all(db)
+        .await?;
+
+    Ok(results)
+}
+
+/// 複数銘柄のバーデータを検索する SELECT を組み立てる
+///
+/// 結果は銘柄コード・タイムスタンプの昇順に並ぶ。
+fn select_bars_for_instruments(
+    instrument_ids: &[String],
+    timeframe: &str,
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+) -> Select<bars::Entity> {
+    let mut select = bars::Entity::find()
+        .filter(bars::Column::InstrumentId.is_in(instrument_ids))
+        .filter(bars::Column::Timeframe.eq(timeframe));
+
+    if let Some(from) = from {
+        select = select.filter(bars::Column::Timestamp.gte(from));
+    }
+
+    if let Some(to) = to {
+        select = select.filter(bars::Column::Timestamp.lte(to));
+    }
+
+    select
+        .order_by_asc(bars::Column::InstrumentId)
+        .order_by_asc(bars::Column::Timestamp)
+}
+
+/// 複数銘柄のバーデータを取得する
+///
+/// 結果は銘柄コード・タイムスタンプの昇順に並ぶ。
+pub async fn find_bars_for_instruments(
+    db: &DatabaseConnection,
+    instrument_ids: &[String],
+    timeframe: &str,
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+) -> Result<Vec<bars::Model>, AppError> {
+    let results = select_bars_for_instruments(instrument_ids, timeframe, from, to)
+        .all(db)
+        .await?;
 
     Ok(results)
 }
 
+/// 複数銘柄のバーデータを 1 行ずつ読み込むストリームを返す
+///
+/// 全件をメモリに載せずに処理するためのもので、エクスポート等の大量読み込みに使う。
+/// 結果は銘柄コード・タイムスタンプの昇順に並ぶ。
+pub async fn stream_bars_for_instruments<'a>(
+    db: &'a DatabaseConnection,
+    instrument_ids: &[String],
+    timeframe: &str,
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+) -> Result<impl Stream<Item = Result<bars::Model, DbErr>> + Send + 'a, AppError> {
+    let stream = select_bars_for_instruments(instrument_ids, timeframe, from, to)
+        .stream(db)
+        .await?;
+
+    Ok(stream)
+}
+
+/// bars の圧縮ポリシーを、`days` 日より古いチャンクを圧縮するよう設定し直す
+///
+/// マイグレーションで設定した初期値を環境ごとに変えるため、起動時に呼ぶ。
+pub async fn set_compress_after(db: &DatabaseConnection, days: i32) -> Result<(), AppError> {
+    if days <= 0 {
+        return Err(AppError::Config(format!(
+            "compress_after must be greater than 0 days: {days}"
+        )));
+    }
+
+    let txn = db.begin().await?;
+    txn.execute_unprepared("SELECT remove_compression_policy('bars', if_exists => true)")
+        .await?;
+    txn.execute_raw(Statement::from_sql_and_values(
+        DatabaseBackend::Postgres,
+        "SELECT add_compression_policy('bars', make_interval(days => $1))",
+        [days.into()],
+    ))
+    .await?;
+    txn.commit().await?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{NaiveDate, TimeZone, Utc};
@@ -89,6 +435,8 @@ mod tests {
             name: Set(format!("Test {id}")),
             market: Set("TSE".to_string()),
             sector: Set(None),
+            market_segment: Set(None),
+            bars_updated_at: Set(None),
         })
         .on_conflict(
             OnConflict::column(instruments::Column::Id)
@@ -115,6 +463,7 @@ mod tests {
             low: Decimal::new(close - 10, 0),
             close: Decimal::new(close, 0),
             volume: 1000,
+            source: None,
         }
     }
 
@@ -143,8 +492,9 @@ mod tests {
             timeframe: "1d".to_string(),
             from: None,
             to: None,
+            ..Default::default()
         };
-        let result = find_bars(&db, query).await.expect("find failed");
+        let result = find_bars(&db, query).await.expect("find failed").bars;
         assert_eq!(result.len(), 2);
     }
 
@@ -170,12 +520,241 @@ mod tests {
             timeframe: "1d".to_string(),
             from: None,
             to: None,
+            ..Default::default()
         };
-        let result = find_bars(&db, query).await.expect("find failed");
+        let result = find_bars(&db, query).await.expect("find failed").bars;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].close, Decimal::new(200, 0));
     }
 
+    #[sqlx::test(migrations = false)]
+    async fn upsert_bars_splits_large_input_into_batches(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let start = NaiveDate::from_ymd_opt(2000, 1, 1).expect("invalid date");
+        let bars: Vec<Bar> = start
+            .iter_days()
+            .take(UPSERT_BATCH_SIZE * 2 + 1)
+            .map(|date| make_test_bar("7203", date, 100))
+            .collect();
+
+        upsert_bars(&db, bars).await.expect("upsert failed");
+
+        let query = BarsQuery {
+            instrument_id: "7203".to_string(),
+            timeframe: "1d".to_string(),
+            ..Default::default()
+        };
+        let result = find_bars(&db, query).await.expect("find failed").bars;
+        assert_eq!(result.len(), UPSERT_BATCH_SIZE * 2 + 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn copy_bars_merges_into_existing_records(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        insert_test_instrument(&db, "6758").await;
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        upsert_bars(
+            &db,
+            vec![
+                make_test_bar("7203", date(6), 100),
+                make_test_bar("7203", date(7), 105),
+            ],
+        )
+        .await
+        .expect("upsert failed");
+
+        let written = copy_bars(
+            &db,
+            vec![
+                // 変更なし
+                make_test_bar("7203", date(6), 100),
+                // 更新
+                make_test_bar("7203", date(7), 110),
+                // 新規
+                make_test_bar("7203", date(8), 120),
+                make_test_bar("6758", date(8), 200),
+                // 入力内の重複
+                make_test_bar("6758", date(8), 200),
+            ],
+        )
+        .await
+        .expect("copy failed")
+        .written_rows;
+
+        assert_eq!(written, 3);
+        let query = BarsQuery {
+            instrument_id: "7203".to_string(),
+            timeframe: "1d".to_string(),
+            ..Default::default()
+        };
+        let closes: Vec<Decimal> = find_bars(&db, query)
+            .await
+            .expect("find failed")
+            .bars
+            .into_iter()
+            .map(|bar| bar.close)
+            .collect();
+        assert_eq!(
+            closes,
+            vec![
+                Decimal::new(100, 0),
+                Decimal::new(110, 0),
+                Decimal::new(120, 0)
+            ]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn copy_bars_touches_only_instruments_with_written_rows(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        insert_test_instrument(&db, "6758").await;
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        copy_bars(
+            &db,
+            vec![
+                make_test_bar("7203", date(6), 100),
+                make_test_bar("6758", date(6), 200),
+            ],
+        )
+        .await
+        .expect("copy v1 failed");
+        let bars_updated_at = |id: &'static str| {
+            let db = db.clone();
+            async move {
+                instruments::Entity::find_by_id(id)
+                    .one(&db)
+                    .await
+                    .expect("find failed")
+                    .and_then(|instrument| instrument.bars_updated_at)
+            }
+        };
+        let touched_7203 = bars_updated_at("7203").await;
+        let touched_6758 = bars_updated_at("6758").await;
+        assert!(touched_7203.is_some());
+
+        // 7203 は値が変わらないため書き込まず、bars_updated_at も更新しない
+        let written = copy_bars(
+            &db,
+            vec![
+                make_test_bar("7203", date(6), 100),
+                make_test_bar("6758", date(6), 210),
+            ],
+        )
+        .await
+        .expect("copy v2 failed")
+        .written_rows;
+
+        assert_eq!(written, 1);
+        assert_eq!(bars_updated_at("7203").await, touched_7203);
+        assert!(bars_updated_at("6758").await > touched_6758);
+    }
+
+    #[tokio::test]
+    async fn copy_bars_without_postgres_connection_returns_error() {
+        let db = sea_orm::MockDatabase::new(sea_orm::DatabaseBackend::Postgres).into_connection();
+        let bar = make_test_bar(
+            "7203",
+            NaiveDate::from_ymd_opt(2025, 1, 6).expect("invalid date"),
+            100,
+        );
+
+        let result = copy_bars(&db, vec![bar]).await;
+
+        assert!(matches!(result, Err(AppError::Internal(_))));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn upsert_and_copy_bars_record_source(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        let with_source = |day, source: Option<&str>| Bar {
+            source: source.map(str::to_string),
+            ..make_test_bar("7203", date(day), 100)
+        };
+        upsert_bars(
+            &db,
+            vec![with_source(6, Some("jquants")), with_source(7, None)],
+        )
+        .await
+        .expect("upsert failed");
+        // 値が同じでも取得元が変われば書き換える
+        let written = copy_bars(
+            &db,
+            vec![with_source(7, Some("synthetic")), with_source(8, None)],
+        )
+        .await
+        .expect("copy failed")
+        .written_rows;
+        assert_eq!(written, 2);
+        // 取得元が未設定のバーで上書きしても、記録済みの取得元は残す
+        upsert_bars(&db, vec![with_source(6, None)])
+            .await
+            .expect("upsert failed");
+        let written = copy_bars(&db, vec![with_source(7, None)])
+            .await
+            .expect("copy failed")
+            .written_rows;
+        assert_eq!(written, 0);
+
+        let query = BarsQuery {
+            instrument_id: "7203".to_string(),
+            timeframe: "1d".to_string(),
+            ..Default::default()
+        };
+        let sources: Vec<Option<String>> = find_bars(&db, query)
+            .await
+            .expect("find failed")
+            .bars
+            .into_iter()
+            .map(|bar| bar.source)
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                Some("jquants".to_string()),
+                Some("synthetic".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn bars_accept_every_timeframe(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let date = NaiveDate::from_ymd_opt(2025, 1, 6).expect("invalid date");
+        let bars = Timeframe::ALL
+            .into_iter()
+            .map(|timeframe| Bar {
+                timeframe,
+                ..make_test_bar("7203", date, 100)
+            })
+            .collect();
+
+        // DB の CHECK 制約と Timeframe の値が一致している
+        upsert_bars(&db, bars).await.expect("upsert failed");
+        let saved: Vec<Timeframe> = bars::Entity::find()
+            .all(&db)
+            .await
+            .expect("find failed")
+            .into_iter()
+            .map(|model| Bar::from(model).timeframe)
+            .collect();
+        assert_eq!(saved.len(), Timeframe::ALL.len());
+        for timeframe in Timeframe::ALL {
+            assert!(saved.contains(&timeframe), "{timeframe} was not saved");
+        }
+    }
+
     #[sqlx::test(migrations = false)]
     async fn upsert_bars_with_empty_vec_is_noop(pool: PgPool) {
         let db = create_test_db(pool).await;
@@ -220,9 +799,181 @@ mod tests {
             timeframe: "1d".to_string(),
             from: from_dt,
             to: to_dt,
+            ..Default::default()
         };
-        let result = find_bars(&db, query).await.expect("find failed");
+        let result = find_bars(&db, query).await.expect("find failed").bars;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].close, Decimal::new(105, 0));
     }
+
+    #[sqlx::test(migrations = false)]
+    async fn find_bars_paginates_with_cursor(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let bars = (6..=10)
+            .map(|day| {
+                make_test_bar(
+                    "7203",
+                    NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date"),
+                    100 + i64::from(day),
+                )
+            })
+            .collect();
+        upsert_bars(&db, bars).await.expect("upsert failed");
+
+        for (order, expected_closes) in [
+            (
+                BarOrder::Asc,
+                vec![vec![106, 107], vec![108, 109], vec![110]],
+            ),
+            (
+                BarOrder::Desc,
+                vec![vec![110, 109], vec![108, 107], vec![106]],
+            ),
+        ] {
+            let mut pages = Vec::new();
+            let mut cursor = None;
+            loop {
+                let query = BarsQuery {
+                    instrument_id: "7203".to_string(),
+                    timeframe: "1d".to_string(),
+                    limit: Some(2),
+                    cursor,
+                    order,
+                    ..Default::default()
+                };
+                let page = find_bars(&db, query).await.expect("find failed");
+                pages.push(
+                    page.bars
+                        .iter()
+                        .map(|bar| bar.close.mantissa() as i64)
+                        .collect::<Vec<_>>(),
+                );
+                cursor = page.next_cursor;
+                if cursor.is_none() {
+                    break;
+                }
+            }
+
+            assert_eq!(pages, expected_closes, "order {order:?}");
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn find_latest_bars_returns_latest_bar_per_instrument(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        for id in ["7203", "6758", "9984"] {
+            insert_test_instrument(&db, id).await;
+        }
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).expect("invalid date");
+        upsert_bars(
+            &db,
+            vec![
+                make_test_bar("7203", date(6), 100),
+                make_test_bar("7203", date(7), 110),
+                make_test_bar("6758", date(6), 200),
+            ],
+        )
+        .await
+        .expect("upsert failed");
+
+        let ids = ["7203", "6758", "9984"].map(String::from);
+        let result = find_latest_bars(&db, &ids, "1d")
+            .await
+            .expect("find failed");
+
+        // 日足のない銘柄は含まない
+        assert_eq!(
+            result
+                .iter()
+                .map(|bar| (bar.instrument_id.as_str(), bar.close))
+                .collect::<Vec<_>>(),
+            vec![
+                ("6758", Decimal::new(200, 0)),
+                ("7203", Decimal::new(110, 0))
+            ]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn retention_job_keeps_daily_bars(pool: PgPool) {
+        use crate::entities::bar_retention_policies;
+
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        let date = NaiveDate::from_ymd_opt(2000, 1, 4).expect("invalid date");
+        upsert_bars(&db, vec![make_test_bar("7203", date, 100)])
+            .await
+            .expect("upsert failed");
+
+        // 日足には保持期間を設定しない
+        let policies = bar_retention_policies::Entity::find()
+            .all(&db)
+            .await
+            .expect("find policies failed");
+        assert!(!policies.is_empty());
+        assert!(policies.iter().all(|p| p.timeframe != "1d"));
+
+        db.execute_unprepared("CALL bars_apply_retention(0, NULL)")
+            .await
+            .expect("retention job failed");
+
+        let query = BarsQuery {
+            instrument_id: "7203".to_string(),
+            timeframe: "1d".to_string(),
+            ..Default::default()
+        };
+        let result = find_bars(&db, query).await.expect("find failed").bars;
+        assert_eq!(result.len(), 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn retention_job_deletes_expired_intraday_bars(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+        // 1m の保持期間は 30 日。日足は期間外でも削除しない
+        db.execute_unprepared(
+            "INSERT INTO bars (instrument_id, timeframe, timestamp, open, high, low, close, volume) VALUES \
+             ('7203', '1m', now() - INTERVAL '31 days', 100, 100, 100, 100, 1), \
+             ('7203', '1m', now() - INTERVAL '1 day', 100, 100, 100, 100, 1), \
+             ('7203', '1d', now() - INTERVAL '31 days', 100, 100, 100, 100, 1)",
+        )
+        .await
+        .expect("insert failed");
+
+        db.execute_unprepared("CALL bars_apply_retention(0, NULL)")
+            .await
+            .expect("retention job failed");
+
+        let mut remaining: Vec<(String, bool)> = bars::Entity::find()
+            .select_only()
+            .column(bars::Column::Timeframe)
+            .column_as(
+                sea_orm::sea_query::Expr::cust("timestamp > now() - INTERVAL '30 days'"),
+                "recent",
+            )
+            .into_tuple()
+            .all(&db)
+            .await
+            .expect("find failed");
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![("1d".to_string(), false), ("1m".to_string(), true)]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn set_compress_after_rejects_non_positive_days(pool: PgPool) {
+        let db = create_test_db(pool).await;
+
+        set_compress_after(&db, 30)
+            .await
+            .expect("set_compress_after failed");
+        let result = set_compress_after(&db, 0).await;
+
+        assert!(matches!(result, Err(AppError::Config(_))));
+    }
 }
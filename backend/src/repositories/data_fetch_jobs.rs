@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set};
+use uuid::Uuid;
+
+use crate::data_provider::DateRange;
+use crate::entities::data_fetch_jobs;
+use crate::error::AppError;
+use crate::models::FetchJobStatus;
+
+/// 終了を記録できなかった実行中のジョブに記録するエラーメッセージ
+pub const ABANDONED_ERROR: &str = "job was abandoned before it finished";
+
+/// 実行中のジョブを作成する (ID と作成日時は DB が採番する)
+///
+/// 同じ銘柄・期間の実行中のジョブがすでにある場合は作成せずに None を返す
+/// (実行中のジョブは部分ユニークインデックスで銘柄・期間ごとに 1 つに制限している)。
+pub async fn insert_running(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<Option<data_fetch_jobs::Model>, AppError> {
+    let job = data_fetch_jobs::ActiveModel {
+        instrument_id: Set(instrument_id.to_string()),
+        from_date: Set(range.from),
+        to_date: Set(range.to),
+        status: Set(FetchJobStatus::Running.as_str().to_string()),
+        ..Default::default()
+    };
+
+    let inserted = data_fetch_jobs::Entity::insert(job)
+        .on_conflict(
+            OnConflict::columns([
+                data_fetch_jobs::Column::InstrumentId,
+                data_fetch_jobs::Column::FromDate,
+                data_fetch_jobs::Column::ToDate,
+            ])
+            .target_and_where(data_fetch_jobs::Column::Status.eq(FetchJobStatus::Running.as_str()))
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_with_returning_many(db)
+        .await?;
+
+    Ok(inserted.into_iter().next())
+}
+
+/// 指定銘柄の `range` 全体を取得している実行中のジョブのうち、最も新しいものを取得する
+pub async fn find_running_covering(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<Option<data_fetch_jobs::Model>, AppError> {
+    data_fetch_jobs::Entity::find()
+        .filter(data_fetch_jobs::Column::InstrumentId.eq(instrument_id))
+        .filter(data_fetch_jobs::Column::Status.eq(FetchJobStatus::Running.as_str()))
+        .filter(data_fetch_jobs::Column::FromDate.lte(range.from))
+        .filter(data_fetch_jobs::Column::ToDate.gte(range.to))
+        .order_by_desc(data_fetch_jobs::Column::CreatedAt)
+        .one(db)
+        .await
+        .map_err(Into::into)
+}
+
+/// 指定銘柄・期間のジョブのうち、最も新しいものを取得する (状態は問わない)
+pub async fn find_latest(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<Option<data_fetch_jobs::Model>, AppError> {
+    data_fetch_jobs::Entity::find()
+        .filter(data_fetch_jobs::Column::InstrumentId.eq(instrument_id))
+        .filter(data_fetch_jobs::Column::FromDate.eq(range.from))
+        .filter(data_fetch_jobs::Column::ToDate.eq(range.to))
+        .order_by_desc(data_fetch_jobs::Column::CreatedAt)
+        .one(db)
+        .await
+        .map_err(Into::into)
+}
+
+/// `finished_after` より後に成功したジョブが取得した期間のうち、`range` と重なるものを取得する
+pub async fn find_succeeded_ranges(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    range: &DateRange,
+    finished_after: DateTime<Utc>,
+) -> Result<Vec<DateRange>, AppError> {
+    let ranges = data_fetch_jobs::Entity::find()
+        .filter(data_fetch_jobs::Column::InstrumentId.eq(instrument_id))
+        .filter(data_fetch_jobs::Column::Status.eq(FetchJobStatus::Succeeded.as_str()))
+        .filter(data_fetch_jobs::Column::FromDate.lte(range.to))
+        .filter(data_fetch_jobs::Column::ToDate.gte(range.from))
+        .filter(data_fetch_jobs::Column::FinishedAt.gt(finished_after.fixed_offset()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|job| DateRange {
+            from: job.from_date,
+            to: job.to_date,
+        })
+        .collect();
+
+    Ok(ranges)
+}
+
+/// `created_before` より前に作成された実行中のジョブを失敗として記録する
+///
+/// プロセスの停止等で終了を記録できなかったジョブが、同じ期間の取得を妨げないようにする。
+/// 失敗として記録したジョブの数を返す。
+pub async fn fail_stale(
+    db: &DatabaseConnection,
+    created_before: DateTime<Utc>,
+) -> Result<u64, AppError> {
+    let result = data_fetch_jobs::Entity::update_many()
+        .col_expr(
+            data_fetch_jobs::Column::Status,
+            Expr::value(FetchJobStatus::Failed.as_str()),
+        )
+        .col_expr(data_fetch_jobs::Column::Error, Expr::value(ABANDONED_ERROR))
+        .col_expr(
+            data_fetch_jobs::Column::FinishedAt,
+            Expr::current_timestamp(),
+        )
+        .filter(data_fetch_jobs::Column::Status.eq(FetchJobStatus::Running.as_str()))
+        .filter(data_fetch_jobs::Column::CreatedAt.lt(created_before.fixed_offset()))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
+
+/// ジョブの終了を記録する
+pub async fn finish(
+    db: &DatabaseConnection,
+    id: Uuid,
+    status: FetchJobStatus,
+    bar_count: Option<i32>,
+    error: Option<String>,
+) -> Result<(), AppError> {
+    data_fetch_jobs::Entity::update_many()
+        .col_expr(
+            data_fetch_jobs::Column::Status,
+            Expr::value(status.as_str()),
+        )
+        .col_expr(data_fetch_jobs::Column::BarCount, Expr::value(bar_count))
+        .col_expr(data_fetch_jobs::Column::Error, Expr::value(error))
+        .col_expr(
+            data_fetch_jobs::Column::FinishedAt,
+            Expr::current_timestamp(),
+        )
+        .filter(data_fetch_jobs::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+use crate::data_provider::DateRange;
+use crate::entities::data_provider_cache;
+use crate::error::AppError;
+use crate::models::Timeframe;
+
+/// 指定銘柄・期間に重なる日足の取得済み期間の記録を削除する
+///
+/// 欠損の修復等で、記録済みの期間も内側のプロバイダーから取得し直すために使う。
+/// 削除した記録の数を返す。
+pub async fn invalidate_daily(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<u64, AppError> {
+    let result = data_provider_cache::Entity::delete_many()
+        .filter(data_provider_cache::Column::InstrumentId.eq(instrument_id))
+        .filter(data_provider_cache::Column::Timeframe.eq(Timeframe::Daily.to_string()))
+        .filter(data_provider_cache::Column::FromDate.lte(range.to))
+        .filter(data_provider_cache::Column::ToDate.gte(range.from))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, Utc};
+    use sea_orm::{ActiveModelTrait, ConnectionTrait, QueryOrder, QuerySelect, Set};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::testing::create_test_db;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    /// 取得済み期間を記録する (`fetched_days_ago` 日前に取得したものとする)
+    async fn insert_entry(db: &DatabaseConnection, from: u32, to: u32, fetched_days_ago: i64) {
+        data_provider_cache::ActiveModel {
+            instrument_id: Set("7203".to_string()),
+            timeframe: Set(Timeframe::Daily.to_string()),
+            from_date: Set(date(from)),
+            to_date: Set(date(to)),
+            bars: Set(serde_json::json!([])),
+            fetched_at: Set((Utc::now() - Duration::days(fetched_days_ago)).fixed_offset()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn entry_ranges(db: &DatabaseConnection) -> Vec<(NaiveDate, NaiveDate)> {
+        data_provider_cache::Entity::find()
+            .select_only()
+            .column(data_provider_cache::Column::FromDate)
+            .column(data_provider_cache::Column::ToDate)
+            .order_by_asc(data_provider_cache::Column::FromDate)
+            .into_tuple()
+            .all(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn invalidate_daily_deletes_overlapping_entries(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_entry(&db, 6, 8, 0).await;
+        insert_entry(&db, 9, 10, 0).await;
+        insert_entry(&db, 14, 17, 0).await;
+
+        let deleted = invalidate_daily(
+            &db,
+            "7203",
+            &DateRange {
+                from: date(8),
+                to: date(9),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(deleted, 2);
+        assert_eq!(entry_ranges(&db).await, vec![(date(14), date(17))]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn prune_job_deletes_old_entries(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_entry(&db, 6, 10, 31).await;
+        insert_entry(&db, 14, 17, 29).await;
+
+        db.execute_unprepared("CALL data_provider_cache_prune(0, '{\"max_age_days\": 30}')")
+            .await
+            .unwrap();
+
+        assert_eq!(entry_ranges(&db).await, vec![(date(14), date(17))]);
+    }
+}
@@ -0,0 +1,55 @@
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, Set};
+
+use crate::entities::instruments;
+use crate::error::AppError;
+
+/// 銘柄が存在しない場合は自動作成する
+///
+/// 既存の銘柄情報は上書きしない。
+pub async fn ensure_exists(
+    db: &impl ConnectionTrait,
+    instrument_id: &str,
+    name: String,
+) -> Result<(), AppError> {
+    let instrument_model = instruments::ActiveModel {
+        id: Set(instrument_id.to_string()),
+        name: Set(name),
+        market: Set("TSE".to_string()),
+        sector: Set(None),
+        market_segment: Set(None),
+        bars_updated_at: Set(None),
+    };
+
+    instruments::Entity::insert(instrument_model)
+        .on_conflict(
+            OnConflict::column(instruments::Column::Id)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+
+    Ok(())
+}
+
+/// 銘柄のバーデータ更新日時を現在時刻にする
+pub async fn touch_bars_updated_at(
+    db: &impl ConnectionTrait,
+    instrument_ids: Vec<String>,
+) -> Result<(), AppError> {
+    if instrument_ids.is_empty() {
+        return Ok(());
+    }
+
+    instruments::Entity::update_many()
+        .col_expr(
+            instruments::Column::BarsUpdatedAt,
+            Expr::current_timestamp(),
+        )
+        .filter(instruments::Column::Id.is_in(instrument_ids))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
@@ -1 +1,6 @@
+pub mod bar_anomalies;
 pub mod bars;
+pub mod data_fetch_jobs;
+pub mod data_provider_cache;
+pub mod instruments;
+pub mod watchlist_items;
@@ -0,0 +1,29 @@
+use sea_orm::{ConnectionTrait, DatabaseBackend, FromQueryResult, Statement};
+use uuid::Uuid;
+
+use crate::entities::watchlist_items;
+use crate::error::AppError;
+
+/// ウォッチリストの末尾に銘柄を追加する
+///
+/// sort_order はサブクエリで既存の最大値 + 1 を算出し、INSERT をアトミックに実行する。
+/// 既にウォッチリストに存在する銘柄の場合は何もせず None を返す。
+/// 銘柄 (instruments) は事前に作成しておく必要がある。
+pub async fn insert_item(
+    db: &impl ConnectionTrait,
+    watchlist_id: Uuid,
+    instrument_id: &str,
+    memo: Option<String>,
+) -> Result<Option<watchlist_items::Model>, AppError> {
+    let row = db
+        .query_one_raw(Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            "INSERT INTO watchlist_items (watchlist_id, instrument_id, sort_order, memo) VALUES ($1, $2, COALESCE((SELECT MAX(sort_order) FROM watchlist_items WHERE watchlist_id = $1), -1) + 1, $3) ON CONFLICT (watchlist_id, instrument_id) DO NOTHING RETURNING watchlist_id, instrument_id, sort_order, added_at, memo, tags, entry_price, target_price, stop_price",
+            [watchlist_id.into(), instrument_id.into(), memo.into()],
+        ))
+        .await?;
+
+    row.map(|row| watchlist_items::Model::from_query_result(&row, ""))
+        .transpose()
+        .map_err(Into::into)
+}
@@ -4,8 +4,12 @@
 //! OpenAPI スキーマ定義はここで分離して管理する。
 
 use utoipa::PartialSchema;
-use utoipa::openapi::schema::{ObjectBuilder, SchemaFormat, Type};
-use utoipa::openapi::{KnownFormat, RefOr, Schema};
+use utoipa::openapi::schema::{
+    ArrayBuilder, ObjectBuilder, OneOfBuilder, SchemaFormat, SchemaType, Type,
+};
+use utoipa::openapi::{KnownFormat, Ref, RefOr, Schema};
+
+use crate::models::Timeframe;
 
 // --- watchlists::Model ---
 
@@ -41,6 +45,20 @@ impl PartialSchema for crate::entities::watchlists::Model {
                     .format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime))),
             )
             .required("created_at")
+            .property(
+                "kind",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .enum_values(Some(["static", "smart"])),
+            )
+            .required("kind")
+            .property(
+                "rule",
+                OneOfBuilder::new()
+                    .item(Ref::from_schema_name("WatchlistRule"))
+                    .item(ObjectBuilder::new().schema_type(Type::Null)),
+            )
+            .required("rule")
             .into()
     }
 }
@@ -65,7 +83,61 @@ impl PartialSchema for crate::entities::bars::Model {
                 "timeframe",
                 ObjectBuilder::new()
                     .schema_type(Type::String)
-                    .enum_values(Some(["1d"])),
+                    .enum_values(Some(Timeframe::ALL.map(Timeframe::as_str))),
+            )
+            .required("timeframe")
+            .property(
+                "timestamp",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime))),
+            )
+            .required("timestamp")
+            .property("open", ObjectBuilder::new().schema_type(Type::Number))
+            .required("open")
+            .property("high", ObjectBuilder::new().schema_type(Type::Number))
+            .required("high")
+            .property("low", ObjectBuilder::new().schema_type(Type::Number))
+            .required("low")
+            .property("close", ObjectBuilder::new().schema_type(Type::Number))
+            .required("close")
+            .property(
+                "volume",
+                ObjectBuilder::new()
+                    .schema_type(Type::Integer)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::Int64))),
+            )
+            .required("volume")
+            .property(
+                "source",
+                ObjectBuilder::new().schema_type(SchemaType::from_iter([Type::String, Type::Null])),
+            )
+            .required("source")
+            .into()
+    }
+}
+
+// --- bar_anomalies::Model ---
+
+impl utoipa::ToSchema for crate::entities::bar_anomalies::Model {
+    fn name() -> std::borrow::Cow<'static, str> {
+        std::borrow::Cow::Borrowed("BarAnomaly")
+    }
+}
+
+impl PartialSchema for crate::entities::bar_anomalies::Model {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .property(
+                "instrument_id",
+                ObjectBuilder::new().schema_type(Type::String),
+            )
+            .required("instrument_id")
+            .property(
+                "timeframe",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .enum_values(Some(Timeframe::ALL.map(Timeframe::as_str))),
             )
             .required("timeframe")
             .property(
@@ -90,6 +162,18 @@ impl PartialSchema for crate::entities::bars::Model {
                     .format(Some(SchemaFormat::KnownFormat(KnownFormat::Int64))),
             )
             .required("volume")
+            .property(
+                "reasons",
+                ArrayBuilder::new().items(Ref::from_schema_name("BarAnomalyReason")),
+            )
+            .required("reasons")
+            .property(
+                "detected_at",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime))),
+            )
+            .required("detected_at")
             .into()
     }
 }
@@ -131,6 +215,31 @@ impl PartialSchema for crate::entities::watchlist_items::Model {
                     .format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime))),
             )
             .required("added_at")
+            .property(
+                "memo",
+                ObjectBuilder::new().schema_type(SchemaType::from_iter([Type::String, Type::Null])),
+            )
+            .required("memo")
+            .property(
+                "tags",
+                ArrayBuilder::new().items(Ref::from_schema_name("WatchlistItemTag")),
+            )
+            .required("tags")
+            .property(
+                "entry_price",
+                ObjectBuilder::new().schema_type(SchemaType::from_iter([Type::Number, Type::Null])),
+            )
+            .required("entry_price")
+            .property(
+                "target_price",
+                ObjectBuilder::new().schema_type(SchemaType::from_iter([Type::Number, Type::Null])),
+            )
+            .required("target_price")
+            .property(
+                "stop_price",
+                ObjectBuilder::new().schema_type(SchemaType::from_iter([Type::Number, Type::Null])),
+            )
+            .required("stop_price")
             .into()
     }
 }
@@ -1,9 +1,12 @@
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDate, Utc};
+use futures_util::TryStreamExt;
 use sea_orm::DatabaseConnection;
 
-use crate::data_provider::{DataProvider, DateRange};
-use crate::models::Timeframe;
-use crate::repositories::bars::upsert_bars;
+use crate::data_provider::{DataProvider, DataProviderError, DateRange};
+use crate::error::AppError;
+use crate::models::{Timeframe, WatchlistRuleInput};
+use crate::repositories::bars::copy_bars;
+use crate::services::{bar_validation, smart_watchlists};
 
 // J-Quants Free プランのデータ取得可能期間:
 // 12 週間前 ~ 2 年 12 週間前
@@ -11,174 +14,145 @@ use crate::repositories::bars::upsert_bars;
 const JQUANTS_FREE_PLAN_OFFSET_WEEKS: i64 = 12;
 const JQUANTS_FREE_PLAN_MAX_HISTORY_DAYS: i64 = 365 * 2;
 
-/// 指定銘柄の日足データを J-Quants Free プランの取得可能期間分バックフィルする
+/// ページ数の上限で打ち切られた取得を、続きのページから再開する回数の上限
+///
+/// API が同じ `pagination_key` を返し続けても取得が終わるよう、無制限には再開しない。
+const MAX_PAGE_LIMIT_RESUMES: u32 = 3;
+
+/// バックフィルの対象期間 (J-Quants Free プランのデータ取得可能範囲)
 ///
 /// Free プランでは 12 週間前 ~ 2 年 12 週間前の範囲のみ取得可能。
+pub fn backfill_range(today: NaiveDate) -> DateRange {
+    let to = today - Duration::weeks(JQUANTS_FREE_PLAN_OFFSET_WEEKS);
+    let from = to - Duration::days(JQUANTS_FREE_PLAN_MAX_HISTORY_DAYS);
+    DateRange { from, to }
+}
+
+/// 指定銘柄の日足データを J-Quants Free プランの取得可能期間分バックフィルする
+///
 /// バックグラウンドタスクとして呼ばれるため、エラー時はログ出力のみで呼び出し元には返さない。
 pub async fn backfill_daily_bars(
     db: &DatabaseConnection,
-    data_provider: &impl DataProvider,
+    data_provider: &dyn DataProvider,
     instrument_id: &str,
 ) {
-    let today = Utc::now().date_naive();
-    // J-Quants Free プランのデータ取得可能範囲にクランプする
-    let to = today - Duration::weeks(JQUANTS_FREE_PLAN_OFFSET_WEEKS);
-    let from = to - Duration::days(JQUANTS_FREE_PLAN_MAX_HISTORY_DAYS);
+    let range = backfill_range(Utc::now().date_naive());
+    backfill_daily_bars_in_range(db, data_provider, instrument_id, &range).await;
+}
 
-    let range = DateRange { from, to };
-
-    let bars = match data_provider.fetch_daily_bars(instrument_id, &range).await {
-        Ok(bars) => bars,
-        Err(e) => {
-            tracing::error!(
-                instrument_id,
-                error = %e,
-                "日足データの取得に失敗しました"
-            );
-            return;
-        }
-    };
-
-    if bars.is_empty() {
-        tracing::info!(instrument_id, "バックフィル対象のデータがありません");
-        return;
+/// 指定銘柄・期間の日足データをバックフィルする
+///
+/// 取得したバーは `bar_validation` で検証し、不正なバーは `bar_anomalies` に隔離する。
+/// エラー時はログ出力のみで呼び出し元には返さない。
+pub async fn backfill_daily_bars_in_range(
+    db: &DatabaseConnection,
+    data_provider: &dyn DataProvider,
+    instrument_id: &str,
+    range: &DateRange,
+) {
+    match fetch_and_store_daily_bars(db, data_provider, instrument_id, range).await {
+        Ok(bar_count) => tracing::info!(
+            instrument_id,
+            bar_count,
+            "日足データのバックフィルが完了しました"
+        ),
+        Err(e) => tracing::error!(
+            instrument_id,
+            error = %e,
+            "日足データのバックフィルに失敗しました"
+        ),
     }
+}
+
+/// 指定銘柄・期間の日足データを取得して保存し、保存したバーの数を返す
+///
+/// 取得したバーは `bar_validation` で検証し、不正なバーは `bar_anomalies` に隔離する。
+/// 全銘柄の同期でも使うため、保存には COPY (`copy_bars`) を使う。
+/// ページ単位で取得でき次第保存するため、途中のページで失敗してもそれまでのページは保存済みになる。
+/// ページ数の上限で打ち切られた場合は、[`MAX_PAGE_LIMIT_RESUMES`] 回まで続きのページから再開する。
+pub async fn fetch_and_store_daily_bars(
+    db: &DatabaseConnection,
+    data_provider: &dyn DataProvider,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<usize, AppError> {
+    let mut pages = data_provider.fetch_daily_bar_pages(instrument_id, range.clone());
+    let mut resumes = 0;
+    let mut fetched = 0;
+    let mut bar_count = 0;
+
+    loop {
+        let bars = match pages.try_next().await {
+            Ok(Some(bars)) => bars,
+            Ok(None) => break,
+            Err(DataProviderError::PageLimitExceeded {
+                pages: page_limit,
+                pagination_key,
+            }) if resumes < MAX_PAGE_LIMIT_RESUMES => {
+                resumes += 1;
+                tracing::info!(
+                    instrument_id,
+                    page_limit,
+                    pagination_key,
+                    resumes,
+                    "ページ数の上限に到達したため、続きのページから取得を再開します"
+                );
+                pages = data_provider.resume_daily_bar_pages(
+                    instrument_id,
+                    range.clone(),
+                    pagination_key,
+                );
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        fetched += bars.len();
 
-    // 日足データのみであることを確認
-    let daily_bars: Vec<_> = bars
-        .into_iter()
-        .filter(|b| b.timeframe == Timeframe::Daily)
-        .collect();
+        // 日足データのみであることを確認
+        let daily_bars: Vec<_> = bars
+            .into_iter()
+            .filter(|b| b.timeframe == Timeframe::Daily)
+            .collect();
 
-    let bar_count = daily_bars.len();
+        let daily_bars = bar_validation::quarantine_invalid_bars(db, daily_bars).await?;
 
-    if let Err(e) = upsert_bars(db, daily_bars).await {
-        tracing::error!(
-            instrument_id,
-            error = %e,
-            "日足データの保存に失敗しました"
-        );
-        return;
+        bar_count += daily_bars.len();
+        copy_bars(db, daily_bars).await?;
+    }
+
+    if fetched == 0 {
+        tracing::info!(instrument_id, "バックフィル対象のデータがありません");
+    }
+    if bar_count > 0 {
+        smart_watchlists::refresh_after_change(db, WatchlistRuleInput::Bars).await;
     }
 
-    tracing::info!(
-        instrument_id,
-        bar_count,
-        "日足データのバックフィルが完了しました"
-    );
+    Ok(bar_count)
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use chrono::{Duration, NaiveDate, Utc};
     use rstest::rstest;
     use rust_decimal::Decimal;
     use sqlx::PgPool;
 
     use super::*;
-    use crate::data_provider::{DataProviderError, DateRange};
-    use crate::models::instrument::{Instrument, Market};
-    use crate::models::{Bar, Timeframe};
+    use crate::data_provider::mock::{MockDataProvider, make_bar, sample_instrument};
     use crate::testing::create_test_db;
 
-    // --- テスト用モック ---
-
-    /// テスト用のモックデータプロバイダー
-    struct MockProvider {
-        bars: Vec<Bar>,
-        instruments: Vec<Instrument>,
-    }
-
-    impl MockProvider {
-        fn new() -> Self {
-            Self {
-                bars: Vec::new(),
-                instruments: Vec::new(),
-            }
-        }
-
-        fn with_bars(mut self, bars: Vec<Bar>) -> Self {
-            self.bars = bars;
-            self
-        }
-
-        fn with_instruments(mut self, instruments: Vec<Instrument>) -> Self {
-            self.instruments = instruments;
-            self
-        }
-    }
-
-    impl DataProvider for MockProvider {
-        async fn fetch_daily_bars(
-            &self,
-            instrument_id: &str,
-            range: &DateRange,
-        ) -> Result<Vec<Bar>, DataProviderError> {
-            let exists = self.instruments.iter().any(|i| i.id == instrument_id);
-            if !exists {
-                return Err(DataProviderError::NotFound(format!(
-                    "instrument '{instrument_id}' not found"
-                )));
-            }
-
-            let from_dt =
-                Utc.from_utc_datetime(&range.from.and_hms_opt(0, 0, 0).unwrap_or_default());
-            let to_exclusive = range.to.succ_opt().unwrap_or(range.to);
-            let to_dt =
-                Utc.from_utc_datetime(&to_exclusive.and_hms_opt(0, 0, 0).unwrap_or_default());
-
-            let mut bars: Vec<Bar> = self
-                .bars
-                .iter()
-                .filter(|b| {
-                    b.instrument_id == instrument_id
-                        && b.timestamp >= from_dt
-                        && b.timestamp < to_dt
-                })
-                .cloned()
-                .collect();
-
-            bars.sort_by_key(|b| b.timestamp);
-            Ok(bars)
-        }
-
-        async fn fetch_instrument(
-            &self,
-            instrument_id: &str,
-        ) -> Result<Instrument, DataProviderError> {
-            self.instruments
-                .iter()
-                .find(|i| i.id == instrument_id)
-                .cloned()
-                .ok_or_else(|| {
-                    DataProviderError::NotFound(format!("instrument '{instrument_id}' not found"))
-                })
-        }
-    }
-
     // --- テスト用ヘルパー ---
 
-    fn make_bar(instrument_id: &str, date: NaiveDate, close: i64) -> Bar {
-        let timestamp = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default());
-        Bar {
-            instrument_id: instrument_id.to_string(),
-            timeframe: Timeframe::Daily,
-            timestamp,
-            open: Decimal::new(close, 0),
-            high: Decimal::new(close + 10, 0),
-            low: Decimal::new(close - 10, 0),
-            close: Decimal::new(close, 0),
-            volume: 1000,
-        }
-    }
-
-    fn sample_instrument(id: &str) -> Instrument {
-        Instrument {
-            id: id.to_string(),
-            name: format!("Test {id}"),
-            market: Market::Tse,
-            sector: None,
-        }
+    /// バックフィルの対象期間の末尾から、営業日を古い順に返す
+    fn trading_days_in_range(count: usize) -> Vec<NaiveDate> {
+        let range = backfill_range(Utc::now().date_naive());
+        let mut days: Vec<NaiveDate> = (1..)
+            .map(|offset| range.to - Duration::days(offset))
+            .filter(|date| crate::calendar::is_trading_day(*date))
+            .take(count)
+            .collect();
+        days.reverse();
+        days
     }
 
     /// テスト用 instrument を DB に挿入する
@@ -192,6 +166,8 @@ mod tests {
             name: Set(format!("Test {id}")),
             market: Set("TSE".to_string()),
             sector: Set(None),
+            market_segment: Set(None),
+            bars_updated_at: Set(None),
         })
         .on_conflict(
             OnConflict::column(instruments::Column::Id)
@@ -210,15 +186,13 @@ mod tests {
         let db = create_test_db(pool).await;
         insert_test_instrument(&db, "7203").await;
 
-        // backfill_daily_bars と同じロジックで日付範囲を決定する
-        let today = Utc::now().date_naive();
-        let to = today - Duration::weeks(super::JQUANTS_FREE_PLAN_OFFSET_WEEKS);
+        let days = trading_days_in_range(2);
         let bars = vec![
-            make_bar("7203", to - Duration::days(2), 100),
-            make_bar("7203", to - Duration::days(1), 105),
+            make_bar("7203", days[0], 100),
+            make_bar("7203", days[1], 105),
         ];
 
-        let provider = MockProvider::new()
+        let provider = MockDataProvider::new()
             .with_instruments(vec![sample_instrument("7203")])
             .with_bars(bars);
 
@@ -233,20 +207,127 @@ mod tests {
                 timeframe: "1d".to_string(),
                 from: None,
                 to: None,
+                ..Default::default()
             },
         )
         .await
-        .expect("find_bars failed");
+        .expect("find_bars failed")
+        .bars;
 
         assert_eq!(result.len(), 2);
     }
 
+    #[sqlx::test(migrations = false)]
+    async fn backfill_quarantines_invalid_bars(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let days = trading_days_in_range(2);
+        let mut invalid = make_bar("7203", days[1], 105);
+        invalid.high = Decimal::new(50, 0);
+        let provider = MockDataProvider::new()
+            .with_instruments(vec![sample_instrument("7203")])
+            .with_bars(vec![make_bar("7203", days[0], 100), invalid]);
+
+        backfill_daily_bars(&db, &provider, "7203").await;
+
+        use crate::repositories::bar_anomalies::find_anomalies;
+        use crate::repositories::bars::{BarsQuery, find_bars};
+        let saved = find_bars(
+            &db,
+            BarsQuery {
+                instrument_id: "7203".to_string(),
+                timeframe: "1d".to_string(),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("find_bars failed")
+        .bars;
+        assert_eq!(saved.len(), 1);
+
+        let anomalies = find_anomalies(&db, Some("7203"), 10)
+            .await
+            .expect("find_anomalies failed");
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].timestamp.date_naive(), days[1]);
+        assert_eq!(
+            anomalies[0].reasons,
+            serde_json::json!(["high_below_open_close"])
+        );
+    }
+
+    async fn saved_bar_count(db: &DatabaseConnection, instrument_id: &str) -> usize {
+        use crate::repositories::bars::{BarsQuery, find_bars};
+        find_bars(
+            db,
+            BarsQuery {
+                instrument_id: instrument_id.to_string(),
+                timeframe: "1d".to_string(),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("find_bars failed")
+        .bars
+        .len()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn fetch_and_store_resumes_after_page_limit(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        let days = trading_days_in_range(5);
+        let provider = MockDataProvider::new()
+            .with_instruments(vec![sample_instrument("7203")])
+            .with_bars(days.iter().map(|day| make_bar("7203", *day, 100)).collect())
+            .with_pages(2, 2);
+
+        let range = DateRange {
+            from: days[0],
+            to: days[4],
+        };
+        let bar_count = fetch_and_store_daily_bars(&db, &provider, "7203", &range)
+            .await
+            .expect("fetch_and_store failed");
+
+        assert_eq!(bar_count, 5);
+        assert_eq!(saved_bar_count(&db, "7203").await, 5);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn fetch_and_store_keeps_pages_when_resumes_are_exhausted(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_test_instrument(&db, "7203").await;
+
+        // 1 回の取得で 1 ページまでのため、最初の取得と 3 回の再開で 4 ページまで取得できる
+        let days = trading_days_in_range(6);
+        let provider = MockDataProvider::new()
+            .with_instruments(vec![sample_instrument("7203")])
+            .with_bars(days.iter().map(|day| make_bar("7203", *day, 100)).collect())
+            .with_pages(1, 1);
+
+        let range = DateRange {
+            from: days[0],
+            to: days[5],
+        };
+        let result = fetch_and_store_daily_bars(&db, &provider, "7203", &range).await;
+
+        assert!(matches!(
+            result,
+            Err(AppError::DataProvider(DataProviderError::PageLimitExceeded { pages: 1, ref pagination_key }))
+                if pagination_key == "page5"
+        ));
+        assert_eq!(saved_bar_count(&db, "7203").await, 4);
+    }
+
     #[sqlx::test(migrations = false)]
     async fn backfill_handles_empty_response(pool: PgPool) {
         let db = create_test_db(pool).await;
 
         // 銘柄は存在するがバーデータなし
-        let provider = MockProvider::new().with_instruments(vec![sample_instrument("9999")]);
+        let provider = MockDataProvider::new().with_instruments(vec![sample_instrument("9999")]);
 
         // パニックせずに正常終了すること
         backfill_daily_bars(&db, &provider, "9999").await;
@@ -260,7 +341,7 @@ mod tests {
         let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
 
         // 銘柄が存在しないプロバイダー → NotFound エラー
-        let provider = MockProvider::new();
+        let provider = MockDataProvider::new();
 
         // パニックせずに正常終了すること
         backfill_daily_bars(&db, &provider, "99999").await;
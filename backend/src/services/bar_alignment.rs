@@ -0,0 +1,164 @@
+//! 複数銘柄のバーデータのタイムスタンプ揃え
+
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+
+use crate::entities::bars;
+use crate::models::{AlignedBar, AlignedBars, AlignedSeries, BarFill};
+
+/// 複数銘柄のバーをタイムスタンプで揃える
+///
+/// いずれかの銘柄にバーが存在するタイムスタンプの和集合を軸とし、
+/// 各銘柄のバーをその位置に並べる。`fill` が Forward の場合、データのない位置は
+/// 直前のバーの終値 (出来高 0) で補完する。最初のバーより前は補完しない。
+pub fn align(instrument_ids: &[String], bars: Vec<bars::Model>, fill: BarFill) -> AlignedBars {
+    let timestamps: Vec<DateTime<Utc>> = bars
+        .iter()
+        .map(|bar| bar.timestamp.to_utc())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let positions: HashMap<DateTime<Utc>, usize> = timestamps
+        .iter()
+        .enumerate()
+        .map(|(position, timestamp)| (*timestamp, position))
+        .collect();
+
+    let mut series: Vec<AlignedSeries> = instrument_ids
+        .iter()
+        .map(|instrument_id| AlignedSeries {
+            instrument_id: instrument_id.clone(),
+            bars: vec![None; timestamps.len()],
+        })
+        .collect();
+    let series_index: HashMap<&str, usize> = instrument_ids
+        .iter()
+        .enumerate()
+        .map(|(index, instrument_id)| (instrument_id.as_str(), index))
+        .collect();
+
+    for bar in bars {
+        let (Some(&index), Some(&position)) = (
+            series_index.get(bar.instrument_id.as_str()),
+            positions.get(&bar.timestamp.to_utc()),
+        ) else {
+            continue;
+        };
+        series[index].bars[position] = Some(AlignedBar {
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            volume: bar.volume,
+            filled: false,
+        });
+    }
+
+    if fill == BarFill::Forward {
+        for s in &mut series {
+            forward_fill(&mut s.bars);
+        }
+    }
+
+    AlignedBars { timestamps, series }
+}
+
+/// データのない位置を直前のバーの終値で補完する
+fn forward_fill(bars: &mut [Option<AlignedBar>]) {
+    let mut last_close = None;
+    for slot in bars.iter_mut() {
+        match slot {
+            Some(bar) => last_close = Some(bar.close),
+            None => {
+                *slot = last_close.map(|close| AlignedBar {
+                    open: close,
+                    high: close,
+                    low: close,
+                    close,
+                    volume: 0,
+                    filled: true,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    fn bar(instrument_id: &str, day: u32, close: i64) -> bars::Model {
+        bars::Model {
+            instrument_id: instrument_id.to_string(),
+            timeframe: "1d".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, day, 0, 0, 0).unwrap().into(),
+            open: Decimal::new(close, 0),
+            high: Decimal::new(close, 0),
+            low: Decimal::new(close, 0),
+            close: Decimal::new(close, 0),
+            volume: 100,
+            source: None,
+        }
+    }
+
+    /// 銘柄ごとの (終値, 補完したか) の並び
+    fn closes(aligned: &AlignedBars) -> Vec<Vec<Option<(i64, bool)>>> {
+        aligned
+            .series
+            .iter()
+            .map(|s| {
+                s.bars
+                    .iter()
+                    .map(|bar| bar.as_ref().map(|b| (b.close.mantissa() as i64, b.filled)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::no_fill(
+        BarFill::None,
+        vec![
+            vec![Some((100, false)), None, Some((120, false))],
+            vec![None, Some((200, false)), Some((210, false))],
+        ]
+    )]
+    #[case::forward_fill(
+        BarFill::Forward,
+        vec![
+            vec![Some((100, false)), Some((100, true)), Some((120, false))],
+            vec![None, Some((200, false)), Some((210, false))],
+        ]
+    )]
+    fn test_align(#[case] fill: BarFill, #[case] expected: Vec<Vec<Option<(i64, bool)>>>) {
+        let ids = vec!["7203".to_string(), "6758".to_string()];
+        let bars = vec![
+            bar("6758", 7, 200),
+            bar("6758", 8, 210),
+            bar("7203", 6, 100),
+            bar("7203", 8, 120),
+        ];
+
+        let aligned = align(&ids, bars, fill);
+
+        assert_eq!(aligned.timestamps.len(), 3);
+        assert_eq!(aligned.series[0].instrument_id, "7203");
+        assert_eq!(closes(&aligned), expected);
+    }
+
+    #[rstest]
+    fn test_align_without_bars_returns_empty_series() {
+        let ids = vec!["7203".to_string()];
+
+        let aligned = align(&ids, Vec::new(), BarFill::Forward);
+
+        assert!(aligned.timestamps.is_empty());
+        assert_eq!(aligned.series.len(), 1);
+        assert!(aligned.series[0].bars.is_empty());
+    }
+}
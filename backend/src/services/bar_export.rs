@@ -0,0 +1,383 @@
+//! バーデータのエクスポート形式 (CSV / Arrow IPC / Parquet) へのエンコード
+//!
+//! DB から読み込んだバーをバッチ単位でエンコードし、エンコード済みのバイト列を
+//! 逐次取り出せるようにする。全件をメモリに載せずにレスポンスをストリーミングするため、
+//! 各エンコーダーの出力バッファはバッチごとに空にする。
+
+use std::sync::Arc;
+
+use arrow_array::{Float64Array, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, FixedOffset};
+use futures_util::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+
+use crate::entities::bars;
+use crate::error::AppError;
+use crate::repositories::bars::stream_bars_for_instruments;
+
+/// 1 回にエンコードするバーの件数 (Parquet の row group の最大行数)
+const EXPORT_BATCH_SIZE: usize = 8192;
+
+/// バーデータのエクスポート形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BarExportFormat {
+    /// ヘッダー付き CSV (価格は DB の値をそのまま出力する)
+    #[default]
+    Csv,
+    /// Apache Arrow IPC ストリーム形式
+    Arrow,
+    /// Apache Parquet (バッチごとに 1 つの row group)
+    Parquet,
+}
+
+impl BarExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            BarExportFormat::Csv => "text/csv; charset=utf-8",
+            BarExportFormat::Arrow => "application/vnd.apache.arrow.stream",
+            BarExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            BarExportFormat::Csv => "csv",
+            BarExportFormat::Arrow => "arrows",
+            BarExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// エクスポートする列
+const COLUMNS: [&str; 7] = [
+    "instrument_id",
+    "timestamp",
+    "open",
+    "high",
+    "low",
+    "close",
+    "volume",
+];
+
+/// Arrow / Parquet のスキーマ (価格は Float64、タイムスタンプは UTC のマイクロ秒)
+fn arrow_schema() -> SchemaRef {
+    let price = |name| Field::new(name, DataType::Float64, false);
+    Arc::new(Schema::new(vec![
+        Field::new(COLUMNS[0], DataType::Utf8, false),
+        Field::new(
+            COLUMNS[1],
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        price(COLUMNS[2]),
+        price(COLUMNS[3]),
+        price(COLUMNS[4]),
+        price(COLUMNS[5]),
+        Field::new(COLUMNS[6], DataType::Int64, false),
+    ]))
+}
+
+fn to_record_batch(schema: &SchemaRef, bars: &[bars::Model]) -> Result<RecordBatch, AppError> {
+    let price = |f: fn(&bars::Model) -> rust_decimal::Decimal| {
+        Float64Array::from_iter_values(bars.iter().map(|bar| f(bar).to_f64().unwrap_or(f64::NAN)))
+    };
+
+    RecordBatch::try_new(
+        Arc::clone(schema),
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                bars.iter().map(|bar| bar.instrument_id.as_str()),
+            )),
+            Arc::new(
+                TimestampMicrosecondArray::from_iter_values(
+                    bars.iter().map(|bar| bar.timestamp.timestamp_micros()),
+                )
+                .with_timezone("UTC"),
+            ),
+            Arc::new(price(|bar| bar.open)),
+            Arc::new(price(|bar| bar.high)),
+            Arc::new(price(|bar| bar.low)),
+            Arc::new(price(|bar| bar.close)),
+            Arc::new(Int64Array::from_iter_values(
+                bars.iter().map(|bar| bar.volume),
+            )),
+        ],
+    )
+    .map_err(|e| AppError::Internal(format!("failed to build record batch: {e}")))
+}
+
+/// バーを CSV の行に変換する (`write_header` が true の場合は先頭にヘッダー行を付ける)
+fn csv_chunk(write_header: bool, bars: &[bars::Model]) -> Result<Vec<u8>, AppError> {
+    let to_error = |e: csv::Error| AppError::Internal(format!("failed to write CSV: {e}"));
+
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    if write_header {
+        writer.write_record(COLUMNS).map_err(to_error)?;
+    }
+    for bar in bars {
+        writer
+            .write_record([
+                bar.instrument_id.as_str(),
+                &bar.timestamp.to_utc().to_rfc3339(),
+                &bar.open.to_string(),
+                &bar.high.to_string(),
+                &bar.low.to_string(),
+                &bar.close.to_string(),
+                &bar.volume.to_string(),
+            ])
+            .map_err(to_error)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("failed to write CSV: {e}")))
+}
+
+/// バーデータをバッチ単位でエンコードする
+pub enum BarEncoder {
+    Csv {
+        header_written: bool,
+    },
+    Arrow {
+        schema: SchemaRef,
+        writer: StreamWriter<Vec<u8>>,
+    },
+    Parquet {
+        schema: SchemaRef,
+        writer: ArrowWriter<Vec<u8>>,
+    },
+}
+
+impl BarEncoder {
+    pub fn new(format: BarExportFormat) -> Result<Self, AppError> {
+        match format {
+            BarExportFormat::Csv => Ok(BarEncoder::Csv {
+                header_written: false,
+            }),
+            BarExportFormat::Arrow => {
+                let schema = arrow_schema();
+                let writer = StreamWriter::try_new(Vec::new(), &schema)
+                    .map_err(|e| AppError::Internal(format!("failed to write Arrow IPC: {e}")))?;
+                Ok(BarEncoder::Arrow { schema, writer })
+            }
+            BarExportFormat::Parquet => {
+                let schema = arrow_schema();
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let writer =
+                    ArrowWriter::try_new(Vec::new(), Arc::clone(&schema), Some(properties))
+                        .map_err(|e| AppError::Internal(format!("failed to write Parquet: {e}")))?;
+                Ok(BarEncoder::Parquet { schema, writer })
+            }
+        }
+    }
+
+    /// バーをエンコードし、これまでに出力可能になったバイト列を取り出す
+    pub fn encode(&mut self, bars: &[bars::Model]) -> Result<Vec<u8>, AppError> {
+        match self {
+            BarEncoder::Csv { header_written } => {
+                let chunk = csv_chunk(!*header_written, bars)?;
+                *header_written = true;
+                Ok(chunk)
+            }
+            BarEncoder::Arrow { schema, writer } => {
+                writer
+                    .write(&to_record_batch(schema, bars)?)
+                    .map_err(|e| AppError::Internal(format!("failed to write Arrow IPC: {e}")))?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+            BarEncoder::Parquet { schema, writer } => {
+                writer
+                    .write(&to_record_batch(schema, bars)?)
+                    .map_err(|e| AppError::Internal(format!("failed to write Parquet: {e}")))?;
+                // row group を閉じて出力バッファに書き出す
+                writer
+                    .flush()
+                    .map_err(|e| AppError::Internal(format!("failed to write Parquet: {e}")))?;
+                Ok(std::mem::take(writer.inner_mut()))
+            }
+        }
+    }
+
+    /// エンコードを終了し、残りのバイト列 (フッター等) を返す
+    pub fn finish(self) -> Result<Vec<u8>, AppError> {
+        match self {
+            // 1 行もない場合もヘッダーは出力する
+            BarEncoder::Csv { header_written } => csv_chunk(!header_written, &[]),
+            BarEncoder::Arrow { mut writer, .. } => {
+                writer
+                    .finish()
+                    .map_err(|e| AppError::Internal(format!("failed to write Arrow IPC: {e}")))?;
+                writer
+                    .into_inner()
+                    .map_err(|e| AppError::Internal(format!("failed to write Arrow IPC: {e}")))
+            }
+            BarEncoder::Parquet { writer, .. } => writer
+                .into_inner()
+                .map_err(|e| AppError::Internal(format!("failed to write Parquet: {e}"))),
+        }
+    }
+}
+
+/// エクスポート対象のバーの条件
+pub struct BarExportQuery {
+    pub instrument_ids: Vec<String>,
+    pub timeframe: String,
+    pub from: Option<DateTime<FixedOffset>>,
+    pub to: Option<DateTime<FixedOffset>>,
+    pub format: BarExportFormat,
+}
+
+/// DB から読み込んだバーをエンコードし、バッチごとに `sink` へ送る
+///
+/// 送信先 (レスポンスボディ) が閉じられた場合はクライアントが切断したとみなし、途中で終了する。
+pub async fn export_bars(
+    db: &DatabaseConnection,
+    query: BarExportQuery,
+    sink: &mpsc::Sender<Result<Vec<u8>, AppError>>,
+) -> Result<(), AppError> {
+    let mut encoder = BarEncoder::new(query.format)?;
+    let mut stream = std::pin::pin!(
+        stream_bars_for_instruments(
+            db,
+            &query.instrument_ids,
+            &query.timeframe,
+            query.from,
+            query.to,
+        )
+        .await?
+    );
+
+    let mut batch = Vec::with_capacity(EXPORT_BATCH_SIZE);
+    let mut bar_count = 0usize;
+    while let Some(bar) = stream.try_next().await? {
+        batch.push(bar);
+        if batch.len() < EXPORT_BATCH_SIZE {
+            continue;
+        }
+
+        bar_count += batch.len();
+        let chunk = encoder.encode(&batch)?;
+        batch.clear();
+        if sink.send(Ok(chunk)).await.is_err() {
+            tracing::info!(
+                bar_count,
+                "クライアントが切断したためエクスポートを中断しました"
+            );
+            return Ok(());
+        }
+    }
+
+    if !batch.is_empty() {
+        bar_count += batch.len();
+        let chunk = encoder.encode(&batch)?;
+        if sink.send(Ok(chunk)).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    // 送信に失敗した場合はクライアントが切断済みなので無視する
+    let _ = sink.send(Ok(encoder.finish()?)).await;
+
+    tracing::info!(
+        bar_count,
+        format = ?query.format,
+        "バーデータのエクスポートが完了しました"
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Float64Type;
+    use axum::body::Bytes;
+    use chrono::{TimeZone, Utc};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    fn bar(instrument_id: &str, day: u32, close: i64) -> bars::Model {
+        bars::Model {
+            instrument_id: instrument_id.to_string(),
+            timeframe: "1d".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, day, 0, 0, 0).unwrap().into(),
+            open: Decimal::new(close, 1),
+            high: Decimal::new(close, 1),
+            low: Decimal::new(close, 1),
+            close: Decimal::new(close, 1),
+            volume: 100,
+            source: None,
+        }
+    }
+
+    /// 2 バッチに分けてエンコードした結果を連結する
+    fn encode_in_batches(format: BarExportFormat) -> Vec<u8> {
+        let mut encoder = BarEncoder::new(format).unwrap();
+        let mut output = encoder
+            .encode(&[bar("7203", 6, 30005), bar("7203", 7, 30100)])
+            .unwrap();
+        output.extend(encoder.encode(&[bar("6758", 6, 1500)]).unwrap());
+        output.extend(encoder.finish().unwrap());
+        output
+    }
+
+    #[rstest]
+    fn test_encode_csv() {
+        let output = String::from_utf8(encode_in_batches(BarExportFormat::Csv)).unwrap();
+
+        assert_eq!(
+            output,
+            indoc::indoc! {"
+                instrument_id,timestamp,open,high,low,close,volume
+                7203,2025-01-06T00:00:00+00:00,3000.5,3000.5,3000.5,3000.5,100
+                7203,2025-01-07T00:00:00+00:00,3010.0,3010.0,3010.0,3010.0,100
+                6758,2025-01-06T00:00:00+00:00,150.0,150.0,150.0,150.0,100
+            "}
+        );
+    }
+
+    #[rstest]
+    fn test_encode_arrow_stream_is_readable() {
+        let output = encode_in_batches(BarExportFormat::Arrow);
+
+        let reader =
+            arrow_ipc::reader::StreamReader::try_new(std::io::Cursor::new(output), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), 2);
+        let close = batches[0].column(5).as_primitive::<Float64Type>();
+        assert_eq!(close.value(0), 3000.5);
+    }
+
+    #[rstest]
+    fn test_encode_parquet_is_readable() {
+        let output = encode_in_batches(BarExportFormat::Parquet);
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(output)).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        let rows: usize = builder
+            .build()
+            .unwrap()
+            .map(|batch| batch.unwrap().num_rows())
+            .sum();
+        assert_eq!(rows, 3);
+    }
+}
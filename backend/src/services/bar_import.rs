@@ -0,0 +1,542 @@
+//! OHLCV CSV の一括インポート
+//!
+//! 他のデータソース (証券会社のダウンロード、購入したデータセット等) の日足 CSV を
+//! 列構成を指定してパースし、検証したうえで COPY (`copy_bars`) で一括して書き込む。
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::error::AppError;
+use crate::models::{
+    Bar, BarImportConflict, BarImportReport, BarImportRowError, OhlcvValues, Timeframe,
+    WatchlistRuleInput,
+};
+use crate::repositories::bars::{copy_bars, find_bars_at};
+use crate::repositories::instruments;
+use crate::services::{bar_validation, smart_watchlists};
+
+/// インポートしたバーの取得元 (`bars.source`)
+pub const SOURCE: &str = "csv_import";
+
+/// 既存の行と比較する際に 1 回のクエリで扱う行数
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// レポートに含める conflicts / errors の最大件数
+const MAX_REPORTED_ITEMS: usize = 100;
+
+/// インポートする CSV の列構成
+///
+/// 列はヘッダー名で指定する (大文字小文字は区別しない)。
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, IntoParams, clap::Args)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct BarCsvLayout {
+    /// 銘柄コード列 (列がない場合は instrument_id の指定が必要)
+    #[arg(long, default_value = "instrument_id")]
+    pub instrument_id_column: String,
+    /// 日付列
+    #[arg(long, default_value = "date")]
+    pub date_column: String,
+    /// 日付の書式 (strftime 形式, 例: "%Y/%m/%d", "%Y%m%d")
+    #[arg(long, default_value = "%Y-%m-%d")]
+    pub date_format: String,
+    /// 始値列
+    #[arg(long, default_value = "open")]
+    pub open_column: String,
+    /// 高値列
+    #[arg(long, default_value = "high")]
+    pub high_column: String,
+    /// 安値列
+    #[arg(long, default_value = "low")]
+    pub low_column: String,
+    /// 終値列
+    #[arg(long, default_value = "close")]
+    pub close_column: String,
+    /// 出来高列
+    #[arg(long, default_value = "volume")]
+    pub volume_column: String,
+    /// 区切り文字 (1 バイト文字)
+    #[arg(long, default_value_t = ',')]
+    pub delimiter: char,
+}
+
+impl Default for BarCsvLayout {
+    fn default() -> Self {
+        Self {
+            instrument_id_column: "instrument_id".to_string(),
+            date_column: "date".to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            open_column: "open".to_string(),
+            high_column: "high".to_string(),
+            low_column: "low".to_string(),
+            close_column: "close".to_string(),
+            volume_column: "volume".to_string(),
+            delimiter: ',',
+        }
+    }
+}
+
+impl BarCsvLayout {
+    /// Stooq のヒストリカルデータ (`<TICKER>,<PER>,<DATE>,<TIME>,<OPEN>,...`) の列構成
+    pub fn stooq() -> Self {
+        Self {
+            instrument_id_column: "<TICKER>".to_string(),
+            date_column: "<DATE>".to_string(),
+            date_format: "%Y%m%d".to_string(),
+            open_column: "<OPEN>".to_string(),
+            high_column: "<HIGH>".to_string(),
+            low_column: "<LOW>".to_string(),
+            close_column: "<CLOSE>".to_string(),
+            volume_column: "<VOL>".to_string(),
+            delimiter: ',',
+        }
+    }
+}
+
+/// CSV のパース結果
+#[derive(Debug)]
+pub struct ParsedBars {
+    /// CSV のデータ行数 (ヘッダーを除く)
+    pub total_rows: usize,
+    /// 検証に成功したバー
+    pub bars: Vec<Bar>,
+    /// 検証に失敗した行
+    pub errors: Vec<BarImportRowError>,
+}
+
+/// ヘッダー行での各列の位置
+struct ColumnIndexes {
+    instrument_id: Option<usize>,
+    date: usize,
+    open: usize,
+    high: usize,
+    low: usize,
+    close: usize,
+    volume: usize,
+}
+
+impl ColumnIndexes {
+    fn resolve(header: &csv::StringRecord, layout: &BarCsvLayout) -> Result<Self, AppError> {
+        let position = |name: &str| {
+            header.iter().position(|field| {
+                field
+                    .trim_start_matches('\u{feff}')
+                    .eq_ignore_ascii_case(name)
+            })
+        };
+        let required = |name: &str| {
+            position(name).ok_or_else(|| {
+                AppError::Validation(format!("CSV header does not contain column '{name}'"))
+            })
+        };
+
+        Ok(Self {
+            instrument_id: position(&layout.instrument_id_column),
+            date: required(&layout.date_column)?,
+            open: required(&layout.open_column)?,
+            high: required(&layout.high_column)?,
+            low: required(&layout.low_column)?,
+            close: required(&layout.close_column)?,
+            volume: required(&layout.volume_column)?,
+        })
+    }
+}
+
+/// OHLCV CSV をパースして検証する
+///
+/// `instrument_id` を指定した場合は全行をその銘柄として扱い、銘柄コード列は無視する。
+/// ヘッダーに必要な列がない場合はエラーを返し、行単位の不正は `errors` に含める。
+pub fn parse_csv(
+    content: &str,
+    layout: &BarCsvLayout,
+    instrument_id: Option<&str>,
+) -> Result<ParsedBars, AppError> {
+    let delimiter = u8::try_from(layout.delimiter).map_err(|_| {
+        AppError::Validation(format!(
+            "delimiter must be a single-byte character: {}",
+            layout.delimiter
+        ))
+    })?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    let header = reader
+        .headers()
+        .map_err(|e| AppError::Validation(format!("invalid CSV header: {e}")))?
+        .clone();
+    let columns = ColumnIndexes::resolve(&header, layout)?;
+    if instrument_id.is_none() && columns.instrument_id.is_none() {
+        return Err(AppError::Validation(format!(
+            "CSV header does not contain column '{}'; specify instrument_id instead",
+            layout.instrument_id_column
+        )));
+    }
+
+    let mut parsed = ParsedBars {
+        total_rows: 0,
+        bars: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut seen = HashSet::new();
+
+    for record in reader.records() {
+        parsed.total_rows += 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, csv::Position::line);
+                parsed.errors.push(BarImportRowError {
+                    line,
+                    message: format!("invalid CSV row: {e}"),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, csv::Position::line);
+
+        let result = parse_row(&record, &columns, layout, instrument_id).and_then(|bar| {
+            if seen.insert((bar.instrument_id.clone(), bar.timestamp)) {
+                Ok(bar)
+            } else {
+                Err(format!(
+                    "duplicate row for instrument {} at {}",
+                    bar.instrument_id,
+                    bar.timestamp.date_naive()
+                ))
+            }
+        });
+        match result {
+            Ok(bar) => parsed.bars.push(bar),
+            Err(message) => parsed.errors.push(BarImportRowError { line, message }),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn parse_row(
+    record: &csv::StringRecord,
+    columns: &ColumnIndexes,
+    layout: &BarCsvLayout,
+    instrument_id: Option<&str>,
+) -> Result<Bar, String> {
+    let field = |index: usize, name: &str| {
+        record
+            .get(index)
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| format!("{name} is empty"))
+    };
+    let price = |index: usize, name: &str| {
+        let value = field(index, name)?;
+        Decimal::from_str(value).map_err(|_| format!("{name} is not a number: {value}"))
+    };
+
+    let instrument_id = match (instrument_id, columns.instrument_id) {
+        (Some(id), _) => id.to_string(),
+        (None, Some(index)) => field(index, "instrument_id")?.to_string(),
+        (None, None) => return Err("instrument_id is not specified".to_string()),
+    };
+    if !instrument_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        return Err(format!("invalid instrument_id: {instrument_id}"));
+    }
+
+    let date_value = field(columns.date, "date")?;
+    let date = NaiveDate::parse_from_str(date_value, &layout.date_format).map_err(|_| {
+        format!(
+            "date '{date_value}' does not match format '{}'",
+            layout.date_format
+        )
+    })?;
+    let timestamp: DateTime<Utc> = date
+        .and_hms_opt(0, 0, 0)
+        .map(|dt| dt.and_utc())
+        .ok_or_else(|| format!("invalid date: {date_value}"))?;
+
+    let open = price(columns.open, "open")?;
+    let high = price(columns.high, "high")?;
+    let low = price(columns.low, "low")?;
+    let close = price(columns.close, "close")?;
+
+    let volume_value = field(columns.volume, "volume")?;
+    // 出来高は "1234.0" のような小数表記も整数値であれば受け付ける
+    let volume = Decimal::from_str(volume_value)
+        .ok()
+        .filter(|v| v.fract().is_zero())
+        .and_then(|v| i64::try_from(v).ok())
+        .ok_or_else(|| format!("volume is not an integer: {volume_value}"))?;
+
+    let bar = Bar {
+        instrument_id,
+        timeframe: Timeframe::Daily,
+        timestamp,
+        open,
+        high,
+        low,
+        close,
+        volume,
+        source: Some(SOURCE.to_string()),
+    };
+    validate_bar(&bar)?;
+
+    Ok(bar)
+}
+
+/// 取り込み時のデータ品質検証 (`bar_validation::validate`) を行う
+fn validate_bar(bar: &Bar) -> Result<(), String> {
+    let reasons = bar_validation::validate(bar);
+    if reasons.is_empty() {
+        return Ok(());
+    }
+
+    let reasons: Vec<String> = reasons.iter().map(ToString::to_string).collect();
+    Err(format!(
+        "{}: open={}, high={}, low={}, close={}, volume={}",
+        reasons.join(", "),
+        bar.open,
+        bar.high,
+        bar.low,
+        bar.close,
+        bar.volume
+    ))
+}
+
+/// パース済みのバーを既存の行と比較し、ドライランでなければ書き込む
+///
+/// DB に存在しない行と既存の行と値が異なる行のみを upsert する。
+/// 未登録の銘柄は銘柄コードを銘柄名として作成する。
+pub async fn import_bars(
+    db: &DatabaseConnection,
+    parsed: ParsedBars,
+    dry_run: bool,
+) -> Result<BarImportReport, AppError> {
+    let mut report = BarImportReport {
+        dry_run,
+        total_rows: parsed.total_rows,
+        valid_rows: parsed.bars.len(),
+        new_rows: 0,
+        unchanged_rows: 0,
+        conflicting_rows: 0,
+        written_rows: 0,
+        write_elapsed_ms: 0,
+        rows_per_sec: 0,
+        conflicts: Vec::new(),
+        errors: parsed.errors.into_iter().take(MAX_REPORTED_ITEMS).collect(),
+    };
+
+    let mut bars = parsed.bars;
+    bars.sort_by(|a, b| {
+        (a.instrument_id.as_str(), a.timestamp).cmp(&(b.instrument_id.as_str(), b.timestamp))
+    });
+
+    if !dry_run {
+        let instrument_ids: BTreeSet<&str> =
+            bars.iter().map(|bar| bar.instrument_id.as_str()).collect();
+        for instrument_id in instrument_ids {
+            instruments::ensure_exists(db, instrument_id, instrument_id.to_string()).await?;
+        }
+    }
+
+    let mut changed = Vec::new();
+    for chunk in bars.chunks(IMPORT_BATCH_SIZE) {
+        // チャンク内の銘柄ごとに既存の行を取得して比較する
+        let mut by_instrument: HashMap<&str, Vec<&Bar>> = HashMap::new();
+        for bar in chunk {
+            by_instrument
+                .entry(bar.instrument_id.as_str())
+                .or_default()
+                .push(bar);
+        }
+        let mut existing = HashMap::new();
+        for (instrument_id, bars) in &by_instrument {
+            let timestamps = bars
+                .iter()
+                .map(|bar| bar.timestamp.fixed_offset())
+                .collect();
+            for model in
+                find_bars_at(db, instrument_id, &Timeframe::Daily.to_string(), timestamps).await?
+            {
+                existing.insert(
+                    (model.instrument_id.clone(), model.timestamp.to_utc()),
+                    model,
+                );
+            }
+        }
+
+        for bar in chunk {
+            match existing.get(&(bar.instrument_id.clone(), bar.timestamp)) {
+                None => {
+                    report.new_rows += 1;
+                    changed.push(bar.clone());
+                }
+                Some(model) if OhlcvValues::from(model) == OhlcvValues::from(bar) => {
+                    report.unchanged_rows += 1;
+                }
+                Some(model) => {
+                    report.conflicting_rows += 1;
+                    if report.conflicts.len() < MAX_REPORTED_ITEMS {
+                        report.conflicts.push(BarImportConflict {
+                            instrument_id: bar.instrument_id.clone(),
+                            timestamp: bar.timestamp,
+                            existing: model.into(),
+                            imported: bar.into(),
+                        });
+                    }
+                    changed.push(bar.clone());
+                }
+            }
+        }
+    }
+
+    if !dry_run && !changed.is_empty() {
+        let stats = copy_bars(db, changed).await?;
+        report.written_rows = stats.written_rows;
+        report.write_elapsed_ms = u64::try_from(stats.elapsed.as_millis()).unwrap_or(u64::MAX);
+        report.rows_per_sec = stats.rows_per_sec() as u64;
+        smart_watchlists::refresh_after_change(db, WatchlistRuleInput::Bars).await;
+    }
+
+    tracing::info!(
+        dry_run,
+        total_rows = report.total_rows,
+        new_rows = report.new_rows,
+        conflicting_rows = report.conflicting_rows,
+        written_rows = report.written_rows,
+        rows_per_sec = report.rows_per_sec,
+        error_rows = report.total_rows - report.valid_rows,
+        "バーデータの CSV インポートが完了しました"
+    );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn messages(parsed: &ParsedBars) -> Vec<(u64, &str)> {
+        parsed
+            .errors
+            .iter()
+            .map(|e| (e.line, e.message.as_str()))
+            .collect()
+    }
+
+    #[rstest]
+    fn test_parse_default_layout() {
+        let content = indoc! {"
+            instrument_id,date,open,high,low,close,volume
+            7203,2025-01-06,3000,3100,2950,3050,1200000
+            6758,2025-01-06,1500.5,1510,1490,1505.5,800000.0
+        "};
+
+        let parsed = parse_csv(content, &BarCsvLayout::default(), None).unwrap();
+
+        assert_eq!(parsed.total_rows, 2);
+        assert!(parsed.errors.is_empty());
+        assert_eq!(parsed.bars[1].instrument_id, "6758");
+        assert_eq!(parsed.bars[1].close, Decimal::new(15055, 1));
+        assert_eq!(parsed.bars[1].volume, 800_000);
+    }
+
+    #[rstest]
+    fn test_parse_custom_layout_with_fixed_instrument() {
+        let content = indoc! {"
+            日付;始値;高値;安値;終値;出来高
+            2025/01/06;3000;3100;2950;3050;1200000
+        "};
+        let layout = BarCsvLayout {
+            date_column: "日付".to_string(),
+            date_format: "%Y/%m/%d".to_string(),
+            open_column: "始値".to_string(),
+            high_column: "高値".to_string(),
+            low_column: "安値".to_string(),
+            close_column: "終値".to_string(),
+            volume_column: "出来高".to_string(),
+            delimiter: ';',
+            ..Default::default()
+        };
+
+        let parsed = parse_csv(content, &layout, Some("7203")).unwrap();
+
+        assert_eq!(parsed.bars.len(), 1);
+        assert_eq!(parsed.bars[0].instrument_id, "7203");
+        assert_eq!(
+            parsed.bars[0].timestamp.date_naive(),
+            NaiveDate::from_ymd_opt(2025, 1, 6).unwrap()
+        );
+    }
+
+    #[rstest]
+    fn test_parse_reports_invalid_rows() {
+        let content = indoc! {"
+            instrument_id,date,open,high,low,close,volume
+            7203,2025-01-06,3000,3100,2950,3050,100
+            7203,2025-01-06,3000,3100,2950,3050,100
+            7203,06/01/2025,3000,3100,2950,3050,100
+            7203,2025-01-08,3000,2900,2950,3050,100
+            7203,2025-01-09,abc,3100,2950,3050,100
+            7203,2025-01-10,3000,3100,2950,3050,1.5
+            7203,2025-01-11,3000,3100,2950,3050,100
+        "};
+
+        let parsed = parse_csv(content, &BarCsvLayout::default(), None).unwrap();
+
+        assert_eq!(parsed.total_rows, 7);
+        assert_eq!(parsed.bars.len(), 1);
+        assert_eq!(
+            messages(&parsed),
+            vec![
+                (3, "duplicate row for instrument 7203 at 2025-01-06"),
+                (4, "date '06/01/2025' does not match format '%Y-%m-%d'"),
+                (
+                    5,
+                    "high is below open/close: open=3000, high=2900, low=2950, close=3050, volume=100"
+                ),
+                (6, "open is not a number: abc"),
+                (7, "volume is not an integer: 1.5"),
+                (
+                    8,
+                    "date is not a trading day: open=3000, high=3100, low=2950, close=3050, volume=100"
+                ),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::missing_column(
+        indoc! {"
+            instrument_id,date,open,high,low,close
+            7203,2025-01-06,3000,3100,2950,3050
+        "},
+        None
+    )]
+    #[case::missing_instrument_id(
+        indoc! {"
+            date,open,high,low,close,volume
+            2025-01-06,3000,3100,2950,3050,100
+        "},
+        None
+    )]
+    fn test_parse_invalid_header_returns_validation_error(
+        #[case] content: &str,
+        #[case] instrument_id: Option<&str>,
+    ) {
+        let result = parse_csv(content, &BarCsvLayout::default(), instrument_id);
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+}
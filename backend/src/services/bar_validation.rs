@@ -0,0 +1,125 @@
+//! 取り込み時の OHLCV データ品質検証
+//!
+//! DataProvider から取得したバーを `bars` に書き込む前に検証し、
+//! 不正なバーは `bar_anomalies` に隔離してレビューできるようにする。
+
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+
+use crate::calendar;
+use crate::error::AppError;
+use crate::models::{Bar, BarAnomalyReason, Timeframe};
+use crate::repositories::bar_anomalies;
+
+/// バーを検証し、不正と判定した理由を返す (正常なバーは空)
+pub fn validate(bar: &Bar) -> Vec<BarAnomalyReason> {
+    let mut reasons = Vec::new();
+
+    if bar.high < bar.open.max(bar.close) {
+        reasons.push(BarAnomalyReason::HighBelowOpenClose);
+    }
+    if bar.low > bar.open.min(bar.close) {
+        reasons.push(BarAnomalyReason::LowAboveOpenClose);
+    }
+    if [bar.open, bar.high, bar.low, bar.close]
+        .iter()
+        .any(|price| *price <= Decimal::ZERO)
+    {
+        reasons.push(BarAnomalyReason::NonPositivePrice);
+    }
+    if bar.volume < 0 {
+        reasons.push(BarAnomalyReason::NegativeVolume);
+    }
+    if bar.timeframe == Timeframe::Daily && !calendar::is_trading_day(bar.timestamp.date_naive()) {
+        reasons.push(BarAnomalyReason::NonTradingDay);
+    }
+
+    reasons
+}
+
+/// 不正なバーを `bar_anomalies` に隔離し、正常なバーのみを返す
+pub async fn quarantine_invalid_bars(
+    db: &DatabaseConnection,
+    bars: Vec<Bar>,
+) -> Result<Vec<Bar>, AppError> {
+    let mut valid = Vec::with_capacity(bars.len());
+    let mut anomalies = Vec::new();
+
+    for bar in bars {
+        let reasons = validate(&bar);
+        if reasons.is_empty() {
+            valid.push(bar);
+        } else {
+            anomalies.push((bar, reasons));
+        }
+    }
+
+    if !anomalies.is_empty() {
+        tracing::warn!(
+            anomaly_count = anomalies.len(),
+            "不正なバーデータを bar_anomalies に隔離しました"
+        );
+        bar_anomalies::upsert_anomalies(db, anomalies).await?;
+    }
+
+    Ok(valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use rstest::rstest;
+
+    use super::*;
+
+    fn bar(date: (i32, u32, u32), ohlc: [i64; 4], volume: i64) -> Bar {
+        let date = NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap();
+        Bar {
+            instrument_id: "7203".to_string(),
+            timeframe: Timeframe::Daily,
+            timestamp: Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()),
+            open: Decimal::new(ohlc[0], 0),
+            high: Decimal::new(ohlc[1], 0),
+            low: Decimal::new(ohlc[2], 0),
+            close: Decimal::new(ohlc[3], 0),
+            volume,
+            source: None,
+        }
+    }
+
+    #[rstest]
+    #[case::valid(bar((2025, 1, 6), [100, 110, 90, 105], 1000), vec![])]
+    #[case::flat_with_zero_volume(bar((2025, 1, 6), [100, 100, 100, 100], 0), vec![])]
+    #[case::high_below_close(
+        bar((2025, 1, 6), [100, 104, 90, 105], 1000),
+        vec![BarAnomalyReason::HighBelowOpenClose]
+    )]
+    #[case::low_above_open(
+        bar((2025, 1, 6), [100, 110, 101, 105], 1000),
+        vec![BarAnomalyReason::LowAboveOpenClose]
+    )]
+    #[case::zero_price(
+        bar((2025, 1, 6), [0, 110, 0, 105], 1000),
+        vec![BarAnomalyReason::NonPositivePrice]
+    )]
+    #[case::negative_volume(
+        bar((2025, 1, 6), [100, 110, 90, 105], -1),
+        vec![BarAnomalyReason::NegativeVolume]
+    )]
+    #[case::before_supported_calendar(bar((1999, 1, 11), [100, 110, 90, 105], 1000), vec![])]
+    #[case::holiday(
+        bar((2025, 1, 13), [100, 110, 90, 105], 1000),
+        vec![BarAnomalyReason::NonTradingDay]
+    )]
+    #[case::multiple_reasons(
+        bar((2025, 1, 4), [100, 90, 110, 105], 1000),
+        vec![
+            BarAnomalyReason::HighBelowOpenClose,
+            BarAnomalyReason::LowAboveOpenClose,
+            BarAnomalyReason::NonTradingDay,
+        ]
+    )]
+    fn test_validate(#[case] bar: Bar, #[case] expected: Vec<BarAnomalyReason>) {
+        assert_eq!(validate(&bar), expected);
+    }
+}
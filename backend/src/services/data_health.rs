@@ -0,0 +1,341 @@
+//! バーデータの欠損検出と修復
+//!
+//! ウォッチリストに含まれる銘柄について、JPX カレンダー上の営業日と
+//! 保存されている日足を突き合わせて欠損日を求める。
+
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{Duration, NaiveDate};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use crate::calendar;
+use crate::data_provider::{DataProvider, DateRange};
+use crate::entities::{bars, instruments, watchlist_items};
+use crate::error::AppError;
+use crate::models::{DataHealthReport, InstrumentDataHealth, RepairTarget, Timeframe};
+use crate::repositories;
+use crate::services::backfill;
+
+/// ウォッチリストに含まれる銘柄の欠損レポートを作成する
+pub async fn build_report(
+    db: &DatabaseConnection,
+    range: &DateRange,
+) -> Result<DataHealthReport, AppError> {
+    let expected = trading_days(range);
+
+    let watched = instruments::Entity::find()
+        .inner_join(watchlist_items::Entity)
+        .distinct()
+        .order_by_asc(instruments::Column::Id)
+        .all(db)
+        .await?;
+
+    let mut stored: BTreeMap<String, HashSet<NaiveDate>> = BTreeMap::new();
+    if !watched.is_empty() {
+        let ids: Vec<String> = watched.iter().map(|i| i.id.clone()).collect();
+        let rows: Vec<(String, DateTimeWithTimeZone)> = bars::Entity::find()
+            .select_only()
+            .column(bars::Column::InstrumentId)
+            .column(bars::Column::Timestamp)
+            .filter(bars::Column::InstrumentId.is_in(ids))
+            .filter(bars::Column::Timeframe.eq(Timeframe::Daily.to_string()))
+            .filter(bars::Column::Timestamp.gte(start_of_day(range.from)))
+            .filter(bars::Column::Timestamp.lt(start_of_day(range.to + Duration::days(1))))
+            .into_tuple()
+            .all(db)
+            .await?;
+        for (instrument_id, timestamp) in rows {
+            stored
+                .entry(instrument_id)
+                .or_default()
+                .insert(timestamp.date_naive());
+        }
+    }
+
+    let instruments = watched
+        .into_iter()
+        .map(|instrument| {
+            let stored_dates = stored.remove(&instrument.id).unwrap_or_default();
+            let missing_dates: Vec<NaiveDate> = expected
+                .iter()
+                .filter(|date| !stored_dates.contains(date))
+                .copied()
+                .collect();
+            InstrumentDataHealth {
+                stored_days: expected.len() - missing_dates.len(),
+                instrument_id: instrument.id,
+                name: instrument.name,
+                missing_dates,
+                bars_updated_at: instrument.bars_updated_at,
+            }
+        })
+        .collect();
+
+    Ok(DataHealthReport {
+        from: range.from,
+        to: range.to,
+        expected_days: expected.len(),
+        instruments,
+    })
+}
+
+/// 1 銘柄の期間内の営業日のうち、日足が保存されていない日を求める (昇順)
+pub async fn missing_dates(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<Vec<NaiveDate>, AppError> {
+    let stored: HashSet<NaiveDate> = bars::Entity::find()
+        .select_only()
+        .column(bars::Column::Timestamp)
+        .filter(bars::Column::InstrumentId.eq(instrument_id))
+        .filter(bars::Column::Timeframe.eq(Timeframe::Daily.to_string()))
+        .filter(bars::Column::Timestamp.gte(start_of_day(range.from)))
+        .filter(bars::Column::Timestamp.lt(start_of_day(range.to + Duration::days(1))))
+        .into_tuple::<DateTimeWithTimeZone>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|timestamp| timestamp.date_naive())
+        .collect();
+
+    Ok(trading_days(range)
+        .into_iter()
+        .filter(|date| !stored.contains(date))
+        .collect())
+}
+
+/// 欠損のある銘柄ごとに、最初の欠損日から最後の欠損日までのバックフィル対象を求める
+///
+/// `instrument_ids` を指定した場合はその銘柄のみを対象にする。
+pub fn repair_targets(
+    report: &DataHealthReport,
+    instrument_ids: Option<&[String]>,
+) -> Vec<RepairTarget> {
+    report
+        .instruments
+        .iter()
+        .filter(|health| instrument_ids.is_none_or(|ids| ids.contains(&health.instrument_id)))
+        .filter_map(|health| {
+            Some(RepairTarget {
+                instrument_id: health.instrument_id.clone(),
+                from: *health.missing_dates.first()?,
+                to: *health.missing_dates.last()?,
+                missing_days: health.missing_dates.len(),
+            })
+        })
+        .collect()
+}
+
+/// バックフィル対象を順にバックフィルする
+///
+/// 欠損日がデータのない日としてキャッシュ (`data_provider_cache`) に記録されていても
+/// 取得し直すよう、対象期間の記録を削除してから取得する。
+/// バックグラウンドタスクとして呼ばれるため、エラーはバックフィル内でログ出力のみ行う。
+pub async fn repair(
+    db: &DatabaseConnection,
+    data_provider: &dyn DataProvider,
+    targets: Vec<RepairTarget>,
+) {
+    for target in targets {
+        let range = DateRange {
+            from: target.from,
+            to: target.to,
+        };
+        if let Err(e) =
+            repositories::data_provider_cache::invalidate_daily(db, &target.instrument_id, &range)
+                .await
+        {
+            tracing::warn!(
+                instrument_id = target.instrument_id,
+                error = %e,
+                "キャッシュの削除に失敗しました"
+            );
+        }
+        backfill::backfill_daily_bars_in_range(db, data_provider, &target.instrument_id, &range)
+            .await;
+    }
+}
+
+/// 期間内の東証の営業日 (昇順)
+fn trading_days(range: &DateRange) -> Vec<NaiveDate> {
+    range
+        .from
+        .iter_days()
+        .take_while(|date| *date <= range.to)
+        .filter(|date| calendar::is_trading_day(*date))
+        .collect()
+}
+
+fn start_of_day(date: NaiveDate) -> DateTimeWithTimeZone {
+    date.and_time(chrono::NaiveTime::MIN)
+        .and_utc()
+        .fixed_offset()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use sea_orm::{ActiveModelTrait, Set};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::data_provider::caching::CachingProvider;
+    use crate::data_provider::mock::MockDataProvider;
+    use crate::entities::{data_provider_cache, watchlists};
+    use crate::models::Bar;
+    use crate::models::instrument::{Instrument, Market};
+    use crate::repositories;
+    use crate::testing::create_test_db;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    fn make_bar(instrument_id: &str, date: NaiveDate) -> Bar {
+        Bar {
+            instrument_id: instrument_id.to_string(),
+            timeframe: Timeframe::Daily,
+            timestamp: Utc.from_utc_datetime(&date.and_time(chrono::NaiveTime::MIN)),
+            open: Decimal::new(100, 0),
+            high: Decimal::new(110, 0),
+            low: Decimal::new(90, 0),
+            close: Decimal::new(100, 0),
+            volume: 1000,
+            source: None,
+        }
+    }
+
+    /// ウォッチリストに銘柄を登録する
+    async fn watch(db: &DatabaseConnection, instrument_ids: &[&str]) {
+        let watchlist = watchlists::ActiveModel {
+            id: Set(uuid::Uuid::from_u128(1)),
+            name: Set("テスト".to_string()),
+            sort_order: Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        for instrument_id in instrument_ids {
+            repositories::instruments::ensure_exists(db, instrument_id, instrument_id.to_string())
+                .await
+                .unwrap();
+            repositories::watchlist_items::insert_item(db, watchlist.id, instrument_id, None)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn build_report_lists_missing_trading_days(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        watch(&db, &["7203", "6758"]).await;
+        // ウォッチリストに含まれない銘柄は対象外
+        repositories::instruments::ensure_exists(&db, "9984", "9984".to_string())
+            .await
+            .unwrap();
+        repositories::bars::upsert_bars(
+            &db,
+            vec![
+                make_bar("7203", date(6)),
+                make_bar("7203", date(8)),
+                make_bar("9984", date(6)),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // 2025-01-06 ~ 01-13: 営業日は 6, 7, 8, 9, 10 (11, 12 は週末, 13 は成人の日)
+        let range = DateRange {
+            from: date(6),
+            to: date(13),
+        };
+        let report = build_report(&db, &range).await.unwrap();
+
+        assert_eq!(report.expected_days, 5);
+        let summary: Vec<(&str, usize, Vec<NaiveDate>, bool)> = report
+            .instruments
+            .iter()
+            .map(|h| {
+                (
+                    h.instrument_id.as_str(),
+                    h.stored_days,
+                    h.missing_dates.clone(),
+                    h.bars_updated_at.is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "6758",
+                    0,
+                    vec![date(6), date(7), date(8), date(9), date(10)],
+                    false
+                ),
+                ("7203", 2, vec![date(7), date(9), date(10)], true),
+            ]
+        );
+
+        assert_eq!(
+            repair_targets(&report, Some(&["7203".to_string()])),
+            vec![RepairTarget {
+                instrument_id: "7203".to_string(),
+                from: date(7),
+                to: date(10),
+                missing_days: 3,
+            }]
+        );
+        // ウォッチリストに含まれない銘柄も個別に欠損日を求められる
+        assert_eq!(
+            missing_dates(&db, "9984", &range).await.unwrap(),
+            vec![date(7), date(8), date(9), date(10)]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn repair_backfills_missing_days(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        watch(&db, &["7203"]).await;
+        repositories::bars::upsert_bars(&db, vec![make_bar("7203", date(6))])
+            .await
+            .unwrap();
+        let provider = MockDataProvider::new()
+            .with_instruments(vec![Instrument {
+                id: "7203".to_string(),
+                name: "トヨタ自動車".to_string(),
+                market: Market::Tse,
+                sector: None,
+                market_segment: None,
+            }])
+            .with_bars((6..=10).map(|day| make_bar("7203", date(day))).collect());
+        // 以前の取得でデータがなかった期間としてキャッシュに記録されていても取得し直す
+        data_provider_cache::ActiveModel {
+            instrument_id: Set("7203".to_string()),
+            timeframe: Set(Timeframe::Daily.to_string()),
+            from_date: Set(date(6)),
+            to_date: Set(date(10)),
+            bars: Set(serde_json::json!([])),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let provider = CachingProvider::new(db.clone(), provider);
+
+        let range = DateRange {
+            from: date(6),
+            to: date(10),
+        };
+        let report = build_report(&db, &range).await.unwrap();
+        repair(&db, &provider, repair_targets(&report, None)).await;
+
+        let report = build_report(&db, &range).await.unwrap();
+        assert_eq!(report.instruments[0].stored_days, 5);
+        assert!(report.instruments[0].missing_dates.is_empty());
+    }
+}
@@ -0,0 +1,523 @@
+//! バー取得時の欠損取得ジョブ
+//!
+//! `GET /api/bars?ensure=true` で、保存されていない日足をデータプロバイダーから取得する。
+//! 取得は data_fetch_jobs テーブルに記録したジョブとして実行し、リクエストが待ちきれずに
+//! 応答を返した後もバックグラウンドで続行する。進捗は `GET /api/jobs/{id}` で確認できる。
+//! 終了したジョブは `data_fetch_jobs_prune` ジョブが古いものから削除する。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::data_provider::priority::{RequestPriority, with_priority};
+use crate::data_provider::{DataProvider, DateRange};
+use crate::entities::{data_fetch_jobs, instruments};
+use crate::error::AppError;
+use crate::models::{FetchJob, FetchJobStatus};
+use crate::repositories;
+use crate::services::{backfill, data_health};
+
+/// 実行中のジョブを再利用する期間
+///
+/// これより前に作成された実行中のジョブは、プロセスの停止等で終了を記録できなかったものとみなし、
+/// 失敗として記録する (`data_fetch_jobs_prune` ジョブの `stale_after_minutes` と合わせる)。
+const STALE_JOB_AFTER: chrono::Duration = chrono::Duration::minutes(10);
+
+/// 取得に成功したジョブの期間を、日足がなくても取得済みとみなす期間
+///
+/// 取得してもバーが保存されない日 (J-Quants Free プランの 12 週間の遅延、上場前、売買停止、
+/// 隔離されたバー) を、チャートを開くたびに取得し直さないようにする。
+/// この期間を過ぎたら、配信された可能性があるため再び取得する。
+const ATTEMPTED_RANGE_TTL: chrono::Duration = chrono::Duration::hours(6);
+
+/// 他のリクエストが開始したジョブの終了を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 開始したジョブ
+pub enum StartedJob {
+    /// 新しく開始したジョブと、その実行タスク (保存したバーの数を返す)
+    Spawned(FetchJob, JoinHandle<Result<usize, AppError>>),
+    /// 同じ銘柄・期間を取得中の既存のジョブ
+    Running(FetchJob),
+}
+
+/// ジョブを待った結果
+pub enum JobOutcome {
+    /// 取得したバーを保存した
+    Completed,
+    /// 待ち時間内に終了しなかった (ジョブはバックグラウンドで続行する)
+    Pending(FetchJob),
+}
+
+/// 期間内の営業日のうち日足が保存されていない日について、最初の欠損日から最後の欠損日までを求める
+///
+/// [`ATTEMPTED_RANGE_TTL`] 以内に成功したジョブが取得した日は、日足がなくても欠損とみなさない。
+/// 欠損がない場合は None を返す。
+pub async fn uncovered_range(
+    db: &DatabaseConnection,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<Option<DateRange>, AppError> {
+    let attempted = repositories::data_fetch_jobs::find_succeeded_ranges(
+        db,
+        instrument_id,
+        range,
+        Utc::now() - ATTEMPTED_RANGE_TTL,
+    )
+    .await?;
+    let missing: Vec<_> = data_health::missing_dates(db, instrument_id, range)
+        .await?
+        .into_iter()
+        .filter(|date| {
+            !attempted
+                .iter()
+                .any(|attempted| attempted.from <= *date && *date <= attempted.to)
+        })
+        .collect();
+    Ok(missing
+        .first()
+        .zip(missing.last())
+        .map(|(from, to)| DateRange {
+            from: *from,
+            to: *to,
+        }))
+}
+
+/// 指定銘柄・期間の日足を取得するジョブを開始する
+///
+/// 同じ銘柄で `range` 全体を取得中のジョブがあれば、新しく開始せずにそれを返す。
+/// 同時に同じ銘柄・期間のジョブが開始された場合も、先に開始されたジョブを返す。
+/// ジョブはユーザーが応答を待っているリクエストとして、優先度 `Interactive` で取得する。
+pub async fn start(
+    db: &DatabaseConnection,
+    data_provider: Arc<dyn DataProvider>,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<StartedJob, AppError> {
+    let abandoned =
+        repositories::data_fetch_jobs::fail_stale(db, Utc::now() - STALE_JOB_AFTER).await?;
+    if abandoned > 0 {
+        tracing::warn!(
+            abandoned,
+            "終了を記録できなかった欠損取得ジョブを失敗にしました"
+        );
+    }
+
+    if let Some(job) =
+        repositories::data_fetch_jobs::find_running_covering(db, instrument_id, range).await?
+    {
+        return Ok(StartedJob::Running(job.try_into()?));
+    }
+
+    let Some(job) = repositories::data_fetch_jobs::insert_running(db, instrument_id, range).await?
+    else {
+        // 同じ銘柄・期間のジョブが先に作成された (すでに終了していればその結果を返す)
+        let job = repositories::data_fetch_jobs::find_latest(db, instrument_id, range)
+            .await?
+            .ok_or_else(|| {
+                AppError::Internal(format!(
+                    "fetch job for {instrument_id} conflicted but was not found"
+                ))
+            })?;
+        return Ok(StartedJob::Running(job.try_into()?));
+    };
+    let job: FetchJob = job.try_into()?;
+
+    let db = db.clone();
+    let job_id = job.id;
+    let instrument_id = instrument_id.to_string();
+    let range = range.clone();
+    let handle = tokio::spawn(with_priority(RequestPriority::Interactive, async move {
+        run(&db, data_provider.as_ref(), job_id, &instrument_id, &range).await
+    }));
+
+    Ok(StartedJob::Spawned(job, handle))
+}
+
+/// ジョブの終了を最大 `timeout` 待つ
+///
+/// ジョブが失敗した場合はそのエラーを返す。
+pub async fn wait(
+    db: &DatabaseConnection,
+    job: StartedJob,
+    timeout: Duration,
+) -> Result<JobOutcome, AppError> {
+    match job {
+        StartedJob::Spawned(job, handle) => match tokio::time::timeout(timeout, handle).await {
+            Ok(Ok(result)) => result.map(|_| JobOutcome::Completed),
+            Ok(Err(e)) => Err(AppError::Internal(format!(
+                "fetch job {} panicked: {e}",
+                job.id
+            ))),
+            Err(_) => Ok(JobOutcome::Pending(job)),
+        },
+        StartedJob::Running(job) => {
+            let deadline = Instant::now() + timeout;
+            let mut job = job;
+            loop {
+                match job.status {
+                    FetchJobStatus::Succeeded => return Ok(JobOutcome::Completed),
+                    FetchJobStatus::Failed => {
+                        return Err(AppError::ServiceUnavailable(format!(
+                            "fetch job {} failed: {}",
+                            job.id,
+                            job.error.as_deref().unwrap_or("unknown error")
+                        )));
+                    }
+                    FetchJobStatus::Running if Instant::now() >= deadline => {
+                        return Ok(JobOutcome::Pending(job));
+                    }
+                    FetchJobStatus::Running => {}
+                }
+                tokio::time::sleep_until(deadline.min(Instant::now() + POLL_INTERVAL)).await;
+                job = find(db, job.id).await?;
+            }
+        }
+    }
+}
+
+/// ジョブを取得する (存在しない場合は 404 エラー)
+pub async fn find(db: &DatabaseConnection, id: Uuid) -> Result<FetchJob, AppError> {
+    data_fetch_jobs::Entity::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("job {id} not found")))?
+        .try_into()
+}
+
+/// ジョブを実行し、結果を data_fetch_jobs テーブルに記録する
+async fn run(
+    db: &DatabaseConnection,
+    data_provider: &dyn DataProvider,
+    job_id: Uuid,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<usize, AppError> {
+    let result = fetch(db, data_provider, instrument_id, range).await;
+
+    let (status, bar_count, error) = match &result {
+        Ok(bar_count) => (
+            FetchJobStatus::Succeeded,
+            Some(i32::try_from(*bar_count).unwrap_or(i32::MAX)),
+            None,
+        ),
+        Err(e) => {
+            tracing::warn!(%job_id, instrument_id, error = %e, "欠損の取得に失敗しました");
+            (FetchJobStatus::Failed, None, Some(e.to_string()))
+        }
+    };
+    if let Err(e) =
+        repositories::data_fetch_jobs::finish(db, job_id, status, bar_count, error).await
+    {
+        tracing::error!(%job_id, error = %e, "欠損取得ジョブの結果を記録できませんでした");
+    }
+
+    result
+}
+
+/// 銘柄が未登録ならデータプロバイダーの銘柄情報で登録し、日足を取得して保存する
+///
+/// 欠損とみなした期間はデータプロバイダーに問い合わせ直すため、先にキャッシュの取得済み期間の
+/// 記録を削除する (キャッシュはデータのない日を [`ATTEMPTED_RANGE_TTL`] より長く取得済みとみなす)。
+async fn fetch(
+    db: &DatabaseConnection,
+    data_provider: &dyn DataProvider,
+    instrument_id: &str,
+    range: &DateRange,
+) -> Result<usize, AppError> {
+    if instruments::Entity::find_by_id(instrument_id)
+        .one(db)
+        .await?
+        .is_none()
+    {
+        let instrument = data_provider.fetch_instrument(instrument_id).await?;
+        repositories::instruments::ensure_exists(db, instrument_id, instrument.name).await?;
+    }
+
+    if let Err(e) =
+        repositories::data_provider_cache::invalidate_daily(db, instrument_id, range).await
+    {
+        tracing::warn!(instrument_id, error = %e, "キャッシュの削除に失敗しました");
+    }
+    backfill::fetch_and_store_daily_bars(db, data_provider, instrument_id, range).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use sea_orm::ConnectionTrait;
+    use sea_orm::sea_query::Expr;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::data_provider::mock::{MockDataProvider, date, make_bar, sample_instrument};
+    use crate::testing::create_test_db;
+
+    fn range(from: NaiveDate, to: NaiveDate) -> DateRange {
+        DateRange { from, to }
+    }
+
+    /// 01-`from` ~ 01-10 を取得する実行中のジョブを作成する
+    async fn insert_job(db: &DatabaseConnection, from: u32) -> data_fetch_jobs::Model {
+        repositories::data_fetch_jobs::insert_running(
+            db,
+            "72030",
+            &range(date(2025, 1, from), date(2025, 1, 10)),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn uncovered_range_spans_first_to_last_missing_day(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        repositories::instruments::ensure_exists(&db, "72030", "トヨタ自動車".to_string())
+            .await
+            .unwrap();
+        repositories::bars::upsert_bars(
+            &db,
+            vec![
+                make_bar("72030", date(2025, 1, 6), 100),
+                make_bar("72030", date(2025, 1, 10), 100),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // 2025-01-06 ~ 01-10 は全て営業日
+        let uncovered = uncovered_range(&db, "72030", &range(date(2025, 1, 6), date(2025, 1, 10)))
+            .await
+            .unwrap();
+        assert_eq!(uncovered, Some(range(date(2025, 1, 7), date(2025, 1, 9))));
+
+        let covered = uncovered_range(&db, "72030", &range(date(2025, 1, 10), date(2025, 1, 13)))
+            .await
+            .unwrap();
+        assert_eq!(covered, None);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn uncovered_range_skips_days_attempted_by_recent_job(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        // 01-07 ~ 01-09 のバーは配信されていない (遅延や売買停止等)
+        let provider = MockDataProvider::new()
+            .with_instruments(vec![sample_instrument("72030")])
+            .with_bars(vec![
+                make_bar("72030", date(2025, 1, 6), 100),
+                make_bar("72030", date(2025, 1, 10), 100),
+            ]);
+        let attempted = range(date(2025, 1, 6), date(2025, 1, 10));
+
+        let started = start(&db, Arc::new(provider), "72030", &attempted)
+            .await
+            .unwrap();
+        wait(&db, started, Duration::from_secs(5)).await.unwrap();
+
+        assert_eq!(
+            uncovered_range(&db, "72030", &attempted).await.unwrap(),
+            None
+        );
+        // 取得を試みていない日は欠損として扱う
+        let wider = range(date(2025, 1, 6), date(2025, 1, 14));
+        assert_eq!(
+            uncovered_range(&db, "72030", &wider).await.unwrap(),
+            Some(range(date(2025, 1, 14), date(2025, 1, 14)))
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn job_registers_instrument_and_records_result(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let provider = MockDataProvider::new()
+            .with_instruments(vec![sample_instrument("72030")])
+            .with_bars(vec![
+                make_bar("72030", date(2025, 1, 6), 100),
+                make_bar("72030", date(2025, 1, 7), 100),
+            ]);
+        let range = range(date(2025, 1, 6), date(2025, 1, 7));
+
+        let started = start(&db, Arc::new(provider), "72030", &range)
+            .await
+            .unwrap();
+        let StartedJob::Spawned(job, _) = &started else {
+            panic!("expected a new job");
+        };
+        let job_id = job.id;
+        assert!(matches!(
+            wait(&db, started, Duration::from_secs(5)).await.unwrap(),
+            JobOutcome::Completed
+        ));
+
+        let job = find(&db, job_id).await.unwrap();
+        assert_eq!(job.status, FetchJobStatus::Succeeded);
+        assert_eq!(job.bar_count, Some(2));
+        assert!(job.finished_at.is_some());
+        assert!(
+            instruments::Entity::find_by_id("72030")
+                .one(&db)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn running_job_is_reused_for_covered_range(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let provider: Arc<dyn DataProvider> = Arc::new(
+            MockDataProvider::new()
+                .with_instruments(vec![sample_instrument("72030")])
+                .with_delay(Duration::from_secs(60)),
+        );
+
+        let first = start(
+            &db,
+            Arc::clone(&provider),
+            "72030",
+            &range(date(2025, 1, 6), date(2025, 1, 10)),
+        )
+        .await
+        .unwrap();
+        let second = start(
+            &db,
+            provider,
+            "72030",
+            &range(date(2025, 1, 7), date(2025, 1, 8)),
+        )
+        .await
+        .unwrap();
+
+        let (StartedJob::Spawned(first, _), StartedJob::Running(second)) = (first, second) else {
+            panic!("expected the running job to be reused");
+        };
+        assert_eq!(second.id, first.id);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn concurrent_start_for_same_range_reuses_job(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let range = range(date(2025, 1, 6), date(2025, 1, 10));
+        // 別のリクエストが find_running_covering の後に同じ期間のジョブを作成した
+        let first = repositories::data_fetch_jobs::insert_running(&db, "72030", &range)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            repositories::data_fetch_jobs::insert_running(&db, "72030", &range)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let started = start(&db, Arc::new(MockDataProvider::new()), "72030", &range)
+            .await
+            .unwrap();
+
+        let StartedJob::Running(job) = started else {
+            panic!("expected the running job to be reused");
+        };
+        assert_eq!(job.id, first.id);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn stale_running_job_is_marked_failed(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let range = range(date(2025, 1, 6), date(2025, 1, 10));
+        let orphan = repositories::data_fetch_jobs::insert_running(&db, "72030", &range)
+            .await
+            .unwrap()
+            .unwrap();
+        // プロセスの停止で終了を記録できなかったジョブ
+        data_fetch_jobs::Entity::update_many()
+            .col_expr(
+                data_fetch_jobs::Column::CreatedAt,
+                Expr::value((Utc::now() - STALE_JOB_AFTER * 2).fixed_offset()),
+            )
+            .exec(&db)
+            .await
+            .unwrap();
+
+        let started = start(
+            &db,
+            Arc::new(MockDataProvider::new().with_delay(Duration::from_secs(60))),
+            "72030",
+            &range,
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(started, StartedJob::Spawned(..)));
+        let orphan = find(&db, orphan.id).await.unwrap();
+        assert_eq!(orphan.status, FetchJobStatus::Failed);
+        assert_eq!(
+            orphan.error.as_deref(),
+            Some(repositories::data_fetch_jobs::ABANDONED_ERROR)
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn prune_job_fails_stale_and_deletes_old_jobs(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        let finished = insert_job(&db, 6).await;
+        let stale = insert_job(&db, 7).await;
+        let running = insert_job(&db, 8).await;
+        db.execute_unprepared(&format!(
+            "UPDATE data_fetch_jobs SET status = 'succeeded', finished_at = now() - INTERVAL '8 days' \
+             WHERE id = '{}'; \
+             UPDATE data_fetch_jobs SET created_at = now() - INTERVAL '11 minutes' WHERE id = '{}'",
+            finished.id, stale.id
+        ))
+        .await
+        .unwrap();
+
+        db.execute_unprepared(
+            "CALL data_fetch_jobs_prune(0, '{\"max_age_days\": 7, \"stale_after_minutes\": 10}')",
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            find(&db, finished.id).await,
+            Err(AppError::NotFound(_))
+        ));
+        assert_eq!(
+            find(&db, stale.id).await.unwrap().status,
+            FetchJobStatus::Failed
+        );
+        assert_eq!(
+            find(&db, running.id).await.unwrap().status,
+            FetchJobStatus::Running
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn failed_job_records_error(pool: PgPool) {
+        let db = create_test_db(pool).await;
+
+        let started = start(
+            &db,
+            Arc::new(MockDataProvider::new()),
+            "99990",
+            &range(date(2025, 1, 6), date(2025, 1, 10)),
+        )
+        .await
+        .unwrap();
+        let StartedJob::Spawned(job, _) = &started else {
+            panic!("expected a new job");
+        };
+        let job_id = job.id;
+
+        let result = wait(&db, started, Duration::from_secs(5)).await;
+
+        assert!(matches!(result, Err(AppError::DataProvider(_))));
+        let job = find(&db, job_id).await.unwrap();
+        assert_eq!(job.status, FetchJobStatus::Failed);
+        assert!(job.error.unwrap().contains("99990"));
+    }
+}
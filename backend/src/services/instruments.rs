@@ -0,0 +1,96 @@
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, sea_query::Expr};
+
+use crate::data_provider::DataProvider;
+use crate::entities::instruments;
+use crate::error::AppError;
+use crate::models::WatchlistRuleInput;
+use crate::services::smart_watchlists;
+
+/// DataProvider から銘柄情報を取得し、instruments テーブルの銘柄名・業種・市場区分を更新する
+///
+/// 業種や市場区分が変わった場合はスマートウォッチリストの銘柄を再計算する。
+/// instruments テーブルに存在しない銘柄は作成しない。
+pub async fn sync_instrument_metadata(
+    db: &DatabaseConnection,
+    data_provider: &dyn DataProvider,
+    instrument_id: &str,
+) -> Result<(), AppError> {
+    let Some(current) = instruments::Entity::find_by_id(instrument_id)
+        .one(db)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let fetched = data_provider.fetch_instrument(instrument_id).await?;
+
+    let classification_changed =
+        current.sector != fetched.sector || current.market_segment != fetched.market_segment;
+    if current.name == fetched.name && !classification_changed {
+        return Ok(());
+    }
+
+    instruments::Entity::update_many()
+        .col_expr(instruments::Column::Name, Expr::value(fetched.name))
+        .col_expr(instruments::Column::Sector, Expr::value(fetched.sector))
+        .col_expr(
+            instruments::Column::MarketSegment,
+            Expr::value(fetched.market_segment),
+        )
+        .filter(instruments::Column::Id.eq(instrument_id))
+        .exec(db)
+        .await?;
+
+    if classification_changed {
+        smart_watchlists::refresh_affected(db, WatchlistRuleInput::Instruments).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ActiveModelTrait, Set};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::data_provider::mock::MockDataProvider;
+    use crate::models::instrument::{Instrument, Market};
+    use crate::testing::create_test_db;
+
+    #[sqlx::test(migrations = false)]
+    async fn sync_instrument_metadata_updates_existing_instrument(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        instruments::ActiveModel {
+            id: Set("7203".to_string()),
+            name: Set("7203".to_string()),
+            market: Set("TSE".to_string()),
+            sector: Set(None),
+            market_segment: Set(None),
+            bars_updated_at: Set(None),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        let provider = MockDataProvider::new().with_instruments(vec![Instrument {
+            id: "7203".to_string(),
+            name: "トヨタ自動車".to_string(),
+            market: Market::Tse,
+            sector: Some("輸送用機器".to_string()),
+            market_segment: Some("プライム".to_string()),
+        }]);
+
+        sync_instrument_metadata(&db, &provider, "7203")
+            .await
+            .unwrap();
+
+        let instrument = instruments::Entity::find_by_id("7203")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(instrument.name, "トヨタ自動車");
+        assert_eq!(instrument.sector.as_deref(), Some("輸送用機器"));
+        assert_eq!(instrument.market_segment.as_deref(), Some("プライム"));
+    }
+}
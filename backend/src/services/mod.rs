@@ -1 +1,11 @@
 pub mod backfill;
+pub mod bar_alignment;
+pub mod bar_export;
+pub mod bar_import;
+pub mod bar_validation;
+pub mod data_health;
+pub mod fetch_jobs;
+pub mod instruments;
+pub mod price_alerts;
+pub mod smart_watchlists;
+pub mod watchlist_transfer;
@@ -0,0 +1,99 @@
+//! ウォッチリスト項目の価格閾値 (entry/target/stop) の判定
+
+use crate::entities::{bars, watchlist_items};
+use crate::models::{PriceAlert, PriceAlertKind};
+
+/// 日足 1 本に対して、ウォッチリスト項目の価格閾値に到達したものを返す
+///
+/// - entry: 安値〜高値の範囲にエントリー価格が含まれる
+/// - target: 高値が目標価格以上
+/// - stop: 安値が損切り価格以下
+pub fn evaluate(item: &watchlist_items::Model, bar: &bars::Model) -> Vec<PriceAlert> {
+    let thresholds = [
+        (PriceAlertKind::Entry, item.entry_price),
+        (PriceAlertKind::Target, item.target_price),
+        (PriceAlertKind::Stop, item.stop_price),
+    ];
+
+    thresholds
+        .into_iter()
+        .filter_map(|(kind, threshold)| {
+            let threshold = threshold?;
+            let reached = match kind {
+                PriceAlertKind::Entry => bar.low <= threshold && threshold <= bar.high,
+                PriceAlertKind::Target => bar.high >= threshold,
+                PriceAlertKind::Stop => bar.low <= threshold,
+            };
+            reached.then(|| PriceAlert {
+                instrument_id: item.instrument_id.clone(),
+                kind,
+                threshold,
+                timestamp: bar.timestamp.to_utc(),
+                close: bar.close,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn item(
+        entry_price: Option<i64>,
+        target_price: Option<i64>,
+        stop_price: Option<i64>,
+    ) -> watchlist_items::Model {
+        watchlist_items::Model {
+            watchlist_id: Uuid::nil(),
+            instrument_id: "7203".to_string(),
+            sort_order: 0,
+            added_at: Utc.with_ymd_and_hms(2025, 1, 6, 0, 0, 0).unwrap().into(),
+            memo: None,
+            tags: serde_json::json!([]),
+            entry_price: entry_price.map(|p| Decimal::new(p, 0)),
+            target_price: target_price.map(|p| Decimal::new(p, 0)),
+            stop_price: stop_price.map(|p| Decimal::new(p, 0)),
+        }
+    }
+
+    /// 安値 2900, 高値 3100, 終値 3000 の日足
+    fn bar() -> bars::Model {
+        bars::Model {
+            instrument_id: "7203".to_string(),
+            timeframe: "1d".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 7, 0, 0, 0).unwrap().into(),
+            open: Decimal::new(2950, 0),
+            high: Decimal::new(3100, 0),
+            low: Decimal::new(2900, 0),
+            close: Decimal::new(3000, 0),
+            volume: 1000,
+            source: None,
+        }
+    }
+
+    #[rstest]
+    #[case::no_thresholds(item(None, None, None), vec![])]
+    #[case::entry_within_range(item(Some(2950), None, None), vec![PriceAlertKind::Entry])]
+    #[case::entry_out_of_range(item(Some(2800), None, None), vec![])]
+    #[case::target_reached(item(None, Some(3100), None), vec![PriceAlertKind::Target])]
+    #[case::target_not_reached(item(None, Some(3200), None), vec![])]
+    #[case::stop_reached(item(None, None, Some(2950)), vec![PriceAlertKind::Stop])]
+    #[case::stop_not_reached(item(None, None, Some(2800)), vec![])]
+    #[case::multiple(
+        item(Some(3000), Some(3050), Some(2900)),
+        vec![PriceAlertKind::Entry, PriceAlertKind::Target, PriceAlertKind::Stop]
+    )]
+    fn test_evaluate(#[case] item: watchlist_items::Model, #[case] expected: Vec<PriceAlertKind>) {
+        let kinds: Vec<PriceAlertKind> = evaluate(&item, &bar())
+            .into_iter()
+            .map(|alert| alert.kind)
+            .collect();
+        assert_eq!(kinds, expected);
+    }
+}
@@ -0,0 +1,434 @@
+//! スマート (ルールベース) ウォッチリストの銘柄再計算
+//!
+//! スマートウォッチリストの銘柄も watchlist_items に保持し、一覧系のエンドポイントからは
+//! 通常のウォッチリストと区別なく扱えるようにする。ルールの評価対象は instruments
+//! テーブルに登録済みの銘柄で、ルールが参照するデータ (銘柄情報・日足・保有銘柄) が
+//! 変わるたびに再計算する。
+
+use std::collections::HashSet;
+
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+
+use crate::entities::{instruments, watchlist_items, watchlists};
+use crate::error::AppError;
+use crate::models::{WatchlistKind, WatchlistRule, WatchlistRuleInput};
+
+/// ウォッチリストの銘柄選定ルールを取得する (static の場合は None)
+pub fn watchlist_rule(watchlist: &watchlists::Model) -> Result<Option<WatchlistRule>, AppError> {
+    if watchlist.kind != WatchlistKind::Smart.as_str() {
+        return Ok(None);
+    }
+
+    let rule = watchlist.rule.clone().ok_or_else(|| {
+        AppError::Internal(format!("smart watchlist {} has no rule", watchlist.id))
+    })?;
+
+    serde_json::from_value(rule).map(Some).map_err(|e| {
+        AppError::Internal(format!(
+            "invalid rule for smart watchlist {}: {e}",
+            watchlist.id
+        ))
+    })
+}
+
+/// ルールを instruments テーブルに対する条件に変換する
+fn rule_condition(rule: &WatchlistRule) -> Condition {
+    match rule {
+        WatchlistRule::Sector { sector } => {
+            Condition::all().add(instruments::Column::Sector.eq(sector))
+        }
+        WatchlistRule::MarketSegment { market_segment } => {
+            Condition::all().add(instruments::Column::MarketSegment.eq(market_segment))
+        }
+        WatchlistRule::Screen {
+            sector,
+            market_segment,
+            min_close,
+            max_close,
+        } => Condition::all()
+            .add_option(sector.as_ref().map(|s| instruments::Column::Sector.eq(s)))
+            .add_option(
+                market_segment
+                    .as_ref()
+                    .map(|s| instruments::Column::MarketSegment.eq(s)),
+            )
+            .add_option((min_close.is_some() || max_close.is_some()).then(|| {
+                // 銘柄ごとの最新の日足の終値で絞り込む
+                Expr::cust_with_values(
+                    "instruments.id IN (SELECT latest.instrument_id FROM (SELECT DISTINCT ON (instrument_id) instrument_id, close FROM bars WHERE timeframe = '1d' ORDER BY instrument_id, timestamp DESC) AS latest WHERE ($1::numeric IS NULL OR latest.close >= $1) AND ($2::numeric IS NULL OR latest.close <= $2))",
+                    [*min_close, *max_close],
+                )
+            })),
+        WatchlistRule::Holdings => Condition::all().add(Expr::cust_with_values(
+            "instruments.id IN (SELECT wi.instrument_id FROM watchlist_items wi JOIN watchlists w ON w.id = wi.watchlist_id WHERE w.kind = $1 AND wi.entry_price IS NOT NULL)",
+            [WatchlistKind::Static.as_str()],
+        )),
+    }
+}
+
+/// スマートウォッチリストの銘柄をルールに従って再計算する
+///
+/// ルールに一致しなくなった銘柄だけを削除し、新たに一致した銘柄を銘柄コード順に末尾へ追加する。
+/// 引き続き一致する銘柄の行は変更しないため、sort_order や added_at、メモ・タグ等は維持される。
+/// static のウォッチリストに対しては何もしない。
+///
+/// 一覧と差分の反映を一貫させるため、トランザクション内で呼ぶこと。
+pub async fn refresh_watchlist(
+    db: &impl ConnectionTrait,
+    watchlist: &watchlists::Model,
+) -> Result<(), AppError> {
+    let Some(rule) = watchlist_rule(watchlist)? else {
+        return Ok(());
+    };
+
+    let matching: Vec<String> = instruments::Entity::find()
+        .select_only()
+        .column(instruments::Column::Id)
+        .filter(rule_condition(&rule))
+        .order_by_asc(instruments::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    watchlist_items::Entity::delete_many()
+        .filter(watchlist_items::Column::WatchlistId.eq(watchlist.id))
+        .filter(watchlist_items::Column::InstrumentId.is_not_in(matching.clone()))
+        .exec(db)
+        .await?;
+
+    let existing: Vec<(String, i32)> = watchlist_items::Entity::find()
+        .select_only()
+        .column(watchlist_items::Column::InstrumentId)
+        .column(watchlist_items::Column::SortOrder)
+        .filter(watchlist_items::Column::WatchlistId.eq(watchlist.id))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let next_sort_order = existing
+        .iter()
+        .map(|(_, order)| order + 1)
+        .max()
+        .unwrap_or(0);
+    let existing_ids: HashSet<String> = existing.into_iter().map(|(id, _)| id).collect();
+
+    let new_items: Vec<watchlist_items::ActiveModel> = matching
+        .into_iter()
+        .filter(|id| !existing_ids.contains(id))
+        .zip(next_sort_order..)
+        .map(|(instrument_id, sort_order)| watchlist_items::ActiveModel {
+            watchlist_id: Set(watchlist.id),
+            instrument_id: Set(instrument_id),
+            sort_order: Set(sort_order),
+            ..Default::default()
+        })
+        .collect();
+
+    if !new_items.is_empty() {
+        watchlist_items::Entity::insert_many(new_items)
+            .on_conflict(
+                OnConflict::columns([
+                    watchlist_items::Column::WatchlistId,
+                    watchlist_items::Column::InstrumentId,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// `input` を参照するルールを持つスマートウォッチリストの銘柄を再計算する
+///
+/// 銘柄情報・日足・保有銘柄 (static のウォッチリストの entry_price) を更新したときに呼ぶ。
+/// すべてのウォッチリストを 1 トランザクションで再計算する。
+pub async fn refresh_affected(
+    db: &DatabaseConnection,
+    input: WatchlistRuleInput,
+) -> Result<(), AppError> {
+    let txn = db.begin().await?;
+
+    let smart_watchlists = watchlists::Entity::find()
+        .filter(watchlists::Column::Kind.eq(WatchlistKind::Smart.as_str()))
+        .all(&txn)
+        .await?;
+
+    for watchlist in &smart_watchlists {
+        let depends = watchlist_rule(watchlist)?.is_some_and(|rule| rule.depends_on(input));
+        if depends {
+            refresh_watchlist(&txn, watchlist).await?;
+        }
+    }
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+/// データの更新後に、そのデータを参照するスマートウォッチリストの銘柄を再計算する
+///
+/// 更新自体は確定済みのため、再計算に失敗してもログ出力のみとし、呼び出し元には返さない。
+pub async fn refresh_after_change(db: &DatabaseConnection, input: WatchlistRuleInput) {
+    if let Err(e) = refresh_affected(db, input).await {
+        tracing::warn!(?input, error = %e, "スマートウォッチリストの再計算に失敗しました");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use sea_orm::ActiveModelTrait;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::data_provider::mock::{date, make_bar};
+    use crate::repositories;
+    use crate::testing::create_test_db;
+
+    async fn insert_instrument(
+        db: &DatabaseConnection,
+        id: &str,
+        sector: &str,
+        market_segment: &str,
+    ) {
+        instruments::ActiveModel {
+            id: Set(id.to_string()),
+            name: Set(format!("銘柄 {id}")),
+            market: Set("TSE".to_string()),
+            sector: Set(Some(sector.to_string())),
+            market_segment: Set(Some(market_segment.to_string())),
+            bars_updated_at: Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_smart_watchlist(
+        db: &DatabaseConnection,
+        rule: &WatchlistRule,
+    ) -> watchlists::Model {
+        watchlists::ActiveModel {
+            id: Set(Uuid::from_u128(1)),
+            name: Set("スマート".to_string()),
+            sort_order: Set(0),
+            kind: Set(WatchlistKind::Smart.as_str().to_string()),
+            rule: Set(Some(serde_json::to_value(rule).unwrap())),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap()
+    }
+
+    async fn item_ids(db: &DatabaseConnection, watchlist_id: Uuid) -> Vec<String> {
+        watchlist_items::Entity::find()
+            .filter(watchlist_items::Column::WatchlistId.eq(watchlist_id))
+            .order_by_asc(watchlist_items::Column::SortOrder)
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|item| item.instrument_id)
+            .collect()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn refresh_adds_instruments_matching_rule(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_instrument(&db, "7203", "輸送用機器", "プライム").await;
+        insert_instrument(&db, "7267", "輸送用機器", "プライム").await;
+        insert_instrument(&db, "6758", "電気機器", "プライム").await;
+        let watchlist = insert_smart_watchlist(
+            &db,
+            &WatchlistRule::Sector {
+                sector: "輸送用機器".to_string(),
+            },
+        )
+        .await;
+
+        refresh_watchlist(&db, &watchlist).await.unwrap();
+
+        assert_eq!(item_ids(&db, watchlist.id).await, vec!["7203", "7267"]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn refresh_follows_instrument_changes_and_keeps_order(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_instrument(&db, "7203", "輸送用機器", "プライム").await;
+        insert_instrument(&db, "7267", "輸送用機器", "プライム").await;
+        let watchlist = insert_smart_watchlist(
+            &db,
+            &WatchlistRule::MarketSegment {
+                market_segment: "プライム".to_string(),
+            },
+        )
+        .await;
+        refresh_watchlist(&db, &watchlist).await.unwrap();
+
+        // 7203 が市場区分を変更し、新たに 1301 が登録された
+        instruments::Entity::update_many()
+            .col_expr(
+                instruments::Column::MarketSegment,
+                Expr::value("スタンダード"),
+            )
+            .filter(instruments::Column::Id.eq("7203"))
+            .exec(&db)
+            .await
+            .unwrap();
+        insert_instrument(&db, "1301", "水産・農林業", "プライム").await;
+
+        refresh_affected(&db, WatchlistRuleInput::Instruments)
+            .await
+            .unwrap();
+
+        assert_eq!(item_ids(&db, watchlist.id).await, vec!["7267", "1301"]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn refresh_keeps_memo_and_tags_of_remaining_items(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_instrument(&db, "7203", "輸送用機器", "プライム").await;
+        insert_instrument(&db, "7267", "輸送用機器", "プライム").await;
+        let watchlist = insert_smart_watchlist(
+            &db,
+            &WatchlistRule::Sector {
+                sector: "輸送用機器".to_string(),
+            },
+        )
+        .await;
+        refresh_watchlist(&db, &watchlist).await.unwrap();
+        watchlist_items::Entity::update_many()
+            .col_expr(watchlist_items::Column::Memo, Expr::value("決算待ち"))
+            .col_expr(
+                watchlist_items::Column::Tags,
+                Expr::value(serde_json::json!([{ "name": "注目", "color": "#ff0000" }])),
+            )
+            .filter(watchlist_items::Column::InstrumentId.eq("7203"))
+            .exec(&db)
+            .await
+            .unwrap();
+
+        insert_instrument(&db, "7201", "輸送用機器", "プライム").await;
+        refresh_affected(&db, WatchlistRuleInput::Instruments)
+            .await
+            .unwrap();
+
+        let item = watchlist_items::Entity::find_by_id((watchlist.id, "7203".to_string()))
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(item.memo.as_deref(), Some("決算待ち"));
+        assert_eq!(
+            item.tags,
+            serde_json::json!([{ "name": "注目", "color": "#ff0000" }])
+        );
+        assert_eq!(
+            item_ids(&db, watchlist.id).await,
+            vec!["7203", "7267", "7201"]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn screen_rule_combines_classification_and_latest_close(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_instrument(&db, "7203", "輸送用機器", "プライム").await;
+        insert_instrument(&db, "7267", "輸送用機器", "プライム").await;
+        insert_instrument(&db, "7201", "輸送用機器", "スタンダード").await;
+        repositories::bars::upsert_bars(
+            &db,
+            vec![
+                // 7203 は最新の終値で判定する (以前の終値は範囲外)
+                make_bar("7203", date(2025, 1, 6), 5000),
+                make_bar("7203", date(2025, 1, 7), 2500),
+                make_bar("7267", date(2025, 1, 7), 5000),
+                make_bar("7201", date(2025, 1, 7), 2500),
+            ],
+        )
+        .await
+        .unwrap();
+        let watchlist = insert_smart_watchlist(
+            &db,
+            &WatchlistRule::Screen {
+                sector: None,
+                market_segment: Some("プライム".to_string()),
+                min_close: Some(Decimal::from(1000)),
+                max_close: Some(Decimal::from(3000)),
+            },
+        )
+        .await;
+
+        refresh_watchlist(&db, &watchlist).await.unwrap();
+        assert_eq!(item_ids(&db, watchlist.id).await, vec!["7203"]);
+
+        repositories::bars::upsert_bars(&db, vec![make_bar("7267", date(2025, 1, 8), 2800)])
+            .await
+            .unwrap();
+        refresh_affected(&db, WatchlistRuleInput::Bars)
+            .await
+            .unwrap();
+        assert_eq!(item_ids(&db, watchlist.id).await, vec!["7203", "7267"]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn holdings_rule_follows_entry_prices_in_static_watchlists(pool: PgPool) {
+        let db = create_test_db(pool).await;
+        insert_instrument(&db, "7203", "輸送用機器", "プライム").await;
+        insert_instrument(&db, "6758", "電気機器", "プライム").await;
+        watchlists::ActiveModel {
+            id: Set(Uuid::from_u128(2)),
+            name: Set("監視".to_string()),
+            sort_order: Set(1),
+            kind: Set(WatchlistKind::Static.as_str().to_string()),
+            rule: Set(None),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        for (instrument_id, entry_price) in [("7203", Some(2500)), ("6758", None)] {
+            watchlist_items::ActiveModel {
+                watchlist_id: Set(Uuid::from_u128(2)),
+                instrument_id: Set(instrument_id.to_string()),
+                sort_order: Set(0),
+                entry_price: Set(entry_price.map(Decimal::from)),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        let watchlist = insert_smart_watchlist(&db, &WatchlistRule::Holdings).await;
+
+        refresh_watchlist(&db, &watchlist).await.unwrap();
+        assert_eq!(item_ids(&db, watchlist.id).await, vec!["7203"]);
+
+        // 6758 を購入し、7203 を手放した
+        for (instrument_id, entry_price) in [("7203", None), ("6758", Some(Decimal::from(3000)))] {
+            watchlist_items::Entity::update_many()
+                .col_expr(
+                    watchlist_items::Column::EntryPrice,
+                    Expr::value(entry_price),
+                )
+                .filter(watchlist_items::Column::WatchlistId.eq(Uuid::from_u128(2)))
+                .filter(watchlist_items::Column::InstrumentId.eq(instrument_id))
+                .exec(&db)
+                .await
+                .unwrap();
+        }
+        refresh_affected(&db, WatchlistRuleInput::Holdings)
+            .await
+            .unwrap();
+
+        assert_eq!(item_ids(&db, watchlist.id).await, vec!["6758"]);
+    }
+}
@@ -0,0 +1,349 @@
+//! ウォッチリストのインポート/エクスポート形式の変換
+//!
+//! 受け付ける形式:
+//! - JSON: [`WatchlistExport`] (エクスポート結果そのもの)
+//! - CSV: 銘柄コード列を含むヘッダー付き CSV。エクスポート CSV のほか、
+//!   証券会社のお気に入りエクスポート (`銘柄コード`, `銘柄名` 列等) を想定する
+//! - テキスト: 改行・カンマ区切りの銘柄コード一覧。TradingView の `TSE:7203` 形式や
+//!   `###` で始まるセクション行を含むリストをそのまま受け付ける
+
+use crate::error::AppError;
+use crate::models::{WatchlistExport, WatchlistExportItem, WatchlistImportFormat};
+
+/// エクスポート CSV のヘッダー
+const EXPORT_CSV_HEADER: [&str; 3] = ["instrument_id", "name", "memo"];
+
+/// 銘柄コード列として認識するヘッダー名
+const CODE_HEADERS: &[&str] = &[
+    "instrument_id",
+    "code",
+    "symbol",
+    "ticker",
+    "銘柄コード",
+    "証券コード",
+    "コード",
+];
+/// 銘柄名列として認識するヘッダー名
+const NAME_HEADERS: &[&str] = &["name", "銘柄名", "銘柄", "名称"];
+/// メモ列として認識するヘッダー名
+const MEMO_HEADERS: &[&str] = &["memo", "note", "メモ"];
+
+/// 証券会社の CSV は先頭にタイトル行等を含むことがあるため、ヘッダー行を探す行数の上限
+const MAX_HEADER_SEARCH_ROWS: usize = 10;
+
+/// 銘柄一覧をエクスポート CSV に変換する
+pub fn to_csv(items: &[WatchlistExportItem]) -> Result<String, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(EXPORT_CSV_HEADER)
+        .map_err(|e| AppError::Internal(format!("failed to write CSV: {e}")))?;
+
+    for item in items {
+        writer
+            .write_record([
+                item.instrument_id.as_str(),
+                item.name.as_deref().unwrap_or_default(),
+                item.memo.as_deref().unwrap_or_default(),
+            ])
+            .map_err(|e| AppError::Internal(format!("failed to write CSV: {e}")))?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("failed to write CSV: {e}")))?;
+    String::from_utf8(bytes).map_err(|e| AppError::Internal(format!("invalid UTF-8 in CSV: {e}")))
+}
+
+/// インポート内容をパースして銘柄一覧を返す
+///
+/// `format` が None の場合は内容から形式を自動判定する。
+/// 銘柄コードは正規化 (取引所プレフィックス・サフィックスの除去) のみ行い、
+/// 値の妥当性検証は呼び出し元で行う。
+pub fn parse(
+    content: &str,
+    format: Option<WatchlistImportFormat>,
+) -> Result<Vec<WatchlistExportItem>, AppError> {
+    // Excel 等で保存した CSV は BOM 付きになる
+    let content = content.trim_start_matches('\u{feff}');
+    let format = format.unwrap_or_else(|| detect_format(content));
+
+    match format {
+        WatchlistImportFormat::Json => parse_json(content),
+        WatchlistImportFormat::Csv => parse_csv(content),
+        WatchlistImportFormat::Text => Ok(parse_text(content)),
+    }
+}
+
+/// 内容からインポート形式を推定する
+fn detect_format(content: &str) -> WatchlistImportFormat {
+    if content.trim_start().starts_with('{') {
+        WatchlistImportFormat::Json
+    } else if find_csv_header(content).is_some() {
+        WatchlistImportFormat::Csv
+    } else {
+        WatchlistImportFormat::Text
+    }
+}
+
+fn parse_json(content: &str) -> Result<Vec<WatchlistExportItem>, AppError> {
+    let export: WatchlistExport = serde_json::from_str(content)
+        .map_err(|e| AppError::Validation(format!("invalid watchlist JSON: {e}")))?;
+
+    Ok(export
+        .items
+        .into_iter()
+        .map(|item| WatchlistExportItem {
+            instrument_id: normalize_code(&item.instrument_id),
+            name: non_empty(item.name.as_deref()),
+            memo: non_empty(item.memo.as_deref()),
+        })
+        .collect())
+}
+
+/// CSV のヘッダー行で各列が何番目にあるか
+struct CsvColumns {
+    /// ヘッダー行の位置 (0 始まり)
+    header_row: usize,
+    code: usize,
+    name: Option<usize>,
+    memo: Option<usize>,
+}
+
+fn csv_reader(content: &str) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes())
+}
+
+/// 銘柄コード列を含むヘッダー行を探す
+fn find_csv_header(content: &str) -> Option<CsvColumns> {
+    let position = |record: &csv::StringRecord, candidates: &[&str]| {
+        record
+            .iter()
+            .position(|field| candidates.contains(&field.to_lowercase().as_str()))
+    };
+
+    csv_reader(content)
+        .records()
+        .take(MAX_HEADER_SEARCH_ROWS)
+        .enumerate()
+        .find_map(|(header_row, record)| {
+            let record = record.ok()?;
+            Some(CsvColumns {
+                header_row,
+                code: position(&record, CODE_HEADERS)?,
+                name: position(&record, NAME_HEADERS),
+                memo: position(&record, MEMO_HEADERS),
+            })
+        })
+}
+
+fn parse_csv(content: &str) -> Result<Vec<WatchlistExportItem>, AppError> {
+    let columns = find_csv_header(content).ok_or_else(|| {
+        AppError::Validation(format!(
+            "CSV header must contain one of the code columns: {CODE_HEADERS:?}"
+        ))
+    })?;
+
+    let mut items = Vec::new();
+    for record in csv_reader(content).records().skip(columns.header_row + 1) {
+        let record = record.map_err(|e| AppError::Validation(format!("invalid CSV: {e}")))?;
+
+        let code = normalize_code(record.get(columns.code).unwrap_or_default());
+        if code.is_empty() {
+            continue;
+        }
+
+        items.push(WatchlistExportItem {
+            instrument_id: code,
+            name: non_empty(columns.name.and_then(|i| record.get(i))),
+            memo: non_empty(columns.memo.and_then(|i| record.get(i))),
+        });
+    }
+
+    Ok(items)
+}
+
+fn parse_text(content: &str) -> Vec<WatchlistExportItem> {
+    content
+        .split(['\n', ','])
+        .map(str::trim)
+        // TradingView のセクション見出し (`###Section`)
+        .filter(|segment| !segment.starts_with("###"))
+        .flat_map(str::split_whitespace)
+        .map(normalize_code)
+        .filter(|code| !code.is_empty())
+        .map(|instrument_id| WatchlistExportItem {
+            instrument_id,
+            name: None,
+            memo: None,
+        })
+        .collect()
+}
+
+/// 外部サービスの銘柄表記を銘柄コードに正規化する
+///
+/// - `TSE:7203` (TradingView) → `7203`
+/// - `7203.T` (Yahoo Finance) / `7203.JP` (Stooq) → `7203`
+fn normalize_code(raw: &str) -> String {
+    let code = raw.trim().trim_matches('"');
+    let code = code.rsplit_once(':').map_or(code, |(_, code)| code);
+
+    let upper = code.to_ascii_uppercase();
+    let code = [".T", ".JP"]
+        .iter()
+        .find_map(|suffix| {
+            upper
+                .strip_suffix(suffix)
+                .filter(|rest| !rest.is_empty())
+                .map(|rest| &code[..rest.len()])
+        })
+        .unwrap_or(code);
+
+    code.trim().to_string()
+}
+
+/// 空白のみの値を None として扱う
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn item(instrument_id: &str, name: Option<&str>, memo: Option<&str>) -> WatchlistExportItem {
+        WatchlistExportItem {
+            instrument_id: instrument_id.to_string(),
+            name: name.map(str::to_string),
+            memo: memo.map(str::to_string),
+        }
+    }
+
+    #[rstest]
+    #[case::tradingview("TSE:7203", "7203")]
+    #[case::yahoo_suffix("7203.T", "7203")]
+    #[case::stooq_suffix("7203.jp", "7203")]
+    #[case::plain(" 9984 ", "9984")]
+    #[case::alphanumeric_code("TSE:130A", "130A")]
+    fn test_normalize_code(#[case] raw: &str, #[case] expected: &str) {
+        assert_eq!(normalize_code(raw), expected);
+    }
+
+    #[rstest]
+    #[case::json(r#"{"name": "a", "items": []}"#, WatchlistImportFormat::Json)]
+    #[case::export_csv(
+        indoc! {"
+            instrument_id,name,memo
+            7203,トヨタ,
+        "},
+        WatchlistImportFormat::Csv
+    )]
+    #[case::tradingview("TSE:7203,TSE:6758", WatchlistImportFormat::Text)]
+    #[case::code_lines(
+        indoc! {"
+            7203
+            6758
+        "},
+        WatchlistImportFormat::Text
+    )]
+    fn test_detect_format(#[case] content: &str, #[case] expected: WatchlistImportFormat) {
+        assert_eq!(detect_format(content), expected);
+    }
+
+    #[rstest]
+    fn test_csv_round_trip() {
+        let items = vec![
+            item("7203", Some("トヨタ自動車"), Some("決算前に確認, 要注意")),
+            item("9984", None, None),
+        ];
+
+        let csv = to_csv(&items).unwrap();
+        let parsed = parse(&csv, None).unwrap();
+
+        assert_eq!(parsed, items);
+    }
+
+    #[rstest]
+    fn test_parse_json_export() {
+        let content = r#"{
+            "name": "お気に入り",
+            "items": [
+                {"instrument_id": "7203", "name": "トヨタ自動車", "memo": "長期"},
+                {"instrument_id": "TSE:6758", "name": "", "memo": null}
+            ]
+        }"#;
+
+        let parsed = parse(content, None).unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![
+                item("7203", Some("トヨタ自動車"), Some("長期")),
+                item("6758", None, None),
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_parse_broker_csv_with_preamble_and_bom() {
+        let content = indoc! {"
+            \u{feff}お気に入り銘柄一覧
+            銘柄コード,銘柄名,市場,現在値
+            7203,トヨタ自動車,東証プライム,3000
+            6758,ソニーグループ,東証プライム,3500
+            ,,,
+        "};
+
+        let parsed = parse(content, None).unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![
+                item("7203", Some("トヨタ自動車"), None),
+                item("6758", Some("ソニーグループ"), None),
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_parse_tradingview_list() {
+        let content = "###日本株,TSE:7203,TSE:6758,###米国株 ハイテク,NASDAQ:AAPL";
+
+        let parsed = parse(content, None).unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![
+                item("7203", None, None),
+                item("6758", None, None),
+                item("AAPL", None, None),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::invalid_json(r#"{"items": "#, WatchlistImportFormat::Json)]
+    #[case::csv_without_code_column(
+        indoc! {"
+            name,memo
+            トヨタ,
+        "},
+        WatchlistImportFormat::Csv
+    )]
+    fn test_parse_invalid_content_returns_validation_error(
+        #[case] content: &str,
+        #[case] format: WatchlistImportFormat,
+    ) {
+        let result = parse(content, Some(format));
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+}
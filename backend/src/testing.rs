@@ -1,8 +1,11 @@
+use std::sync::Arc;
+
 use axum_test::TestServer;
 use migration::{Migrator, MigratorTrait};
 use sea_orm::{DatabaseConnection, SqlxPostgresConnector};
 use sqlx::PgPool;
 
+use crate::data_provider::DataProvider;
 use crate::{AppState, create_router};
 
 /// `#[sqlx::test]` から注入された PgPool を SeaORM DatabaseConnection に変換する
@@ -31,3 +34,20 @@ pub async fn create_test_server(pool: PgPool) -> TestServer {
     let router = create_router(state);
     TestServer::new(router).expect("failed to create test server")
 }
+
+/// DataProvider を差し込んだ TestServer を作成する
+///
+/// `MockDataProvider` 等のテスト用プロバイダーを使ってデータ取得系のエンドポイントを検証する。
+pub async fn create_test_server_with_provider(
+    pool: PgPool,
+    data_provider: Arc<dyn DataProvider>,
+) -> TestServer {
+    let db = create_test_db(pool).await;
+
+    let state = AppState {
+        db,
+        data_provider: Some(data_provider),
+    };
+    let router = create_router(state);
+    TestServer::new(router).expect("failed to create test server")
+}
@@ -125,12 +125,22 @@ export interface components {
       low: number
       open: number
       /** @enum {string} */
-      timeframe: '1d'
+      timeframe: '1m' | '5m' | '15m' | '1h' | '1d'
       /** Format: date-time */
       timestamp: string
       /** Format: int64 */
       volume: number
     }
+    /** @description 1 銘柄のバーデータの 1 ページ */
+    BarsPage: {
+      /** @description バーデータ (`order` の順) */
+      bars: components['schemas']['Bar'][]
+      /**
+       * Format: date-time
+       * @description 続きのページがある場合、次のリクエストの `cursor` に指定する値 (最後のページでは null)
+       */
+      next_cursor?: string | null
+    }
     CreateWatchlistRequest: {
       /** @description ウォッチリスト名 */
       name: string
@@ -177,7 +187,7 @@ export interface operations {
       query: {
         /** @description 銘柄コード (必須) */
         instrument_id: string
-        /** @description 時間足 (デフォルト: "1d") */
+        /** @description 時間足 ("1m", "5m", "15m", "1h", "1d"。デフォルト: "1d") */
         timeframe?: string
         /** @description 取得開始日 (YYYY-MM-DD, inclusive) */
         from?: string
@@ -196,7 +206,7 @@ export interface operations {
           [name: string]: unknown
         }
         content: {
-          'application/json': components['schemas']['Bar'][]
+          'application/json': components['schemas']['BarsPage']
         }
       }
       /** @description バリデーションエラー */
@@ -133,7 +133,7 @@ function ChartPage() {
         {toolbar}
       </div>
       <div className="flex min-h-0 flex-1 gap-4">
-        <CandlestickChart bars={data ?? []} className="h-[600px] w-full" />
+        <CandlestickChart bars={data?.bars ?? []} className="h-[600px] w-full" />
         <ChartMarketDepthPanel
           instrumentId={instrumentId}
           isOpen={isMarketDepthOpen}